@@ -0,0 +1,149 @@
+#ifndef RUSTY_PIPE_H
+#define RUSTY_PIPE_H
+
+#include <gio/gio.h>
+
+G_BEGIN_DECLS
+
+typedef enum {
+  RP_ERROR_PARSING,
+  RP_ERROR_AGE_RESTRICTED,
+  RP_ERROR_DOWNLOAD,
+  RP_ERROR_CONSENT_REQUIRED,
+  RP_ERROR_FAILED,
+} RpError;
+
+#define RP_ERROR (rp_error_quark ())
+GQuark rp_error_quark (void);
+GType rp_error_get_type (void);
+
+/**
+ * RpThumbnail:
+ *
+ * Properties: url, width, height
+ */
+#define RP_TYPE_THUMBNAIL (rp_thumbnail_get_type ())
+G_DECLARE_FINAL_TYPE (RpThumbnail, rp_thumbnail, RP, THUMBNAIL, GObject)
+
+/**
+ * RpStream:
+ *
+ * Properties: kind ("video", "video-only" or "audio"), url, itag, mime-type, quality,
+ * quality-label, bitrate, width, height
+ */
+#define RP_TYPE_STREAM (rp_stream_get_type ())
+G_DECLARE_FINAL_TYPE (RpStream, rp_stream, RP, STREAM, GObject)
+
+/**
+ * RpVideoItem:
+ *
+ * Properties: video-id, title, uploader, uploader-url, duration, view-count,
+ * textual-upload-date, is-live, thumbnails (#GListStore of #RpThumbnail)
+ */
+#define RP_TYPE_VIDEO_ITEM (rp_video_item_get_type ())
+G_DECLARE_FINAL_TYPE (RpVideoItem, rp_video_item, RP, VIDEO_ITEM, GObject)
+
+/**
+ * RpVideoInfo:
+ *
+ * Properties: video-id, title, description, uploader, uploader-url, upload-date, length,
+ * view-count, like-count, thumbnails, uploader-avatars, streams (#GListStore of #RpStream),
+ * related (#GListStore of #RpVideoItem)
+ */
+#define RP_TYPE_VIDEO_INFO (rp_video_info_get_type ())
+G_DECLARE_FINAL_TYPE (RpVideoInfo, rp_video_info, RP, VIDEO_INFO, GObject)
+
+/**
+ * RpFeed:
+ *
+ * Properties: name, avatars, banners, videos (#GListStore of #RpVideoItem), next-page
+ */
+#define RP_TYPE_FEED (rp_feed_get_type ())
+G_DECLARE_FINAL_TYPE (RpFeed, rp_feed, RP, FEED, GObject)
+
+#define RP_TYPE_CLIENT (rp_client_get_type ())
+G_DECLARE_FINAL_TYPE (RpClient, rp_client, RP, CLIENT, GObject)
+
+/**
+ * rp_client_new:
+ *
+ * Returns: (transfer full): a new #RpClient
+ */
+RpClient *rp_client_new (void);
+
+/**
+ * rp_client_video_info_async:
+ * @self: a #RpClient
+ * @video_id: the 11 character video id
+ * @cancellable: (nullable): a #GCancellable
+ * @callback: (scope async): called when the extraction finished
+ * @user_data: (closure): data for @callback
+ */
+void rp_client_video_info_async (RpClient            *self,
+                                 const char          *video_id,
+                                 GCancellable        *cancellable,
+                                 GAsyncReadyCallback  callback,
+                                 gpointer             user_data);
+
+/**
+ * rp_client_video_info_finish:
+ *
+ * Returns: (transfer full): the #RpVideoInfo
+ */
+RpVideoInfo *rp_client_video_info_finish (RpClient      *self,
+                                          GAsyncResult  *result,
+                                          GError       **error);
+
+/**
+ * rp_client_channel_feed_async:
+ * @self: a #RpClient
+ * @channel_id: the channel id
+ * @page: (nullable): #RpFeed:next-page of a previous result
+ * @cancellable: (nullable): a #GCancellable
+ * @callback: (scope async): called when the extraction finished
+ * @user_data: (closure): data for @callback
+ */
+void rp_client_channel_feed_async (RpClient            *self,
+                                   const char          *channel_id,
+                                   const char          *page,
+                                   GCancellable        *cancellable,
+                                   GAsyncReadyCallback  callback,
+                                   gpointer             user_data);
+
+/**
+ * rp_client_channel_feed_finish:
+ *
+ * Returns: (transfer full): the #RpFeed
+ */
+RpFeed *rp_client_channel_feed_finish (RpClient      *self,
+                                       GAsyncResult  *result,
+                                       GError       **error);
+
+/**
+ * rp_client_search_async:
+ * @self: a #RpClient
+ * @query: the search query
+ * @page: (nullable): #RpFeed:next-page of a previous result
+ * @cancellable: (nullable): a #GCancellable
+ * @callback: (scope async): called when the extraction finished
+ * @user_data: (closure): data for @callback
+ */
+void rp_client_search_async (RpClient            *self,
+                             const char          *query,
+                             const char          *page,
+                             GCancellable        *cancellable,
+                             GAsyncReadyCallback  callback,
+                             gpointer             user_data);
+
+/**
+ * rp_client_search_finish:
+ *
+ * Returns: (transfer full): the #RpFeed
+ */
+RpFeed *rp_client_search_finish (RpClient      *self,
+                                 GAsyncResult  *result,
+                                 GError       **error);
+
+G_END_DECLS
+
+#endif
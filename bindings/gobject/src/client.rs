@@ -0,0 +1,96 @@
+use crate::downloader::SoupDownloader;
+use crate::error::Error;
+use crate::feed::Feed;
+use crate::video_info::VideoInfo;
+use glib::subclass::prelude::*;
+use rusty_pipe::blocking::{YTChannelExtractor, YTSearchExtractor, YTStreamExtractor};
+use rusty_pipe::{json, ParsingError};
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct Client {
+        pub downloader: SoupDownloader,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Client {
+        const NAME: &'static str = "RpClient";
+        type Type = super::Client;
+    }
+
+    impl ObjectImpl for Client {}
+}
+
+glib::wrapper! {
+    /// Entry point of the bindings, extractions run on a worker thread and the results are
+    /// turned into objects on the thread default main context
+    pub struct Client(ObjectSubclass<imp::Client>);
+}
+
+impl Client {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    fn downloader(&self) -> SoupDownloader {
+        self.imp().downloader.clone()
+    }
+
+    async fn run<F>(extract: F) -> Result<Value, glib::Error>
+    where
+        F: FnOnce() -> Result<Value, ParsingError> + Send + 'static,
+    {
+        match gio::spawn_blocking(extract).await {
+            Ok(result) => result.map_err(glib::Error::from),
+            Err(_) => Err(glib::Error::new(Error::Failed, "Extraction panicked")),
+        }
+    }
+
+    pub async fn video_info(&self, video_id: &str) -> Result<VideoInfo, glib::Error> {
+        let downloader = self.downloader();
+        let video_id = video_id.to_string();
+        let info = Self::run(move || {
+            let extractor = YTStreamExtractor::new(downloader, &video_id)?;
+            Ok(json::stream_to_json(&extractor))
+        })
+        .await?;
+        Ok(VideoInfo::from_json(&info))
+    }
+
+    pub async fn channel_feed(
+        &self,
+        channel_id: &str,
+        page: Option<&str>,
+    ) -> Result<Feed, glib::Error> {
+        let downloader = self.downloader();
+        let channel_id = channel_id.to_string();
+        let page = page.map(|p| p.to_string());
+        let channel = Self::run(move || {
+            let extractor = YTChannelExtractor::new(downloader, &channel_id, page)?;
+            Ok(json::channel_to_json(&extractor))
+        })
+        .await?;
+        Ok(Feed::from_channel_json(&channel))
+    }
+
+    pub async fn search(&self, query: &str, page: Option<&str>) -> Result<Feed, glib::Error> {
+        let downloader = self.downloader();
+        let search_query = query.to_string();
+        let page = page.map(|p| p.to_string());
+        let search = Self::run(move || {
+            let extractor = YTSearchExtractor::new(downloader, &search_query, page)?;
+            Ok(json::search_to_json(&extractor))
+        })
+        .await?;
+        Ok(Feed::from_search_json(query, &search))
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
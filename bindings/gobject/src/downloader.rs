@@ -0,0 +1,104 @@
+use glib::translate::IntoGlib;
+use rusty_pipe::blocking::BlockingDownloader;
+use rusty_pipe::downloaders::DownloaderConfig;
+use rusty_pipe::{CookieJar, ParsingError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Synchronous libsoup downloader, a session is created per request so it can be used from
+/// the worker threads the extractions run on
+#[derive(Clone)]
+pub struct SoupDownloader {
+    config: DownloaderConfig,
+    cookies: Arc<Mutex<CookieJar>>,
+}
+
+impl SoupDownloader {
+    pub fn new() -> Self {
+        Self::with_config(DownloaderConfig::default())
+    }
+
+    pub fn with_config(config: DownloaderConfig) -> Self {
+        Self {
+            cookies: Arc::new(Mutex::new(config.cookie_jar.clone())),
+            config,
+        }
+    }
+
+    fn session(&self) -> soup3::Session {
+        let session = soup3::Session::new();
+        session.set_user_agent(&self.config.user_agent);
+        if let Some(timeout) = self.config.timeout {
+            session.set_timeout(timeout.as_secs() as u32);
+        }
+        if let Some(proxy) = &self.config.proxy {
+            let resolver = gio::SimpleProxyResolver::new(Some(proxy), &[]);
+            session.set_proxy_resolver(Some(&resolver));
+        }
+        session
+    }
+}
+
+impl Default for SoupDownloader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlockingDownloader for SoupDownloader {
+    fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.download_with_header(url, HashMap::new())
+    }
+
+    fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        let message = soup3::Message::new("GET", url).map_err(|e| ParsingError::DownloadError {
+            cause: e.to_string(),
+        })?;
+        if let Some(headers) = message.request_headers() {
+            for (name, value) in &header {
+                headers.replace(name, value);
+            }
+        }
+        let body = self
+            .session()
+            .send_and_read(&message, gio::Cancellable::NONE)
+            .map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+
+        let is_youtube = message
+            .uri()
+            .and_then(|uri| uri.host())
+            .map(|host| host == "youtube.com" || host.ends_with(".youtube.com"))
+            .unwrap_or(false);
+        if let (true, Some(headers)) = (is_youtube, message.response_headers()) {
+            let mut cookies = self.cookies.lock().unwrap();
+            headers.foreach(|name, value| {
+                if name.eq_ignore_ascii_case("set-cookie") {
+                    cookies.store_set_cookie(value);
+                }
+            });
+        }
+
+        let status = message.status().into_glib() as u32;
+        if !(200..300).contains(&status) {
+            return Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, status),
+                status: status as u16,
+            });
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.config.eval_js(script)
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        self.cookies.lock().unwrap().clone()
+    }
+}
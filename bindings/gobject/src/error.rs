@@ -0,0 +1,25 @@
+use rusty_pipe::ParsingError;
+
+/// Error domain of the `GError`s returned by `RpClient`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, glib::ErrorDomain, glib::Enum)]
+#[error_domain(name = "rp-error")]
+#[enum_type(name = "RpError")]
+pub enum Error {
+    Parsing,
+    AgeRestricted,
+    Download,
+    ConsentRequired,
+    Failed,
+}
+
+impl From<ParsingError> for glib::Error {
+    fn from(error: ParsingError) -> Self {
+        let domain = match &error {
+            ParsingError::ParsingError { .. } => Error::Parsing,
+            ParsingError::AgeRestricted => Error::AgeRestricted,
+            ParsingError::DownloadError { .. } | ParsingError::HttpError { .. } => Error::Download,
+            ParsingError::ConsentRequired => Error::ConsentRequired,
+        };
+        glib::Error::new(domain, &error.to_string())
+    }
+}
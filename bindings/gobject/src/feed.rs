@@ -0,0 +1,65 @@
+use crate::thumbnail::Thumbnail;
+use crate::video_item::VideoItem;
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::Feed)]
+    pub struct Feed {
+        /// Channel name, or the query for search results
+        #[property(get, set)]
+        name: RefCell<Option<String>>,
+        #[property(get, set)]
+        avatars: RefCell<Option<gio::ListStore>>,
+        #[property(get, set)]
+        banners: RefCell<Option<gio::ListStore>>,
+        #[property(get, set)]
+        videos: RefCell<Option<gio::ListStore>>,
+        /// Pass to the same method again to load the next page
+        #[property(get, set)]
+        next_page: RefCell<Option<String>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Feed {
+        const NAME: &'static str = "RpFeed";
+        type Type = super::Feed;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Feed {}
+}
+
+glib::wrapper! {
+    /// A page of videos from a channel or a search
+    pub struct Feed(ObjectSubclass<imp::Feed>);
+}
+
+impl Feed {
+    /// Builds a feed from `rusty_pipe::json::channel_to_json`
+    pub fn from_channel_json(channel: &Value) -> Self {
+        glib::Object::builder()
+            .property("name", channel["name"].as_str())
+            .property("avatars", Thumbnail::list_from_json(&channel["avatars"]))
+            .property("banners", Thumbnail::list_from_json(&channel["banners"]))
+            .property("videos", VideoItem::list_from_json(&channel["videos"]))
+            .property("next-page", channel["nextPage"].as_str())
+            .build()
+    }
+
+    /// Builds a feed from `rusty_pipe::json::search_to_json`
+    pub fn from_search_json(query: &str, search: &Value) -> Self {
+        glib::Object::builder()
+            .property("name", query)
+            .property("avatars", gio::ListStore::new::<Thumbnail>())
+            .property("banners", gio::ListStore::new::<Thumbnail>())
+            .property("videos", VideoItem::list_from_json(&search["items"]))
+            .property("next-page", search["nextPage"].as_str())
+            .build()
+    }
+}
@@ -0,0 +1,182 @@
+//! C ABI matching `include/rusty-pipe.h`
+
+use crate::client::Client;
+use crate::error::Error;
+use crate::feed::Feed;
+use crate::stream::Stream;
+use crate::thumbnail::Thumbnail;
+use crate::video_info::VideoInfo;
+use crate::video_item::VideoItem;
+use futures::future::{select, Either};
+use gio::ffi::{GAsyncReadyCallback, GAsyncResult, GCancellable, GTask};
+use gio::prelude::*;
+use glib::ffi::{gpointer, GError, GType};
+use glib::gobject_ffi::{g_object_unref, GObject};
+use glib::prelude::*;
+use glib::translate::*;
+use std::future::Future;
+use std::os::raw::c_char;
+
+macro_rules! get_type {
+    ($name:ident, $type:ty) => {
+        #[no_mangle]
+        pub extern "C" fn $name() -> GType {
+            <$type>::static_type().into_glib()
+        }
+    };
+}
+
+get_type!(rp_client_get_type, Client);
+get_type!(rp_feed_get_type, Feed);
+get_type!(rp_stream_get_type, Stream);
+get_type!(rp_thumbnail_get_type, Thumbnail);
+get_type!(rp_video_info_get_type, VideoInfo);
+get_type!(rp_video_item_get_type, VideoItem);
+get_type!(rp_error_get_type, Error);
+
+#[no_mangle]
+pub extern "C" fn rp_error_quark() -> glib::ffi::GQuark {
+    <Error as glib::error::ErrorDomain>::domain().into_glib()
+}
+
+#[no_mangle]
+pub extern "C" fn rp_client_new() -> *mut GObject {
+    Client::new().upcast::<glib::Object>().into_glib_ptr()
+}
+
+unsafe extern "C" fn unref_object(object: gpointer) {
+    g_object_unref(object as *mut GObject);
+}
+
+unsafe fn client_from_ptr(client: *mut GObject) -> Client {
+    from_glib_none::<_, glib::Object>(client).unsafe_cast()
+}
+
+/// Runs `future` on the calling thread's default main context and completes a `GTask` with its
+/// result. Cancelling completes the task with `G_IO_ERROR_CANCELLED` right away, an extraction
+/// already running on the worker thread still finishes and its result is dropped.
+unsafe fn spawn_task<T, F>(
+    client: &Client,
+    cancellable: *mut GCancellable,
+    callback: GAsyncReadyCallback,
+    user_data: gpointer,
+    future: F,
+) where
+    T: IsA<glib::Object>,
+    F: Future<Output = Result<T, glib::Error>> + 'static,
+{
+    let task = gio::ffi::g_task_new(
+        client.as_ptr() as *mut GObject,
+        cancellable,
+        callback,
+        user_data,
+    );
+    let cancellable: Option<gio::Cancellable> = from_glib_none(cancellable);
+    glib::MainContext::ref_thread_default().spawn_local(async move {
+        let result = match cancellable {
+            Some(cancellable) => {
+                futures::pin_mut!(future);
+                match select(future, cancellable.future()).await {
+                    Either::Left((result, _)) => result,
+                    Either::Right(_) => Err(glib::Error::new(
+                        gio::IOErrorEnum::Cancelled,
+                        "Operation was cancelled",
+                    )),
+                }
+            }
+            None => future.await,
+        };
+        match result {
+            Ok(object) => gio::ffi::g_task_return_pointer(
+                task,
+                object.upcast::<glib::Object>().into_glib_ptr() as gpointer,
+                Some(unref_object),
+            ),
+            Err(error) => gio::ffi::g_task_return_error(task, error.into_glib_ptr()),
+        }
+        g_object_unref(task as *mut GObject);
+    });
+}
+
+unsafe fn finish_task(result: *mut GAsyncResult, error: *mut *mut GError) -> *mut GObject {
+    gio::ffi::g_task_propagate_pointer(result as *mut GTask, error) as *mut GObject
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_video_info_async(
+    client: *mut GObject,
+    video_id: *const c_char,
+    cancellable: *mut GCancellable,
+    callback: GAsyncReadyCallback,
+    user_data: gpointer,
+) {
+    let client = client_from_ptr(client);
+    let video_id: String = from_glib_none(video_id);
+    let owner = client.clone();
+    spawn_task(&owner, cancellable, callback, user_data, async move {
+        client.video_info(&video_id).await
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_video_info_finish(
+    _client: *mut GObject,
+    result: *mut GAsyncResult,
+    error: *mut *mut GError,
+) -> *mut GObject {
+    finish_task(result, error)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_channel_feed_async(
+    client: *mut GObject,
+    channel_id: *const c_char,
+    page: *const c_char,
+    cancellable: *mut GCancellable,
+    callback: GAsyncReadyCallback,
+    user_data: gpointer,
+) {
+    let client = client_from_ptr(client);
+    let channel_id: String = from_glib_none(channel_id);
+    let page: Option<String> = from_glib_none(page);
+    let owner = client.clone();
+    spawn_task(&owner, cancellable, callback, user_data, async move {
+        client.channel_feed(&channel_id, page.as_deref()).await
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_channel_feed_finish(
+    _client: *mut GObject,
+    result: *mut GAsyncResult,
+    error: *mut *mut GError,
+) -> *mut GObject {
+    finish_task(result, error)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_search_async(
+    client: *mut GObject,
+    query: *const c_char,
+    page: *const c_char,
+    cancellable: *mut GCancellable,
+    callback: GAsyncReadyCallback,
+    user_data: gpointer,
+) {
+    let client = client_from_ptr(client);
+    let query: String = from_glib_none(query);
+    let page: Option<String> = from_glib_none(page);
+    let owner = client.clone();
+    spawn_task(&owner, cancellable, callback, user_data, async move {
+        client.search(&query, page.as_deref()).await
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn rp_client_search_finish(
+    _client: *mut GObject,
+    result: *mut GAsyncResult,
+    error: *mut *mut GError,
+) -> *mut GObject {
+    finish_task(result, error)
+}
@@ -0,0 +1,30 @@
+//! GObject classes over rusty_pipe, usable from GJS, Vala and Python through GObject
+//! Introspection
+//!
+//! Build the cdylib, then generate the typelib from the header:
+//!
+//! ```sh
+//! g-ir-scanner --namespace=RustyPipe --nsversion=0.1 --identifier-prefix=Rp \
+//!     --symbol-prefix=rp --include=Gio-2.0 --library=rusty_pipe_gobject \
+//!     include/rusty-pipe.h --output=RustyPipe-0.1.gir
+//! g-ir-compiler RustyPipe-0.1.gir --output=RustyPipe-0.1.typelib
+//! ```
+
+mod client;
+mod downloader;
+mod error;
+mod feed;
+mod ffi;
+mod stream;
+mod thumbnail;
+mod video_info;
+mod video_item;
+
+pub use crate::client::Client;
+pub use crate::downloader::SoupDownloader;
+pub use crate::error::Error;
+pub use crate::feed::Feed;
+pub use crate::stream::Stream;
+pub use crate::thumbnail::Thumbnail;
+pub use crate::video_info::VideoInfo;
+pub use crate::video_item::VideoItem;
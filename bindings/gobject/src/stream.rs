@@ -0,0 +1,64 @@
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::Stream)]
+    pub struct Stream {
+        /// "video", "video-only" or "audio"
+        #[property(get, set)]
+        kind: RefCell<String>,
+        #[property(get, set)]
+        url: RefCell<Option<String>>,
+        #[property(get, set)]
+        itag: Cell<u32>,
+        #[property(get, set)]
+        mime_type: RefCell<String>,
+        #[property(get, set)]
+        quality: RefCell<String>,
+        #[property(get, set)]
+        quality_label: RefCell<Option<String>>,
+        #[property(get, set)]
+        bitrate: Cell<u32>,
+        /// 0 for audio streams
+        #[property(get, set)]
+        width: Cell<u32>,
+        /// 0 for audio streams
+        #[property(get, set)]
+        height: Cell<u32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Stream {
+        const NAME: &'static str = "RpStream";
+        type Type = super::Stream;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Stream {}
+}
+
+glib::wrapper! {
+    pub struct Stream(ObjectSubclass<imp::Stream>);
+}
+
+impl Stream {
+    /// Builds a stream from a serialized `StreamItem`
+    pub fn from_json(kind: &str, stream: &Value) -> Self {
+        glib::Object::builder()
+            .property("kind", kind)
+            .property("url", stream["url"].as_str())
+            .property("itag", stream["itag"].as_u64().unwrap_or_default() as u32)
+            .property("mime-type", stream["mimeType"].as_str().unwrap_or_default())
+            .property("quality", stream["quality"].as_str().unwrap_or_default())
+            .property("quality-label", stream["qualityLabel"].as_str())
+            .property("bitrate", stream["bitrate"].as_u64().unwrap_or_default() as u32)
+            .property("width", stream["width"].as_u64().unwrap_or_default() as u32)
+            .property("height", stream["height"].as_u64().unwrap_or_default() as u32)
+            .build()
+    }
+}
@@ -0,0 +1,51 @@
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::Thumbnail)]
+    pub struct Thumbnail {
+        #[property(get, set)]
+        url: RefCell<String>,
+        #[property(get, set)]
+        width: Cell<u64>,
+        #[property(get, set)]
+        height: Cell<u64>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Thumbnail {
+        const NAME: &'static str = "RpThumbnail";
+        type Type = super::Thumbnail;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Thumbnail {}
+}
+
+glib::wrapper! {
+    pub struct Thumbnail(ObjectSubclass<imp::Thumbnail>);
+}
+
+impl Thumbnail {
+    pub fn from_json(thumbnail: &Value) -> Self {
+        glib::Object::builder()
+            .property("url", thumbnail["url"].as_str().unwrap_or_default())
+            .property("width", thumbnail["width"].as_u64().unwrap_or_default())
+            .property("height", thumbnail["height"].as_u64().unwrap_or_default())
+            .build()
+    }
+
+    /// List model of `RpThumbnail` built from a JSON array, empty when the field is missing
+    pub fn list_from_json(thumbnails: &Value) -> gio::ListStore {
+        let store = gio::ListStore::new::<Thumbnail>();
+        for thumbnail in thumbnails.as_array().into_iter().flatten() {
+            store.append(&Thumbnail::from_json(thumbnail));
+        }
+        store
+    }
+}
@@ -0,0 +1,94 @@
+use crate::stream::Stream;
+use crate::thumbnail::Thumbnail;
+use crate::video_item::VideoItem;
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::VideoInfo)]
+    pub struct VideoInfo {
+        #[property(get, set)]
+        video_id: RefCell<String>,
+        #[property(get, set)]
+        title: RefCell<Option<String>>,
+        #[property(get, set)]
+        description: RefCell<Option<String>>,
+        #[property(get, set)]
+        uploader: RefCell<Option<String>>,
+        #[property(get, set)]
+        uploader_url: RefCell<Option<String>>,
+        /// ISO 8601 date
+        #[property(get, set)]
+        upload_date: RefCell<Option<String>>,
+        /// Seconds
+        #[property(get, set)]
+        length: Cell<u64>,
+        #[property(get, set)]
+        view_count: Cell<u64>,
+        /// -1 when hidden or unknown
+        #[property(get, set, default = -1)]
+        like_count: Cell<i64>,
+        #[property(get, set)]
+        thumbnails: RefCell<Option<gio::ListStore>>,
+        #[property(get, set)]
+        uploader_avatars: RefCell<Option<gio::ListStore>>,
+        /// `RpStream` items of every kind
+        #[property(get, set)]
+        streams: RefCell<Option<gio::ListStore>>,
+        #[property(get, set)]
+        related: RefCell<Option<gio::ListStore>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for VideoInfo {
+        const NAME: &'static str = "RpVideoInfo";
+        type Type = super::VideoInfo;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for VideoInfo {}
+}
+
+glib::wrapper! {
+    pub struct VideoInfo(ObjectSubclass<imp::VideoInfo>);
+}
+
+impl VideoInfo {
+    /// Builds the info from `rusty_pipe::json::stream_to_json`
+    pub fn from_json(info: &Value) -> Self {
+        let streams = gio::ListStore::new::<Stream>();
+        for (kind, key) in &[
+            ("video", "videoStreams"),
+            ("video-only", "videoOnlyStreams"),
+            ("audio", "audioStreams"),
+        ] {
+            for stream in info[*key].as_array().into_iter().flatten() {
+                streams.append(&Stream::from_json(kind, stream));
+            }
+        }
+        let count = |key: &str| info[key].as_str().and_then(|c| c.parse::<i64>().ok());
+        glib::Object::builder()
+            .property("video-id", info["id"].as_str().unwrap_or_default())
+            .property("title", info["title"].as_str())
+            .property("description", info["description"].as_str())
+            .property("uploader", info["uploaderName"].as_str())
+            .property("uploader-url", info["uploaderUrl"].as_str())
+            .property("upload-date", info["uploadDate"].as_str())
+            .property("length", info["length"].as_u64().unwrap_or_default())
+            .property("view-count", count("viewCount").unwrap_or_default() as u64)
+            .property("like-count", count("likeCount").unwrap_or(-1))
+            .property("thumbnails", Thumbnail::list_from_json(&info["thumbnails"]))
+            .property(
+                "uploader-avatars",
+                Thumbnail::list_from_json(&info["uploaderAvatars"]),
+            )
+            .property("streams", streams)
+            .property("related", VideoItem::list_from_json(&info["related"]))
+            .build()
+    }
+}
@@ -0,0 +1,75 @@
+use crate::thumbnail::Thumbnail;
+use glib::prelude::*;
+use glib::subclass::prelude::*;
+use serde_json::Value;
+
+mod imp {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::VideoItem)]
+    pub struct VideoItem {
+        #[property(get, set)]
+        video_id: RefCell<Option<String>>,
+        #[property(get, set)]
+        title: RefCell<Option<String>>,
+        #[property(get, set)]
+        uploader: RefCell<Option<String>>,
+        #[property(get, set)]
+        uploader_url: RefCell<Option<String>>,
+        /// Seconds, -1 for live streams or when unknown
+        #[property(get, set, default = -1)]
+        duration: Cell<i32>,
+        /// -1 when unknown
+        #[property(get, set, default = -1)]
+        view_count: Cell<i32>,
+        #[property(get, set)]
+        textual_upload_date: RefCell<Option<String>>,
+        #[property(get, set)]
+        is_live: Cell<bool>,
+        #[property(get, set)]
+        thumbnails: RefCell<Option<gio::ListStore>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for VideoItem {
+        const NAME: &'static str = "RpVideoItem";
+        type Type = super::VideoItem;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for VideoItem {}
+}
+
+glib::wrapper! {
+    pub struct VideoItem(ObjectSubclass<imp::VideoItem>);
+}
+
+impl VideoItem {
+    /// Builds an item from a serialized `YTStreamInfoItemExtractor`
+    pub fn from_json(item: &Value) -> Self {
+        glib::Object::builder()
+            .property("video-id", item["id"].as_str())
+            .property("title", item["title"].as_str())
+            .property("uploader", item["uploaderName"].as_str())
+            .property("uploader-url", item["uploaderUrl"].as_str())
+            .property("duration", item["duration"].as_i64().unwrap_or(-1) as i32)
+            .property("view-count", item["viewCount"].as_i64().unwrap_or(-1) as i32)
+            .property("textual-upload-date", item["textualUploadDate"].as_str())
+            .property("is-live", item["isLive"].as_bool().unwrap_or_default())
+            .property("thumbnails", Thumbnail::list_from_json(&item["thumbnails"]))
+            .build()
+    }
+
+    /// List model of `RpVideoItem`, items that are not videos are skipped
+    pub fn list_from_json(items: &Value) -> gio::ListStore {
+        let store = gio::ListStore::new::<VideoItem>();
+        for item in items.as_array().into_iter().flatten() {
+            if item["type"] == "stream" {
+                store.append(&VideoItem::from_json(item));
+            }
+        }
+        store
+    }
+}
@@ -0,0 +1,392 @@
+//! UniFFI bindings, generate the foreign code with
+//! `cargo run -p rusty_pipe_ffi --bin uniffi-bindgen generate --library <lib> --language kotlin`
+//!
+//! Extraction results are plain records. Network access and javascript evaluation are done by
+//! a foreign implementation of [`Downloader`].
+
+use async_trait::async_trait;
+use rusty_pipe::elements::{StreamItem, YTSearchItem};
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTChannelInfoItemExtractor, YTPlaylistInfoItemExtractor, YTSearchExtractor,
+    YTStreamExtractor, YTStreamInfoItemExtractor,
+};
+use rusty_pipe::ParsingError;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+uniffi::setup_scaffolding!();
+
+#[derive(Debug, uniffi::Error)]
+pub enum ExtractionError {
+    Parsing {
+        cause: String,
+    },
+    AgeRestricted,
+    Download {
+        cause: String,
+    },
+    /// The server answered with an error `status`, a 404 means e.g. no SponsorBlock segments
+    Http {
+        cause: String,
+        status: u16,
+    },
+    ConsentRequired,
+    Callback {
+        cause: String,
+    },
+}
+
+impl fmt::Display for ExtractionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExtractionError::Parsing { cause } => write!(f, "Parsing Error : {}", cause),
+            ExtractionError::AgeRestricted => write!(f, "Age restricted video not supported"),
+            ExtractionError::Download { cause } | ExtractionError::Http { cause, .. } => {
+                write!(f, "Download Error : {}", cause)
+            }
+            ExtractionError::ConsentRequired => write!(f, "Redirected to the consent page"),
+            ExtractionError::Callback { cause } => write!(f, "Callback Error : {}", cause),
+        }
+    }
+}
+
+impl std::error::Error for ExtractionError {}
+
+impl From<ParsingError> for ExtractionError {
+    fn from(error: ParsingError) -> Self {
+        match error {
+            ParsingError::ParsingError { cause } => ExtractionError::Parsing { cause },
+            ParsingError::AgeRestricted => ExtractionError::AgeRestricted,
+            ParsingError::DownloadError { cause } => ExtractionError::Download { cause },
+            ParsingError::HttpError { cause, status } => ExtractionError::Http { cause, status },
+            ParsingError::ConsentRequired => ExtractionError::ConsentRequired,
+        }
+    }
+}
+
+impl From<uniffi::UnexpectedUniFFICallbackError> for ExtractionError {
+    fn from(error: uniffi::UnexpectedUniFFICallbackError) -> Self {
+        ExtractionError::Callback {
+            cause: error.reason,
+        }
+    }
+}
+
+/// Implemented by the host application. `download` is a suspend function in Kotlin and an
+/// async one in Swift and Python, so requests don't block the thread awaiting the extraction.
+#[uniffi::export(with_foreign)]
+#[async_trait]
+pub trait Downloader: Send + Sync {
+    async fn download(
+        &self,
+        url: String,
+        headers: HashMap<String, String>,
+    ) -> Result<String, ExtractionError>;
+    fn eval_js(&self, script: String) -> Result<String, ExtractionError>;
+}
+
+#[derive(Clone)]
+struct ForeignDownloader(Arc<dyn Downloader>);
+
+#[async_trait]
+impl rusty_pipe::Downloader for ForeignDownloader {
+    async fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.download_with_header(url, HashMap::new()).await
+    }
+
+    async fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        self.0
+            .download(url.to_string(), header)
+            .await
+            .map_err(|e| match e {
+                ExtractionError::Download { cause } => ParsingError::DownloadError { cause },
+                ExtractionError::Http { cause, status } => {
+                    ParsingError::HttpError { cause, status }
+                }
+                e => ParsingError::DownloadError {
+                    cause: e.to_string(),
+                },
+            })
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.0
+            .eval_js(script.to_string())
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Thumbnail {
+    pub url: String,
+    pub width: u64,
+    pub height: u64,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct Stream {
+    pub url: Option<String>,
+    pub itag: u32,
+    pub mime_type: String,
+    pub bitrate: u32,
+    pub quality: String,
+    pub quality_label: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub content_length: Option<u64>,
+    pub audio_sample_rate: Option<u32>,
+    pub audio_channels: Option<u32>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct VideoInfo {
+    pub id: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub upload_date: Option<String>,
+    pub length_seconds: Option<u64>,
+    pub view_count: Option<u64>,
+    pub like_count: Option<i64>,
+    pub dislike_count: Option<i64>,
+    pub uploader_name: Option<String>,
+    pub uploader_url: Option<String>,
+    pub uploader_avatars: Vec<Thumbnail>,
+    pub thumbnails: Vec<Thumbnail>,
+    pub video_streams: Vec<Stream>,
+    pub video_only_streams: Vec<Stream>,
+    pub audio_streams: Vec<Stream>,
+    pub related: Vec<SearchItem>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct StreamInfoItem {
+    pub video_id: Option<String>,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    pub is_live: bool,
+    pub duration_seconds: Option<i32>,
+    pub view_count: Option<i32>,
+    pub uploader_name: Option<String>,
+    pub uploader_url: Option<String>,
+    pub textual_upload_date: Option<String>,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ChannelInfoItem {
+    pub channel_id: Option<String>,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub description: Option<String>,
+    pub subscriber_count: Option<i32>,
+    pub stream_count: Option<i32>,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct PlaylistInfoItem {
+    pub playlist_id: Option<String>,
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub uploader_name: Option<String>,
+    pub stream_count: Option<i32>,
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+#[derive(Debug, Clone, uniffi::Enum)]
+pub enum SearchItem {
+    Stream { item: StreamInfoItem },
+    Channel { item: ChannelInfoItem },
+    Playlist { item: PlaylistInfoItem },
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct SearchPage {
+    pub items: Vec<SearchItem>,
+    pub next_page: Option<String>,
+}
+
+#[derive(Debug, Clone, uniffi::Record)]
+pub struct ChannelPage {
+    pub name: Option<String>,
+    pub avatars: Vec<Thumbnail>,
+    pub banners: Vec<Thumbnail>,
+    pub videos: Vec<StreamInfoItem>,
+    pub next_page: Option<String>,
+}
+
+#[derive(uniffi::Object)]
+pub struct RustyPipe {
+    downloader: ForeignDownloader,
+}
+
+#[uniffi::export]
+impl RustyPipe {
+    #[uniffi::constructor]
+    pub fn new(downloader: Arc<dyn Downloader>) -> Arc<Self> {
+        Arc::new(RustyPipe {
+            downloader: ForeignDownloader(downloader),
+        })
+    }
+
+    pub async fn stream(&self, video_id: String) -> Result<VideoInfo, ExtractionError> {
+        let extractor = YTStreamExtractor::new(self.downloader.clone(), &video_id).await?;
+        Ok(VideoInfo {
+            id: extractor.video_id(),
+            title: extractor.name().ok(),
+            description: extractor.description(false).ok().map(|(desc, _)| desc),
+            upload_date: extractor.textual_upload_date().ok(),
+            length_seconds: extractor.length().ok(),
+            view_count: extractor
+                .view_count()
+                .ok()
+                .and_then(|c| u64::try_from(c).ok()),
+            like_count: extractor
+                .like_count()
+                .ok()
+                .and_then(|c| i64::try_from(c).ok()),
+            dislike_count: extractor
+                .dislike_count()
+                .ok()
+                .and_then(|c| i64::try_from(c).ok()),
+            uploader_name: extractor.uploader_name().ok(),
+            uploader_url: extractor.uploader_url().ok(),
+            uploader_avatars: thumbnails(extractor.uploader_avatar_url()),
+            thumbnails: thumbnails(extractor.video_thumbnails()),
+            video_streams: streams(extractor.video_stream()),
+            video_only_streams: streams(extractor.video_only_stream()),
+            audio_streams: streams(extractor.audio_streams()),
+            related: extractor
+                .related()
+                .map(|items| items.iter().map(search_item).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    pub async fn search(
+        &self,
+        query: String,
+        page: Option<String>,
+    ) -> Result<SearchPage, ExtractionError> {
+        let extractor = YTSearchExtractor::new(self.downloader.clone(), &query, page).await?;
+        Ok(SearchPage {
+            items: extractor
+                .search_results()?
+                .iter()
+                .map(search_item)
+                .collect(),
+            next_page: extractor.next_page_url()?,
+        })
+    }
+
+    pub async fn search_suggestions(&self, query: String) -> Result<Vec<String>, ExtractionError> {
+        Ok(YTSearchExtractor::search_suggestion(&self.downloader, &query).await?)
+    }
+
+    pub async fn channel(
+        &self,
+        channel_id: String,
+        page: Option<String>,
+    ) -> Result<ChannelPage, ExtractionError> {
+        let extractor = YTChannelExtractor::new(self.downloader.clone(), &channel_id, page).await?;
+        Ok(ChannelPage {
+            name: extractor.name().ok(),
+            avatars: thumbnails(extractor.avatars()),
+            banners: thumbnails(extractor.banners()),
+            videos: extractor.videos()?.iter().map(stream_info_item).collect(),
+            next_page: extractor.next_page_url()?,
+        })
+    }
+}
+
+fn thumbnails(
+    thumbnails: Result<Vec<rusty_pipe::elements::Thumbnail>, ParsingError>,
+) -> Vec<Thumbnail> {
+    thumbnails
+        .unwrap_or_default()
+        .into_iter()
+        .map(|t| Thumbnail {
+            url: t.url,
+            width: t.width,
+            height: t.height,
+        })
+        .collect()
+}
+
+fn streams(streams: Result<Vec<StreamItem>, ParsingError>) -> Vec<Stream> {
+    streams
+        .unwrap_or_default()
+        .into_iter()
+        .map(|s| Stream {
+            url: s.url,
+            itag: s.itag,
+            mime_type: s.mime_type,
+            bitrate: s.bitrate,
+            quality: s.quality,
+            quality_label: s.quality_label,
+            width: s.width,
+            height: s.height,
+            content_length: s.content_length.and_then(|l| l.parse().ok()),
+            audio_sample_rate: s.audio_sample_rate.and_then(|r| r.parse().ok()),
+            audio_channels: s.audio_channels,
+        })
+        .collect()
+}
+
+fn stream_info_item(item: &YTStreamInfoItemExtractor) -> StreamInfoItem {
+    StreamInfoItem {
+        video_id: item.video_id().ok(),
+        title: item.name().ok(),
+        url: item.url().ok(),
+        is_live: item.is_live().unwrap_or(false),
+        duration_seconds: item.duration().ok(),
+        view_count: item.view_count().ok(),
+        uploader_name: item.uploader_name().ok(),
+        uploader_url: item.uploader_url().ok(),
+        textual_upload_date: item.textual_upload_date().ok(),
+        thumbnails: thumbnails(item.thumbnails()),
+    }
+}
+
+fn channel_info_item(item: &YTChannelInfoItemExtractor) -> ChannelInfoItem {
+    ChannelInfoItem {
+        channel_id: item.channel_id().ok(),
+        name: item.name().ok(),
+        url: item.url().ok(),
+        description: item.description().ok().flatten(),
+        subscriber_count: item.subscriber_count().ok(),
+        stream_count: item.stream_count().ok(),
+        thumbnails: thumbnails(item.thumbnails()),
+    }
+}
+
+fn playlist_info_item(item: &YTPlaylistInfoItemExtractor) -> PlaylistInfoItem {
+    PlaylistInfoItem {
+        playlist_id: item.playlist_id().ok(),
+        name: item.name().ok(),
+        url: item.url().ok(),
+        uploader_name: item.uploader_name().ok(),
+        stream_count: item.stream_count().ok(),
+        thumbnails: thumbnails(item.thumbnails()),
+    }
+}
+
+fn search_item(item: &YTSearchItem) -> SearchItem {
+    match item {
+        YTSearchItem::StreamInfoItem(item) => SearchItem::Stream {
+            item: stream_info_item(item),
+        },
+        YTSearchItem::ChannelInfoItem(item) => SearchItem::Channel {
+            item: channel_info_item(item),
+        },
+        YTSearchItem::PlaylistInfoItem(item) => SearchItem::Playlist {
+            item: playlist_info_item(item),
+        },
+    }
+}
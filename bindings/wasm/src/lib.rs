@@ -0,0 +1,98 @@
+//! Browser and worker bindings, build with `wasm-pack build bindings/wasm`
+//!
+//! Every method returns a promise resolving to a plain JSON object. Requests go through the
+//! host's `fetch` and signatures are decrypted with the host's own javascript engine.
+#![cfg(target_arch = "wasm32")]
+
+use js_sys::Promise;
+use rusty_pipe::downloaders::{DownloaderConfig, FetchDownloader};
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTPlaylistExtractor, YTSearchExtractor, YTStreamExtractor,
+    YTTrendingExtractor,
+};
+use rusty_pipe::{json, ParsingError};
+use serde_json::Value;
+use std::future::Future;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::future_to_promise;
+
+#[wasm_bindgen]
+pub struct RustyPipe {
+    downloader: FetchDownloader,
+}
+
+#[wasm_bindgen]
+impl RustyPipe {
+    /// `proxy` is put in front of every requested url, e.g. a CORS proxy
+    #[wasm_bindgen(constructor)]
+    pub fn new(proxy: Option<String>) -> RustyPipe {
+        let config = DownloaderConfig {
+            proxy,
+            ..DownloaderConfig::default()
+        };
+        RustyPipe {
+            downloader: FetchDownloader::with_config(config),
+        }
+    }
+
+    pub fn stream(&self, video_id: String) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let extractor = YTStreamExtractor::new(downloader, &video_id).await?;
+            Ok(json::stream_to_json(&extractor))
+        })
+    }
+
+    pub fn search(&self, query: String, page: Option<String>) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let extractor = YTSearchExtractor::new(downloader, &query, page).await?;
+            Ok(json::search_to_json(&extractor))
+        })
+    }
+
+    #[wasm_bindgen(js_name = searchSuggestions)]
+    pub fn search_suggestions(&self, query: String) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let suggestions = YTSearchExtractor::search_suggestion(&downloader, &query).await?;
+            Ok(Value::from(suggestions))
+        })
+    }
+
+    pub fn channel(&self, channel_id: String, page: Option<String>) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let extractor = YTChannelExtractor::new(downloader, &channel_id, page).await?;
+            Ok(json::channel_to_json(&extractor))
+        })
+    }
+
+    pub fn playlist(&self, playlist_id: String, page: Option<String>) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let extractor = YTPlaylistExtractor::new(downloader, &playlist_id, page).await?;
+            Ok(json::playlist_to_json(&extractor))
+        })
+    }
+
+    pub fn trending(&self) -> Promise {
+        let downloader = self.downloader.clone();
+        to_promise(async move {
+            let extractor = YTTrendingExtractor::new(downloader).await?;
+            Ok(json::trending_to_json(&extractor))
+        })
+    }
+}
+
+fn to_promise<F>(future: F) -> Promise
+where
+    F: Future<Output = Result<Value, ParsingError>> + 'static,
+{
+    future_to_promise(async move {
+        let value = future
+            .await
+            .map_err(|e| JsValue::from(js_sys::Error::new(&e.to_string())))?;
+        js_sys::JSON::parse(&value.to_string())
+    })
+}
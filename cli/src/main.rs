@@ -0,0 +1,502 @@
+mod table;
+
+use clap::{Args, Parser, Subcommand};
+use failure::{err_msg, Error};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rusty_pipe::diagnostics::{self, Report};
+use rusty_pipe::downloaders::{DownloaderConfig, ReqwestDownloader};
+use rusty_pipe::elements::{StreamItem, YTSearchItem};
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTPlaylistExtractor, YTSearchExtractor, YTStreamExtractor,
+    YTStreamInfoItemExtractor, YTTrendingExtractor,
+};
+use rusty_pipe::ratings::{RatingCount, RatingSource, ReturnYouTubeDislike};
+use rusty_pipe::{json, YTLink};
+use serde_json::Value;
+use table::Table;
+
+#[derive(Parser)]
+#[command(
+    name = "rusty-pipe",
+    version,
+    about = "Query YouTube without an API key"
+)]
+struct Cli {
+    /// Print JSON instead of tables, one document per page
+    #[arg(long, global = true)]
+    json: bool,
+    /// Proxy for all requests, e.g. socks5://127.0.0.1:9050
+    #[arg(long, global = true)]
+    proxy: Option<String>,
+    /// Send YouTube requests to this origin instead, e.g. http://127.0.0.1:8080 of rusty-pipe-mock
+    #[arg(long, global = true)]
+    origin: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct Paging {
+    /// Start at a next page URL printed by an earlier run
+    #[arg(long)]
+    page: Option<String>,
+    /// Number of pages to fetch
+    #[arg(long, default_value_t = 1)]
+    pages: u32,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the details of a video
+    Info {
+        url: String,
+        /// Fill in like and dislike counts from Return YouTube Dislike
+        #[arg(long)]
+        dislikes: bool,
+    },
+    /// List the streams of a video
+    Streams {
+        url: String,
+        /// Include the stream urls
+        #[arg(long)]
+        urls: bool,
+    },
+    /// Search for videos, channels and playlists
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        #[command(flatten)]
+        paging: Paging,
+    },
+    /// List the videos of a channel
+    Channel {
+        url: String,
+        #[command(flatten)]
+        paging: Paging,
+    },
+    /// List the videos of a playlist
+    Playlist {
+        url: String,
+        #[command(flatten)]
+        paging: Paging,
+    },
+    /// List trending videos
+    Trending,
+    /// Show search suggestions
+    Suggest {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Run every field accessor and report the ones that failed or fell back, exits with an
+    /// error if any failed
+    Doctor {
+        /// Video, channel or playlist URLs, trending, a search and a trending video if none given
+        urls: Vec<String>,
+        /// Also check the results of this search
+        #[arg(long)]
+        search: Vec<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    reset_sigpipe();
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Rust ignores SIGPIPE, which makes `println!` panic once a pipe like `| head` is closed.
+/// With the default action the process ends quietly like other command line tools.
+#[cfg(unix)]
+fn reset_sigpipe() {
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+}
+
+#[cfg(not(unix))]
+fn reset_sigpipe() {}
+
+async fn run(cli: Cli) -> Result<(), Error> {
+    let downloader = ReqwestDownloader::with_config(DownloaderConfig {
+        proxy: cli.proxy.clone(),
+        origin_override: cli.origin.clone(),
+        ..DownloaderConfig::default()
+    })?;
+    let as_json = cli.json;
+
+    match cli.command {
+        Command::Info { url, dislikes } => {
+            let video_id = stream_id(&url)?;
+            let mut extractor = YTStreamExtractor::new(downloader.clone(), &video_id).await?;
+            if dislikes {
+                let provider = ReturnYouTubeDislike::new(downloader);
+                if let Err(e) = extractor.load_ratings(&provider).await {
+                    eprintln!("warning: no Return YouTube Dislike counts: {}", e);
+                }
+            }
+            if as_json {
+                println!("{}", json::stream_to_json(&extractor));
+            } else {
+                let field = |name: &str, value: Option<String>| {
+                    println!("{:<12} {}", name, value.unwrap_or_else(|| "-".to_string()))
+                };
+                field("Title", extractor.name().ok());
+                field("Id", Some(extractor.video_id()));
+                field("Uploader", extractor.uploader_name().ok());
+                field("Channel", extractor.uploader_url().ok());
+                field("Uploaded", extractor.textual_upload_date().ok());
+                field("Length", extractor.length().ok().map(format_duration));
+                field("Views", extractor.view_count().ok().map(|c| c.to_string()));
+                let rating = |count: RatingCount| match count.source {
+                    RatingSource::YouTube => count.count.to_string(),
+                    RatingSource::ReturnYouTubeDislike => format!("{} (RYD)", count.count),
+                };
+                field("Likes", extractor.like_count_with_source().ok().map(rating));
+                if dislikes {
+                    field(
+                        "Dislikes",
+                        extractor.dislike_count_with_source().ok().map(rating),
+                    );
+                }
+                if let Ok((description, _)) = extractor.description(false) {
+                    println!();
+                    println!("{}", description);
+                }
+            }
+        }
+        Command::Streams { url, urls } => {
+            let video_id = stream_id(&url)?;
+            let extractor = YTStreamExtractor::new(downloader, &video_id).await?;
+            if as_json {
+                let info = json::stream_to_json(&extractor);
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "videoStreams": info["videoStreams"],
+                        "videoOnlyStreams": info["videoOnlyStreams"],
+                        "audioStreams": info["audioStreams"],
+                    })
+                );
+            } else {
+                let mut header = vec!["ITAG", "KIND", "QUALITY", "TYPE", "BITRATE", "SIZE"];
+                if urls {
+                    header.push("URL");
+                }
+                let mut table = Table::new(header);
+                let kinds = vec![
+                    ("video", extractor.video_stream()?),
+                    ("video-only", extractor.video_only_stream()?),
+                    ("audio", extractor.audio_streams()?),
+                ];
+                for (kind, streams) in kinds {
+                    for stream in streams {
+                        table.row(stream_row(kind, &stream, urls));
+                    }
+                }
+                table.print();
+            }
+        }
+        Command::Search { query, paging } => {
+            let query = encode(&query.join(" "));
+            let mut page = paging.page;
+            for _ in 0..paging.pages {
+                let extractor = YTSearchExtractor::new(downloader.clone(), &query, page).await?;
+                if as_json {
+                    println!("{}", json::search_to_json(&extractor));
+                } else {
+                    let mut table = Table::new(vec!["TYPE", "TITLE", "BY", "LENGTH", "URL"]);
+                    for item in extractor.search_results()? {
+                        table.row(search_row(&item));
+                    }
+                    table.print();
+                }
+                page = extractor.next_page_url()?;
+                if page.is_none() {
+                    break;
+                }
+            }
+            print_next_page(as_json, page);
+        }
+        Command::Channel { url, paging } => {
+            let channel_id = channel_id(&url)?;
+            let mut page = paging.page;
+            for _ in 0..paging.pages {
+                let extractor =
+                    YTChannelExtractor::new(downloader.clone(), &channel_id, page).await?;
+                if as_json {
+                    println!("{}", json::channel_to_json(&extractor));
+                } else {
+                    println!("{}", extractor.name().unwrap_or_default());
+                    print_videos(&extractor.videos()?);
+                }
+                page = extractor.next_page_url()?;
+                if page.is_none() {
+                    break;
+                }
+            }
+            print_next_page(as_json, page);
+        }
+        Command::Playlist { url, paging } => {
+            let playlist_id = playlist_id(&url)?;
+            let mut page = paging.page;
+            for _ in 0..paging.pages {
+                let extractor =
+                    YTPlaylistExtractor::new(downloader.clone(), &playlist_id, page).await?;
+                if as_json {
+                    println!("{}", json::playlist_to_json(&extractor));
+                } else {
+                    println!(
+                        "{} by {} ({} videos)",
+                        extractor.name().unwrap_or_default(),
+                        extractor.uploader_name().unwrap_or_default(),
+                        extractor.stream_count().unwrap_or_default()
+                    );
+                    print_videos(&extractor.videos()?);
+                }
+                page = extractor.next_page_url()?;
+                if page.is_none() {
+                    break;
+                }
+            }
+            print_next_page(as_json, page);
+        }
+        Command::Trending => {
+            let extractor = YTTrendingExtractor::new(downloader).await?;
+            if as_json {
+                println!("{}", json::trending_to_json(&extractor));
+            } else {
+                print_videos(&extractor.videos()?);
+            }
+        }
+        Command::Suggest { query } => {
+            let query = encode(&query.join(" "));
+            let suggestions = YTSearchExtractor::search_suggestion(&downloader, &query).await?;
+            if as_json {
+                println!("{}", Value::from(suggestions));
+            } else {
+                for suggestion in suggestions {
+                    println!("{}", suggestion);
+                }
+            }
+        }
+        Command::Doctor { urls, search } => {
+            let reports = doctor(downloader, urls, search).await?;
+            if as_json {
+                println!("{}", serde_json::to_string(&reports)?);
+            } else {
+                let mut table = Table::new(vec![
+                    "EXTRACTOR",
+                    "FIELD",
+                    "OK",
+                    "FALLBACK",
+                    "FAILED",
+                    "ERROR",
+                ]);
+                for report in &reports {
+                    for field in &report.fields {
+                        let fallback = if field.fell_back > 0 {
+                            format!(
+                                "{} ({})",
+                                field.fell_back,
+                                field.fallback_sources.join(", ")
+                            )
+                        } else {
+                            "0".to_string()
+                        };
+                        table.row(vec![
+                            report.extractor.to_string(),
+                            field.field.clone(),
+                            field.succeeded.to_string(),
+                            fallback,
+                            field.failed.to_string(),
+                            field.error.clone().unwrap_or_default(),
+                        ]);
+                    }
+                }
+                table.print();
+            }
+            let failed: Vec<&str> = reports
+                .iter()
+                .flat_map(|r| r.failed().map(move |f| f.field.as_str()))
+                .collect();
+            if !failed.is_empty() {
+                return Err(err_msg(format!("fields failed: {}", failed.join(", "))));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn doctor(
+    downloader: ReqwestDownloader,
+    urls: Vec<String>,
+    search: Vec<String>,
+) -> Result<Vec<Report>, Error> {
+    let mut reports = vec![];
+    if urls.is_empty() && search.is_empty() {
+        let trending = YTTrendingExtractor::new(downloader.clone()).await?;
+        reports.push(diagnostics::trending(&trending));
+        let search = YTSearchExtractor::new(downloader.clone(), "music", None).await?;
+        reports.push(diagnostics::search(&search));
+        if let Some(video_id) = trending.videos()?.iter().find_map(|v| v.video_id().ok()) {
+            let stream = YTStreamExtractor::new(downloader.clone(), &video_id).await?;
+            reports.push(diagnostics::stream(&stream));
+        }
+    }
+    for url in urls {
+        match YTLink::parse_or_id(&url) {
+            Some(YTLink::Stream(video_id)) => {
+                let extractor = YTStreamExtractor::new(downloader.clone(), &video_id).await?;
+                reports.push(diagnostics::stream(&extractor));
+            }
+            Some(YTLink::Playlist(playlist_id)) => {
+                let extractor =
+                    YTPlaylistExtractor::new(downloader.clone(), &playlist_id, None).await?;
+                reports.push(diagnostics::playlist(&extractor));
+            }
+            Some(YTLink::Channel(channel_id)) => {
+                let extractor =
+                    YTChannelExtractor::new(downloader.clone(), &channel_id, None).await?;
+                reports.push(diagnostics::channel(&extractor));
+            }
+            _ => return Err(not_a(&url, "video, playlist or channel")),
+        }
+    }
+    for query in search {
+        let extractor = YTSearchExtractor::new(downloader.clone(), &encode(&query), None).await?;
+        reports.push(diagnostics::search(&extractor));
+    }
+    Ok(reports)
+}
+
+fn stream_id(url: &str) -> Result<String, Error> {
+    match YTLink::parse_or_id(url) {
+        Some(YTLink::Stream(id)) => Ok(id),
+        _ => Err(not_a(url, "video")),
+    }
+}
+
+/// Also takes watch URLs that carry a `list` parameter
+fn playlist_id(url: &str) -> Result<String, Error> {
+    match YTLink::playlist_id(url)
+        .map(YTLink::Playlist)
+        .or_else(|| YTLink::parse_or_id(url))
+    {
+        Some(YTLink::Playlist(id)) => Ok(id),
+        _ => Err(not_a(url, "playlist")),
+    }
+}
+
+fn channel_id(url: &str) -> Result<String, Error> {
+    match YTLink::parse_or_id(url) {
+        Some(YTLink::Channel(id)) => Ok(id),
+        _ => Err(not_a(url, "channel")),
+    }
+}
+
+fn not_a(url: &str, kind: &str) -> Error {
+    err_msg(format!("{} is not a YouTube {} URL or id", url, kind))
+}
+
+fn encode(query: &str) -> String {
+    utf8_percent_encode(query, NON_ALPHANUMERIC).to_string()
+}
+
+fn format_duration(seconds: u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+fn stream_row(kind: &str, stream: &StreamItem, with_url: bool) -> Vec<String> {
+    let size = match (stream.width, stream.height) {
+        (Some(width), Some(height)) => format!("{}x{}", width, height),
+        _ => String::new(),
+    };
+    let mut row = vec![
+        stream.itag.to_string(),
+        kind.to_string(),
+        stream
+            .quality_label
+            .clone()
+            .unwrap_or_else(|| stream.quality.clone()),
+        stream.mime_type.clone(),
+        format!("{}k", stream.bitrate / 1000),
+        size,
+    ];
+    if with_url {
+        row.push(stream.url.clone().unwrap_or_default());
+    }
+    row
+}
+
+fn video_length(video: &YTStreamInfoItemExtractor) -> String {
+    if video.is_live().unwrap_or(false) {
+        "live".to_string()
+    } else {
+        video.textual_duration().unwrap_or_default()
+    }
+}
+
+fn search_row(item: &YTSearchItem) -> Vec<String> {
+    match item {
+        YTSearchItem::StreamInfoItem(video) => vec![
+            "video".to_string(),
+            video.name().unwrap_or_default(),
+            video.uploader_name().unwrap_or_default(),
+            video_length(video),
+            video.url().unwrap_or_default(),
+        ],
+        YTSearchItem::ChannelInfoItem(channel) => vec![
+            "channel".to_string(),
+            channel.name().unwrap_or_default(),
+            String::new(),
+            String::new(),
+            channel.url().unwrap_or_default(),
+        ],
+        YTSearchItem::PlaylistInfoItem(playlist) => vec![
+            "playlist".to_string(),
+            playlist.name().unwrap_or_default(),
+            playlist.uploader_name().unwrap_or_default(),
+            playlist
+                .stream_count()
+                .map(|c| format!("{} videos", c))
+                .unwrap_or_default(),
+            playlist.url().unwrap_or_default(),
+        ],
+    }
+}
+
+fn print_videos(videos: &[YTStreamInfoItemExtractor]) {
+    let mut table = Table::new(vec![
+        "TITLE", "UPLOADER", "LENGTH", "UPLOADED", "VIEWS", "URL",
+    ]);
+    for video in videos {
+        table.row(vec![
+            video.name().unwrap_or_default(),
+            video.uploader_name().unwrap_or_default(),
+            video_length(video),
+            video.textual_upload_date().unwrap_or_default(),
+            video
+                .view_count()
+                .map(|c| c.to_string())
+                .unwrap_or_default(),
+            video.url().unwrap_or_default(),
+        ]);
+    }
+    table.print();
+}
+
+/// In JSON mode the next page is part of each document
+fn print_next_page(as_json: bool, page: Option<String>) {
+    if let (false, Some(page)) = (as_json, page) {
+        eprintln!("Next page: --page '{}'", page);
+    }
+}
@@ -0,0 +1,56 @@
+const MAX_WIDTH: usize = 60;
+
+/// Left aligned plain text table, cells wider than `MAX_WIDTH` are cut
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: Vec<&'static str>) -> Self {
+        Table {
+            header,
+            rows: vec![],
+        }
+    }
+
+    pub fn row(&mut self, row: Vec<String>) {
+        self.rows
+            .push(row.into_iter().map(|cell| truncate(&cell)).collect());
+    }
+
+    pub fn print(&self) {
+        let mut widths: Vec<usize> = self.header.iter().map(|h| h.chars().count()).collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        let header: Vec<String> = self.header.iter().map(|h| h.to_string()).collect();
+        print_row(&widths, &header);
+        for row in &self.rows {
+            print_row(&widths, row);
+        }
+    }
+}
+
+fn truncate(cell: &str) -> String {
+    let cell = cell.replace('\n', " ");
+    if cell.chars().count() > MAX_WIDTH {
+        let mut cut: String = cell.chars().take(MAX_WIDTH - 1).collect();
+        cut.push('…');
+        cut
+    } else {
+        cell
+    }
+}
+
+fn print_row(widths: &[usize], row: &[String]) {
+    let line = widths
+        .iter()
+        .zip(row)
+        .map(|(width, cell)| format!("{:<width$}", cell, width = width))
+        .collect::<Vec<String>>()
+        .join("  ");
+    println!("{}", line.trim_end());
+}
@@ -0,0 +1,25 @@
+extern crate rusty_pipe;
+
+use rusty_pipe::blocking::{YTSearchExtractor, YTStreamExtractor};
+use rusty_pipe::downloaders::UreqDownloader;
+use rusty_pipe::elements::YTSearchItem;
+
+use failure::Error;
+
+fn main() -> Result<(), Error> {
+    let downloader = UreqDownloader::new()?;
+
+    let search_extractor = YTSearchExtractor::new(downloader.clone(), "rust", None)?;
+    for item in search_extractor.search_results()? {
+        if let YTSearchItem::StreamInfoItem(stream) = item {
+            println!("{:#?} {:#?}", stream.video_id(), stream.name());
+        }
+    }
+
+    let stream_extractor = YTStreamExtractor::new(downloader, "09R8_2nJtjg")?;
+    println!("title: {:#?}", stream_extractor.name());
+    println!("uploader name: {:#?}", stream_extractor.uploader_name());
+    println!("AUDIO ONLY STREAMS \n");
+    println!("{:#?}", stream_extractor.audio_streams());
+    Ok(())
+}
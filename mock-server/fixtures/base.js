@@ -0,0 +1,6 @@
+var _yt_player={};(function(g){
+var Ab={rv:function(a){a.reverse()},
+sw:function(a,b){var c=a[0];a[0]=a[b%a.length];a[b%a.length]=c}};
+var Xy=function(a){a=a.split("");Ab.rv(a);Ab.sw(a,3);return a.join("")};
+g.mockSignature=Xy;
+})(_yt_player);
@@ -0,0 +1,350 @@
+//! The fixed catalogue the mock serves, rendered into the templates in `fixtures/`
+
+use serde_json::{json, Value};
+
+pub const CHANNEL_ID: &str = "UCrustypipemockchannel01";
+pub const CHANNEL_NAME: &str = "Mock Channel";
+pub const CHANNEL_HANDLE: &str = "@mockchannel";
+pub const PLAYLIST_ID: &str = "PLrustypipemock0001";
+pub const PLAYLIST_TITLE: &str = "Mock playlist";
+/// The mock channel has this many videos, see [`channel_videos`] for the others
+pub const CHANNEL_VIDEOS: usize = 90;
+pub const PLAYLIST_VIDEOS: usize = 45;
+pub const PAGE_SIZE: usize = 30;
+pub const SEARCH_PAGES: u32 = 3;
+
+const VIDEO_RENDERER: &str = include_str!("../fixtures/video_renderer.json");
+const CHANNEL_RENDERER: &str = include_str!("../fixtures/channel_renderer.json");
+const PLAYLIST_RENDERER: &str = include_str!("../fixtures/playlist_renderer.json");
+pub const SEARCH: &str = include_str!("../fixtures/search.json");
+pub const CHANNEL: &str = include_str!("../fixtures/channel.json");
+pub const CHANNEL_CONTINUATION: &str = include_str!("../fixtures/channel_continuation.json");
+pub const CHANNEL_REDIRECT: &str = include_str!("../fixtures/channel_redirect.json");
+pub const PLAYLIST: &str = include_str!("../fixtures/playlist.json");
+pub const PLAYLIST_CONTINUATION: &str = include_str!("../fixtures/playlist_continuation.json");
+pub const TRENDING: &str = include_str!("../fixtures/trending.json");
+pub const PLAYER_RESPONSE: &str = include_str!("../fixtures/player_response.json");
+pub const FORMATS: &str = include_str!("../fixtures/formats.json");
+pub const WATCH: &str = include_str!("../fixtures/watch.json");
+pub const WATCH_AGE_GATE: &str = include_str!("../fixtures/watch_age_gate.json");
+pub const WATCH_HTML: &str = include_str!("../fixtures/watch.html");
+pub const CONSENT_HTML: &str = include_str!("../fixtures/consent.html");
+pub const PLAYER_JS: &str = include_str!("../fixtures/base.js");
+pub const PLAYER_JS_PATH: &str = "/s/player/mock0001/player_ias.vflset/en_US/base.js";
+
+/// Fills a template
+///
+/// A quoted `"{{key}}"` is replaced by the JSON of the value, so templates stay valid JSON
+/// and can take arrays and objects. `{{key}}` anywhere else is replaced by the value's text,
+/// escaped for use inside a JSON string.
+pub fn render(template: &str, values: &[(&str, Value)]) -> String {
+    let mut rendered = template.to_string();
+    for (key, value) in values {
+        rendered = rendered.replace(&format!("\"{{{{{}}}}}\"", key), &value.to_string());
+    }
+    for (key, value) in values {
+        let text = match value {
+            Value::String(s) => {
+                let quoted = Value::from(s.as_str()).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+            other => other.to_string(),
+        };
+        rendered = rendered.replace(&format!("{{{{{}}}}}", key), &text);
+    }
+    rendered
+}
+
+/// Like `render`, for templates that are raw text rather than JSON
+pub fn render_text(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = template.to_string();
+    for (key, value) in values {
+        rendered = rendered.replace(&format!("{{{{{}}}}}", key), value);
+    }
+    rendered
+}
+
+fn render_json(template: &str, values: &[(&str, Value)]) -> Value {
+    serde_json::from_str(&render(template, values)).expect("fixture is not valid JSON")
+}
+
+/// Mock video ids are `mockvideo` and two digits
+pub fn video_id(n: usize) -> String {
+    format!("mockvideo{:02}", n % 100)
+}
+
+pub fn video_number(video_id: &str) -> Option<usize> {
+    video_id.strip_prefix("mockvideo")?.parse().ok()
+}
+
+pub struct Video {
+    pub id: String,
+    pub title: String,
+    pub length_seconds: u64,
+    pub view_count: u64,
+    pub days_ago: u64,
+}
+
+impl Video {
+    pub fn new(n: usize) -> Self {
+        Video {
+            id: video_id(n),
+            title: format!("Mock video {}", n),
+            length_seconds: 60 + n as u64 * 7,
+            view_count: 1000 * (n as u64 + 1),
+            days_ago: n as u64 + 1,
+        }
+    }
+
+    pub fn length_text(&self) -> String {
+        format!(
+            "{}:{:02}",
+            self.length_seconds / 60,
+            self.length_seconds % 60
+        )
+    }
+
+    pub fn upload_date(&self) -> String {
+        (chrono::Utc::now().date_naive() - chrono::Duration::days(self.days_ago as i64))
+            .format("%Y-%m-%d")
+            .to_string()
+    }
+
+    fn values(&self, channel_id: &str, index: usize) -> Vec<(&'static str, Value)> {
+        vec![
+            ("video_id", json!(self.id)),
+            ("title", json!(self.title)),
+            ("channel_id", json!(channel_id)),
+            ("channel_name", json!(CHANNEL_NAME)),
+            ("published", json!(format!("{} days ago", self.days_ago))),
+            ("length_text", json!(self.length_text())),
+            (
+                "view_count_text",
+                json!(format!("{} views", group_digits(self.view_count))),
+            ),
+            ("index", json!((index + 1).to_string())),
+        ]
+    }
+
+    /// The item under `key`, e.g. `videoRenderer` or `gridVideoRenderer`
+    pub fn renderer(&self, key: &str, channel_id: &str, index: usize) -> Value {
+        json!({ key: render_json(VIDEO_RENDERER, &self.values(channel_id, index)) })
+    }
+}
+
+/// SponsorBlock segments of a mock video, even numbered ones have a sponsor and a highlight
+pub fn skip_segments_of(video: &Video) -> Vec<Value> {
+    let n = video_number(&video.id).unwrap_or_default();
+    if n % 2 == 1 {
+        return vec![];
+    }
+    vec![
+        json!({
+            "segment": [10.0, 25.5],
+            "UUID": format!("{}sponsor", video.id),
+            "category": "sponsor",
+            "actionType": "skip",
+            "videoDuration": video.length_seconds,
+            "locked": 0,
+            "votes": 3,
+        }),
+        json!({
+            "segment": [40.0, 40.0],
+            "UUID": format!("{}highlight", video.id),
+            "category": "poi_highlight",
+            "actionType": "poi",
+            "videoDuration": video.length_seconds,
+            "locked": 0,
+            "votes": 1,
+        }),
+    ]
+}
+
+/// DeArrow submissions of a mock video: every third one has a new title and thumbnail, the
+/// ones after them had their original title voted for
+pub fn branding(video: &Video) -> Option<Value> {
+    let n = video_number(&video.id)?;
+    let (titles, thumbnails) = match n % 3 {
+        0 => (
+            json!([
+                { "title": format!(">Mock video {} explained", n), "original": false, "votes": 2, "locked": false, "UUID": format!("{}title", video.id) },
+                { "title": video.title, "original": true, "votes": 0, "locked": false, "UUID": format!("{}original", video.id) },
+            ]),
+            json!([
+                { "timestamp": 12.5, "original": false, "votes": 1, "locked": false, "UUID": format!("{}thumbnail", video.id) },
+            ]),
+        ),
+        1 => (
+            json!([
+                { "title": video.title, "original": true, "votes": 3, "locked": false, "UUID": format!("{}original", video.id) },
+            ]),
+            json!([]),
+        ),
+        _ => return None,
+    };
+    Some(json!({
+        "titles": titles,
+        "thumbnails": thumbnails,
+        "randomTime": 0.5,
+        "videoDuration": video.length_seconds,
+    }))
+}
+
+pub fn group_digits(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+pub fn channel_renderer(channel_id: &str) -> Value {
+    json!({
+        "channelRenderer": render_json(CHANNEL_RENDERER, &[
+            ("channel_id", json!(channel_id)),
+            ("channel_name", json!(CHANNEL_NAME)),
+            ("video_count", json!(CHANNEL_VIDEOS.to_string())),
+        ])
+    })
+}
+
+pub fn playlist_renderer() -> Value {
+    json!({
+        "playlistRenderer": render_json(PLAYLIST_RENDERER, &[
+            ("playlist_id", json!(PLAYLIST_ID)),
+            ("title", json!(PLAYLIST_TITLE)),
+            ("first_video_id", json!(video_id(0))),
+            ("video_count", json!(PLAYLIST_VIDEOS.to_string())),
+            ("channel_id", json!(CHANNEL_ID)),
+            ("channel_name", json!(CHANNEL_NAME)),
+        ])
+    })
+}
+
+/// `continuations` array pointing at the next page, empty on the last one
+pub fn continuations(token: Option<String>) -> Value {
+    match token {
+        Some(token) => json!([{
+            "nextContinuationData": {
+                "continuation": token,
+                "clickTrackingParams": "CBAQybcCIhMI-mock",
+            }
+        }]),
+        None => json!([]),
+    }
+}
+
+/// Continuation tokens are `channel.<id>.<page>` and `playlist.<id>.<page>`
+pub fn continuation_token(kind: &str, id: &str, page: usize) -> String {
+    format!("{}.{}.{}", kind, id, page)
+}
+
+pub fn parse_continuation_token(token: &str) -> Option<(&str, &str, usize)> {
+    let mut split = token.splitn(3, '.');
+    let kind = split.next()?;
+    let id = split.next()?;
+    let page = split.next()?.parse().ok()?;
+    Some((kind, id, page))
+}
+
+/// Grid items and continuation of a page of channel videos, pages start at 1
+pub fn channel_page(channel_id: &str, page: usize) -> (Value, Value) {
+    let videos = channel_videos(channel_id);
+    let start = ((page - 1) * PAGE_SIZE).min(videos.len());
+    let end = (start + PAGE_SIZE).min(videos.len());
+    let items: Vec<Value> = videos[start..end]
+        .iter()
+        .enumerate()
+        .map(|(i, n)| Video::new(*n).renderer("gridVideoRenderer", channel_id, start + i))
+        .collect();
+    let next = if end < videos.len() {
+        Some(continuation_token("channel", channel_id, page + 1))
+    } else {
+        None
+    };
+    (Value::from(items), continuations(next))
+}
+
+/// Id of the `n`th channel, the first is [`CHANNEL_ID`]
+pub fn channel_id(n: usize) -> String {
+    format!("UCrustypipemockchannel{:02}", n % 100)
+}
+
+/// Numbers of a channel's videos, newest first. The mock channel has all of them, the other
+/// channels of [`channel_id`] every third, so that they share their videos with the mock
+/// channel and neighbouring ones share none.
+pub fn channel_videos(channel_id: &str) -> Vec<usize> {
+    let n = channel_id
+        .strip_prefix("UCrustypipemockchannel")
+        .and_then(|n| n.parse::<usize>().ok())
+        .filter(|n| *n != 1);
+    (0..CHANNEL_VIDEOS)
+        .filter(|video| n.is_none_or(|n| video % 3 == n % 3))
+        .collect()
+}
+
+/// Videos and continuation of a page of the playlist, pages start at 1
+pub fn playlist_page(playlist_id: &str, page: usize) -> (Value, Value) {
+    let start = (page - 1) * PAGE_SIZE;
+    let end = (start + PAGE_SIZE).min(PLAYLIST_VIDEOS);
+    let items: Vec<Value> = (start..end)
+        .map(|n| Video::new(n).renderer("playlistVideoRenderer", CHANNEL_ID, n))
+        .collect();
+    let next = if end < PLAYLIST_VIDEOS {
+        Some(continuation_token("playlist", playlist_id, page + 1))
+    } else {
+        None
+    };
+    (Value::from(items), continuations(next))
+}
+
+pub fn search_page(page: u32) -> Value {
+    if page > SEARCH_PAGES {
+        return json!([{ "backgroundPromoRenderer": {} }]);
+    }
+    let mut items = vec![];
+    if page == 1 {
+        items.push(channel_renderer(CHANNEL_ID));
+        items.push(playlist_renderer());
+    }
+    let start = (page as usize - 1) * 20;
+    for n in start..start + 20 {
+        items.push(Video::new(n).renderer("videoRenderer", CHANNEL_ID, n));
+    }
+    Value::from(items)
+}
+
+pub fn trending_shelves() -> (Value, Value) {
+    let shelf = |range: std::ops::Range<usize>| -> Value {
+        range
+            .map(|n| Video::new(n).renderer("videoRenderer", CHANNEL_ID, n))
+            .collect()
+    };
+    (shelf(0..10), shelf(10..20))
+}
+
+pub fn related(video: &Video) -> Value {
+    let first = video_number(&video.id).unwrap_or_default() + 1;
+    (first..first + 5)
+        .map(|n| Video::new(n).renderer("compactVideoRenderer", CHANNEL_ID, n))
+        .collect()
+}
+
+/// The signature a ciphered format's url needs, checked by `/videoplayback`
+pub fn signature(video_id: &str, itag: u64) -> String {
+    format!("MOCKSIG{}{}AOq0QJ8wRAIg", itag, video_id)
+}
+
+/// Inverse of the transform in `fixtures/base.js`: reverse, then swap the first and fourth
+/// character
+pub fn scramble(signature: &str) -> String {
+    let mut chars: Vec<char> = signature.chars().collect();
+    let len = chars.len();
+    chars.swap(0, 3 % len);
+    chars.reverse();
+    chars.into_iter().collect()
+}
@@ -0,0 +1,34 @@
+use clap::Parser;
+use rusty_pipe_mock::{FailureMode, MockServer};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "rusty-pipe-mock",
+    version,
+    about = "Serve canned YouTube responses for offline testing"
+)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    listen: String,
+    /// Misbehave: consent, rate-limited, age-gate, ciphered or hidden-ratings
+    #[arg(long)]
+    failure: Option<FailureMode>,
+    /// Seconds stream urls stay valid
+    #[arg(long)]
+    url_ttl: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let args = Args::parse();
+    let mut server = MockServer::bind(&args.listen).await?;
+    server.set_failure(args.failure);
+    if let Some(ttl) = args.url_ttl {
+        server.set_url_ttl(Duration::from_secs(ttl));
+    }
+    eprintln!("Serving on {}", server.origin());
+    server.wait().await;
+    Ok(())
+}
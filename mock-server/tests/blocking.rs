@@ -0,0 +1,47 @@
+//! The blocking extractors, called from a thread without an async runtime
+
+use rusty_pipe::blocking::{YTChannelExtractor, YTStreamExtractor};
+use rusty_pipe::downloaders::UreqDownloader;
+use rusty_pipe::ratings::{RatingCount, RatingSource, ReturnYouTubeDislike};
+use rusty_pipe_mock::fixtures::{video_id, Video, CHANNEL_HANDLE, CHANNEL_ID};
+use rusty_pipe_mock::{FailureMode, MockServer};
+
+#[test]
+fn blocking_requests() {
+    // The mock is served by the runtime's worker threads
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let mock = runtime.block_on(MockServer::start()).unwrap();
+    let downloader = UreqDownloader::with_config(mock.downloader_config()).unwrap();
+
+    let channel_id =
+        YTChannelExtractor::resolve_channel_id(downloader.clone(), CHANNEL_HANDLE).unwrap();
+    assert_eq!(channel_id, CHANNEL_ID);
+
+    let mut stream = YTStreamExtractor::new(downloader.clone(), &video_id(1)).unwrap();
+    assert_eq!(stream.name().unwrap(), Video::new(1).title);
+    stream
+        .load_ratings(&ReturnYouTubeDislike::new(downloader.clone()))
+        .unwrap();
+    // The watch page has counts of its own, which win over the loaded ones
+    assert_eq!(
+        stream.dislike_count_with_source().unwrap(),
+        RatingCount {
+            count: Video::new(1).view_count as i128 / 400,
+            source: RatingSource::YouTube,
+        }
+    );
+
+    mock.set_failure(Some(FailureMode::HiddenRatings));
+    let mut stream = YTStreamExtractor::new(downloader.clone(), &video_id(2)).unwrap();
+    assert!(stream.dislike_count().is_err());
+    stream
+        .load_ratings(&ReturnYouTubeDislike::new(downloader))
+        .unwrap();
+    assert_eq!(
+        stream.dislike_count_with_source().unwrap(),
+        RatingCount {
+            count: Video::new(2).view_count as i128 / 400,
+            source: RatingSource::ReturnYouTubeDislike,
+        }
+    );
+}
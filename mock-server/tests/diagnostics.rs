@@ -0,0 +1,127 @@
+//! The field reports of every extractor against the mock, and that taking them leaves the
+//! parse failure counters alone
+
+use rusty_pipe::diagnostics::{self, FieldReport, Report};
+use rusty_pipe::downloaders::ReqwestDownloader;
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTPlaylistExtractor, YTSearchExtractor, YTStreamExtractor,
+    YTTrendingExtractor,
+};
+use rusty_pipe::metrics;
+use rusty_pipe::ratings::ReturnYouTubeDislike;
+use rusty_pipe_mock::fixtures::{video_id, CHANNEL_ID, PAGE_SIZE, PLAYLIST_ID};
+use rusty_pipe_mock::{FailureMode, MockServer};
+
+fn field<'a>(report: &'a Report, name: &str) -> &'a FieldReport {
+    report
+        .fields
+        .iter()
+        .find(|f| f.field == name)
+        .unwrap_or_else(|| panic!("{} has no field {}", report.extractor, name))
+}
+
+fn assert_succeeded(report: &Report, name: &str, count: u64) {
+    let field = field(report, name);
+    assert_eq!(
+        (field.succeeded, field.fell_back, field.failed),
+        (count, 0, 0),
+        "{:?}",
+        field
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn field_reports() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let stream = YTStreamExtractor::new(downloader.clone(), &video_id(1))
+        .await
+        .unwrap();
+    mock.set_failure(Some(FailureMode::Ciphered));
+    let ciphered = YTStreamExtractor::new(downloader.clone(), &video_id(1))
+        .await
+        .unwrap();
+    mock.set_failure(Some(FailureMode::HiddenRatings));
+    let hidden_ratings = YTStreamExtractor::new(downloader.clone(), &video_id(1))
+        .await
+        .unwrap();
+    let mut with_ratings = YTStreamExtractor::new(downloader.clone(), &video_id(1))
+        .await
+        .unwrap();
+    with_ratings
+        .load_ratings(&ReturnYouTubeDislike::new(downloader.clone()))
+        .await
+        .unwrap();
+    mock.set_failure(None);
+    let channel = YTChannelExtractor::new(downloader.clone(), CHANNEL_ID, None)
+        .await
+        .unwrap();
+    let playlist = YTPlaylistExtractor::new(downloader.clone(), PLAYLIST_ID, None)
+        .await
+        .unwrap();
+    let search = YTSearchExtractor::new(downloader.clone(), "mock", None)
+        .await
+        .unwrap();
+    let trending = YTTrendingExtractor::new(downloader).await.unwrap();
+    let failures = metrics::parse_failures();
+
+    let report = diagnostics::stream(&stream);
+    assert_eq!(report.extractor, metrics::STREAM);
+    assert!(report.is_healthy(), "{:?}", report);
+    assert_eq!(report.fell_back().count(), 0, "{:?}", report);
+    for name in ["new", "name", "description", "like_count", "audio_streams"].iter() {
+        assert_succeeded(&report, name, 1);
+    }
+
+    // The stream urls come from the player config of the html page
+    let report = diagnostics::stream(&ciphered);
+    assert!(report.is_healthy(), "{:?}", report);
+    let new = field(&report, "new");
+    assert_eq!((new.succeeded, new.fell_back), (0, 1));
+    assert_eq!(new.fallback_sources, vec!["player_config"]);
+
+    let report = diagnostics::stream(&hidden_ratings);
+    assert!(!report.is_healthy());
+    let failed: Vec<&str> = report.failed().map(|f| f.field.as_str()).collect();
+    assert_eq!(failed, vec!["like_count", "dislike_count"]);
+    let like_count = field(&report, "like_count");
+    assert_eq!((like_count.succeeded, like_count.failed), (0, 1));
+    assert!(like_count
+        .error
+        .as_ref()
+        .is_some_and(|e| e.contains("like button is missing")));
+
+    let report = diagnostics::stream(&with_ratings);
+    assert!(report.is_healthy(), "{:?}", report);
+    for name in ["like_count", "dislike_count"].iter() {
+        let field = field(&report, name);
+        assert_eq!((field.succeeded, field.fell_back), (0, 1));
+        assert_eq!(field.fallback_sources, vec!["ratings_provider"]);
+    }
+
+    // Fields of list items are summed over the items
+    let report = diagnostics::channel(&channel);
+    assert_eq!(report.extractor, metrics::CHANNEL);
+    assert!(report.is_healthy(), "{:?}", report);
+    assert_succeeded(&report, "channel_id", 1);
+    assert_succeeded(&report, "videos.name", PAGE_SIZE as u64);
+
+    let report = diagnostics::playlist(&playlist);
+    assert_eq!(report.extractor, metrics::PLAYLIST);
+    assert!(report.is_healthy(), "{:?}", report);
+    assert_succeeded(&report, "name", 1);
+    assert_succeeded(&report, "videos.video_id", PAGE_SIZE as u64);
+
+    let report = diagnostics::search(&search);
+    assert_eq!(report.extractor, metrics::SEARCH);
+    assert!(report.is_healthy(), "{:?}", report);
+    assert_succeeded(&report, "search_results", 1);
+    assert!(field(&report, "search_results.stream.name").succeeded > 0);
+
+    let report = diagnostics::trending(&trending);
+    assert_eq!(report.extractor, metrics::TRENDING);
+    assert!(report.is_healthy(), "{:?}", report);
+    assert!(field(&report, "videos.duration").succeeded > 0);
+
+    assert_eq!(metrics::parse_failures(), failures);
+}
@@ -0,0 +1,183 @@
+//! `DownloadManager` through the built-in downloaders, with the mock's stream urls expiring
+//! and getting rejected midway
+
+use rusty_pipe::download::{DownloadConfig, DownloadManager, DownloadStatus, PauseHandle};
+use rusty_pipe::downloaders::{DownloaderConfig, ReqwestDownloader, UreqDownloader};
+use rusty_pipe::elements::StreamItem;
+use rusty_pipe::extractors::YTStreamExtractor;
+use rusty_pipe::Downloader;
+use rusty_pipe_mock::fixtures::video_id;
+use rusty_pipe_mock::{FailureMode, MockServer};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const CHUNK_SIZE: u64 = 100_000;
+
+/// The mock fills every stream with `i % 251`
+fn media_bytes(len: u64) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+async fn audio_stream<D: Downloader>(downloader: D) -> StreamItem {
+    let extractor = YTStreamExtractor::new(downloader, &video_id(1))
+        .await
+        .unwrap();
+    extractor.audio_streams().unwrap().remove(0)
+}
+
+fn manager<D: Downloader + Clone + Sync>(downloader: &D, connections: usize) -> DownloadManager<D> {
+    DownloadManager::new(
+        downloader.clone(),
+        DownloadConfig {
+            connections,
+            chunk_size: CHUNK_SIZE,
+            retries: 2,
+        },
+    )
+}
+
+/// Downloads the stream to `path` and checks what ends up there
+async fn download_and_check<D: Downloader + Clone + Sync>(
+    manager: &DownloadManager<D>,
+    stream: &StreamItem,
+    path: &Path,
+) {
+    let last = Mutex::new(None);
+    let status = manager
+        .download(&video_id(1), stream, path, &PauseHandle::default(), |p| {
+            *last.lock().unwrap() = Some(p)
+        })
+        .await
+        .unwrap();
+    assert_eq!(status, DownloadStatus::Finished);
+    let last = last.into_inner().unwrap().unwrap();
+    assert_eq!(last.downloaded, last.total);
+    assert_eq!(std::fs::read(path).unwrap(), media_bytes(last.total));
+    let dir = path.parent().unwrap();
+    let leftovers: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .filter(|name| name.to_string_lossy().contains(".part"))
+        .collect();
+    assert!(leftovers.is_empty(), "{:?}", leftovers);
+}
+
+async fn check_downloads<D: Downloader + Clone + Sync>(mock: &MockServer, downloader: D) {
+    let dir = tempfile::tempdir().unwrap();
+    let stream = audio_stream(downloader.clone()).await;
+    let total: u64 = stream.content_length.as_ref().unwrap().parse().unwrap();
+    assert!(total > 3 * CHUNK_SIZE && !total.is_multiple_of(CHUNK_SIZE));
+
+    // Chunks over several connections
+    download_and_check(&manager(&downloader, 3), &stream, &dir.path().join("a.m4a")).await;
+    let fetched = manager(&downloader, 1)
+        .fetch(stream.url.as_ref().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(fetched, media_bytes(total));
+
+    // Without a content length the size comes from a one byte request
+    let mut unknown_length = audio_stream(downloader.clone()).await;
+    unknown_length.content_length = None;
+    download_and_check(
+        &manager(&downloader, 2),
+        &unknown_length,
+        &dir.path().join("b.m4a"),
+    )
+    .await;
+
+    // Paused after the first chunk, the second download continues from the sidecar
+    let path = dir.path().join("c.m4a");
+    let pause = PauseHandle::default();
+    let status = manager(&downloader, 1)
+        .download(&video_id(1), &stream, &path, &pause, |p| {
+            if p.downloaded > 0 {
+                pause.pause();
+            }
+        })
+        .await
+        .unwrap();
+    assert_eq!(status, DownloadStatus::Paused);
+    assert!(dir.path().join("c.m4a.part.json").exists());
+    let first = Mutex::new(None);
+    manager(&downloader, 1)
+        .download(&video_id(1), &stream, &path, &PauseHandle::default(), |p| {
+            first.lock().unwrap().get_or_insert(p);
+        })
+        .await
+        .unwrap();
+    assert_eq!(first.into_inner().unwrap().unwrap().downloaded, CHUNK_SIZE);
+    assert_eq!(std::fs::read(&path).unwrap(), media_bytes(total));
+
+    // A url past its expiry is replaced before the first request
+    mock.set_url_ttl(Duration::from_secs(0));
+    let expired = audio_stream(downloader.clone()).await;
+    mock.set_url_ttl(Duration::from_secs(6 * 60 * 60));
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    download_and_check(
+        &manager(&downloader, 2),
+        &expired,
+        &dir.path().join("d.m4a"),
+    )
+    .await;
+
+    // A url that looks valid but gets a 403 is replaced once the first chunk is refused
+    mock.set_failure(Some(FailureMode::Ciphered));
+    let mut rejected = audio_stream(downloader.clone()).await;
+    let url = rejected.url.take().unwrap();
+    assert!(url.contains("sig="), "signature was not decrypted: {}", url);
+    rejected.url = Some(url.replace("sig=", "sig=stale"));
+    download_and_check(
+        &manager(&downloader, 2),
+        &rejected,
+        &dir.path().join("e.m4a"),
+    )
+    .await;
+    mock.set_failure(None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reqwest_downloads() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    check_downloads(&mock, downloader).await;
+}
+
+// ureq blocks the worker thread of the request, the mock needs another one to answer
+#[tokio::test(flavor = "multi_thread")]
+async fn ureq_downloads() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = UreqDownloader::with_config(mock.downloader_config()).unwrap();
+    check_downloads(&mock, downloader).await;
+}
+
+/// Every chunk takes longer than the timeout, which only bounds connecting and each read
+async fn check_slow_downloads<D: Downloader + Clone + Sync>(mock: &MockServer, downloader: D) {
+    let dir = tempfile::tempdir().unwrap();
+    let stream = audio_stream(downloader.clone()).await;
+    mock.set_stream_delay(Some(Duration::from_millis(50)));
+    download_and_check(&manager(&downloader, 2), &stream, &dir.path().join("a.m4a")).await;
+    mock.set_stream_delay(None);
+}
+
+fn slow_config(mock: &MockServer) -> DownloaderConfig {
+    DownloaderConfig {
+        timeout: Some(Duration::from_millis(250)),
+        ..mock.downloader_config()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reqwest_slow_downloads() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(slow_config(&mock)).unwrap();
+    check_slow_downloads(&mock, downloader).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ureq_slow_downloads() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = UreqDownloader::with_config(slow_config(&mock)).unwrap();
+    check_slow_downloads(&mock, downloader).await;
+}
@@ -0,0 +1,176 @@
+//! The built-in downloaders and the stream extractor against every failure mode of the mock
+
+use rusty_pipe::dearrow::{Branding, DeArrow};
+use rusty_pipe::downloaders::{ReqwestDownloader, UreqDownloader};
+use rusty_pipe::extractors::YTStreamExtractor;
+use rusty_pipe::ratings::{RatingCount, RatingSource, RatingsProvider, ReturnYouTubeDislike};
+use rusty_pipe::sponsorblock::{Action, Category, Segment, SponsorBlock};
+use rusty_pipe::{Downloader, ParsingError};
+use rusty_pipe_mock::fixtures::{video_id, Video};
+use rusty_pipe_mock::{FailureMode, MockServer};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// First bytes of the audio stream, which the mock fills with `i % 251`
+async fn download_audio<D: Downloader>(
+    downloader: &D,
+    extractor: &YTStreamExtractor<D>,
+) -> Result<String, ParsingError> {
+    let streams = extractor.audio_streams()?;
+    let url = streams[0].url.as_ref().expect("stream has no url");
+    let mut header = HashMap::new();
+    header.insert("Range".to_string(), "bytes=0-9".to_string());
+    downloader.download_with_header(url, header).await
+}
+
+async fn check_failure_modes<D: Downloader + Clone + Send + Sync>(
+    mock: &MockServer,
+    downloader: D,
+) {
+    let id = video_id(1);
+
+    mock.set_failure(None);
+    let extractor = YTStreamExtractor::new(downloader.clone(), &id)
+        .await
+        .unwrap();
+    assert_eq!(extractor.name().unwrap(), Video::new(1).title);
+    let bytes = download_audio(&downloader, &extractor).await.unwrap();
+    assert_eq!(bytes.as_bytes(), (0..10).collect::<Vec<u8>>().as_slice());
+
+    mock.set_failure(Some(FailureMode::Consent));
+    match YTStreamExtractor::new(downloader.clone(), &id).await {
+        Err(ParsingError::ConsentRequired) => {}
+        other => panic!("expected ConsentRequired, got {:?}", other.err()),
+    }
+
+    mock.set_failure(Some(FailureMode::RateLimited));
+    let error = YTStreamExtractor::new(downloader.clone(), &id)
+        .await
+        .err()
+        .expect("rate limited page extracted");
+    assert_eq!(error.http_status(), Some(429));
+
+    mock.set_failure(Some(FailureMode::AgeGate));
+    match YTStreamExtractor::new(downloader.clone(), &id).await {
+        Err(ParsingError::AgeRestricted) => {}
+        other => panic!("expected AgeRestricted, got {:?}", other.err()),
+    }
+
+    mock.set_failure(Some(FailureMode::Ciphered));
+    let extractor = YTStreamExtractor::new(downloader.clone(), &id)
+        .await
+        .unwrap();
+    let url = extractor.audio_streams().unwrap()[0].url.clone().unwrap();
+    assert!(url.contains("sig="), "signature was not decrypted: {}", url);
+    download_audio(&downloader, &extractor).await.unwrap();
+
+    // Without counts on the page, those of Return YouTube Dislike are used
+    mock.set_failure(Some(FailureMode::HiddenRatings));
+    let mut extractor = YTStreamExtractor::new(downloader.clone(), &id)
+        .await
+        .unwrap();
+    assert!(extractor.like_count().is_err());
+    extractor
+        .load_ratings(&ReturnYouTubeDislike::new(downloader.clone()))
+        .await
+        .unwrap();
+    let view_count = Video::new(1).view_count as i128;
+    assert_eq!(
+        extractor.like_count_with_source().unwrap(),
+        RatingCount {
+            count: view_count / 20,
+            source: RatingSource::ReturnYouTubeDislike,
+        }
+    );
+    assert_eq!(
+        extractor.dislike_count_with_source().unwrap(),
+        RatingCount {
+            count: view_count / 400,
+            source: RatingSource::ReturnYouTubeDislike,
+        }
+    );
+
+    mock.set_failure(None);
+    mock.set_url_ttl(Duration::from_secs(0));
+    let extractor = YTStreamExtractor::new(downloader.clone(), &id)
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let error = download_audio(&downloader, &extractor).await.unwrap_err();
+    assert_eq!(error.http_status(), Some(403));
+    mock.set_url_ttl(Duration::from_secs(6 * 60 * 60));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reqwest_downloader() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    check_failure_modes(&mock, downloader).await;
+}
+
+// ureq blocks the worker thread of the request, the mock needs another one to answer
+#[tokio::test(flavor = "multi_thread")]
+async fn ureq_downloader() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = UreqDownloader::with_config(mock.downloader_config()).unwrap();
+    check_failure_modes(&mock, downloader).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn third_party_apis_follow_the_origin_override() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let ratings = ReturnYouTubeDislike::new(downloader.clone())
+        .ratings(&video_id(1))
+        .await
+        .unwrap();
+    assert_eq!(
+        ratings.dislikes,
+        Some(Video::new(1).view_count as i128 / 400)
+    );
+    // The mock only has segments for even videos and answers 404 for the others
+    let sponsor_block = SponsorBlock::new(downloader.clone());
+    assert_eq!(sponsor_block.segments(&video_id(1)).await.unwrap(), vec![]);
+    assert_eq!(
+        sponsor_block.segments(&video_id(2)).await.unwrap(),
+        vec![
+            Segment {
+                start: 10.0,
+                end: 25.5,
+                category: Category::Sponsor,
+                action: Action::Skip,
+            },
+            Segment {
+                start: 40.0,
+                end: 40.0,
+                category: Category::PoiHighlight,
+                action: Action::Poi,
+            },
+        ]
+    );
+    let highlights = SponsorBlock::new(downloader.clone())
+        .with_categories(vec![Category::PoiHighlight])
+        .segments(&video_id(2))
+        .await
+        .unwrap();
+    assert_eq!(highlights.len(), 1);
+    assert_eq!(highlights[0].category, Category::PoiHighlight);
+    // Every third video has a new title and thumbnail, the next ones kept their original title
+    // and the others have no submissions
+    let dearrow = DeArrow::new(downloader);
+    assert_eq!(
+        dearrow.branding(&video_id(3)).await.unwrap(),
+        Branding {
+            title: Some("Mock video 3 explained".to_string()),
+            thumbnail_time: Some(12.5),
+        }
+    );
+    assert_eq!(
+        dearrow.branding(&video_id(1)).await.unwrap(),
+        Branding::default()
+    );
+    assert_eq!(
+        dearrow.branding(&video_id(2)).await.unwrap(),
+        Branding::default()
+    );
+}
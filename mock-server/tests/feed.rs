@@ -0,0 +1,136 @@
+//! `FeedAggregator` over the mock's channels, which share videos with the mock channel
+
+use rusty_pipe::downloaders::ReqwestDownloader;
+use rusty_pipe::feed::{Feed, FeedAggregator, FeedConfig};
+use rusty_pipe_mock::fixtures::{channel_id, video_id, video_number, CHANNEL_ID};
+use rusty_pipe_mock::MockServer;
+use std::collections::HashMap;
+
+/// Video numbers and channel ids of the feed's items
+fn items(feed: &Feed) -> Vec<(usize, String)> {
+    feed.items
+        .iter()
+        .map(|item| {
+            let n = video_number(&item.video.video_id().unwrap()).unwrap();
+            (n, item.channel_id.clone())
+        })
+        .collect()
+}
+
+fn aggregator(
+    downloader: &ReqwestDownloader,
+    channel_ids: &[&str],
+) -> FeedAggregator<ReqwestDownloader> {
+    FeedAggregator::new(
+        downloader.clone(),
+        channel_ids.iter().map(|id| id.to_string()).collect(),
+        FeedConfig {
+            concurrency: 2,
+            pages: 1,
+        },
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn merges_newest_first() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let (second, third) = (channel_id(2), channel_id(3));
+    let mut feed = aggregator(&downloader, &[&second, &third]);
+
+    let first = feed.refresh().await;
+    assert!(first.errors.is_empty());
+    // Lower numbers are newer, the channels have every third video from 2 and from 0
+    let expected: Vec<(usize, String)> = (0..90)
+        .filter(|n| n % 3 != 1)
+        .map(|n| (n, if n % 3 == 2 { &second } else { &third }.clone()))
+        .collect();
+    assert_eq!(items(&first), expected);
+    assert!(first
+        .items
+        .windows(2)
+        .all(|pair| pair[0].upload_date > pair[1].upload_date));
+    let mut last_seen = HashMap::new();
+    last_seen.insert(second.clone(), video_id(2));
+    last_seen.insert(third.clone(), video_id(0));
+    assert_eq!(feed.last_seen(), &last_seen);
+
+    // Nothing was uploaded since
+    let again = feed.refresh().await;
+    assert!(again.items.is_empty() && again.errors.is_empty());
+    assert_eq!(feed.last_seen(), &last_seen);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stops_at_the_last_seen_video() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let second = channel_id(2);
+    let mut last_seen = HashMap::new();
+    last_seen.insert(second.clone(), video_id(11));
+    last_seen.insert(CHANNEL_ID.to_string(), video_id(40));
+    let mut feed = FeedAggregator::new(
+        downloader,
+        vec![second.clone(), CHANNEL_ID.to_string()],
+        FeedConfig {
+            concurrency: 2,
+            pages: 3,
+        },
+    )
+    .with_last_seen(last_seen);
+
+    let refreshed = feed.refresh().await;
+    // The mock channel's marker is on its second page
+    let numbers: Vec<usize> = items(&refreshed).into_iter().map(|(n, _)| n).collect();
+    assert_eq!(numbers, (0..40).collect::<Vec<usize>>());
+    assert_eq!(feed.last_seen()[&second], video_id(2));
+    assert_eq!(feed.last_seen()[CHANNEL_ID], video_id(0));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn keeps_the_marker_of_failed_channels() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let second = channel_id(2);
+    let mut last_seen = HashMap::new();
+    last_seen.insert("@nobody".to_string(), video_id(5));
+    let mut feed = aggregator(&downloader, &["@nobody", &second]).with_last_seen(last_seen);
+
+    let refreshed = feed.refresh().await;
+    assert_eq!(refreshed.errors.len(), 1);
+    let (failed, error) = &refreshed.errors[0];
+    assert_eq!(failed, "@nobody");
+    assert_eq!(error.http_status(), Some(404));
+    assert_eq!(refreshed.items.len(), 30);
+    assert!(refreshed.items.iter().all(|item| item.channel_id == second));
+    assert_eq!(feed.last_seen()["@nobody"], video_id(5));
+    assert_eq!(feed.last_seen()[&second], video_id(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shared_videos_go_to_the_first_channel() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let second = channel_id(2);
+
+    for channel_ids in [[second.as_str(), CHANNEL_ID], [CHANNEL_ID, second.as_str()]].iter() {
+        // Repeated, as the channels may finish loading in either order
+        for _ in 0..3 {
+            let feed = aggregator(&downloader, channel_ids).refresh().await;
+            let items = items(&feed);
+            // The mock channel's first page has 0 to 29, the second channel 2, 5, … 89
+            let mut numbers: Vec<usize> = items.iter().map(|(n, _)| *n).collect();
+            numbers.dedup();
+            assert_eq!(numbers.len(), items.len(), "{:?}", items);
+            assert_eq!(items.len(), 30 + 30 - 10);
+            for (n, channel) in &items {
+                let expected = match (n % 3, *n < 30) {
+                    (2, true) => channel_ids[0],
+                    (2, false) => second.as_str(),
+                    _ => CHANNEL_ID,
+                };
+                assert_eq!(channel, expected, "video {} of {:?}", n, channel_ids);
+            }
+        }
+    }
+}
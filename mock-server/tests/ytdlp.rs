@@ -0,0 +1,37 @@
+//! yt-dlp info documents of the mock video
+
+use rusty_pipe::downloaders::ReqwestDownloader;
+use rusty_pipe::extractors::YTStreamExtractor;
+use rusty_pipe::ytdlp::stream_info;
+use rusty_pipe_mock::fixtures::{video_id, Video};
+use rusty_pipe_mock::MockServer;
+use serde_json::json;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn stream_info_lists_captions() {
+    let mock = MockServer::start().await.unwrap();
+    let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+    let id = video_id(1);
+    let extractor = YTStreamExtractor::new(downloader, &id).await.unwrap();
+
+    let info = stream_info(&extractor);
+    assert_eq!(info["id"], id);
+    assert_eq!(info["title"], Video::new(1).title);
+    let timedtext = format!("https://www.youtube.com/api/timedtext?v={}&lang=en", id);
+    let subtitles = info["subtitles"]["en"].as_array().unwrap();
+    assert_eq!(subtitles.len(), 6);
+    assert_eq!(
+        subtitles[5],
+        json!({ "ext": "vtt", "url": format!("{}&fmt=vtt", timedtext), "name": "English" })
+    );
+    let automatic = info["automatic_captions"]["en"].as_array().unwrap();
+    assert_eq!(
+        automatic[0],
+        json!({
+            "ext": "json3",
+            "url": format!("{}&kind=asr&fmt=json3", timedtext),
+            "name": "English (auto-generated)",
+        })
+    );
+    assert_eq!(info["subtitles"].as_object().unwrap().len(), 1);
+}
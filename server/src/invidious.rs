@@ -0,0 +1,362 @@
+//! Conversions from the extractors to the JSON shapes of the Invidious API
+
+use rusty_pipe::elements::{StreamItem, Thumbnail, YTSearchItem};
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTChannelInfoItemExtractor, YTPlaylistExtractor,
+    YTPlaylistInfoItemExtractor, YTStreamExtractor, YTStreamInfoItemExtractor,
+};
+use rusty_pipe::{Downloader, YTLink};
+use serde_json::{json, Value};
+
+/// The fixed set of thumbnails Invidious generates for every video
+pub fn video_thumbnails(video_id: &str) -> Value {
+    let qualities = [
+        ("maxres", "maxresdefault", 1280, 720),
+        ("sddefault", "sddefault", 640, 480),
+        ("high", "hqdefault", 480, 360),
+        ("medium", "mqdefault", 320, 180),
+        ("default", "default", 120, 90),
+    ];
+    qualities
+        .iter()
+        .map(|(quality, name, width, height)| {
+            json!({
+                "quality": quality,
+                "url": format!("https://i.ytimg.com/vi/{}/{}.jpg", video_id, name),
+                "width": width,
+                "height": height,
+            })
+        })
+        .collect()
+}
+
+fn thumbnails(thumbnails: &[Thumbnail]) -> Value {
+    thumbnails
+        .iter()
+        .map(|t| json!({"url": t.url, "width": t.width, "height": t.height}))
+        .collect()
+}
+
+/// `authorId` from a channel url. Invidious only knows `UC…` ids, so urls of handles and
+/// legacy user or custom urls give none.
+fn channel_id_of(url: &str) -> Option<String> {
+    YTLink::channel_id(url).filter(|id| id.starts_with("UC"))
+}
+
+fn author_url(id: &Option<String>) -> Option<String> {
+    id.as_ref().map(|id| format!("/channel/{}", id))
+}
+
+/// Container and codec from a mime type like `video/mp4; codecs="avc1.4d401e, mp4a.40.2"`
+fn container_and_encoding(mime_type: &str) -> (String, String) {
+    let container = mime_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .nth(1)
+        .unwrap_or_default()
+        .to_string();
+    let encoding = mime_type
+        .split("codecs=\"")
+        .nth(1)
+        .unwrap_or_default()
+        .split(['.', ',', '"'])
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    (container, encoding)
+}
+
+fn format_stream(stream: &StreamItem) -> Value {
+    let (container, encoding) = container_and_encoding(&stream.mime_type);
+    json!({
+        "url": stream.url,
+        "itag": stream.itag.to_string(),
+        "type": stream.mime_type,
+        "quality": stream.quality,
+        "container": container,
+        "encoding": encoding,
+        "qualityLabel": stream.quality_label,
+        "resolution": stream.quality_label,
+        "size": match (stream.width, stream.height) {
+            (Some(width), Some(height)) => Some(format!("{}x{}", width, height)),
+            _ => None,
+        },
+    })
+}
+
+fn adaptive_format(stream: &StreamItem) -> Value {
+    let (container, encoding) = container_and_encoding(&stream.mime_type);
+    json!({
+        "url": stream.url,
+        "itag": stream.itag.to_string(),
+        "type": stream.mime_type,
+        "bitrate": stream.bitrate.to_string(),
+        "clen": stream.content_length,
+        "lmt": stream.last_modified,
+        "projectionType": "RECTANGULAR",
+        "container": container,
+        "encoding": encoding,
+        "qualityLabel": stream.quality_label,
+        "resolution": stream.quality_label,
+        "size": match (stream.width, stream.height) {
+            (Some(width), Some(height)) => Some(format!("{}x{}", width, height)),
+            _ => None,
+        },
+        "audioQuality": stream.audio_quality,
+        "audioSampleRate": stream
+            .audio_sample_rate
+            .as_ref()
+            .and_then(|rate| rate.parse::<u32>().ok()),
+        "audioChannels": stream.audio_channels,
+    })
+}
+
+pub fn video<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Value {
+    let video_id = extractor.video_id();
+    let author_id = extractor.uploader_id().ok().or_else(|| {
+        extractor
+            .uploader_url()
+            .ok()
+            .and_then(|u| channel_id_of(&u))
+    });
+    let mut adaptive_formats = vec![];
+    for streams in [extractor.video_only_stream(), extractor.audio_streams()]
+        .iter()
+        .flatten()
+    {
+        adaptive_formats.extend(streams.iter().map(adaptive_format));
+    }
+    let recommended: Vec<Value> = extractor
+        .related()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|item| match item {
+            YTSearchItem::StreamInfoItem(video) => Some(recommended_video(video)),
+            _ => None,
+        })
+        .collect();
+    let description = extractor
+        .description(false)
+        .map(|(d, _)| d)
+        .unwrap_or_default();
+    let like_count = extractor.like_count().unwrap_or_default();
+    let dislike_count = extractor.dislike_count().unwrap_or_default();
+    json!({
+        "type": "video",
+        "title": extractor.name().unwrap_or_default(),
+        "videoId": video_id,
+        "videoThumbnails": video_thumbnails(&video_id),
+        "storyboards": [],
+        "description": description,
+        "descriptionHtml": extractor
+            .description(true)
+            .map(|(d, _)| d)
+            .unwrap_or_default(),
+        "published": extractor
+            .upload_date()
+            .ok()
+            .map(|d| d.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc().timestamp())
+            .unwrap_or_default(),
+        "publishedText": extractor.textual_upload_date().unwrap_or_default(),
+        "keywords": [],
+        "viewCount": extractor.view_count().unwrap_or_default() as u64,
+        "likeCount": like_count.max(0) as u64,
+        "dislikeCount": dislike_count.max(0) as u64,
+        "paid": false,
+        "premium": false,
+        "isFamilyFriendly": true,
+        "allowedRegions": [],
+        "genre": "",
+        "author": extractor.uploader_name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "authorThumbnails": thumbnails(&extractor.uploader_avatar_url().unwrap_or_default()),
+        "subCountText": "",
+        "lengthSeconds": extractor.length().unwrap_or_default(),
+        "allowRatings": true,
+        "rating": 0,
+        "isListed": true,
+        "liveNow": false,
+        "isUpcoming": false,
+        "adaptiveFormats": adaptive_formats,
+        "formatStreams": extractor
+            .video_stream()
+            .unwrap_or_default()
+            .iter()
+            .map(format_stream)
+            .collect::<Vec<Value>>(),
+        "captions": [],
+        "recommendedVideos": recommended,
+    })
+}
+
+fn recommended_video(video: &YTStreamInfoItemExtractor) -> Value {
+    let video_id = video.video_id().unwrap_or_default();
+    let author_id = video.uploader_url().ok().and_then(|u| channel_id_of(&u));
+    json!({
+        "videoId": video_id,
+        "title": video.name().unwrap_or_default(),
+        "videoThumbnails": video_thumbnails(&video_id),
+        "author": video.uploader_name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "lengthSeconds": video.duration().unwrap_or_default(),
+        "viewCountText": video.textual_view_count().unwrap_or_default(),
+        "viewCount": video.view_count().unwrap_or_default(),
+    })
+}
+
+/// Video entry of search results, channel pages and trending
+pub fn video_item(video: &YTStreamInfoItemExtractor) -> Value {
+    let video_id = video.video_id().unwrap_or_default();
+    let author_id = video.uploader_url().ok().and_then(|u| channel_id_of(&u));
+    json!({
+        "type": "video",
+        "title": video.name().unwrap_or_default(),
+        "videoId": video_id,
+        "author": video.uploader_name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "videoThumbnails": video_thumbnails(&video_id),
+        "description": "",
+        "descriptionHtml": "",
+        "viewCount": video.view_count().unwrap_or_default(),
+        "viewCountText": video.textual_view_count().unwrap_or_default(),
+        "published": video
+            .upload_date()
+            .map(|d| d.and_utc().timestamp())
+            .unwrap_or_default(),
+        "publishedText": video.textual_upload_date().unwrap_or_default(),
+        "lengthSeconds": video.duration().unwrap_or_default(),
+        "liveNow": video.is_live().unwrap_or_default(),
+        "premium": video.is_premium_video().unwrap_or_default(),
+        "isUpcoming": false,
+    })
+}
+
+fn channel_item(channel: &YTChannelInfoItemExtractor) -> Value {
+    let author_id = channel.channel_id().ok();
+    let description = channel.description().ok().flatten().unwrap_or_default();
+    json!({
+        "type": "channel",
+        "author": channel.name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "authorThumbnails": thumbnails(&channel.thumbnails().unwrap_or_default()),
+        "subCount": channel.subscriber_count().unwrap_or_default(),
+        "videoCount": channel.stream_count().unwrap_or_default(),
+        "description": description,
+        "descriptionHtml": description,
+    })
+}
+
+fn playlist_item(playlist: &YTPlaylistInfoItemExtractor) -> Value {
+    json!({
+        "type": "playlist",
+        "title": playlist.name().unwrap_or_default(),
+        "playlistId": playlist.playlist_id().unwrap_or_default(),
+        "playlistThumbnail": playlist
+            .thumbnails()
+            .ok()
+            .and_then(|t| t.first().map(|t| t.url.clone())),
+        "author": playlist.uploader_name().unwrap_or_default(),
+        "videoCount": playlist.stream_count().unwrap_or_default(),
+        "videos": [],
+    })
+}
+
+pub fn search_item(item: &YTSearchItem) -> Value {
+    match item {
+        YTSearchItem::StreamInfoItem(video) => video_item(video),
+        YTSearchItem::ChannelInfoItem(channel) => channel_item(channel),
+        YTSearchItem::PlaylistInfoItem(playlist) => playlist_item(playlist),
+    }
+}
+
+/// `channel_id` is the id the channel was requested by, which may be a handle
+pub fn channel<D: Downloader>(channel_id: &str, extractor: &YTChannelExtractor<D>) -> Value {
+    let author_id = extractor
+        .channel_id()
+        .ok()
+        .or_else(|| Some(channel_id.to_string()).filter(|id| id.starts_with("UC")));
+    json!({
+        "author": extractor.name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "authorBanners": thumbnails(&extractor.banners().unwrap_or_default()),
+        "authorThumbnails": thumbnails(&extractor.avatars().unwrap_or_default()),
+        "subCount": 0,
+        "totalViews": 0,
+        "joined": 0,
+        "autoGenerated": false,
+        "isFamilyFriendly": true,
+        "description": "",
+        "descriptionHtml": "",
+        "allowedRegions": [],
+        "latestVideos": channel_videos(extractor)["videos"],
+        "relatedChannels": [],
+    })
+}
+
+/// Body of `/channels/:id/videos`, `continuation` is passed back to load the next page
+pub fn channel_videos<D: Downloader>(extractor: &YTChannelExtractor<D>) -> Value {
+    json!({
+        "videos": extractor
+            .videos()
+            .unwrap_or_default()
+            .iter()
+            .map(video_item)
+            .collect::<Vec<Value>>(),
+        "continuation": extractor.next_page_url().ok().flatten(),
+    })
+}
+
+pub fn playlist<D: Downloader>(playlist_id: &str, extractor: &YTPlaylistExtractor<D>) -> Value {
+    let author_id = extractor
+        .uploader_url()
+        .ok()
+        .and_then(|u| channel_id_of(&u));
+    let videos: Vec<Value> = extractor
+        .videos()
+        .unwrap_or_default()
+        .iter()
+        .enumerate()
+        .map(|(index, video)| {
+            let video_id = video.video_id().unwrap_or_default();
+            let author_id = video.uploader_url().ok().and_then(|u| channel_id_of(&u));
+            json!({
+                "title": video.name().unwrap_or_default(),
+                "videoId": video_id,
+                "author": video.uploader_name().unwrap_or_default(),
+                "authorId": author_id,
+                "authorUrl": author_url(&author_id),
+                "videoThumbnails": video_thumbnails(&video_id),
+                "index": index,
+                "lengthSeconds": video.duration().unwrap_or_default(),
+            })
+        })
+        .collect();
+    json!({
+        "type": "playlist",
+        "title": extractor.name().unwrap_or_default(),
+        "playlistId": playlist_id,
+        "playlistThumbnail": extractor
+            .thumbnails()
+            .ok()
+            .and_then(|t| t.first().map(|t| t.url.clone())),
+        "author": extractor.uploader_name().unwrap_or_default(),
+        "authorId": author_id,
+        "authorUrl": author_url(&author_id),
+        "authorThumbnails": thumbnails(&extractor.uploader_avatars().unwrap_or_default()),
+        "description": "",
+        "descriptionHtml": "",
+        "videoCount": extractor.stream_count().unwrap_or_default(),
+        "viewCount": 0,
+        "updated": 0,
+        "isListed": true,
+        "videos": videos,
+        "continuation": extractor.next_page_url().ok().flatten(),
+    })
+}
@@ -0,0 +1,322 @@
+mod invidious;
+mod podcasts;
+mod proxy;
+
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use clap::Parser;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rusty_pipe::downloaders::{DownloaderConfig, ReqwestDownloader};
+use rusty_pipe::elements::YTSearchItem;
+use rusty_pipe::extractors::{
+    YTChannelExtractor, YTPlaylistExtractor, YTSearchExtractor, YTStreamExtractor,
+    YTTrendingExtractor,
+};
+use rusty_pipe::podcast::PodcastConfig;
+use rusty_pipe::ParsingError;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(
+    name = "rusty-pipe-server",
+    version,
+    about = "Serve the Invidious API from rusty_pipe"
+)]
+struct Args {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:3000")]
+    listen: String,
+    /// Proxy for the requests to YouTube
+    #[arg(long)]
+    proxy: Option<String>,
+    /// Url clients reach this server at, used for podcast enclosures. Defaults to the
+    /// listen address
+    #[arg(long)]
+    public_url: Option<String>,
+    /// Send YouTube requests to this origin instead, e.g. a rusty-pipe-mock server
+    #[arg(long)]
+    youtube_origin: Option<String>,
+}
+
+type ApiResult = Result<Json<Value>, ApiError>;
+
+/// Invidious reports errors as `{"error": message}`
+struct ApiError(StatusCode, String);
+
+impl From<ParsingError> for ApiError {
+    fn from(error: ParsingError) -> Self {
+        let status = match error {
+            ParsingError::DownloadError { .. }
+            | ParsingError::HttpError { .. }
+            | ParsingError::ConsentRequired => StatusCode::BAD_GATEWAY,
+            ParsingError::AgeRestricted => StatusCode::FORBIDDEN,
+            ParsingError::ParsingError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, Json(json!({ "error": self.1 }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    q: Option<String>,
+    page: Option<u32>,
+    /// video, channel, playlist or all
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct VideoParams {
+    /// Whether stream urls should point at this server's proxy
+    local: Option<bool>,
+}
+
+#[derive(Deserialize)]
+struct ContinuationParams {
+    continuation: Option<String>,
+}
+
+async fn video(
+    State(downloader): State<ReqwestDownloader>,
+    Path(id): Path<String>,
+    Query(params): Query<VideoParams>,
+) -> ApiResult {
+    let extractor = YTStreamExtractor::new(downloader, &id).await?;
+    let mut video = invidious::video(&extractor);
+    if params.local.unwrap_or_default() {
+        proxy::localize(&mut video);
+    }
+    Ok(Json(video))
+}
+
+async fn search(
+    State(downloader): State<ReqwestDownloader>,
+    Query(params): Query<SearchParams>,
+) -> ApiResult {
+    let query = utf8_percent_encode(&params.q.unwrap_or_default(), NON_ALPHANUMERIC).to_string();
+    let page = params.page.filter(|page| *page > 1).map(|p| p.to_string());
+    let extractor = YTSearchExtractor::new(downloader, &query, page).await?;
+    let kind = params.kind.unwrap_or_else(|| "video".to_string());
+    let items: Vec<Value> = extractor
+        .search_results()?
+        .iter()
+        .filter(|item| {
+            matches!(
+                (kind.as_str(), item),
+                ("all", _)
+                    | ("video", YTSearchItem::StreamInfoItem(_))
+                    | ("channel", YTSearchItem::ChannelInfoItem(_))
+                    | ("playlist", YTSearchItem::PlaylistInfoItem(_))
+            )
+        })
+        .map(invidious::search_item)
+        .collect();
+    Ok(Json(Value::from(items)))
+}
+
+async fn search_suggestions(
+    State(downloader): State<ReqwestDownloader>,
+    Query(params): Query<SearchParams>,
+) -> ApiResult {
+    let query = params.q.unwrap_or_default();
+    let encoded = utf8_percent_encode(&query, NON_ALPHANUMERIC).to_string();
+    let suggestions = YTSearchExtractor::search_suggestion(&downloader, &encoded).await?;
+    Ok(Json(json!({
+        "query": query,
+        "suggestions": suggestions,
+    })))
+}
+
+async fn channel(State(downloader): State<ReqwestDownloader>, Path(id): Path<String>) -> ApiResult {
+    let extractor = YTChannelExtractor::new(downloader, &id, None).await?;
+    Ok(Json(invidious::channel(&id, &extractor)))
+}
+
+async fn channel_videos(
+    State(downloader): State<ReqwestDownloader>,
+    Path(id): Path<String>,
+    Query(params): Query<ContinuationParams>,
+) -> ApiResult {
+    let extractor = YTChannelExtractor::new(downloader, &id, params.continuation).await?;
+    Ok(Json(invidious::channel_videos(&extractor)))
+}
+
+async fn playlist(
+    State(downloader): State<ReqwestDownloader>,
+    Path(id): Path<String>,
+    Query(params): Query<ContinuationParams>,
+) -> ApiResult {
+    let extractor = YTPlaylistExtractor::new(downloader, &id, params.continuation).await?;
+    Ok(Json(invidious::playlist(&id, &extractor)))
+}
+
+async fn trending(State(downloader): State<ReqwestDownloader>) -> ApiResult {
+    let extractor = YTTrendingExtractor::new(downloader).await?;
+    let videos: Vec<Value> = extractor
+        .videos()?
+        .iter()
+        .map(invidious::video_item)
+        .collect();
+    Ok(Json(Value::from(videos)))
+}
+
+pub fn router(
+    downloader: ReqwestDownloader,
+    stream_proxy: Arc<proxy::StreamProxy>,
+    podcasts: podcasts::Podcasts,
+) -> Router {
+    let streams = Router::new()
+        .route("/{video_id}/{itag}", get(proxy::stream))
+        .with_state(stream_proxy);
+    let feeds = Router::new()
+        .route("/podcast/channels/{id}", get(podcasts::channel))
+        .route("/podcast/playlists/{id}", get(podcasts::playlist))
+        .with_state(podcasts);
+    Router::new()
+        .route("/api/v1/videos/{id}", get(video))
+        .route("/api/v1/search", get(search))
+        .route("/api/v1/search/suggestions", get(search_suggestions))
+        .route("/api/v1/channels/{id}", get(channel))
+        .route("/api/v1/channels/{id}/videos", get(channel_videos))
+        .route("/api/v1/playlists/{id}", get(playlist))
+        .route("/api/v1/trending", get(trending))
+        .with_state(downloader)
+        .merge(streams)
+        .merge(feeds)
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    // RUST_LOG=debug also shows the extractors' parse failures and fallbacks
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+    let downloader = ReqwestDownloader::with_config(DownloaderConfig {
+        proxy: args.proxy,
+        origin_override: args.youtube_origin,
+        ..DownloaderConfig::default()
+    })
+    .expect("Cannot create downloader");
+    let stream_proxy =
+        proxy::StreamProxy::new(downloader.clone()).expect("Cannot create stream proxy");
+    let listen = args.listen;
+    let public_url = args
+        .public_url
+        .unwrap_or_else(|| format!("http://{}", listen));
+    let podcasts = podcasts::Podcasts {
+        downloader: downloader.clone(),
+        config: PodcastConfig {
+            enclosure_url: format!("{}/{{video_id}}/{{itag}}", public_url.trim_end_matches('/')),
+            ..PodcastConfig::default()
+        },
+    };
+    let listener = tokio::net::TcpListener::bind(&listen)
+        .await
+        .expect("Cannot bind listen address");
+    tracing::info!(address = %listen, "listening");
+    axum::serve(
+        listener,
+        router(downloader, Arc::new(stream_proxy), podcasts),
+    )
+    .await
+    .expect("Server error");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_pipe_mock::fixtures::{video_id, CHANNEL_HANDLE, CHANNEL_ID};
+    use rusty_pipe_mock::MockServer;
+
+    /// Serves the API from an ephemeral port, backed by `mock`
+    async fn serve(mock: &MockServer) -> String {
+        let downloader = ReqwestDownloader::with_config(mock.downloader_config()).unwrap();
+        let stream_proxy = proxy::StreamProxy::new(downloader.clone()).unwrap();
+        let podcasts = podcasts::Podcasts {
+            downloader: downloader.clone(),
+            config: PodcastConfig::default(),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(downloader, Arc::new(stream_proxy), podcasts);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{}", addr)
+    }
+
+    async fn get(url: &str) -> (StatusCode, Value) {
+        let response = reqwest::get(url).await.unwrap();
+        let status = response.status();
+        let body = response.text().await.unwrap();
+        (status, serde_json::from_str(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn video() {
+        let mock = MockServer::start().await.unwrap();
+        let server = serve(&mock).await;
+        let (status, video) = get(&format!("{}/api/v1/videos/{}", server, video_id(1))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(video["type"], "video");
+        assert_eq!(video["videoId"], video_id(1));
+        assert!(video["title"].as_str().is_some_and(|t| !t.is_empty()));
+        // The watch page links the uploader by handle, the id comes from the player response
+        assert_eq!(video["authorId"], CHANNEL_ID);
+        assert_eq!(video["authorUrl"], format!("/channel/{}", CHANNEL_ID));
+        assert!(video["lengthSeconds"].as_u64().is_some_and(|l| l > 0));
+        assert!(video["viewCount"].is_u64());
+        assert_eq!(video["videoThumbnails"].as_array().map(Vec::len), Some(5));
+        let formats = video["adaptiveFormats"].as_array().unwrap();
+        assert!(!formats.is_empty());
+        for format in formats {
+            assert!(format["url"].is_string());
+            assert!(format["itag"].is_string());
+            assert!(format["type"].is_string());
+        }
+        for recommended in video["recommendedVideos"].as_array().unwrap() {
+            assert!(recommended["videoId"].is_string());
+            assert!(recommended["authorId"]
+                .as_str()
+                .is_some_and(|id| id.starts_with("UC")));
+        }
+
+        let (status, error) = get(&format!("{}/api/v1/videos/unknown0000", server)).await;
+        assert!(!status.is_success());
+        assert!(error["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn channel() {
+        let mock = MockServer::start().await.unwrap();
+        let server = serve(&mock).await;
+        for id in [CHANNEL_ID, CHANNEL_HANDLE].iter() {
+            let (status, channel) = get(&format!("{}/api/v1/channels/{}", server, id)).await;
+            assert_eq!(status, StatusCode::OK, "{}", id);
+            assert_eq!(channel["authorId"], CHANNEL_ID, "{}", id);
+            assert_eq!(channel["authorUrl"], format!("/channel/{}", CHANNEL_ID));
+            assert!(channel["author"].as_str().is_some_and(|a| !a.is_empty()));
+            assert!(channel["authorThumbnails"].is_array());
+            let videos = channel["latestVideos"].as_array().unwrap();
+            assert!(!videos.is_empty());
+            for video in videos {
+                assert_eq!(video["type"], "video");
+                assert!(video["videoId"].is_string());
+                assert_eq!(video["authorId"], CHANNEL_ID);
+            }
+        }
+    }
+}
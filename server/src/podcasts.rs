@@ -0,0 +1,63 @@
+//! Podcast feeds of channels and playlists whose episodes are served by the stream proxy
+
+use crate::ApiError;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use rusty_pipe::downloaders::ReqwestDownloader;
+use rusty_pipe::podcast::{self, PodcastConfig};
+use serde::Deserialize;
+
+/// Upper bound for `pages`, every page is another request to YouTube
+const MAX_PAGES: usize = 10;
+
+#[derive(Clone)]
+pub struct Podcasts {
+    pub downloader: ReqwestDownloader,
+    pub config: PodcastConfig,
+}
+
+#[derive(Deserialize)]
+pub struct FeedParams {
+    pages: Option<usize>,
+}
+
+impl Podcasts {
+    fn config(&self, params: &FeedParams) -> PodcastConfig {
+        PodcastConfig {
+            pages: params
+                .pages
+                .unwrap_or(self.config.pages)
+                .clamp(1, MAX_PAGES),
+            ..self.config.clone()
+        }
+    }
+}
+
+fn rss(feed: String) -> Response {
+    (
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed,
+    )
+        .into_response()
+}
+
+pub async fn channel(
+    State(podcasts): State<Podcasts>,
+    Path(id): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Result<Response, ApiError> {
+    let config = podcasts.config(&params);
+    let feed = podcast::channel_feed(podcasts.downloader, &id, &config).await?;
+    Ok(rss(feed))
+}
+
+pub async fn playlist(
+    State(podcasts): State<Podcasts>,
+    Path(id): Path<String>,
+    Query(params): Query<FeedParams>,
+) -> Result<Response, ApiError> {
+    let config = podcasts.config(&params);
+    let feed = podcast::playlist_feed(podcasts.downloader, &id, &config).await?;
+    Ok(rss(feed))
+}
@@ -0,0 +1,221 @@
+//! Stable `/{video_id}/{itag}` urls for media that is fetched from googlevideo on demand
+//!
+//! Stream urls expire after a few hours and only work from the address that extracted them,
+//! so clients on other machines go through the server instead. The extracted urls are cached
+//! per video until they expire and extraction is run again then or when googlevideo rejects
+//! them.
+
+use crate::ApiError;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderMap, HeaderName, StatusCode};
+use axum::response::Response;
+use rusty_pipe::downloaders::ReqwestDownloader;
+use rusty_pipe::extractors::YTStreamExtractor;
+use rusty_pipe::ParsingError;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Urls expiring within this many seconds are refreshed before use
+const EXPIRY_MARGIN: u64 = 60;
+
+/// How long urls without an `expire` parameter are cached, googlevideo's are valid for 6 hours
+const DEFAULT_TTL: u64 = 6 * 60 * 60;
+
+const FORWARDED_HEADERS: [HeaderName; 6] = [
+    header::CONTENT_TYPE,
+    header::CONTENT_LENGTH,
+    header::CONTENT_RANGE,
+    header::ACCEPT_RANGES,
+    header::LAST_MODIFIED,
+    header::ETAG,
+];
+
+/// Stream urls of a video by itag
+struct CachedUrls {
+    urls: HashMap<u32, String>,
+    /// Unix time the first of the urls expires
+    expire: u64,
+}
+
+pub struct StreamProxy {
+    downloader: ReqwestDownloader,
+    client: reqwest::Client,
+    /// Per video id, entries are dropped once they expired
+    urls: Mutex<HashMap<String, CachedUrls>>,
+}
+
+impl StreamProxy {
+    pub fn new(downloader: ReqwestDownloader) -> Result<Self, ParsingError> {
+        let config = downloader.config();
+        // No overall timeout, long streams stay open for a while
+        let mut builder = reqwest::Client::builder().user_agent(config.user_agent.as_str());
+        if let Some(timeout) = config.timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+            builder = builder.proxy(proxy);
+        }
+        let client = builder.build().map_err(|e| ParsingError::DownloadError {
+            cause: e.to_string(),
+        })?;
+        Ok(StreamProxy {
+            downloader,
+            client,
+            urls: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Runs the extractor and caches the urls of all of the video's streams
+    async fn extract(&self, video_id: &str) -> Result<HashMap<u32, String>, ParsingError> {
+        tracing::debug!(video_id, "extracting stream urls");
+        let extractor = YTStreamExtractor::new(self.downloader.clone(), video_id).await?;
+        let mut urls = HashMap::new();
+        for streams in [
+            extractor.video_stream(),
+            extractor.video_only_stream(),
+            extractor.audio_streams(),
+        ]
+        .iter()
+        .flatten()
+        {
+            for stream in streams {
+                if let Some(url) = &stream.url {
+                    urls.insert(stream.itag, url.clone());
+                }
+            }
+        }
+        let now = unix_now();
+        let expire = urls
+            .values()
+            .filter_map(|url| expiry(url))
+            .min()
+            .unwrap_or(now + DEFAULT_TTL);
+        let mut cache = self.urls.lock().unwrap();
+        cache.retain(|_, cached| cached.expire > now);
+        cache.insert(
+            video_id.to_string(),
+            CachedUrls {
+                urls: urls.clone(),
+                expire,
+            },
+        );
+        Ok(urls)
+    }
+
+    /// The upstream url of a stream and whether it was just extracted
+    async fn url(
+        &self,
+        video_id: &str,
+        itag: u32,
+        refresh: bool,
+    ) -> Result<(String, bool), ApiError> {
+        if !refresh {
+            let cached = (|| {
+                let urls = self.urls.lock().unwrap();
+                urls.get(video_id)?.urls.get(&itag).cloned()
+            })();
+            if let Some(url) = cached.filter(|url| !expires_soon(url)) {
+                return Ok((url, false));
+            }
+        }
+        let urls = self.extract(video_id).await?;
+        let url = urls.get(&itag).cloned().ok_or_else(|| {
+            ApiError(
+                StatusCode::NOT_FOUND,
+                format!("No stream with itag {} for {}", itag, video_id),
+            )
+        })?;
+        Ok((url, true))
+    }
+
+    async fn fetch(&self, url: &str, range: Option<&str>) -> Result<reqwest::Response, ApiError> {
+        let url = self.downloader.config().request_url(url);
+        let mut request = self.client.get(&url);
+        if let Some(range) = range {
+            request = request.header(header::RANGE, range);
+        }
+        request
+            .send()
+            .await
+            .map_err(|e| ApiError(StatusCode::BAD_GATEWAY, e.to_string()))
+    }
+}
+
+/// Unix time of the url's `expire` parameter
+fn expiry(url: &str) -> Option<u64> {
+    let query = url.split_once('?')?.1;
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("expire="))?
+        .parse()
+        .ok()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn expires_soon(url: &str) -> bool {
+    expiry(url).is_some_and(|expire| expire <= unix_now() + EXPIRY_MARGIN)
+}
+
+/// Proxies a stream, forwarding the Range header. Client errors like 416 Range Not Satisfiable
+/// are passed on as googlevideo sent them.
+pub async fn stream(
+    State(proxy): State<Arc<StreamProxy>>,
+    Path((video_id, itag)): Path<(String, u32)>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|range| range.to_str().ok());
+    let (url, fresh) = proxy.url(&video_id, itag, false).await?;
+    let mut upstream = proxy.fetch(&url, range).await?;
+    let rejected = matches!(
+        upstream.status(),
+        reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::GONE
+    );
+    if rejected && !fresh {
+        tracing::debug!(video_id, itag, "stream url rejected, refreshing");
+        let (url, _) = proxy.url(&video_id, itag, true).await?;
+        upstream = proxy.fetch(&url, range).await?;
+    }
+    if !upstream.status().is_success() && !upstream.status().is_client_error() {
+        return Err(ApiError(
+            StatusCode::BAD_GATEWAY,
+            format!("googlevideo returned {}", upstream.status()),
+        ));
+    }
+
+    let mut response = Response::builder().status(upstream.status().as_u16());
+    for name in FORWARDED_HEADERS.iter() {
+        if let Some(value) = upstream.headers().get(name.as_str()) {
+            response = response.header(name, value.as_bytes());
+        }
+    }
+    response
+        .body(Body::from_stream(upstream.bytes_stream()))
+        .map_err(|e| ApiError(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Points the stream urls of an Invidious video at the proxy, like Invidious' `local=true`
+pub fn localize(video: &mut Value) {
+    let video_id = video["videoId"].as_str().unwrap_or_default().to_string();
+    for key in ["formatStreams", "adaptiveFormats"].iter() {
+        if let Some(formats) = video[*key].as_array_mut() {
+            for format in formats {
+                let itag = format["itag"].as_str().unwrap_or_default().to_string();
+                format["url"] = Value::from(format!("/{}/{}", video_id, itag));
+            }
+        }
+    }
+}
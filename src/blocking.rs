@@ -0,0 +1,230 @@
+//! Synchronous versions of the extractors
+//!
+//! Requests go through a [`BlockingDownloader`] and every constructor drives the async
+//! extractor to completion with `futures::executor::block_on`, so no async runtime is needed.
+//! The wrappers deref to the async extractors for their accessors, which are synchronous, and
+//! have blocking versions of the async functions that make requests.
+
+use crate::cookie_jar::CookieJar;
+use crate::downloader_trait::Downloader;
+use crate::ratings::RatingsProvider;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::{
+    channel_extractor, playlist_extractor, search_extractor, stream_extractor, trending_extractor,
+};
+use async_trait::async_trait;
+use futures::executor::block_on;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+pub trait BlockingDownloader {
+    fn download(&self, url: &str) -> Result<String, ParsingError>;
+    fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError>;
+    fn eval_js(&self, script: &str) -> Result<String, String>;
+
+    /// Cookies sent along with youtube requests, override to persist them
+    fn cookie_jar(&self) -> CookieJar {
+        CookieJar::with_consent()
+    }
+}
+
+/// Makes a `BlockingDownloader` usable where a `Downloader` is expected
+#[derive(Clone, PartialEq)]
+pub struct Blocking<D>(pub D);
+
+#[async_trait]
+impl<D: BlockingDownloader + Send + Sync> Downloader for Blocking<D> {
+    async fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.0.download(url)
+    }
+
+    async fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        self.0.download_with_header(url, header)
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.0.eval_js(script)
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        self.0.cookie_jar()
+    }
+}
+
+#[cfg(feature = "ureq-downloader")]
+impl BlockingDownloader for crate::downloaders::UreqDownloader {
+    fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.get(url, HashMap::new())
+    }
+
+    fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        self.get(url, header)
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.config().eval_js(script)
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        Downloader::cookie_jar(self)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct YTStreamExtractor<D: BlockingDownloader + Send + Sync>(
+    stream_extractor::YTStreamExtractor<Blocking<D>>,
+);
+
+impl<D: BlockingDownloader + Send + Sync> YTStreamExtractor<D> {
+    pub fn new(downloader: D, video_id: &str) -> Result<Self, ParsingError> {
+        block_on(stream_extractor::YTStreamExtractor::new(
+            Blocking(downloader),
+            video_id,
+        ))
+        .map(Self)
+    }
+
+    /// Fetches the counts `like_count` and `dislike_count` fall back to when the page has none
+    pub fn load_ratings<P: RatingsProvider + ?Sized>(
+        &mut self,
+        provider: &P,
+    ) -> Result<(), ParsingError> {
+        block_on(self.0.load_ratings(provider))
+    }
+
+    pub fn player_code(downloader: D, player_url: &str) -> Result<String, ParsingError> {
+        block_on(stream_extractor::YTStreamExtractor::player_code(
+            &Blocking(downloader),
+            player_url,
+        ))
+    }
+}
+
+impl<D: BlockingDownloader + Send + Sync> Deref for YTStreamExtractor<D> {
+    type Target = stream_extractor::YTStreamExtractor<Blocking<D>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct YTSearchExtractor<D>(search_extractor::YTSearchExtractor<Blocking<D>>);
+
+impl<D: BlockingDownloader + Send + Sync> YTSearchExtractor<D> {
+    pub fn new(downloader: D, query: &str, page_url: Option<String>) -> Result<Self, ParsingError> {
+        block_on(search_extractor::YTSearchExtractor::new(
+            Blocking(downloader),
+            query,
+            page_url,
+        ))
+        .map(Self)
+    }
+
+    pub fn search_suggestion(downloader: D, query: &str) -> Result<Vec<String>, ParsingError> {
+        block_on(search_extractor::YTSearchExtractor::search_suggestion(
+            &Blocking(downloader),
+            query,
+        ))
+    }
+}
+
+impl<D> Deref for YTSearchExtractor<D> {
+    type Target = search_extractor::YTSearchExtractor<Blocking<D>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct YTChannelExtractor<D>(channel_extractor::YTChannelExtractor<Blocking<D>>);
+
+impl<D: BlockingDownloader + Send + Sync> YTChannelExtractor<D> {
+    pub fn new(
+        downloader: D,
+        channel_id: &str,
+        page_url: Option<String>,
+    ) -> Result<Self, ParsingError> {
+        block_on(channel_extractor::YTChannelExtractor::new(
+            Blocking(downloader),
+            channel_id,
+            page_url,
+        ))
+        .map(Self)
+    }
+
+    /// Channel id (`UC…`) of a handle, `user/name` or `c/name` path
+    pub fn resolve_channel_id(downloader: D, id: &str) -> Result<String, ParsingError> {
+        block_on(channel_extractor::YTChannelExtractor::resolve_channel_id(
+            &Blocking(downloader),
+            id,
+        ))
+    }
+}
+
+impl<D> Deref for YTChannelExtractor<D> {
+    type Target = channel_extractor::YTChannelExtractor<Blocking<D>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct YTPlaylistExtractor<D>(playlist_extractor::YTPlaylistExtractor<Blocking<D>>);
+
+impl<D: BlockingDownloader + Send + Sync> YTPlaylistExtractor<D> {
+    pub fn new(
+        downloader: D,
+        playlist_id: &str,
+        page_url: Option<String>,
+    ) -> Result<Self, ParsingError> {
+        block_on(playlist_extractor::YTPlaylistExtractor::new(
+            Blocking(downloader),
+            playlist_id,
+            page_url,
+        ))
+        .map(Self)
+    }
+}
+
+impl<D> Deref for YTPlaylistExtractor<D> {
+    type Target = playlist_extractor::YTPlaylistExtractor<Blocking<D>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct YTTrendingExtractor<D>(trending_extractor::YTTrendingExtractor<Blocking<D>>);
+
+impl<D: BlockingDownloader + Send + Sync> YTTrendingExtractor<D> {
+    pub fn new(downloader: D) -> Result<Self, ParsingError> {
+        block_on(trending_extractor::YTTrendingExtractor::new(Blocking(
+            downloader,
+        )))
+        .map(Self)
+    }
+}
+
+impl<D> Deref for YTTrendingExtractor<D> {
+    type Target = trending_extractor::YTTrendingExtractor<Blocking<D>>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Value of the legacy `CONSENT` cookie that marks the consent form as answered
+pub const CONSENT_COOKIE_VALUE: &str = "YES+cb";
+/// Value of the `SOCS` cookie that marks the consent form as answered
+pub const SOCS_COOKIE_VALUE: &str = "CAI";
+
+/// Cookies sent to youtube.com
+///
+/// Serializable so a `Downloader` can persist it between runs and hand it back through
+/// `Downloader::cookie_jar`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CookieJar {
+    cookies: BTreeMap<String, String>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Jar that already answered the EU consent interstitial
+    pub fn with_consent() -> Self {
+        let mut jar = Self::new();
+        jar.accept_consent();
+        jar
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(|v| v.as_str())
+    }
+
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.cookies.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.cookies.remove(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cookies.is_empty()
+    }
+
+    /// Sets `CONSENT` and `SOCS` unless they already hold an accepted value
+    pub fn accept_consent(&mut self) {
+        if !self.has_consent() {
+            self.insert("CONSENT", CONSENT_COOKIE_VALUE);
+            self.insert("SOCS", SOCS_COOKIE_VALUE);
+        }
+    }
+
+    pub fn has_consent(&self) -> bool {
+        let consent = self
+            .get("CONSENT")
+            .map(|c| c.starts_with("YES"))
+            .unwrap_or(false);
+        consent && self.get("SOCS").is_some()
+    }
+
+    /// Stores the cookie of a `Set-Cookie` response header, attributes are ignored
+    pub fn store_set_cookie(&mut self, header: &str) {
+        let pair = header.split(';').next().unwrap_or_default();
+        let mut split = pair.splitn(2, '=');
+        let name = split.next().unwrap_or_default().trim();
+        let value = split.next().unwrap_or_default().trim();
+        if name.is_empty() {
+            return;
+        }
+        let expired = header.split(';').skip(1).any(|attr| {
+            let attr = attr.trim().to_ascii_lowercase();
+            attr == "max-age=0" || attr.starts_with("max-age=-")
+        });
+        if expired {
+            self.cookies.remove(name);
+        } else {
+            self.insert(name, value);
+        }
+    }
+
+    /// Merges the cookies of a `Cookie` request header
+    pub fn store_cookie_header(&mut self, header: &str) {
+        for pair in header.split(';') {
+            let mut split = pair.splitn(2, '=');
+            let name = split.next().unwrap_or_default().trim();
+            let value = split.next().unwrap_or_default().trim();
+            if !name.is_empty() {
+                self.insert(name, value);
+            }
+        }
+    }
+
+    /// Value for a `Cookie` request header
+    pub fn cookie_header(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn store_set_cookie() {
+        let mut jar = CookieJar::new();
+        jar.store_set_cookie("VISITOR_INFO1_LIVE=abc=def; Domain=.youtube.com; Path=/; Secure");
+        assert_eq!(jar.get("VISITOR_INFO1_LIVE"), Some("abc=def"));
+        jar.store_set_cookie(
+            "YSC=xyz; Expires=Thu, 01 Jan 2026 00:00:00 GMT; Max-Age=3600; HttpOnly",
+        );
+        assert_eq!(jar.get("YSC"), Some("xyz"));
+        jar.store_set_cookie(" YSC = new ");
+        assert_eq!(jar.get("YSC"), Some("new"));
+
+        jar.store_set_cookie("YSC=; Max-Age=0");
+        assert_eq!(jar.get("YSC"), None);
+        jar.store_set_cookie("VISITOR_INFO1_LIVE=gone; max-age=-1");
+        assert_eq!(jar.get("VISITOR_INFO1_LIVE"), None);
+
+        jar.store_set_cookie("=nameless; Path=/");
+        jar.store_set_cookie("");
+        assert!(jar.is_empty());
+    }
+
+    #[test]
+    fn cookie_header() {
+        let mut jar = CookieJar::new();
+        assert_eq!(jar.cookie_header(), "");
+        jar.insert("b", "2");
+        jar.insert("a", "1");
+        assert_eq!(jar.cookie_header(), "a=1; b=2");
+
+        let mut copy = CookieJar::new();
+        copy.store_cookie_header(&jar.cookie_header());
+        assert_eq!(copy, jar);
+    }
+
+    #[test]
+    fn with_consent() {
+        let jar = CookieJar::with_consent();
+        assert!(jar.has_consent());
+        assert_eq!(jar.get("CONSENT"), Some(CONSENT_COOKIE_VALUE));
+        assert_eq!(jar.get("SOCS"), Some(SOCS_COOKIE_VALUE));
+        assert_eq!(jar.cookie_header(), "CONSENT=YES+cb; SOCS=CAI");
+
+        // An answer YouTube gave keeps its value
+        let mut jar = CookieJar::new();
+        jar.insert("CONSENT", "YES+cb.20210328-17-p0.en+FX+123");
+        jar.insert("SOCS", "CAISEwgDEgk0");
+        jar.accept_consent();
+        assert_eq!(jar.get("SOCS"), Some("CAISEwgDEgk0"));
+
+        // A pending one is replaced
+        let mut jar = CookieJar::new();
+        jar.insert("CONSENT", "PENDING+123");
+        assert!(!jar.has_consent());
+        jar.accept_consent();
+        assert_eq!(jar.get("CONSENT"), Some(CONSENT_COOKIE_VALUE));
+    }
+}
@@ -0,0 +1,428 @@
+//! Crowdsourced replacements for clickbait titles and thumbnails from
+//! [DeArrow](https://dearrow.ajay.app)
+
+use crate::downloader_trait::Downloader;
+use crate::sponsorblock::video_hash;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::stream_extractor::{Thumbnail, YTStreamExtractor};
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use futures::stream::{self, StreamExt};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value;
+
+pub const DEFAULT_API_URL: &str = "https://sponsor.ajay.app";
+pub const DEFAULT_THUMBNAIL_URL: &str = "https://dearrow-thumb.ajay.app";
+
+lazy_static! {
+    /// `>` in front of a word keeps it from being auto formatted by the extension
+    static ref FORMAT_MARKER: Regex = Regex::new(r"(^|\s)>(\S)").unwrap();
+}
+
+/// What the community submitted for a video, `None` where the original should stay
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Branding {
+    pub title: Option<String>,
+    /// Seconds into the video of the frame to show as thumbnail
+    pub thumbnail_time: Option<f64>,
+}
+
+/// A value of the video next to its DeArrow replacement, if there is one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Branded<T> {
+    pub original: T,
+    pub replacement: Option<T>,
+}
+
+impl<T> Branded<T> {
+    /// The replacement, or the original if there is none
+    pub fn preferred(&self) -> &T {
+        self.replacement.as_ref().unwrap_or(&self.original)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrandedVideo {
+    pub video_id: String,
+    pub title: Branded<String>,
+    /// Url of the largest thumbnail, replaced by a frame rendered by the thumbnail server
+    pub thumbnail: Branded<String>,
+}
+
+/// The first locked submission, or else the first that isn't downvoted, unless it's the
+/// original
+fn top_submission(submissions: Option<&Value>) -> Option<&Value> {
+    let submissions = submissions?.as_array()?;
+    let submission = submissions
+        .iter()
+        .find(|s| s.get("locked").and_then(|l| l.as_bool()) == Some(true))
+        .or_else(|| {
+            submissions
+                .iter()
+                .find(|s| s.get("votes").and_then(|v| v.as_i64()).unwrap_or_default() >= 0)
+        })?;
+    if submission.get("original").and_then(|o| o.as_bool()) == Some(true) {
+        return None;
+    }
+    Some(submission)
+}
+
+fn parse_branding(branding: &Value) -> Branding {
+    let title = top_submission(branding.get("titles"))
+        .and_then(|t| t.get("title"))
+        .and_then(|t| t.as_str())
+        .map(|t| FORMAT_MARKER.replace_all(t, "$1$2").trim().to_string())
+        .filter(|t| !t.is_empty());
+    let thumbnail_time = top_submission(branding.get("thumbnails"))
+        .and_then(|t| t.get("timestamp"))
+        .and_then(|t| t.as_f64());
+    Branding {
+        title,
+        thumbnail_time,
+    }
+}
+
+/// Branding of the video with id `video_id` in a response for a hash prefix
+pub fn parse_brandings(response: &str, video_id: &str) -> Result<Branding, ParsingError> {
+    let videos: Value = serde_json::from_str(response).map_err(|e| e.to_string())?;
+    let videos = videos
+        .as_object()
+        .ok_or("branding response is not an object")?;
+    Ok(videos.get(video_id).map(parse_branding).unwrap_or_default())
+}
+
+fn largest_thumbnail(thumbnails: Result<Vec<Thumbnail>, ParsingError>, video_id: &str) -> String {
+    thumbnails
+        .ok()
+        .and_then(|t| t.into_iter().max_by_key(|t| t.width * t.height))
+        .map(|t| t.url)
+        .unwrap_or_else(|| format!("https://i.ytimg.com/vi/{}/hqdefault.jpg", video_id))
+}
+
+pub struct DeArrow<D> {
+    downloader: D,
+    api_url: String,
+    thumbnail_url: String,
+    /// Hex digits of the video id's SHA-256 sent to the server, 4 to 32
+    hash_prefix_length: usize,
+    /// Branding requests in flight at the same time in `decorate_items`
+    concurrency: usize,
+}
+
+impl<D: Downloader> DeArrow<D> {
+    pub fn new(downloader: D) -> Self {
+        DeArrow {
+            downloader,
+            api_url: DEFAULT_API_URL.to_string(),
+            thumbnail_url: DEFAULT_THUMBNAIL_URL.to_string(),
+            hash_prefix_length: 4,
+            concurrency: 8,
+        }
+    }
+
+    /// Uses another instance or a mock server
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_thumbnail_url(mut self, thumbnail_url: &str) -> Self {
+        self.thumbnail_url = thumbnail_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_hash_prefix_length(mut self, length: usize) -> Self {
+        self.hash_prefix_length = length.clamp(4, 32);
+        self
+    }
+
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    pub fn url(&self, video_id: &str) -> String {
+        let hash = video_hash(video_id);
+        format!(
+            "{}/api/branding/{}",
+            self.api_url,
+            &hash[..self.hash_prefix_length]
+        )
+    }
+
+    /// Image of the frame at `time` seconds into the video
+    pub fn thumbnail_url(&self, video_id: &str, time: f64) -> String {
+        format!(
+            "{}/api/v1/getThumbnail?videoID={}&time={}",
+            self.thumbnail_url, video_id, time
+        )
+    }
+
+    /// Empty if nothing was submitted for the video
+    pub async fn branding(&self, video_id: &str) -> Result<Branding, ParsingError> {
+        match self.downloader.download(&self.url(video_id)).await {
+            Ok(response) => parse_brandings(&response, video_id),
+            Err(error) if error.http_status() == Some(404) => Ok(Branding::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn decorate(
+        &self,
+        video_id: String,
+        title: String,
+        thumbnail: String,
+    ) -> Result<BrandedVideo, ParsingError> {
+        let branding = self.branding(&video_id).await?;
+        let thumbnail_replacement = branding
+            .thumbnail_time
+            .map(|time| self.thumbnail_url(&video_id, time));
+        Ok(BrandedVideo {
+            title: Branded {
+                original: title,
+                replacement: branding.title,
+            },
+            thumbnail: Branded {
+                original: thumbnail,
+                replacement: thumbnail_replacement,
+            },
+            video_id,
+        })
+    }
+
+    pub async fn decorate_item(
+        &self,
+        item: &YTStreamInfoItemExtractor,
+    ) -> Result<BrandedVideo, ParsingError> {
+        let video_id = item.video_id()?;
+        let thumbnail = largest_thumbnail(item.thumbnails(), &video_id);
+        self.decorate(video_id, item.name()?, thumbnail).await
+    }
+
+    /// Items in the same order, each failing on its own
+    pub async fn decorate_items(
+        &self,
+        items: &[YTStreamInfoItemExtractor],
+    ) -> Vec<Result<BrandedVideo, ParsingError>> {
+        stream::iter(items)
+            .map(|item| self.decorate_item(item))
+            .buffered(self.concurrency)
+            .collect()
+            .await
+    }
+
+    pub async fn decorate_stream<E: Downloader>(
+        &self,
+        extractor: &YTStreamExtractor<E>,
+    ) -> Result<BrandedVideo, ParsingError> {
+        let video_id = extractor.video_id();
+        let thumbnail = largest_thumbnail(extractor.video_thumbnails(), &video_id);
+        self.decorate(video_id, extractor.name()?, thumbnail).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const RESPONSE: &str = r#"{
+        "otherVideo1": {
+            "titles": [{"title": "Not this one", "original": false, "votes": 5, "locked": true}],
+            "thumbnails": [{"timestamp": 1.0, "original": false, "votes": 5, "locked": true}]
+        },
+        "dQw4w9WgXcQ": {
+            "titles": [
+                {"title": "Never >gonna give you up, >the music video", "original": false, "votes": 3, "locked": false},
+                {"title": "Rick Astley - Never Gonna Give You Up", "original": true, "votes": 1, "locked": false}
+            ],
+            "thumbnails": [
+                {"timestamp": 42.25, "original": false, "votes": 0, "locked": false}
+            ],
+            "randomTime": 0.5,
+            "videoDuration": 212
+        },
+        "aaaaaaaaaaa": {
+            "titles": [{"title": "Original", "original": true, "votes": 0, "locked": false}],
+            "thumbnails": []
+        }
+    }"#;
+
+    /// Answers with `RESPONSE`, and a 500 for the urls in `failing`
+    struct Api {
+        failing: Vec<String>,
+    }
+
+    #[async_trait]
+    impl Downloader for Api {
+        async fn download(&self, url: &str) -> Result<String, ParsingError> {
+            if self.failing.iter().any(|failing| failing == url) {
+                return Err(ParsingError::HttpError {
+                    cause: format!("{} returned 500", url),
+                    status: 500,
+                });
+            }
+            Ok(RESPONSE.to_string())
+        }
+
+        async fn download_with_header(
+            &self,
+            url: &str,
+            _header: HashMap<String, String>,
+        ) -> Result<String, ParsingError> {
+            self.download(url).await
+        }
+
+        fn eval_js(&self, _script: &str) -> Result<String, String> {
+            unimplemented!()
+        }
+    }
+
+    fn title(submission: Option<&Value>) -> Option<&str> {
+        submission?.get("title")?.as_str()
+    }
+
+    #[test]
+    fn top_submissions() {
+        let locked = json!([
+            {"title": "Popular", "original": false, "votes": 10, "locked": false},
+            {"title": "Locked", "original": false, "votes": 0, "locked": true},
+        ]);
+        assert_eq!(title(top_submission(Some(&locked))), Some("Locked"));
+        // Even a downvoted one
+        let downvoted_lock = json!([
+            {"title": "Popular", "original": false, "votes": 10, "locked": false},
+            {"title": "Locked", "original": false, "votes": -3, "locked": true},
+        ]);
+        assert_eq!(title(top_submission(Some(&downvoted_lock))), Some("Locked"));
+
+        let downvoted = json!([
+            {"title": "Bad", "original": false, "votes": -1, "locked": false},
+            {"title": "Unvoted", "original": false, "votes": 0, "locked": false},
+        ]);
+        assert_eq!(title(top_submission(Some(&downvoted))), Some("Unvoted"));
+        let all_downvoted = json!([{"title": "Bad", "votes": -1}]);
+        assert_eq!(top_submission(Some(&all_downvoted)), None);
+
+        // The original winning means there is nothing to replace
+        let original = json!([
+            {"title": "Original", "original": true, "votes": 4, "locked": false},
+            {"title": "New", "original": false, "votes": 2, "locked": false},
+        ]);
+        assert_eq!(top_submission(Some(&original)), None);
+        let locked_original = json!([
+            {"title": "New", "original": false, "votes": 9, "locked": false},
+            {"title": "Original", "original": true, "votes": 0, "locked": true},
+        ]);
+        assert_eq!(top_submission(Some(&locked_original)), None);
+
+        assert_eq!(top_submission(Some(&json!([]))), None);
+        assert_eq!(top_submission(Some(&json!({}))), None);
+        assert_eq!(top_submission(None), None);
+    }
+
+    #[test]
+    fn parses_brandings() {
+        assert_eq!(
+            parse_brandings(RESPONSE, "dQw4w9WgXcQ").unwrap(),
+            Branding {
+                title: Some("Never gonna give you up, the music video".to_string()),
+                thumbnail_time: Some(42.25),
+            }
+        );
+        assert_eq!(
+            parse_brandings(RESPONSE, "aaaaaaaaaaa").unwrap(),
+            Branding::default()
+        );
+        // Other videos with the same hash prefix
+        assert_eq!(
+            parse_brandings(RESPONSE, "bbbbbbbbbbb").unwrap(),
+            Branding::default()
+        );
+        let blank = r#"{"dQw4w9WgXcQ": {"titles": [{"title": "  ", "votes": 1}]}}"#;
+        assert_eq!(
+            parse_brandings(blank, "dQw4w9WgXcQ").unwrap(),
+            Branding::default()
+        );
+        assert!(parse_brandings("[]", "dQw4w9WgXcQ").is_err());
+        assert!(parse_brandings("<html>", "dQw4w9WgXcQ").is_err());
+    }
+
+    fn item(video_info: Value) -> YTStreamInfoItemExtractor {
+        YTStreamInfoItemExtractor {
+            video_info: video_info.as_object().unwrap().clone(),
+        }
+    }
+
+    #[test]
+    fn decorates_items_next_to_the_originals() {
+        let dearrow = DeArrow::new(Api {
+            failing: vec![DeArrow::new(Api { failing: vec![] }).url("ccccccccccc")],
+        })
+        .with_thumbnail_url("https://thumbnails.example/")
+        .with_concurrency(2);
+        let items = [
+            item(json!({
+                "videoId": "dQw4w9WgXcQ",
+                "title": { "simpleText": "Rick Astley - Never Gonna Give You Up" },
+                "thumbnail": { "thumbnails": [
+                    { "url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/mq.jpg", "width": 320, "height": 180 },
+                    { "url": "https://i.ytimg.com/vi/dQw4w9WgXcQ/hq.jpg", "width": 480, "height": 360 },
+                ] },
+            })),
+            item(json!({
+                "videoId": "aaaaaaaaaaa",
+                "title": { "simpleText": "Original" },
+            })),
+            item(json!({
+                "videoId": "ccccccccccc",
+                "title": { "simpleText": "Unreachable" },
+            })),
+            item(json!({ "title": { "simpleText": "No id" } })),
+        ];
+        let decorated = block_on(dearrow.decorate_items(&items));
+        assert_eq!(decorated.len(), 4);
+
+        let rick = decorated[0].as_ref().unwrap();
+        assert_eq!(rick.video_id, "dQw4w9WgXcQ");
+        assert_eq!(
+            rick.title,
+            Branded {
+                original: "Rick Astley - Never Gonna Give You Up".to_string(),
+                replacement: Some("Never gonna give you up, the music video".to_string()),
+            }
+        );
+        assert_eq!(
+            rick.thumbnail,
+            Branded {
+                original: "https://i.ytimg.com/vi/dQw4w9WgXcQ/hq.jpg".to_string(),
+                replacement: Some(
+                    "https://thumbnails.example/api/v1/getThumbnail?videoID=dQw4w9WgXcQ&time=42.25"
+                        .to_string()
+                ),
+            }
+        );
+        assert_eq!(
+            rick.title.preferred(),
+            rick.title.replacement.as_ref().unwrap()
+        );
+
+        let original = decorated[1].as_ref().unwrap();
+        assert_eq!(original.title.replacement, None);
+        assert_eq!(original.title.preferred(), "Original");
+        // Without thumbnails the default one of the video
+        assert_eq!(
+            original.thumbnail,
+            Branded {
+                original: "https://i.ytimg.com/vi/aaaaaaaaaaa/hqdefault.jpg".to_string(),
+                replacement: None,
+            }
+        );
+
+        assert_eq!(decorated[2].as_ref().unwrap_err().http_status(), Some(500));
+        assert!(decorated[3].is_err());
+    }
+}
@@ -0,0 +1,251 @@
+//! Self-check of the extractors against the pages YouTube currently serves, to notice renderer
+//! changes before users do
+
+use crate::downloader_trait::Downloader;
+use crate::metrics::{self, capture};
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::channel_info_item_extractor::YTChannelInfoItemExtractor;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::playlist_extractor::YTPlaylistExtractor;
+use crate::youtube_extractor::playlist_info_item_extractor::YTPlaylistInfoItemExtractor;
+use crate::youtube_extractor::search_extractor::{YTSearchExtractor, YTSearchItem};
+use crate::youtube_extractor::stream_extractor::YTStreamExtractor;
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use crate::youtube_extractor::trending_extractor::YTTrendingExtractor;
+use serde::Serialize;
+
+/// Outcome of an accessor, summed over all items for fields of item lists like `videos.name`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldReport {
+    pub field: String,
+    pub succeeded: u64,
+    /// Succeeded, but from a secondary source
+    pub fell_back: u64,
+    pub failed: u64,
+    /// Distinct secondary sources that were read
+    pub fallback_sources: Vec<&'static str>,
+    /// First error, the others are usually the same
+    pub error: Option<String>,
+}
+
+impl FieldReport {
+    fn new(field: String) -> Self {
+        FieldReport {
+            field,
+            succeeded: 0,
+            fell_back: 0,
+            failed: 0,
+            fallback_sources: vec![],
+            error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub extractor: &'static str,
+    pub fields: Vec<FieldReport>,
+}
+
+impl Report {
+    fn new(extractor: &'static str) -> Self {
+        Report {
+            extractor,
+            fields: vec![],
+        }
+    }
+
+    /// Runs `accessor` and counts its outcome for `field`
+    fn check<T>(
+        &mut self,
+        field: &str,
+        accessor: impl FnOnce() -> Result<T, ParsingError>,
+    ) -> Option<T> {
+        let (result, sources) = capture(accessor);
+        let report = match self.fields.iter().position(|f| f.field == field) {
+            Some(i) => &mut self.fields[i],
+            None => {
+                self.fields.push(FieldReport::new(field.to_string()));
+                self.fields.last_mut().unwrap()
+            }
+        };
+        match result {
+            Ok(value) => {
+                if sources.is_empty() {
+                    report.succeeded += 1;
+                } else {
+                    report.fell_back += 1;
+                    for source in sources {
+                        if !report.fallback_sources.contains(&source) {
+                            report.fallback_sources.push(source);
+                        }
+                    }
+                }
+                Some(value)
+            }
+            Err(error) => {
+                report.failed += 1;
+                report.error.get_or_insert_with(|| error.to_string());
+                None
+            }
+        }
+    }
+
+    /// The `new` entry, for the fallbacks the extractor took while loading
+    fn loaded(&mut self, sources: &[&'static str]) {
+        let mut report = FieldReport::new("new".to_string());
+        if sources.is_empty() {
+            report.succeeded = 1;
+        } else {
+            report.fell_back = 1;
+            report.fallback_sources = sources.to_vec();
+        }
+        self.fields.push(report);
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &FieldReport> {
+        self.fields.iter().filter(|f| f.failed > 0)
+    }
+
+    pub fn fell_back(&self) -> impl Iterator<Item = &FieldReport> {
+        self.fields.iter().filter(|f| f.fell_back > 0)
+    }
+
+    /// No field failed, fallbacks are fine
+    pub fn is_healthy(&self) -> bool {
+        self.failed().next().is_none()
+    }
+
+    fn stream_info_item(&mut self, prefix: &str, item: &YTStreamInfoItemExtractor) {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        self.check(&field("name"), || item.name());
+        self.check(&field("video_id"), || item.video_id());
+        self.check(&field("url"), || item.url());
+        self.check(&field("is_ad"), || item.is_ad());
+        self.check(&field("is_premium_video"), || item.is_premium_video());
+        let live = self.check(&field("is_live"), || item.is_live());
+        // Live streams have neither a length nor an upload date
+        if live != Some(true) {
+            self.check(&field("textual_duration"), || item.textual_duration());
+            self.check(&field("duration"), || item.duration());
+            self.check(&field("textual_upload_date"), || item.textual_upload_date());
+            self.check(&field("upload_date"), || item.upload_date());
+        }
+        self.check(&field("uploader_name"), || item.uploader_name());
+        self.check(&field("uploader_url"), || item.uploader_url());
+        self.check(&field("textual_view_count"), || item.textual_view_count());
+        self.check(&field("view_count"), || item.view_count());
+        self.check(&field("thumbnails"), || item.thumbnails());
+        self.check(&field("uploader_thumbnails"), || item.uploader_thumbnails());
+    }
+
+    fn channel_info_item(&mut self, prefix: &str, item: &YTChannelInfoItemExtractor) {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        self.check(&field("name"), || item.name());
+        self.check(&field("channel_id"), || item.channel_id());
+        self.check(&field("url"), || item.url());
+        self.check(&field("thumbnails"), || item.thumbnails());
+        self.check(&field("subscriber_count"), || item.subscriber_count());
+        self.check(&field("stream_count"), || item.stream_count());
+        self.check(&field("description"), || item.description());
+    }
+
+    fn playlist_info_item(&mut self, prefix: &str, item: &YTPlaylistInfoItemExtractor) {
+        let field = |name: &str| format!("{}.{}", prefix, name);
+        self.check(&field("name"), || item.name());
+        self.check(&field("playlist_id"), || item.playlist_id());
+        self.check(&field("url"), || item.url());
+        self.check(&field("thumbnails"), || item.thumbnails());
+        self.check(&field("uploader_name"), || item.uploader_name());
+        self.check(&field("stream_count"), || item.stream_count());
+    }
+
+    fn search_items(&mut self, prefix: &str, items: &[YTSearchItem]) {
+        for item in items {
+            match item {
+                YTSearchItem::StreamInfoItem(item) => {
+                    self.stream_info_item(&format!("{}.stream", prefix), item)
+                }
+                YTSearchItem::ChannelInfoItem(item) => {
+                    self.channel_info_item(&format!("{}.channel", prefix), item)
+                }
+                YTSearchItem::PlaylistInfoItem(item) => {
+                    self.playlist_info_item(&format!("{}.playlist", prefix), item)
+                }
+            }
+        }
+    }
+
+    fn videos(&mut self, videos: Option<Vec<YTStreamInfoItemExtractor>>) {
+        for video in videos.unwrap_or_default() {
+            self.stream_info_item("videos", &video);
+        }
+    }
+}
+
+pub fn stream<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Report {
+    let mut report = Report::new(metrics::STREAM);
+    report.loaded(extractor.load_fallbacks());
+    report.check("name", || extractor.name());
+    report.check("textual_upload_date", || extractor.textual_upload_date());
+    report.check("upload_date", || extractor.upload_date());
+    report.check("description", || extractor.description(true));
+    report.check("video_thumbnails", || extractor.video_thumbnails());
+    report.check("length", || extractor.length());
+    report.check("view_count", || extractor.view_count());
+    report.check("like_count", || extractor.like_count());
+    report.check("dislike_count", || extractor.dislike_count());
+    report.check("uploader_url", || extractor.uploader_url());
+    report.check("uploader_id", || extractor.uploader_id());
+    report.check("uploader_name", || extractor.uploader_name());
+    report.check("uploader_avatar_url", || extractor.uploader_avatar_url());
+    report.check("video_stream", || extractor.video_stream());
+    report.check("video_only_stream", || extractor.video_only_stream());
+    report.check("audio_streams", || extractor.audio_streams());
+    if let Some(related) = report.check("related", || extractor.related()) {
+        report.search_items("related", &related);
+    }
+    report
+}
+
+pub fn channel<D: Downloader>(extractor: &YTChannelExtractor<D>) -> Report {
+    let mut report = Report::new(metrics::CHANNEL);
+    report.check("channel_id", || extractor.channel_id());
+    report.check("name", || extractor.name());
+    report.check("avatars", || extractor.avatars());
+    report.check("banners", || extractor.banners());
+    let videos = report.check("videos", || extractor.videos());
+    report.videos(videos);
+    report.check("next_page_url", || extractor.next_page_url());
+    report
+}
+
+pub fn playlist<D: Downloader>(extractor: &YTPlaylistExtractor<D>) -> Report {
+    let mut report = Report::new(metrics::PLAYLIST);
+    report.check("name", || extractor.name());
+    report.check("thumbnails", || extractor.thumbnails());
+    report.check("uploader_url", || extractor.uploader_url());
+    report.check("uploader_name", || extractor.uploader_name());
+    report.check("uploader_avatars", || extractor.uploader_avatars());
+    report.check("stream_count", || extractor.stream_count());
+    let videos = report.check("videos", || extractor.videos());
+    report.videos(videos);
+    report.check("next_page_url", || extractor.next_page_url());
+    report
+}
+
+pub fn search<D: Downloader>(extractor: &YTSearchExtractor<D>) -> Report {
+    let mut report = Report::new(metrics::SEARCH);
+    if let Some(results) = report.check("search_results", || extractor.search_results()) {
+        report.search_items("search_results", &results);
+    }
+    report.check("next_page_url", || extractor.next_page_url());
+    report
+}
+
+pub fn trending<D: Downloader>(extractor: &YTTrendingExtractor<D>) -> Report {
+    let mut report = Report::new(metrics::TRENDING);
+    let videos = report.check("videos", || extractor.videos());
+    report.videos(videos);
+    report
+}
@@ -0,0 +1,420 @@
+//! Downloads of streams in parallel Range requests
+//!
+//! googlevideo throttles long single connections, so a stream is fetched in chunks over
+//! several connections. Data goes to `<path>.part` and finished chunks are recorded in the
+//! sidecar `<path>.part.json`, so a paused or interrupted download continues where it stopped
+//! when it is started again. Urls that expire or get rejected midway are replaced by running
+//! `YTStreamExtractor` again.
+//!
+//! Requests go through [`Downloader::download_range`], file I/O runs on threads of its own so
+//! the executor is never blocked by the disk.
+
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::stream_extractor::{StreamItem, YTStreamExtractor};
+use futures::channel::oneshot;
+use futures::lock::Mutex as AsyncMutex;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Urls expiring within this many seconds are refreshed before use
+const EXPIRY_MARGIN: u64 = 60;
+
+#[derive(Clone, Debug)]
+pub struct DownloadConfig {
+    /// Chunks downloaded at the same time
+    pub connections: usize,
+    /// Bytes per Range request, googlevideo slows down requests of more than 10 MiB
+    pub chunk_size: u64,
+    /// Attempts per chunk after the first one failed
+    pub retries: usize,
+}
+
+impl Default for DownloadConfig {
+    fn default() -> Self {
+        DownloadConfig {
+            connections: 4,
+            chunk_size: 10 * 1024 * 1024,
+            retries: 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub downloaded: u64,
+    pub total: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DownloadStatus {
+    Finished,
+    /// Stopped by a [`PauseHandle`], downloading the same path again resumes
+    Paused,
+}
+
+/// Pauses a download once the chunks in flight are written
+#[derive(Clone, Default)]
+pub struct PauseHandle(Arc<AtomicBool>);
+
+impl PauseHandle {
+    pub fn pause(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Sidecar of a partial download
+#[derive(Serialize, Deserialize)]
+struct State {
+    video_id: String,
+    itag: u32,
+    url: String,
+    total: u64,
+    chunk_size: u64,
+    done: Vec<bool>,
+}
+
+impl State {
+    fn load(path: &Path) -> Option<State> {
+        serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+    }
+
+    async fn save(&self, path: &Path) -> Result<(), ParsingError> {
+        let json = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        let path = path.to_path_buf();
+        unblock(move || std::fs::write(path, json)).await
+    }
+
+    /// Inclusive byte range of a chunk
+    fn range(&self, chunk: usize) -> (u64, u64) {
+        let start = chunk as u64 * self.chunk_size;
+        (start, (start + self.chunk_size).min(self.total) - 1)
+    }
+}
+
+fn io_error(error: std::io::Error) -> ParsingError {
+    ParsingError::DownloadError {
+        cause: error.to_string(),
+    }
+}
+
+fn download_error(cause: String) -> ParsingError {
+    ParsingError::DownloadError { cause }
+}
+
+/// Runs blocking file I/O on a thread of its own and waits for it without blocking the executor
+async fn unblock<T, F>(io: F) -> Result<T, ParsingError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+{
+    let (sender, receiver) = oneshot::channel();
+    std::thread::spawn(move || sender.send(io()));
+    receiver
+        .await
+        .map_err(|_| download_error("File I/O thread panicked".to_string()))?
+        .map_err(io_error)
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = OsString::from(path);
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn expires_soon(url: &str) -> bool {
+    let expire = url
+        .split_once('?')
+        .and_then(|(_, query)| query.split('&').find_map(|p| p.strip_prefix("expire=")))
+        .and_then(|expire| expire.parse::<u64>().ok());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    expire.is_some_and(|expire| expire <= now + EXPIRY_MARGIN)
+}
+
+enum ChunkError {
+    /// googlevideo refused the url, it expired or belongs to another address
+    Rejected,
+    Failed(ParsingError),
+}
+
+/// Url of the stream being downloaded, with a generation so that workers hitting the same
+/// expired url only extract once
+struct Source<'a, D> {
+    manager: &'a DownloadManager<D>,
+    video_id: &'a str,
+    itag: u32,
+    url: AsyncMutex<(u64, String)>,
+}
+
+impl<'a, D: Downloader + Clone + Sync> Source<'a, D> {
+    async fn url(&self) -> Result<(u64, String), ParsingError> {
+        let (generation, url) = self.url.lock().await.clone();
+        // Fresh urls are used until they get rejected
+        if generation == 0 && expires_soon(&url) {
+            self.refresh(generation).await?;
+            return Ok(self.url.lock().await.clone());
+        }
+        Ok((generation, url))
+    }
+
+    async fn refresh(&self, generation: u64) -> Result<(), ParsingError> {
+        let mut current = self.url.lock().await;
+        if current.0 == generation {
+            let url = self.manager.extract(self.video_id, self.itag).await?;
+            *current = (generation + 1, url);
+        }
+        Ok(())
+    }
+}
+
+pub struct DownloadManager<D> {
+    downloader: D,
+    config: DownloadConfig,
+}
+
+impl<D: Downloader + Clone + Sync> DownloadManager<D> {
+    pub fn new(downloader: D, config: DownloadConfig) -> Self {
+        DownloadManager { downloader, config }
+    }
+
+    /// Body of a small file like a thumbnail, e.g. for [`crate::mux::Cover`]
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, ParsingError> {
+        self.downloader.download_bytes(url).await
+    }
+
+    /// Fresh url of the stream with the itag
+    async fn extract(&self, video_id: &str, itag: u32) -> Result<String, ParsingError> {
+        tracing::debug!(video_id, itag, "extracting stream url");
+        let extractor = YTStreamExtractor::new(self.downloader.clone(), video_id).await?;
+        [
+            extractor.video_stream(),
+            extractor.video_only_stream(),
+            extractor.audio_streams(),
+        ]
+        .iter()
+        .flatten()
+        .flatten()
+        .find(|stream| stream.itag == itag)
+        .and_then(|stream| stream.url.clone())
+        .ok_or_else(|| ParsingError::from(format!("No stream with itag {} for {}", itag, video_id)))
+    }
+
+    async fn request(&self, url: &str, start: u64, end: u64) -> Result<(Vec<u8>, u64), ChunkError> {
+        match self.downloader.download_range(url, start, end).await {
+            Ok(response) => Ok(response),
+            Err(e) if matches!(e.http_status(), Some(403) | Some(410)) => Err(ChunkError::Rejected),
+            Err(e) => Err(ChunkError::Failed(e)),
+        }
+    }
+
+    /// Size of the stream from the `Content-Range` of a one byte request
+    async fn probe_length(&self, source: &Source<'_, D>) -> Result<u64, ParsingError> {
+        for _ in 0..=self.config.retries {
+            let (generation, url) = source.url().await?;
+            match self.request(&url, 0, 0).await {
+                Ok((_, total)) => return Ok(total),
+                Err(ChunkError::Rejected) => source.refresh(generation).await?,
+                Err(ChunkError::Failed(e)) => return Err(e),
+            }
+        }
+        Err(download_error(
+            "Stream url keeps getting rejected".to_string(),
+        ))
+    }
+
+    async fn write_range<R: Fn(u64)>(
+        &self,
+        url: &str,
+        (start, end): (u64, u64),
+        file: &Arc<Mutex<File>>,
+        downloaded: &AtomicU64,
+        report: &R,
+    ) -> Result<(), ChunkError> {
+        let (bytes, _) = self.request(url, start, end).await?;
+        if bytes.len() as u64 != end - start + 1 {
+            return Err(ChunkError::Failed(download_error(format!(
+                "googlevideo sent {} bytes for a range of {}",
+                bytes.len(),
+                end - start + 1
+            ))));
+        }
+        let len = bytes.len() as u64;
+        let file = file.clone();
+        unblock(move || {
+            let mut file = file.lock().unwrap();
+            file.seek(SeekFrom::Start(start))?;
+            file.write_all(&bytes)
+        })
+        .await
+        .map_err(ChunkError::Failed)?;
+        report(downloaded.fetch_add(len, Ordering::SeqCst) + len);
+        Ok(())
+    }
+
+    async fn chunk<R: Fn(u64)>(
+        &self,
+        source: &Source<'_, D>,
+        range: (u64, u64),
+        file: &Arc<Mutex<File>>,
+        downloaded: &AtomicU64,
+        report: &R,
+    ) -> Result<String, ParsingError> {
+        let mut attempt = 0;
+        loop {
+            let (generation, url) = source.url().await?;
+            let error = match self
+                .write_range(&url, range, file, downloaded, report)
+                .await
+            {
+                Ok(()) => return Ok(url),
+                Err(ChunkError::Rejected) => {
+                    tracing::debug!(
+                        video_id = source.video_id,
+                        "stream url rejected, refreshing"
+                    );
+                    source.refresh(generation).await?;
+                    download_error("Stream url keeps getting rejected".to_string())
+                }
+                Err(ChunkError::Failed(e)) => e,
+            };
+            if attempt >= self.config.retries {
+                return Err(error);
+            }
+            tracing::debug!(video_id = source.video_id, attempt, error = %error, "retrying chunk");
+            attempt += 1;
+        }
+    }
+
+    /// Downloads the stream of the video to `path`, or continues an earlier download of it
+    pub async fn download<F: Fn(Progress) + Send + Sync>(
+        &self,
+        video_id: &str,
+        stream: &StreamItem,
+        path: &Path,
+        pause: &PauseHandle,
+        progress: F,
+    ) -> Result<DownloadStatus, ParsingError> {
+        let part_path = suffixed(path, ".part");
+        let state_path = suffixed(path, ".part.json");
+        let (part, state_file) = (part_path.clone(), state_path.clone());
+        let resumed = unblock(move || Ok(State::load(&state_file).filter(|_| part.exists())))
+            .await?
+            .filter(|state| {
+                state.video_id == video_id
+                    && state.itag == stream.itag
+                    && state.chunk_size == self.config.chunk_size.max(1)
+            });
+        let url = match (&resumed, &stream.url) {
+            (Some(state), _) => state.url.clone(),
+            (None, Some(url)) => url.clone(),
+            (None, None) => self.extract(video_id, stream.itag).await?,
+        };
+        let source = Source {
+            manager: self,
+            video_id,
+            itag: stream.itag,
+            url: AsyncMutex::new((0, url)),
+        };
+        let mut state = match resumed {
+            Some(state) => state,
+            None => {
+                let content_length = stream.content_length.as_ref().and_then(|l| l.parse().ok());
+                let total = match content_length {
+                    Some(total) => total,
+                    None => self.probe_length(&source).await?,
+                };
+                let chunk_size = self.config.chunk_size.max(1);
+                State {
+                    video_id: video_id.to_string(),
+                    itag: stream.itag,
+                    url: source.url.lock().await.1.clone(),
+                    total,
+                    chunk_size,
+                    done: vec![false; total.div_ceil(chunk_size) as usize],
+                }
+            }
+        };
+        let (part, total) = (part_path.clone(), state.total);
+        let file = unblock(move || {
+            let file = OpenOptions::new()
+                .create(true)
+                .write(true)
+                .truncate(false)
+                .open(part)?;
+            file.set_len(total)?;
+            Ok(file)
+        })
+        .await?;
+        let file = Arc::new(Mutex::new(file));
+        state.save(&state_path).await?;
+
+        let total = state.total;
+        let downloaded: u64 = (0..state.done.len())
+            .filter(|chunk| state.done[*chunk])
+            .map(|chunk| {
+                let (start, end) = state.range(chunk);
+                end - start + 1
+            })
+            .sum();
+        let downloaded = AtomicU64::new(downloaded);
+        let report = |downloaded| progress(Progress { downloaded, total });
+        report(downloaded.load(Ordering::SeqCst));
+
+        let pending: Vec<(usize, (u64, u64))> = (0..state.done.len())
+            .filter(|chunk| !state.done[*chunk])
+            .map(|chunk| (chunk, state.range(chunk)))
+            .collect();
+        let (source, part_file, downloaded, report) = (&source, &file, &downloaded, &report);
+        let mut chunks = stream::iter(pending)
+            .map(|(chunk, range)| async move {
+                if pause.is_paused() {
+                    return (chunk, None);
+                }
+                let result = self
+                    .chunk(source, range, part_file, downloaded, report)
+                    .await;
+                (chunk, Some(result))
+            })
+            .buffer_unordered(self.config.connections.max(1));
+        while let Some((chunk, result)) = chunks.next().await {
+            match result {
+                Some(Ok(url)) => {
+                    state.done[chunk] = true;
+                    state.url = url;
+                    state.save(&state_path).await?;
+                }
+                Some(Err(e)) => return Err(e),
+                None => {}
+            }
+        }
+        drop(chunks);
+
+        if state.done.iter().any(|done| !done) {
+            return Ok(DownloadStatus::Paused);
+        }
+        // Closed before the rename, which fails for open files on Windows
+        drop(file);
+        let path = path.to_path_buf();
+        unblock(move || {
+            std::fs::rename(&part_path, path)?;
+            let _ = std::fs::remove_file(&state_path);
+            Ok(())
+        })
+        .await?;
+        Ok(DownloadStatus::Finished)
+    }
+}
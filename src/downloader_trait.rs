@@ -1,3 +1,4 @@
+use crate::cookie_jar::CookieJar;
 use crate::youtube_extractor::error::ParsingError;
 use async_trait::async_trait;
 use std::collections::HashMap;
@@ -12,6 +13,27 @@ pub trait Downloader {
         header: HashMap<String, String>,
     ) -> Result<String, ParsingError>;
     fn eval_js(&self, script: &str) -> Result<String, String>;
+
+    /// Cookies sent along with youtube requests, override to persist them
+    fn cookie_jar(&self) -> CookieJar {
+        CookieJar::with_consent()
+    }
+
+    /// Body of a binary resource like a thumbnail, for `DownloadManager`
+    async fn download_bytes(&self, url: &str) -> Result<Vec<u8>, ParsingError> {
+        Err(unsupported(url))
+    }
+
+    /// Bytes `start..=end` of a stream and the stream's size from the `Content-Range` of the
+    /// 206 response, for `DownloadManager`
+    async fn download_range(
+        &self,
+        url: &str,
+        _start: u64,
+        _end: u64,
+    ) -> Result<(Vec<u8>, u64), ParsingError> {
+        Err(unsupported(url))
+    }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -24,4 +46,31 @@ pub trait Downloader {
         header: HashMap<String, String>,
     ) -> Result<String, ParsingError>;
     fn eval_js(&self, script: &str) -> Result<String, String>;
+
+    /// Cookies sent along with youtube requests, override to persist them
+    fn cookie_jar(&self) -> CookieJar {
+        CookieJar::with_consent()
+    }
+
+    /// Body of a binary resource like a thumbnail, for `DownloadManager`
+    async fn download_bytes(&self, url: &str) -> Result<Vec<u8>, ParsingError> {
+        Err(unsupported(url))
+    }
+
+    /// Bytes `start..=end` of a stream and the stream's size from the `Content-Range` of the
+    /// 206 response, for `DownloadManager`
+    async fn download_range(
+        &self,
+        url: &str,
+        _start: u64,
+        _end: u64,
+    ) -> Result<(Vec<u8>, u64), ParsingError> {
+        Err(unsupported(url))
+    }
+}
+
+fn unsupported(url: &str) -> ParsingError {
+    ParsingError::DownloadError {
+        cause: format!("Downloader can not fetch binary data of {}", url),
+    }
 }
@@ -0,0 +1,134 @@
+use super::DownloaderConfig;
+use crate::cookie_jar::CookieJar;
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AbortController, Headers, Request, RequestInit, Response};
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = fetch)]
+    fn fetch_with_request(request: &Request) -> js_sys::Promise;
+
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &js_sys::Function, timeout: i32) -> i32;
+
+    #[wasm_bindgen(js_name = clearTimeout)]
+    fn clear_timeout(handle: i32);
+}
+
+/// Downloader using the global `fetch` of a browser or worker
+///
+/// The user agent can only be set where the host allows it, and `proxy` is put in front of
+/// every url so a CORS proxy can be used. Browsers keep the cookie jar themselves and never
+/// show `Set-Cookie` to scripts, so `cookie_jar` stays the configured one.
+#[derive(Clone)]
+pub struct FetchDownloader {
+    config: DownloaderConfig,
+}
+
+impl FetchDownloader {
+    pub fn new() -> Self {
+        Self::with_config(DownloaderConfig::default())
+    }
+
+    pub fn with_config(config: DownloaderConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &DownloaderConfig {
+        &self.config
+    }
+
+    async fn fetch(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        let url = self.config.request_url(url);
+        let url = match &self.config.proxy {
+            Some(proxy) => format!("{}{}", proxy, url),
+            None => url,
+        };
+        let headers = Headers::new().map_err(js_error)?;
+        for (name, value) in &header {
+            headers.set(name, value).map_err(js_error)?;
+        }
+        // Forbidden in browsers, but honoured by most worker runtimes
+        let _ = headers.set("User-Agent", &self.config.user_agent);
+
+        let init = RequestInit::new();
+        init.set_method("GET");
+        init.set_headers(&headers);
+        let controller = AbortController::new().map_err(js_error)?;
+        init.set_signal(Some(&controller.signal()));
+        let request = Request::new_with_str_and_init(&url, &init).map_err(js_error)?;
+
+        let timeout = self.config.timeout.map(|timeout| {
+            let abort = Closure::once_into_js(move || controller.abort());
+            set_timeout(abort.unchecked_ref(), timeout.as_millis() as i32)
+        });
+        let resp = JsFuture::from(fetch_with_request(&request)).await;
+        if let Some(handle) = timeout {
+            clear_timeout(handle);
+        }
+        let resp: Response = resp.map_err(js_error)?.dyn_into().map_err(js_error)?;
+
+        if !resp.ok() {
+            return Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, resp.status()),
+                status: resp.status(),
+            });
+        }
+        let text = JsFuture::from(resp.text().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        text.as_string().ok_or(ParsingError::DownloadError {
+            cause: "response body is not text".to_string(),
+        })
+    }
+}
+
+impl Default for FetchDownloader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait(?Send)]
+impl Downloader for FetchDownloader {
+    async fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.fetch(url, HashMap::new()).await
+    }
+
+    async fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        self.fetch(url, header).await
+    }
+
+    /// Uses the configured evaluator, or the host's own engine when there is none
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        if self.config.js_evaluator.is_some() {
+            return self.config.eval_js(script);
+        }
+        let result = js_sys::eval(script).map_err(|e| format!("{:?}", e))?;
+        Ok(result.as_string().unwrap_or_default())
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        self.config.cookie_jar.clone()
+    }
+}
+
+fn js_error(error: JsValue) -> ParsingError {
+    ParsingError::DownloadError {
+        cause: format!("{:?}", error),
+    }
+}
@@ -0,0 +1,131 @@
+#[cfg(all(feature = "fetch-downloader", target_arch = "wasm32"))]
+mod fetch_downloader;
+#[cfg(feature = "reqwest-downloader")]
+mod reqwest_downloader;
+#[cfg(feature = "ureq-downloader")]
+mod ureq_downloader;
+
+#[cfg(all(feature = "fetch-downloader", target_arch = "wasm32"))]
+pub use fetch_downloader::FetchDownloader;
+#[cfg(feature = "reqwest-downloader")]
+pub use reqwest_downloader::ReqwestDownloader;
+#[cfg(feature = "ureq-downloader")]
+pub use ureq_downloader::UreqDownloader;
+
+use crate::cookie_jar::CookieJar;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const DEFAULT_USER_AGENT: &str =
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:78.0) Gecko/20100101 Firefox/78.0";
+
+/// Domains, with their subdomains, that `origin_override` redirects
+const OVERRIDDEN_DOMAINS: [&str; 5] = [
+    "youtube.com",
+    "googlevideo.com",
+    "suggestqueries.google.com",
+    // SponsorBlock and DeArrow
+    "sponsor.ajay.app",
+    "returnyoutubedislikeapi.com",
+];
+
+pub type JsEvaluator = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+/// Settings shared by the built-in downloaders
+#[derive(Clone)]
+pub struct DownloaderConfig {
+    pub user_agent: String,
+    pub timeout: Option<Duration>,
+    /// Proxy url, for the fetch downloader this is a prefix put in front of every url
+    pub proxy: Option<String>,
+    /// Used by `eval_js`, defaults to the bundled QuickJS engine when the `quickjs` feature is on
+    pub js_evaluator: Option<JsEvaluator>,
+    /// Initial cookies, e.g. a jar persisted from an earlier run
+    pub cookie_jar: CookieJar,
+    /// Origin like `http://127.0.0.1:8080` that requests to YouTube hosts and the SponsorBlock,
+    /// DeArrow and Return YouTube Dislike APIs are sent to instead, e.g. a local mock server
+    pub origin_override: Option<String>,
+}
+
+impl Default for DownloaderConfig {
+    fn default() -> Self {
+        DownloaderConfig {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            timeout: Some(Duration::from_secs(30)),
+            proxy: None,
+            js_evaluator: bundled_js_evaluator(),
+            cookie_jar: CookieJar::with_consent(),
+            origin_override: None,
+        }
+    }
+}
+
+impl DownloaderConfig {
+    pub fn eval_js(&self, script: &str) -> Result<String, String> {
+        match &self.js_evaluator {
+            Some(evaluator) => evaluator(script),
+            None => Err("No javascript engine configured".to_string()),
+        }
+    }
+
+    /// The url a request for `url` is sent to once `origin_override` is applied
+    pub fn request_url(&self, url: &str) -> String {
+        let origin = match &self.origin_override {
+            Some(origin) => origin.trim_end_matches('/'),
+            None => return url.to_string(),
+        };
+        let host = url_host(url);
+        let overridden = OVERRIDDEN_DOMAINS
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        if !overridden {
+            return url.to_string();
+        }
+        let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+        let path = rest
+            .find(['/', '?'])
+            .map(|start| &rest[start..])
+            .unwrap_or("/");
+        format!("{}{}", origin, path)
+    }
+}
+
+#[cfg(feature = "quickjs")]
+fn bundled_js_evaluator() -> Option<JsEvaluator> {
+    Some(Arc::new(eval_quickjs))
+}
+
+#[cfg(not(feature = "quickjs"))]
+fn bundled_js_evaluator() -> Option<JsEvaluator> {
+    None
+}
+
+/// Evaluates a script in a fresh QuickJS context and returns the result as string
+#[cfg(feature = "quickjs")]
+pub fn eval_quickjs(script: &str) -> Result<String, String> {
+    let context = quick_js::Context::new().map_err(|e| e.to_string())?;
+    let result = context.eval(script).map_err(|e| e.to_string())?;
+    Ok(result.into_string().unwrap_or_default())
+}
+
+/// Whether cookies set by a response from `url` belong in the youtube cookie jar
+#[cfg(any(feature = "reqwest-downloader", feature = "ureq-downloader"))]
+fn is_youtube_url(url: &str) -> bool {
+    let host = url_host(url);
+    host == "youtube.com" || host.ends_with(".youtube.com")
+}
+
+fn url_host(url: &str) -> &str {
+    url.split("://")
+        .nth(1)
+        .unwrap_or_default()
+        .split(['/', ':', '?'])
+        .next()
+        .unwrap_or_default()
+}
+
+/// Size of the whole resource from a `Content-Range` like `bytes 0-99/1234`
+#[cfg(any(feature = "reqwest-downloader", feature = "ureq-downloader"))]
+fn content_range_total(content_range: Option<&str>) -> Option<u64> {
+    content_range?.rsplit('/').next()?.trim().parse().ok()
+}
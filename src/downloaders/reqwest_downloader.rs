@@ -0,0 +1,174 @@
+use super::{content_range_total, is_youtube_url, DownloaderConfig};
+use crate::cookie_jar::CookieJar;
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_RANGE, RANGE, SET_COOKIE};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Async downloader backed by reqwest
+#[derive(Clone)]
+pub struct ReqwestDownloader {
+    client: reqwest::Client,
+    /// Without an overall timeout, chunks of slow streams take a while
+    binary_client: reqwest::Client,
+    config: DownloaderConfig,
+    cookies: Arc<Mutex<CookieJar>>,
+}
+
+impl ReqwestDownloader {
+    pub fn new() -> Result<Self, ParsingError> {
+        Self::with_config(DownloaderConfig::default())
+    }
+
+    pub fn with_config(config: DownloaderConfig) -> Result<Self, ParsingError> {
+        let mut builder = reqwest::Client::builder().user_agent(config.user_agent.as_str());
+        let mut binary_builder = reqwest::Client::builder().user_agent(config.user_agent.as_str());
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+            binary_builder = binary_builder
+                .connect_timeout(timeout)
+                .read_timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy).map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+            builder = builder.proxy(proxy.clone());
+            binary_builder = binary_builder.proxy(proxy);
+        }
+        let build = |builder: reqwest::ClientBuilder| {
+            builder.build().map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })
+        };
+        Ok(Self {
+            client: build(builder)?,
+            binary_client: build(binary_builder)?,
+            cookies: Arc::new(Mutex::new(config.cookie_jar.clone())),
+            config,
+        })
+    }
+
+    async fn get_binary(
+        &self,
+        url: &str,
+        range: Option<(u64, u64)>,
+    ) -> Result<reqwest::Response, ParsingError> {
+        let mut request = self.binary_client.get(self.config.request_url(url));
+        if let Some((start, end)) = range {
+            request = request.header(RANGE, format!("bytes={}-{}", start, end));
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+        let status = resp.status();
+        let expected = match range {
+            Some(_) => status == reqwest::StatusCode::PARTIAL_CONTENT,
+            None => status.is_success(),
+        };
+        if !expected {
+            return Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, status),
+                status: status.as_u16(),
+            });
+        }
+        Ok(resp)
+    }
+
+    async fn body(resp: reqwest::Response) -> Result<Vec<u8>, ParsingError> {
+        let body = resp
+            .bytes()
+            .await
+            .map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+        Ok(body.to_vec())
+    }
+
+    pub fn config(&self) -> &DownloaderConfig {
+        &self.config
+    }
+}
+
+#[async_trait]
+impl Downloader for ReqwestDownloader {
+    async fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.download_with_header(url, HashMap::new()).await
+    }
+
+    async fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in header {
+            headers.insert(
+                HeaderName::from_str(&name).map_err(|e| e.to_string())?,
+                HeaderValue::from_str(&value).map_err(|e| e.to_string())?,
+            );
+        }
+        let request_url = self.config.request_url(url);
+        let resp = self
+            .client
+            .get(&request_url)
+            .headers(headers)
+            .send()
+            .await
+            .map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+        // Responses from an origin override stand in for the requested youtube url
+        let from_youtube =
+            is_youtube_url(resp.url().as_str()) || (request_url != url && is_youtube_url(url));
+        if from_youtube {
+            let mut cookies = self.cookies.lock().unwrap();
+            for set_cookie in resp.headers().get_all(SET_COOKIE) {
+                if let Ok(set_cookie) = set_cookie.to_str() {
+                    cookies.store_set_cookie(set_cookie);
+                }
+            }
+        }
+        let status = resp.status();
+        if !status.is_success() {
+            return Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, status),
+                status: status.as_u16(),
+            });
+        }
+        resp.text().await.map_err(|e| ParsingError::DownloadError {
+            cause: e.to_string(),
+        })
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.config.eval_js(script)
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        self.cookies.lock().unwrap().clone()
+    }
+
+    async fn download_bytes(&self, url: &str) -> Result<Vec<u8>, ParsingError> {
+        Self::body(self.get_binary(url, None).await?).await
+    }
+
+    async fn download_range(
+        &self,
+        url: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<(Vec<u8>, u64), ParsingError> {
+        let resp = self.get_binary(url, Some((start, end))).await?;
+        let content_range = resp.headers().get(CONTENT_RANGE);
+        let total = content_range_total(content_range.and_then(|range| range.to_str().ok()))
+            .ok_or_else(|| ParsingError::from("No length in Content-Range"))?;
+        Ok((Self::body(resp).await?, total))
+    }
+}
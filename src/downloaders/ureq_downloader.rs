@@ -0,0 +1,157 @@
+use super::{content_range_total, is_youtube_url, DownloaderConfig};
+use crate::cookie_jar::CookieJar;
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+/// Blocking downloader backed by ureq, its futures block the executor until the request is done
+#[derive(Clone)]
+pub struct UreqDownloader {
+    agent: ureq::Agent,
+    /// Without an overall timeout, chunks of slow streams take a while
+    binary_agent: ureq::Agent,
+    config: DownloaderConfig,
+    cookies: Arc<Mutex<CookieJar>>,
+}
+
+impl UreqDownloader {
+    pub fn new() -> Result<Self, ParsingError> {
+        Self::with_config(DownloaderConfig::default())
+    }
+
+    pub fn with_config(config: DownloaderConfig) -> Result<Self, ParsingError> {
+        let mut builder = ureq::AgentBuilder::new().user_agent(&config.user_agent);
+        let mut binary_builder = ureq::AgentBuilder::new().user_agent(&config.user_agent);
+        if let Some(timeout) = config.timeout {
+            builder = builder.timeout(timeout);
+            binary_builder = binary_builder
+                .timeout_connect(timeout)
+                .timeout_read(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = ureq::Proxy::new(proxy).map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+            builder = builder.proxy(proxy.clone());
+            binary_builder = binary_builder.proxy(proxy);
+        }
+        Ok(Self {
+            agent: builder.build(),
+            binary_agent: binary_builder.build(),
+            cookies: Arc::new(Mutex::new(config.cookie_jar.clone())),
+            config,
+        })
+    }
+
+    pub fn config(&self) -> &DownloaderConfig {
+        &self.config
+    }
+
+    fn call(
+        &self,
+        agent: &ureq::Agent,
+        url: &str,
+        header: &HashMap<String, String>,
+    ) -> Result<ureq::Response, ParsingError> {
+        let mut request = agent.get(&self.config.request_url(url));
+        for (name, value) in header {
+            request = request.set(name, value);
+        }
+        match request.call() {
+            Ok(resp) => Ok(resp),
+            Err(ureq::Error::Status(status, _)) => Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, status),
+                status: status,
+            }),
+            Err(e) => Err(ParsingError::DownloadError {
+                cause: e.to_string(),
+            }),
+        }
+    }
+
+    pub fn get(&self, url: &str, header: HashMap<String, String>) -> Result<String, ParsingError> {
+        let request_url = self.config.request_url(url);
+        let resp = self.call(&self.agent, url, &header)?;
+        // Responses from an origin override stand in for the requested youtube url
+        let from_youtube =
+            is_youtube_url(resp.get_url()) || (request_url != url && is_youtube_url(url));
+        if from_youtube {
+            let mut cookies = self.cookies.lock().unwrap();
+            for set_cookie in resp.all("set-cookie") {
+                cookies.store_set_cookie(set_cookie);
+            }
+        }
+        resp.into_string().map_err(|e| ParsingError::DownloadError {
+            cause: e.to_string(),
+        })
+    }
+
+    /// Body of a binary resource, `range` is inclusive and expects a 206 response
+    fn get_binary(
+        &self,
+        url: &str,
+        range: Option<(u64, u64)>,
+    ) -> Result<(Vec<u8>, Option<u64>), ParsingError> {
+        let mut header = HashMap::new();
+        if let Some((start, end)) = range {
+            header.insert("Range".to_string(), format!("bytes={}-{}", start, end));
+        }
+        let resp = self.call(&self.binary_agent, url, &header)?;
+        if range.is_some() && resp.status() != 206 {
+            return Err(ParsingError::HttpError {
+                cause: format!("{} returned {}", url, resp.status()),
+                status: resp.status(),
+            });
+        }
+        let total = content_range_total(resp.header("content-range"));
+        let mut body = vec![];
+        resp.into_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| ParsingError::DownloadError {
+                cause: e.to_string(),
+            })?;
+        Ok((body, total))
+    }
+}
+
+#[async_trait]
+impl Downloader for UreqDownloader {
+    async fn download(&self, url: &str) -> Result<String, ParsingError> {
+        self.get(url, HashMap::new())
+    }
+
+    async fn download_with_header(
+        &self,
+        url: &str,
+        header: HashMap<String, String>,
+    ) -> Result<String, ParsingError> {
+        self.get(url, header)
+    }
+
+    fn eval_js(&self, script: &str) -> Result<String, String> {
+        self.config.eval_js(script)
+    }
+
+    fn cookie_jar(&self) -> CookieJar {
+        self.cookies.lock().unwrap().clone()
+    }
+
+    async fn download_bytes(&self, url: &str) -> Result<Vec<u8>, ParsingError> {
+        Ok(self.get_binary(url, None)?.0)
+    }
+
+    async fn download_range(
+        &self,
+        url: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<(Vec<u8>, u64), ParsingError> {
+        match self.get_binary(url, Some((start, end)))? {
+            (body, Some(total)) => Ok((body, total)),
+            (_, None) => Err(ParsingError::from("No length in Content-Range")),
+        }
+    }
+}
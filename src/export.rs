@@ -0,0 +1,239 @@
+//! Playlist files for media players
+//!
+//! The writers take the videos of a playlist or channel page (`videos()`) or of search
+//! results (see [`streams`]). Entries link to the watch page, which players with a youtube-dl
+//! hook resolve themselves. [`edl`] instead combines extracted streams into a single mpv entry.
+
+use crate::utils::utils::escape_xml;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::search_extractor::YTSearchItem;
+use crate::youtube_extractor::stream_extractor::StreamItem;
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+
+/// The videos among search results
+pub fn streams(items: &[YTSearchItem]) -> impl Iterator<Item = &YTStreamInfoItemExtractor> {
+    items.iter().filter_map(|item| match item {
+        YTSearchItem::StreamInfoItem(video) => Some(video),
+        _ => None,
+    })
+}
+
+/// Title and uploader on a single line
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Extended M3U, `#EXTINF` carries the duration in seconds (-1 for live or unknown) and
+/// `uploader - title`
+pub fn m3u8<'a>(videos: impl IntoIterator<Item = &'a YTStreamInfoItemExtractor>) -> String {
+    let mut playlist = String::from("#EXTM3U\n");
+    for video in videos {
+        let url = match video.url() {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        let duration = match video.is_live() {
+            Ok(false) => video.duration().unwrap_or(-1),
+            _ => -1,
+        };
+        let title = video.name().unwrap_or_default();
+        let display = match video.uploader_name() {
+            Ok(uploader) if !uploader.is_empty() => format!("{} - {}", uploader, title),
+            _ => title,
+        };
+        playlist.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            duration,
+            one_line(&display),
+            url
+        ));
+    }
+    playlist
+}
+
+/// XSPF document, `title` names the whole playlist
+pub fn xspf<'a>(
+    title: Option<&str>,
+    videos: impl IntoIterator<Item = &'a YTStreamInfoItemExtractor>,
+) -> String {
+    let mut playlist = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    if let Some(title) = title {
+        playlist.push_str(&format!("  <title>{}</title>\n", escape_xml(title)));
+    }
+    playlist.push_str("  <trackList>\n");
+    for video in videos {
+        let url = match video.url() {
+            Ok(url) => url,
+            Err(_) => continue,
+        };
+        playlist.push_str("    <track>\n");
+        playlist.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape_xml(&url)
+        ));
+        if let Ok(title) = video.name() {
+            playlist.push_str(&format!("      <title>{}</title>\n", escape_xml(&title)));
+        }
+        if let Ok(uploader) = video.uploader_name() {
+            playlist.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                escape_xml(&uploader)
+            ));
+        }
+        if let (Ok(false), Ok(duration)) = (video.is_live(), video.duration()) {
+            // XSPF durations are milliseconds
+            playlist.push_str(&format!(
+                "      <duration>{}</duration>\n",
+                duration as i64 * 1000
+            ));
+        }
+        let thumbnail = video
+            .thumbnails()
+            .ok()
+            .and_then(|thumbnails| thumbnails.into_iter().max_by_key(|t| t.width));
+        if let Some(thumbnail) = thumbnail {
+            playlist.push_str(&format!(
+                "      <image>{}</image>\n",
+                escape_xml(&thumbnail.url)
+            ));
+        }
+        playlist.push_str("    </track>\n");
+    }
+    playlist.push_str("  </trackList>\n</playlist>\n");
+    playlist
+}
+
+fn edl_segments(video: &StreamItem, audio: &StreamItem) -> Result<[String; 2], ParsingError> {
+    let url = |stream: &StreamItem| {
+        stream.url.clone().ok_or_else(|| {
+            ParsingError::from(format!("Stream with itag {} has no url", stream.itag))
+        })
+    };
+    // Urls are written as %length%url so they may contain ',' and ';'
+    let segment = |url: String| format!("!no_clip\n%{}%{}", url.len(), url);
+    Ok([segment(url(video)?), segment(url(audio)?)])
+}
+
+/// mpv EDL file playing a video-only stream with a separate audio stream
+pub fn edl(video: &StreamItem, audio: &StreamItem) -> Result<String, ParsingError> {
+    let [video, audio] = edl_segments(video, audio)?;
+    Ok(format!("# mpv EDL v0\n{}\n!new_stream\n{}\n", video, audio))
+}
+
+/// Like [`edl`], as an `edl://` url that can be passed to mpv directly
+pub fn edl_url(video: &StreamItem, audio: &StreamItem) -> Result<String, ParsingError> {
+    let [video, audio] = edl_segments(video, audio)?;
+    Ok(format!("edl://{};!new_stream;{}", video, audio).replace('\n', ";"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn item(video_info: Value) -> YTStreamInfoItemExtractor {
+        YTStreamInfoItemExtractor {
+            video_info: video_info.as_object().unwrap().clone(),
+        }
+    }
+
+    fn videos() -> Vec<YTStreamInfoItemExtractor> {
+        vec![
+            item(json!({
+                "videoId": "aaaaaaaaaaa",
+                "title": { "runs": [{ "text": "Fish & chips\n<part 2>" }] },
+                "longBylineText": { "runs": [{ "text": "Cook \"Co\"" }] },
+                "lengthText": { "simpleText": "3:32" },
+                "thumbnail": { "thumbnails": [
+                    { "url": "https://i.ytimg.com/vi/aaaaaaaaaaa/default.jpg?a=1&b=2", "width": 120, "height": 90 },
+                    { "url": "https://i.ytimg.com/vi/aaaaaaaaaaa/hq.jpg?a=1&b=2", "width": 480, "height": 360 },
+                    { "url": "https://i.ytimg.com/vi/aaaaaaaaaaa/mq.jpg", "width": 320, "height": 180 },
+                ] },
+            })),
+            // Live, without uploader or thumbnails
+            item(json!({
+                "videoId": "bbbbbbbbbbb",
+                "title": { "simpleText": "Live now" },
+                "badges": [{ "metadataBadgeRenderer": { "label": "LIVE NOW" } }],
+            })),
+            // Without an id there is no url to write
+            item(json!({ "title": { "simpleText": "Skipped" } })),
+        ]
+    }
+
+    fn stream(itag: u32, url: Option<&str>) -> StreamItem {
+        serde_json::from_value(json!({
+            "url": url,
+            "itag": itag,
+            "bitrate": 1000,
+            "quality": "medium",
+            "lastModified": "0",
+            "mimeType": "video/webm",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn writes_m3u8() {
+        assert_eq!(
+            m3u8(&videos()),
+            "#EXTM3U\n\
+             #EXTINF:212,Cook \"Co\" - Fish & chips <part 2>\n\
+             https://www.youtube.com/watch?v=aaaaaaaaaaa\n\
+             #EXTINF:-1,Live now\n\
+             https://www.youtube.com/watch?v=bbbbbbbbbbb\n"
+        );
+        assert_eq!(m3u8(&[]), "#EXTM3U\n");
+    }
+
+    #[test]
+    fn writes_xspf() {
+        assert_eq!(
+            xspf(Some("Tom & Jerry's <best>"), &videos()),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  \
+               <title>Tom &amp; Jerry&apos;s &lt;best&gt;</title>\n  \
+               <trackList>\n    \
+                 <track>\n      \
+                   <location>https://www.youtube.com/watch?v=aaaaaaaaaaa</location>\n      \
+                   <title>Fish &amp; chips\n&lt;part 2&gt;</title>\n      \
+                   <creator>Cook &quot;Co&quot;</creator>\n      \
+                   <duration>212000</duration>\n      \
+                   <image>https://i.ytimg.com/vi/aaaaaaaaaaa/hq.jpg?a=1&amp;b=2</image>\n    \
+                 </track>\n    \
+                 <track>\n      \
+                   <location>https://www.youtube.com/watch?v=bbbbbbbbbbb</location>\n      \
+                   <title>Live now</title>\n    \
+                 </track>\n  \
+               </trackList>\n\
+             </playlist>\n"
+        );
+        assert!(!xspf(None, &[]).contains("<title>"));
+    }
+
+    #[test]
+    fn writes_edl() {
+        let video = stream(248, Some("https://example.com/v?a=1,2;b=3"));
+        let audio = stream(251, Some("https://example.com/a?c=;,"));
+        assert_eq!(
+            edl(&video, &audio).unwrap(),
+            "# mpv EDL v0\n\
+             !no_clip\n%31%https://example.com/v?a=1,2;b=3\n\
+             !new_stream\n\
+             !no_clip\n%26%https://example.com/a?c=;,\n"
+        );
+        assert_eq!(
+            edl_url(&video, &audio).unwrap(),
+            "edl://!no_clip;%31%https://example.com/v?a=1,2;b=3;!new_stream;\
+             !no_clip;%26%https://example.com/a?c=;,"
+        );
+
+        let ciphered = stream(251, None);
+        let error = edl(&video, &ciphered).unwrap_err();
+        assert!(error.to_string().contains("itag 251"), "{}", error);
+        assert!(edl_url(&ciphered, &audio).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! Chronological feed of the newest videos of many channels
+
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Debug)]
+pub struct FeedConfig {
+    /// Channels loaded at the same time
+    pub concurrency: usize,
+    /// Pages of a channel's videos loaded at most, when its last seen video is not found earlier
+    pub pages: usize,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        FeedConfig {
+            concurrency: 8,
+            pages: 1,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct FeedItem {
+    /// Channel id as it was passed to the aggregator
+    pub channel_id: String,
+    pub video: YTStreamInfoItemExtractor,
+    pub upload_date: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Clone, Default)]
+pub struct Feed {
+    /// Newest first, videos without a parsable upload date come last
+    pub items: Vec<FeedItem>,
+    /// Channels that could not be loaded, the feed holds the others
+    pub errors: Vec<(String, ParsingError)>,
+}
+
+pub struct FeedAggregator<D> {
+    downloader: D,
+    channel_ids: Vec<String>,
+    config: FeedConfig,
+    /// Id of the newest video seen per channel
+    last_seen: HashMap<String, String>,
+}
+
+/// Videos of a channel that are newer than `last_seen`, newest first
+async fn new_videos<D: Downloader + Clone>(
+    downloader: D,
+    channel_id: &str,
+    last_seen: Option<&str>,
+    pages: usize,
+) -> Result<Vec<YTStreamInfoItemExtractor>, ParsingError> {
+    let mut videos = vec![];
+    let mut page_url = None;
+    for _ in 0..pages.max(1) {
+        let extractor = YTChannelExtractor::new(downloader.clone(), channel_id, page_url).await?;
+        for video in extractor.videos()? {
+            if last_seen.is_some() && video.video_id().ok().as_deref() == last_seen {
+                return Ok(videos);
+            }
+            videos.push(video);
+        }
+        page_url = match extractor.next_page_url()? {
+            Some(page_url) => Some(page_url),
+            None => break,
+        };
+    }
+    Ok(videos)
+}
+
+impl<D: Downloader + Clone> FeedAggregator<D> {
+    pub fn new(downloader: D, channel_ids: Vec<String>, config: FeedConfig) -> Self {
+        FeedAggregator {
+            downloader,
+            channel_ids,
+            config,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Continues from the markers of an earlier aggregator, see [`FeedAggregator::last_seen`]
+    pub fn with_last_seen(mut self, last_seen: HashMap<String, String>) -> Self {
+        self.last_seen = last_seen;
+        self
+    }
+
+    /// Id of the newest video seen per channel id, to be kept between runs
+    pub fn last_seen(&self) -> &HashMap<String, String> {
+        &self.last_seen
+    }
+
+    pub fn channel_ids(&self) -> &[String] {
+        &self.channel_ids
+    }
+
+    /// Loads the videos uploaded since the last refresh, all recent ones on the first. A
+    /// channel's marker only moves when it loaded successfully.
+    pub async fn refresh(&mut self) -> Feed {
+        let downloader = &self.downloader;
+        let last_seen = &self.last_seen;
+        let pages = self.config.pages;
+        let results: Vec<(String, Result<Vec<YTStreamInfoItemExtractor>, ParsingError>)> =
+            stream::iter(self.channel_ids.iter())
+                .map(|channel_id| async move {
+                    let last_seen = last_seen.get(channel_id).map(String::as_str);
+                    let videos = new_videos(downloader.clone(), channel_id, last_seen, pages).await;
+                    (channel_id.clone(), videos)
+                })
+                // In the order of `channel_ids`, so collaborations always go to the same channel
+                .buffered(self.config.concurrency.max(1))
+                .collect()
+                .await;
+
+        let mut feed = Feed::default();
+        let mut seen = HashSet::new();
+        for (channel_id, videos) in results {
+            let videos = match videos {
+                Ok(videos) => videos,
+                Err(e) => {
+                    tracing::warn!(channel_id = %channel_id, error = %e, "cannot load channel");
+                    feed.errors.push((channel_id, e));
+                    continue;
+                }
+            };
+            if let Some(newest) = videos.first().and_then(|v| v.video_id().ok()) {
+                self.last_seen.insert(channel_id.clone(), newest);
+            }
+            for video in videos {
+                // Collaborations show up on every channel involved
+                if let Ok(video_id) = video.video_id() {
+                    if !seen.insert(video_id) {
+                        continue;
+                    }
+                }
+                feed.items.push(FeedItem {
+                    channel_id: channel_id.clone(),
+                    upload_date: video.upload_date().ok(),
+                    video,
+                });
+            }
+        }
+        // Stable, so videos of a channel without dates keep their order
+        feed.items
+            .sort_by_key(|item| std::cmp::Reverse(item.upload_date));
+        feed
+    }
+}
@@ -0,0 +1,134 @@
+//! Plain JSON snapshots of the extractors, fields that fail to parse become `null`
+
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::channel_info_item_extractor::YTChannelInfoItemExtractor;
+use crate::youtube_extractor::playlist_extractor::YTPlaylistExtractor;
+use crate::youtube_extractor::playlist_info_item_extractor::YTPlaylistInfoItemExtractor;
+use crate::youtube_extractor::search_extractor::{YTSearchExtractor, YTSearchItem};
+use crate::youtube_extractor::stream_extractor::YTStreamExtractor;
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use crate::youtube_extractor::trending_extractor::YTTrendingExtractor;
+use serde_json::{json, Value};
+
+pub fn stream_to_json<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Value {
+    json!({
+        "type": "stream",
+        "id": extractor.video_id(),
+        "title": extractor.name().ok(),
+        "description": extractor.description(false).ok().map(|(desc, _)| desc),
+        "uploadDate": extractor.textual_upload_date().ok(),
+        "length": extractor.length().ok(),
+        "viewCount": extractor.view_count().ok().map(|c| c.to_string()),
+        "likeCount": extractor.like_count().ok().map(|c| c.to_string()),
+        "dislikeCount": extractor.dislike_count().ok().map(|c| c.to_string()),
+        "uploaderName": extractor.uploader_name().ok(),
+        "uploaderUrl": extractor.uploader_url().ok(),
+        "uploaderAvatars": extractor.uploader_avatar_url().ok(),
+        "thumbnails": extractor.video_thumbnails().ok(),
+        "videoStreams": extractor.video_stream().ok(),
+        "videoOnlyStreams": extractor.video_only_stream().ok(),
+        "audioStreams": extractor.audio_streams().ok(),
+        "related": extractor
+            .related()
+            .map(|items| items.iter().map(search_item_to_json).collect::<Vec<Value>>())
+            .ok(),
+    })
+}
+
+pub fn stream_info_item_to_json(item: &YTStreamInfoItemExtractor) -> Value {
+    json!({
+        "type": "stream",
+        "id": item.video_id().ok(),
+        "title": item.name().ok(),
+        "url": item.url().ok(),
+        "isLive": item.is_live().ok(),
+        "duration": item.duration().ok(),
+        "textualDuration": item.textual_duration().ok(),
+        "uploaderName": item.uploader_name().ok(),
+        "uploaderUrl": item.uploader_url().ok(),
+        "uploadDate": item.upload_date().ok().map(|d| d.to_string()),
+        "textualUploadDate": item.textual_upload_date().ok(),
+        "viewCount": item.view_count().ok(),
+        "thumbnails": item.thumbnails().ok(),
+        "uploaderThumbnails": item.uploader_thumbnails().ok(),
+    })
+}
+
+pub fn channel_info_item_to_json(item: &YTChannelInfoItemExtractor) -> Value {
+    json!({
+        "type": "channel",
+        "id": item.channel_id().ok(),
+        "name": item.name().ok(),
+        "url": item.url().ok(),
+        "subscriberCount": item.subscriber_count().ok(),
+        "streamCount": item.stream_count().ok(),
+        "description": item.description().ok().flatten(),
+        "thumbnails": item.thumbnails().ok(),
+    })
+}
+
+pub fn playlist_info_item_to_json(item: &YTPlaylistInfoItemExtractor) -> Value {
+    json!({
+        "type": "playlist",
+        "id": item.playlist_id().ok(),
+        "name": item.name().ok(),
+        "url": item.url().ok(),
+        "uploaderName": item.uploader_name().ok(),
+        "streamCount": item.stream_count().ok(),
+        "thumbnails": item.thumbnails().ok(),
+    })
+}
+
+pub fn search_item_to_json(item: &YTSearchItem) -> Value {
+    match item {
+        YTSearchItem::StreamInfoItem(item) => stream_info_item_to_json(item),
+        YTSearchItem::ChannelInfoItem(item) => channel_info_item_to_json(item),
+        YTSearchItem::PlaylistInfoItem(item) => playlist_info_item_to_json(item),
+    }
+}
+
+fn videos_to_json(videos: &[YTStreamInfoItemExtractor]) -> Vec<Value> {
+    videos.iter().map(stream_info_item_to_json).collect()
+}
+
+pub fn search_to_json<D: Downloader>(extractor: &YTSearchExtractor<D>) -> Value {
+    json!({
+        "items": extractor
+            .search_results()
+            .map(|items| items.iter().map(search_item_to_json).collect::<Vec<Value>>())
+            .ok(),
+        "nextPage": extractor.next_page_url().ok().flatten(),
+    })
+}
+
+pub fn channel_to_json<D: Downloader>(extractor: &YTChannelExtractor<D>) -> Value {
+    json!({
+        "type": "channel",
+        "name": extractor.name().ok(),
+        "avatars": extractor.avatars().ok(),
+        "banners": extractor.banners().ok(),
+        "videos": extractor.videos().map(|v| videos_to_json(&v)).ok(),
+        "nextPage": extractor.next_page_url().ok().flatten(),
+    })
+}
+
+pub fn playlist_to_json<D: Downloader>(extractor: &YTPlaylistExtractor<D>) -> Value {
+    json!({
+        "type": "playlist",
+        "name": extractor.name().ok(),
+        "thumbnails": extractor.thumbnails().ok(),
+        "uploaderName": extractor.uploader_name().ok(),
+        "uploaderUrl": extractor.uploader_url().ok(),
+        "uploaderAvatars": extractor.uploader_avatars().ok(),
+        "streamCount": extractor.stream_count().ok(),
+        "videos": extractor.videos().map(|v| videos_to_json(&v)).ok(),
+        "nextPage": extractor.next_page_url().ok().flatten(),
+    })
+}
+
+pub fn trending_to_json<D: Downloader>(extractor: &YTTrendingExtractor<D>) -> Value {
+    json!({
+        "videos": extractor.videos().map(|v| videos_to_json(&v)).ok(),
+    })
+}
@@ -1,9 +1,31 @@
+#[cfg(all(feature = "blocking", not(target_arch = "wasm32")))]
+pub mod blocking;
+mod cookie_jar;
+pub mod dearrow;
+pub mod diagnostics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod download;
 mod downloader_trait;
+pub mod downloaders;
+pub mod export;
+pub mod feed;
+pub mod json;
+pub mod metrics;
+pub mod mux;
+#[cfg(all(feature = "newpipe-backup", not(target_arch = "wasm32")))]
+pub mod newpipe;
+pub mod podcast;
+pub mod ratings;
+pub mod sponsorblock;
+pub mod subscriptions;
 mod utils;
 mod youtube_extractor;
+pub mod ytdlp;
 
+pub use crate::cookie_jar::CookieJar;
 pub use crate::downloader_trait::Downloader;
 pub use crate::youtube_extractor::error::ParsingError;
+pub use crate::youtube_extractor::link_handler::YTLink;
 pub use crate::youtube_extractor::stream_extractor::HARDCODED_CLIENT_VERSION;
 
 pub mod itag {
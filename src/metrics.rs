@@ -0,0 +1,152 @@
+use crate::youtube_extractor::error::ParsingError;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+pub const STREAM: &str = "stream";
+pub const STREAM_INFO_ITEM: &str = "stream_info_item";
+pub const CHANNEL: &str = "channel";
+pub const CHANNEL_INFO_ITEM: &str = "channel_info_item";
+pub const PLAYLIST: &str = "playlist";
+pub const PLAYLIST_INFO_ITEM: &str = "playlist_info_item";
+pub const SEARCH: &str = "search";
+pub const TRENDING: &str = "trending";
+
+lazy_static! {
+    static ref PARSE_FAILURES: Mutex<BTreeMap<(&'static str, &'static str), u64>> =
+        Mutex::new(BTreeMap::new());
+}
+
+thread_local! {
+    /// Sources of the fallbacks taken on this thread while `capture` runs
+    static CAPTURED_FALLBACKS: RefCell<Option<Vec<&'static str>>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ParseFailureCount {
+    pub extractor: &'static str,
+    pub field: &'static str,
+    pub count: u64,
+}
+
+/// Counts a failure to parse `field` and emits it as a `monotonic_counter` tracing event,
+/// unless it happens inside `capture`
+pub fn record_parse_failure(extractor: &'static str, field: &'static str, error: &ParsingError) {
+    if CAPTURED_FALLBACKS.with(|captured| captured.borrow().is_some()) {
+        return;
+    }
+    *PARSE_FAILURES
+        .lock()
+        .unwrap()
+        .entry((extractor, field))
+        .or_insert(0) += 1;
+    tracing::warn!(
+        monotonic_counter.parse_failures = 1u64,
+        extractor,
+        field,
+        error = %error,
+        "parse failure"
+    );
+}
+
+/// Counts the failure and returns the error, for use at the point an accessor gives up
+pub fn parse_failure(extractor: &'static str, field: &'static str, cause: &str) -> ParsingError {
+    let error = ParsingError::from(cause);
+    record_parse_failure(extractor, field, &error);
+    error
+}
+
+/// Emits an event when a field had to be read from its secondary source
+pub fn record_fallback(extractor: &'static str, field: &'static str, source: &'static str) {
+    tracing::debug!(
+        monotonic_counter.parse_fallbacks = 1u64,
+        extractor,
+        field,
+        source,
+        "fallback path taken"
+    );
+    CAPTURED_FALLBACKS.with(|captured| {
+        if let Some(sources) = captured.borrow_mut().as_mut() {
+            sources.push(source);
+        }
+    });
+}
+
+/// Runs `f` and returns the sources of the fallbacks it took. Its parse failures are left out
+/// of the counters, as the caller reports them itself.
+pub(crate) fn capture<T>(f: impl FnOnce() -> T) -> (T, Vec<&'static str>) {
+    let outer = CAPTURED_FALLBACKS.with(|captured| captured.replace(Some(vec![])));
+    let result = f();
+    let sources = CAPTURED_FALLBACKS.with(|captured| captured.replace(outer));
+    (result, sources.unwrap_or_default())
+}
+
+/// Parse failures counted since start or the last reset
+pub fn parse_failures() -> Vec<ParseFailureCount> {
+    PARSE_FAILURES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|((extractor, field), count)| ParseFailureCount {
+            extractor,
+            field,
+            count: *count,
+        })
+        .collect()
+}
+
+pub fn reset_parse_failures() {
+    PARSE_FAILURES.lock().unwrap().clear();
+}
+
+/// Measures parse durations, a no-op where `std::time::Instant` is unavailable
+pub(crate) struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    start: std::time::Instant,
+}
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Stopwatch {
+            #[cfg(not(target_arch = "wasm32"))]
+            start: std::time::Instant::now(),
+        }
+    }
+
+    /// Records the elapsed milliseconds as `parse_ms` on the current span
+    pub fn record(&self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        tracing::Span::current().record("parse_ms", self.start.elapsed().as_millis() as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(field: &str) -> u64 {
+        parse_failures()
+            .iter()
+            .find(|f| f.extractor == "test" && f.field == field)
+            .map_or(0, |f| f.count)
+    }
+
+    #[test]
+    fn capture_collects_fallbacks_and_keeps_failures_out_of_the_counters() {
+        let (result, sources) = capture(|| {
+            record_fallback("test", "captured", "secondary");
+            record_parse_failure("test", "captured", &ParsingError::from("missing"));
+            1
+        });
+        assert_eq!(result, 1);
+        assert_eq!(sources, vec!["secondary"]);
+        assert_eq!(count("captured"), 0);
+
+        record_parse_failure("test", "uncaptured", &ParsingError::from("missing"));
+        assert_eq!(count("uncaptured"), 1);
+        let (_, sources) = capture(|| ());
+        assert!(sources.is_empty());
+    }
+}
@@ -0,0 +1,1172 @@
+//! Matroska muxing of a video and an audio track
+//!
+//! Inputs are the WebM files of the VP9/AV1 `video_only_stream()` and Opus `audio_streams()`.
+//! Track entries and blocks are copied as they are, blocks are regrouped into clusters that
+//! start at video keyframes. Chapters, tags, WebVTT/SRT subtitles and the cover are added from
+//! the [`Metadata`].
+
+use super::{io_error, Metadata, Subtitle};
+use crate::youtube_extractor::error::ParsingError;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocType {
+    /// `.webm`, without the cover as WebM has no attachments
+    Webm,
+    /// `.mkv`
+    Matroska,
+}
+
+impl DocType {
+    fn name(&self) -> &'static str {
+        match self {
+            DocType::Webm => "webm",
+            DocType::Matroska => "matroska",
+        }
+    }
+
+    /// WebM has its own codec id for WebVTT, Matroska players only know the `S_TEXT` one
+    fn subtitle_codec_id(&self) -> &'static str {
+        match self {
+            DocType::Webm => "D_WEBVTT/SUBTITLES",
+            DocType::Matroska => "S_TEXT/WEBVTT",
+        }
+    }
+}
+
+const EBML: u32 = 0x1A45_DFA3;
+const EBML_VERSION: u32 = 0x4286;
+const EBML_READ_VERSION: u32 = 0x42F7;
+const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+const EBML_DOC_TYPE: u32 = 0x4282;
+const EBML_DOC_TYPE_VERSION: u32 = 0x4287;
+const EBML_DOC_TYPE_READ_VERSION: u32 = 0x4285;
+const SEGMENT: u32 = 0x1853_8067;
+const SEEK_HEAD: u32 = 0x114D_9B74;
+const SEEK: u32 = 0x4DBB;
+const SEEK_ID: u32 = 0x53AB;
+const SEEK_POSITION: u32 = 0x53AC;
+const INFO: u32 = 0x1549_A966;
+const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+const DURATION: u32 = 0x4489;
+const TITLE: u32 = 0x7BA9;
+const MUXING_APP: u32 = 0x4D80;
+const WRITING_APP: u32 = 0x5741;
+const TRACKS: u32 = 0x1654_AE6B;
+const TRACK_ENTRY: u32 = 0xAE;
+const TRACK_NUMBER: u32 = 0xD7;
+const TRACK_UID: u32 = 0x73C5;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const LANGUAGE: u32 = 0x22_B59C;
+const NAME: u32 = 0x536E;
+const FLAG_DEFAULT: u32 = 0x88;
+const CLUSTER: u32 = 0x1F43_B675;
+const TIMESTAMP: u32 = 0xE7;
+const SIMPLE_BLOCK: u32 = 0xA3;
+const BLOCK_GROUP: u32 = 0xA0;
+const BLOCK: u32 = 0xA1;
+const BLOCK_DURATION: u32 = 0x9B;
+const REFERENCE_BLOCK: u32 = 0xFB;
+const CUES: u32 = 0x1C53_BB6B;
+const CUE_POINT: u32 = 0xBB;
+const CUE_TIME: u32 = 0xB3;
+const CUE_TRACK_POSITIONS: u32 = 0xB7;
+const CUE_TRACK: u32 = 0xF7;
+const CUE_CLUSTER_POSITION: u32 = 0xF1;
+const CHAPTERS: u32 = 0x1043_A770;
+const EDITION_ENTRY: u32 = 0x45B9;
+const EDITION_UID: u32 = 0x45BC;
+const CHAPTER_ATOM: u32 = 0xB6;
+const CHAPTER_UID: u32 = 0x73C4;
+const CHAPTER_TIME_START: u32 = 0x91;
+const CHAPTER_TIME_END: u32 = 0x92;
+const CHAPTER_DISPLAY: u32 = 0x80;
+const CHAP_STRING: u32 = 0x85;
+const CHAP_LANGUAGE: u32 = 0x437C;
+const TAGS: u32 = 0x1254_C367;
+const TAG: u32 = 0x7373;
+const TARGETS: u32 = 0x63C0;
+const TARGET_TYPE_VALUE: u32 = 0x68CA;
+const SIMPLE_TAG: u32 = 0x67C8;
+const TAG_NAME: u32 = 0x45A3;
+const TAG_STRING: u32 = 0x4487;
+const ATTACHMENTS: u32 = 0x1941_A469;
+const ATTACHED_FILE: u32 = 0x61A7;
+const FILE_NAME: u32 = 0x466E;
+const FILE_MIME_TYPE: u32 = 0x4660;
+const FILE_DATA: u32 = 0x465C;
+const FILE_UID: u32 = 0x46AE;
+
+/// Elements that can follow a cluster of unknown size
+const LEVEL_1: [u32; 9] = [
+    CLUSTER,
+    CUES,
+    TAGS,
+    CHAPTERS,
+    ATTACHMENTS,
+    SEEK_HEAD,
+    INFO,
+    TRACKS,
+    EBML,
+];
+
+const TRACK_TYPE_VIDEO: u64 = 1;
+const TRACK_TYPE_AUDIO: u64 = 2;
+const TRACK_TYPE_SUBTITLE: u64 = 17;
+/// Output timestamps are in milliseconds
+const NANOSECONDS_PER_TICK: u64 = 1_000_000;
+const CLUSTER_MILLISECONDS: i64 = 5000;
+const SIMPLE_BLOCK_KEYFRAME: u8 = 0x80;
+
+fn id_bytes(id: u32) -> Vec<u8> {
+    let bytes = id.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(3);
+    bytes[start..].to_vec()
+}
+
+fn size_length(size: u64) -> usize {
+    // All ones is reserved for unknown sizes
+    (1..8).find(|n| size < (1 << (7 * n)) - 1).unwrap_or(8)
+}
+
+fn size_bytes(size: u64) -> Vec<u8> {
+    let length = size_length(size);
+    let marked = size | 1 << (7 * length);
+    marked.to_be_bytes()[8 - length..].to_vec()
+}
+
+fn element_size(id: u32, payload_size: u64) -> u64 {
+    (id_bytes(id).len() + size_length(payload_size)) as u64 + payload_size
+}
+
+fn element(id: u32, payload: &[u8]) -> Vec<u8> {
+    let mut element = id_bytes(id);
+    element.extend(size_bytes(payload.len() as u64));
+    element.extend_from_slice(payload);
+    element
+}
+
+fn master(id: u32, children: &[Vec<u8>]) -> Vec<u8> {
+    element(id, &children.concat())
+}
+
+fn uint(id: u32, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(7);
+    element(id, &bytes[start..])
+}
+
+/// Eight bytes wide, so the size of the element doesn't depend on the value
+fn fixed_uint(id: u32, value: u64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn float(id: u32, value: f64) -> Vec<u8> {
+    element(id, &value.to_be_bytes())
+}
+
+fn string(id: u32, value: &str) -> Vec<u8> {
+    element(id, value.as_bytes())
+}
+
+fn read_uint(payload: &[u8]) -> u64 {
+    payload.iter().fold(0, |value, b| value << 8 | *b as u64)
+}
+
+fn read_float(payload: &[u8]) -> Option<f64> {
+    match payload.len() {
+        4 => Some(f32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as f64),
+        8 => {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(payload);
+            Some(f64::from_be_bytes(bytes))
+        }
+        _ => None,
+    }
+}
+
+/// Variable length integer at the start of `data` and its length
+fn vint(data: &[u8], keep_marker: bool) -> Result<(u64, usize), ParsingError> {
+    let first = *data.first().ok_or("EBML data ends early")?;
+    let length = first.leading_zeros() as usize + 1;
+    if length > 8 || data.len() < length {
+        return Err(ParsingError::from("Invalid EBML variable length integer"));
+    }
+    let first = match keep_marker {
+        true => first as u64,
+        false => first as u64 & (0xff >> length),
+    };
+    let value = data[1..length]
+        .iter()
+        .fold(first, |value, b| value << 8 | *b as u64);
+    Ok((value, length))
+}
+
+/// Id, payload and the complete element
+type Element<'a> = (u32, &'a [u8], &'a [u8]);
+
+/// Each element in `data`
+fn elements(data: &[u8]) -> Result<Vec<Element<'_>>, ParsingError> {
+    let mut elements = vec![];
+    let mut position = 0;
+    while position < data.len() {
+        let (id, id_length) = vint(&data[position..], true)?;
+        let (size, size_length) = vint(&data[position + id_length..], false)?;
+        let start = position + id_length + size_length;
+        let end = (start as u64)
+            .checked_add(size)
+            .filter(|end| *end <= data.len() as u64)
+            .ok_or("EBML element ends early")? as usize;
+        elements.push((id as u32, &data[start..end], &data[position..end]));
+        position = end;
+    }
+    Ok(elements)
+}
+
+struct Reader<R> {
+    input: BufReader<R>,
+    position: u64,
+    end: u64,
+}
+
+impl<R: Read + Seek> Reader<R> {
+    fn new(mut input: R) -> Result<Self, ParsingError> {
+        let end = input.seek(SeekFrom::End(0)).map_err(io_error)?;
+        input.seek(SeekFrom::Start(0)).map_err(io_error)?;
+        Ok(Reader {
+            input: BufReader::new(input),
+            position: 0,
+            end,
+        })
+    }
+
+    fn seek(&mut self, position: u64) -> Result<(), ParsingError> {
+        let offset = position as i64 - self.position as i64;
+        self.input.seek_relative(offset).map_err(io_error)?;
+        self.position = position;
+        Ok(())
+    }
+
+    fn bytes(&mut self, length: u64) -> Result<Vec<u8>, ParsingError> {
+        if self.position + length > self.end {
+            return Err(ParsingError::from(
+                "EBML element extends past the end of the file",
+            ));
+        }
+        let mut bytes = vec![0; length as usize];
+        self.input.read_exact(&mut bytes).map_err(io_error)?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn vint(&mut self, keep_marker: bool) -> Result<(u64, usize), ParsingError> {
+        let mut data = self.bytes(1)?;
+        let length = data[0].leading_zeros() as usize + 1;
+        if length > 1 && length <= 8 {
+            data.extend(self.bytes(length as u64 - 1)?);
+        }
+        vint(&data, keep_marker)
+    }
+
+    /// Id and size of the next element, `None` for unknown sizes
+    fn header(&mut self) -> Result<(u32, Option<u64>), ParsingError> {
+        let (id, _) = self.vint(true)?;
+        let (size, length) = self.vint(false)?;
+        let unknown = size == (1 << (7 * length)) - 1;
+        Ok((id as u32, if unknown { None } else { Some(size) }))
+    }
+
+    /// Track number, relative timestamp and flags of a (simple) block of `size` bytes, the
+    /// reader is left at its frame data
+    fn block_header(&mut self, size: u64) -> Result<(u64, i16, u8, u64), ParsingError> {
+        let start = self.position;
+        let (track, _) = self.vint(false)?;
+        let rest = self.bytes(3)?;
+        let header = self.position - start;
+        let size = size.checked_sub(header).ok_or("Block too small")?;
+        Ok((track, i16::from_be_bytes([rest[0], rest[1]]), rest[2], size))
+    }
+}
+
+enum Data {
+    /// Position and size in the video or audio input
+    Input(u64, u64),
+    Text(Vec<u8>),
+}
+
+struct Block {
+    /// 0 is the video, 1 the audio, the subtitles follow
+    track: usize,
+    /// Milliseconds
+    time: i64,
+    flags: u8,
+    keyframe: bool,
+    data: Data,
+    /// The other children of a `BlockGroup`, `None` for simple blocks
+    group: Option<Vec<u8>>,
+}
+
+impl Block {
+    fn data_size(&self) -> u64 {
+        match &self.data {
+            Data::Input(_, size) => *size,
+            Data::Text(text) => text.len() as u64,
+        }
+    }
+
+    /// Size of the `SimpleBlock` or `BlockGroup` element
+    fn size(&self) -> u64 {
+        // One byte track number, timestamp and flags
+        let block = 4 + self.data_size();
+        match &self.group {
+            None => element_size(SIMPLE_BLOCK, block),
+            Some(group) => {
+                element_size(BLOCK_GROUP, element_size(BLOCK, block) + group.len() as u64)
+            }
+        }
+    }
+
+    fn header(&self, cluster_time: i64) -> Vec<u8> {
+        let block = 4 + self.data_size();
+        let mut header = match &self.group {
+            None => id_bytes(SIMPLE_BLOCK),
+            Some(group) => {
+                let mut header = id_bytes(BLOCK_GROUP);
+                header.extend(size_bytes(element_size(BLOCK, block) + group.len() as u64));
+                header.extend(id_bytes(BLOCK));
+                header
+            }
+        };
+        header.extend(size_bytes(block));
+        header.push(0x80 | (self.track as u8 + 1));
+        header.extend_from_slice(&((self.time - cluster_time) as i16).to_be_bytes());
+        header.push(self.flags);
+        header
+    }
+}
+
+struct Input {
+    /// Children of the `TrackEntry` but its number and uid
+    entry: Vec<u8>,
+    /// Milliseconds
+    duration: Option<f64>,
+    blocks: Vec<Block>,
+}
+
+/// Reads the blocks of the first track of the type
+fn read_input<R: Read + Seek>(
+    reader: &mut Reader<R>,
+    track_type: u64,
+    track: usize,
+) -> Result<Input, ParsingError> {
+    let (id, size) = reader.header()?;
+    if id != EBML {
+        return Err(ParsingError::from("Not an EBML file"));
+    }
+    reader.seek(reader.position + size.ok_or("EBML header of unknown size")?)?;
+    let (id, size) = reader.header()?;
+    if id != SEGMENT {
+        return Err(ParsingError::from("No Matroska segment"));
+    }
+    let segment_end = size.map_or(reader.end, |size| reader.position + size);
+
+    let mut scale = NANOSECONDS_PER_TICK;
+    let mut duration = None;
+    let mut entry = None;
+    let mut number = 0;
+    let mut blocks = vec![];
+    while reader.position < segment_end.min(reader.end) {
+        let (id, size) = reader.header()?;
+        match (id, size) {
+            (INFO, Some(size)) => {
+                let info = reader.bytes(size)?;
+                for (id, payload, _) in elements(&info)? {
+                    match id {
+                        TIMESTAMP_SCALE => scale = read_uint(payload).max(1),
+                        DURATION => duration = read_float(payload),
+                        _ => {}
+                    }
+                }
+            }
+            (TRACKS, Some(size)) => {
+                let tracks = reader.bytes(size)?;
+                for (id, payload, _) in elements(&tracks)? {
+                    if id != TRACK_ENTRY || entry.is_some() {
+                        continue;
+                    }
+                    let children = elements(payload)?;
+                    let is_type = |(id, payload, _): &Element| {
+                        *id == TRACK_TYPE && read_uint(payload) == track_type
+                    };
+                    if !children.iter().any(is_type) {
+                        continue;
+                    }
+                    let mut copied = vec![];
+                    for (id, payload, element) in children {
+                        match id {
+                            TRACK_NUMBER => number = read_uint(payload),
+                            TRACK_UID => {}
+                            _ => copied.extend_from_slice(element),
+                        }
+                    }
+                    entry = Some(copied);
+                }
+            }
+            (CLUSTER, size) => {
+                let cluster_end = size.map(|size| reader.position + size);
+                let mut cluster_time = 0;
+                let to_time = |cluster_time: u64, relative: i16| {
+                    let ticks = cluster_time as i64 + relative as i64;
+                    ticks * scale as i64 / NANOSECONDS_PER_TICK as i64
+                };
+                loop {
+                    let position = reader.position;
+                    if cluster_end.map_or(position >= segment_end.min(reader.end), |end| {
+                        position >= end
+                    }) {
+                        break;
+                    }
+                    let (id, size) = reader.header()?;
+                    if cluster_end.is_none() && LEVEL_1.contains(&id) {
+                        reader.seek(position)?;
+                        break;
+                    }
+                    let size = size.ok_or("Cluster element of unknown size")?;
+                    match id {
+                        TIMESTAMP => cluster_time = read_uint(&reader.bytes(size)?),
+                        SIMPLE_BLOCK => {
+                            let (block_track, relative, flags, size) = reader.block_header(size)?;
+                            if block_track == number {
+                                blocks.push(Block {
+                                    track,
+                                    time: to_time(cluster_time, relative),
+                                    flags,
+                                    keyframe: flags & SIMPLE_BLOCK_KEYFRAME != 0,
+                                    data: Data::Input(reader.position, size),
+                                    group: None,
+                                });
+                            }
+                            reader.seek(reader.position + size)?;
+                        }
+                        BLOCK_GROUP => {
+                            let group_end = reader.position + size;
+                            let mut block = None;
+                            let mut group = vec![];
+                            while reader.position < group_end {
+                                let (id, size) = reader.header()?;
+                                let size = size.ok_or("Block group element of unknown size")?;
+                                if id == BLOCK {
+                                    let (block_track, relative, flags, size) =
+                                        reader.block_header(size)?;
+                                    block =
+                                        Some((block_track, relative, flags, reader.position, size));
+                                    reader.seek(reader.position + size)?;
+                                } else {
+                                    group.push((id, reader.bytes(size)?));
+                                }
+                            }
+                            if let Some((block_track, relative, flags, position, size)) = block {
+                                if block_track == number {
+                                    blocks.push(Block {
+                                        track,
+                                        time: to_time(cluster_time, relative),
+                                        flags,
+                                        keyframe: group
+                                            .iter()
+                                            .all(|(id, _)| *id != REFERENCE_BLOCK),
+                                        data: Data::Input(position, size),
+                                        group: Some(
+                                            group
+                                                .iter()
+                                                .flat_map(|(id, payload)| element(*id, payload))
+                                                .collect(),
+                                        ),
+                                    });
+                                }
+                            }
+                        }
+                        _ => reader.seek(reader.position + size)?,
+                    }
+                }
+            }
+            (_, Some(size)) => reader.seek(reader.position + size)?,
+            (_, None) => return Err(ParsingError::from("Element of unknown size")),
+        }
+    }
+
+    let entry = entry.ok_or_else(|| {
+        ParsingError::from(format!("No track of type {} in the WebM file", track_type))
+    })?;
+    Ok(Input {
+        entry,
+        duration: duration.map(|d| d * scale as f64 / NANOSECONDS_PER_TICK as f64),
+        blocks,
+    })
+}
+
+/// Milliseconds of a `hh:mm:ss.ttt` timestamp, SRT's `hh:mm:ss,ttt` or WebVTT's `mm:ss.ttt`
+fn cue_time(timestamp: &str) -> Option<i64> {
+    let timestamp = timestamp.trim().replace(',', ".");
+    let (clock, fraction) = timestamp.split_once('.')?;
+    let seconds = clock.split(':').try_fold(0, |seconds: i64, part| {
+        Some(seconds * 60 + part.parse::<i64>().ok()?)
+    })?;
+    let milliseconds = format!("{:0<3}", fraction).get(..3)?.parse::<i64>().ok()?;
+    Some(seconds * 1000 + milliseconds)
+}
+
+/// Start, end and text of the cues of a WebVTT or SRT file
+fn cues(text: &str) -> Vec<(i64, i64, String)> {
+    let text = text.replace("\r\n", "\n");
+    let mut cues = vec![];
+    for block in text.split("\n\n") {
+        let lines: Vec<&str> = block.lines().collect();
+        let timing = match lines.iter().position(|line| line.contains("-->")) {
+            Some(timing) => timing,
+            None => continue,
+        };
+        let mut times = lines[timing].split("-->");
+        let start = times.next().and_then(cue_time);
+        // Cue settings follow the end
+        let end = times
+            .next()
+            .and_then(|end| end.split_whitespace().next())
+            .and_then(cue_time);
+        let cue = lines[timing + 1..].join("\n");
+        if let (Some(start), Some(end), false) = (start, end, cue.trim().is_empty()) {
+            cues.push((start, end, cue));
+        }
+    }
+    cues
+}
+
+struct Cluster {
+    time: i64,
+    blocks: std::ops::Range<usize>,
+}
+
+impl Cluster {
+    fn content_size(&self, blocks: &[Block]) -> u64 {
+        let timestamp = uint(TIMESTAMP, self.time as u64).len() as u64;
+        timestamp
+            + blocks[self.blocks.clone()]
+                .iter()
+                .map(Block::size)
+                .sum::<u64>()
+    }
+}
+
+/// Clusters of about `CLUSTER_MILLISECONDS` starting at video keyframes
+fn clusters(blocks: &[Block]) -> Vec<Cluster> {
+    let mut clusters: Vec<Cluster> = vec![];
+    for (i, block) in blocks.iter().enumerate() {
+        let split = match clusters.last() {
+            None => true,
+            Some(cluster) => {
+                let elapsed = block.time - cluster.time;
+                (block.track == 0 && block.keyframe && elapsed >= CLUSTER_MILLISECONDS)
+                    || elapsed > i16::MAX as i64
+            }
+        };
+        match clusters.last_mut() {
+            Some(cluster) if !split => cluster.blocks.end = i + 1,
+            // Cluster timestamps are unsigned, blocks before 0 get negative relative ones
+            _ => clusters.push(Cluster {
+                time: block.time.max(0),
+                blocks: i..i + 1,
+            }),
+        }
+    }
+    clusters
+}
+
+fn info(metadata: &Metadata, duration: f64) -> Vec<u8> {
+    let app = concat!("rusty_pipe ", env!("CARGO_PKG_VERSION"));
+    let mut children = vec![
+        uint(TIMESTAMP_SCALE, NANOSECONDS_PER_TICK),
+        float(DURATION, duration),
+    ];
+    if let Some(title) = &metadata.title {
+        children.push(string(TITLE, title));
+    }
+    children.push(string(MUXING_APP, app));
+    children.push(string(WRITING_APP, app));
+    master(INFO, &children)
+}
+
+fn tags(metadata: &Metadata) -> Option<Vec<u8>> {
+    let values = [
+        ("TITLE", metadata.title.clone()),
+        ("ARTIST", metadata.artist.clone()),
+        (
+            "DATE_RELEASED",
+            metadata.date.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+        ("DESCRIPTION", metadata.description.clone()),
+    ];
+    let mut children = vec![master(TARGETS, &[uint(TARGET_TYPE_VALUE, 50)])];
+    for (name, value) in values.iter() {
+        if let Some(value) = value {
+            children.push(master(
+                SIMPLE_TAG,
+                &[string(TAG_NAME, name), string(TAG_STRING, value)],
+            ));
+        }
+    }
+    if children.len() == 1 {
+        return None;
+    }
+    Some(master(TAGS, &[master(TAG, &children)]))
+}
+
+fn chapters(metadata: &Metadata) -> Option<Vec<u8>> {
+    if metadata.chapters.is_empty() {
+        return None;
+    }
+    let mut edition = vec![uint(EDITION_UID, 1)];
+    for (i, chapter) in metadata.chapters.iter().enumerate() {
+        let mut atom = vec![
+            uint(CHAPTER_UID, i as u64 + 1),
+            uint(CHAPTER_TIME_START, chapter.start * 1_000_000_000),
+        ];
+        if let Some(end) = chapter.end {
+            atom.push(uint(CHAPTER_TIME_END, end * 1_000_000_000));
+        }
+        atom.push(master(
+            CHAPTER_DISPLAY,
+            &[
+                string(CHAP_STRING, &chapter.title),
+                string(CHAP_LANGUAGE, "eng"),
+            ],
+        ));
+        edition.push(master(CHAPTER_ATOM, &atom));
+    }
+    Some(master(CHAPTERS, &[master(EDITION_ENTRY, &edition)]))
+}
+
+fn attachments(metadata: &Metadata, doc_type: DocType) -> Option<Vec<u8>> {
+    let cover = metadata.cover.as_ref()?;
+    let format = cover.format()?;
+    if doc_type == DocType::Webm {
+        tracing::debug!("skipping the cover, WebM has no attachments");
+        return None;
+    }
+    let extension = format.mime_type().trim_start_matches("image/");
+    let extension = if extension == "jpeg" {
+        "jpg"
+    } else {
+        extension
+    };
+    let file = master(
+        ATTACHED_FILE,
+        &[
+            string(FILE_NAME, &format!("cover.{}", extension)),
+            string(FILE_MIME_TYPE, format.mime_type()),
+            element(FILE_DATA, &cover.data),
+            uint(FILE_UID, 1),
+        ],
+    );
+    Some(master(ATTACHMENTS, &[file]))
+}
+
+fn copy_block<R: Read + Seek, W: Write>(
+    reader: &mut Reader<R>,
+    position: u64,
+    size: u64,
+    output: &mut W,
+) -> Result<(), ParsingError> {
+    reader.seek(position)?;
+    let copied = std::io::copy(&mut (&mut reader.input).take(size), output).map_err(io_error)?;
+    reader.position += copied;
+    if copied != size {
+        return Err(ParsingError::from("Block data past the end of the input"));
+    }
+    Ok(())
+}
+
+const CODEC_PRIVATE: u32 = 0x63A2;
+const DISCARD_PADDING: u32 = 0x75A2;
+const LACING: u8 = 0x06;
+
+/// Frames of the audio track of a WebM file, for [`super::tags`]
+pub(crate) struct AudioFrames<R> {
+    reader: Reader<R>,
+    pub(crate) codec_id: String,
+    pub(crate) codec_private: Vec<u8>,
+    blocks: Vec<Block>,
+}
+
+impl<R: Read + Seek> AudioFrames<R> {
+    pub(crate) fn read(input: R) -> Result<Self, ParsingError> {
+        let mut reader = Reader::new(input)?;
+        let input = read_input(&mut reader, TRACK_TYPE_AUDIO, 0)?;
+        let mut codec_id = String::new();
+        let mut codec_private = vec![];
+        for (id, payload, _) in elements(&input.entry)? {
+            match id {
+                CODEC_ID => codec_id = String::from_utf8_lossy(payload).to_string(),
+                CODEC_PRIVATE => codec_private = payload.to_vec(),
+                _ => {}
+            }
+        }
+        if input.blocks.iter().any(|block| block.flags & LACING != 0) {
+            return Err(ParsingError::from("Laced blocks are not supported"));
+        }
+        Ok(AudioFrames {
+            reader,
+            codec_id,
+            codec_private,
+            blocks: input.blocks,
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Data of the frame and its discard padding in nanoseconds
+    pub(crate) fn frame(&mut self, index: usize) -> Result<(Vec<u8>, i64), ParsingError> {
+        let block = &self.blocks[index];
+        let mut discard_padding = 0;
+        if let Some(group) = &block.group {
+            for (id, payload, _) in elements(group)? {
+                if id == DISCARD_PADDING {
+                    let value = read_uint(payload);
+                    // Signed, sign extended from the payload's width
+                    let shift = 64 - 8 * payload.len().clamp(1, 8) as u32;
+                    discard_padding = ((value << shift) as i64) >> shift;
+                }
+            }
+        }
+        let mut data = vec![];
+        if let Data::Input(position, size) = block.data {
+            copy_block(&mut self.reader, position, size, &mut data)?;
+        }
+        Ok((data, discard_padding))
+    }
+}
+
+/// Writes a WebM or Matroska file with the video track of `video`, the audio track of `audio`
+/// and a track per subtitle
+pub fn mux<V: Read + Seek, A: Read + Seek, W: Write>(
+    video: V,
+    audio: A,
+    output: W,
+    doc_type: DocType,
+    metadata: &Metadata,
+    subtitles: &[Subtitle],
+) -> Result<(), ParsingError> {
+    if subtitles.len() > 125 {
+        // Track numbers are written as one byte in blocks
+        return Err(ParsingError::from("Too many subtitle tracks"));
+    }
+    let mut video = Reader::new(video)?;
+    let mut audio = Reader::new(audio)?;
+    let inputs = [
+        read_input(&mut video, TRACK_TYPE_VIDEO, 0)?,
+        read_input(&mut audio, TRACK_TYPE_AUDIO, 1)?,
+    ];
+
+    let mut entries = vec![];
+    for (i, input) in inputs.iter().enumerate() {
+        let mut entry = uint(TRACK_NUMBER, i as u64 + 1);
+        entry.extend(uint(TRACK_UID, i as u64 + 1));
+        entry.extend_from_slice(&input.entry);
+        entries.push(element(TRACK_ENTRY, &entry));
+    }
+    let mut blocks: Vec<Block> = vec![];
+    for (i, subtitle) in subtitles.iter().enumerate() {
+        let track = inputs.len() + i;
+        let mut entry = vec![
+            uint(TRACK_NUMBER, track as u64 + 1),
+            uint(TRACK_UID, track as u64 + 1),
+            uint(TRACK_TYPE, TRACK_TYPE_SUBTITLE),
+            uint(FLAG_DEFAULT, 0),
+            string(CODEC_ID, doc_type.subtitle_codec_id()),
+            string(LANGUAGE, &subtitle.language),
+        ];
+        if let Some(name) = &subtitle.name {
+            entry.push(string(NAME, name));
+        }
+        entries.push(master(TRACK_ENTRY, &entry));
+        for (start, end, text) in cues(&subtitle.text) {
+            blocks.push(Block {
+                track,
+                time: start,
+                flags: 0,
+                keyframe: true,
+                data: Data::Text(text.into_bytes()),
+                group: Some(uint(BLOCK_DURATION, (end - start).max(0) as u64)),
+            });
+        }
+    }
+    let duration = inputs
+        .iter()
+        .filter_map(|input| input.duration)
+        .fold(0.0, f64::max);
+    for input in inputs {
+        blocks.extend(input.blocks);
+    }
+    // Stable, so blocks of a track keep their order
+    blocks.sort_by_key(|block| (block.time, block.track));
+    let clusters = clusters(&blocks);
+    let duration = if duration > 0.0 {
+        duration
+    } else {
+        blocks.iter().map(|b| b.time).max().unwrap_or(0) as f64
+    };
+
+    let mut level_1 = vec![
+        (INFO, info(metadata, duration)),
+        (TRACKS, master(TRACKS, &entries)),
+    ];
+    level_1.extend(chapters(metadata).map(|chapters| (CHAPTERS, chapters)));
+    level_1.extend(tags(metadata).map(|tags| (TAGS, tags)));
+    level_1.extend(attachments(metadata, doc_type).map(|attachments| (ATTACHMENTS, attachments)));
+
+    let seek_head = |positions: &[(u32, u64)]| {
+        let seeks: Vec<Vec<u8>> = positions
+            .iter()
+            .map(|(id, position)| {
+                master(
+                    SEEK,
+                    &[
+                        element(SEEK_ID, &id_bytes(*id)),
+                        fixed_uint(SEEK_POSITION, *position),
+                    ],
+                )
+            })
+            .collect();
+        master(SEEK_HEAD, &seeks)
+    };
+    let mut ids: Vec<u32> = level_1.iter().map(|(id, _)| *id).collect();
+    ids.push(CUES);
+    let mut position = seek_head(&ids.iter().map(|id| (*id, 0)).collect::<Vec<_>>()).len() as u64;
+    let mut positions = vec![];
+    for (id, element) in &level_1 {
+        positions.push((*id, position));
+        position += element.len() as u64;
+    }
+    let mut cue_points = vec![];
+    let mut cluster_sizes = vec![];
+    for cluster in &clusters {
+        for block in &blocks[cluster.blocks.clone()] {
+            if block.track == 0 && block.keyframe {
+                let positions = master(
+                    CUE_TRACK_POSITIONS,
+                    &[uint(CUE_TRACK, 1), uint(CUE_CLUSTER_POSITION, position)],
+                );
+                cue_points.push(master(
+                    CUE_POINT,
+                    &[uint(CUE_TIME, block.time.max(0) as u64), positions],
+                ));
+            }
+        }
+        let size = cluster.content_size(&blocks);
+        cluster_sizes.push(size);
+        position += element_size(CLUSTER, size);
+    }
+    positions.push((CUES, position));
+    let cues = master(CUES, &cue_points);
+    let segment_size = position + cues.len() as u64;
+
+    let mut output = std::io::BufWriter::new(output);
+    let header = master(
+        EBML,
+        &[
+            uint(EBML_VERSION, 1),
+            uint(EBML_READ_VERSION, 1),
+            uint(EBML_MAX_ID_LENGTH, 4),
+            uint(EBML_MAX_SIZE_LENGTH, 8),
+            string(EBML_DOC_TYPE, doc_type.name()),
+            uint(EBML_DOC_TYPE_VERSION, 4),
+            uint(EBML_DOC_TYPE_READ_VERSION, 2),
+        ],
+    );
+    output.write_all(&header).map_err(io_error)?;
+    let mut segment = id_bytes(SEGMENT);
+    segment.extend(size_bytes(segment_size));
+    output.write_all(&segment).map_err(io_error)?;
+    output.write_all(&seek_head(&positions)).map_err(io_error)?;
+    for (_, element) in &level_1 {
+        output.write_all(element).map_err(io_error)?;
+    }
+    for (cluster, size) in clusters.iter().zip(cluster_sizes) {
+        let mut header = id_bytes(CLUSTER);
+        header.extend(size_bytes(size));
+        header.extend(uint(TIMESTAMP, cluster.time as u64));
+        output.write_all(&header).map_err(io_error)?;
+        for block in &blocks[cluster.blocks.clone()] {
+            output
+                .write_all(&block.header(cluster.time))
+                .map_err(io_error)?;
+            match (&block.data, block.track) {
+                (Data::Input(position, size), 0) => {
+                    copy_block(&mut video, *position, *size, &mut output)?
+                }
+                (Data::Input(position, size), _) => {
+                    copy_block(&mut audio, *position, *size, &mut output)?
+                }
+                (Data::Text(text), _) => output.write_all(text).map_err(io_error)?,
+            }
+            if let Some(group) = &block.group {
+                output.write_all(group).map_err(io_error)?;
+            }
+        }
+    }
+    output.write_all(&cues).map_err(io_error)?;
+    output.flush().map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mux::{Chapter, Cover};
+    use std::io::Cursor;
+
+    /// VP9 track 1, frames `0x10 + i` of `20 + i` bytes every second with keyframes at 0 and
+    /// 6 s. The segment and the first cluster have unknown sizes, the second cluster holds block
+    /// groups.
+    const VIDEO: &[u8] = include_bytes!("fixtures/video.webm");
+    /// Opus track 2, ten frames `0xa0 + i` every 20 ms at a 10 us timestamp scale, the last one
+    /// in a block group with 5 ms of discard padding
+    const AUDIO: &[u8] = include_bytes!("fixtures/audio.webm");
+
+    const SUBTITLE: &str =
+        "WEBVTT\n\n00:00.500 --> 00:01.500\nHello\n\n00:02.000 --> 00:03.250 align:start\nWorld\n";
+
+    fn read(file: &[u8], track_type: u64) -> Input {
+        read_input(&mut Reader::new(Cursor::new(file)).unwrap(), track_type, 0).unwrap()
+    }
+
+    /// Time, keyframe, data and block group children
+    type BlockContent = (i64, bool, Vec<u8>, Option<Vec<u8>>);
+
+    fn blocks(file: &[u8], input: &Input) -> Vec<BlockContent> {
+        input
+            .blocks
+            .iter()
+            .map(|block| {
+                let data = match &block.data {
+                    Data::Input(position, size) => {
+                        file[*position as usize..(position + size) as usize].to_vec()
+                    }
+                    Data::Text(text) => text.clone(),
+                };
+                (block.time, block.keyframe, data, block.group.clone())
+            })
+            .collect()
+    }
+
+    fn child(data: &[u8], id: u32) -> Option<&[u8]> {
+        elements(data)
+            .unwrap()
+            .into_iter()
+            .find(|(i, _, _)| *i == id)
+            .map(|(_, payload, _)| payload)
+    }
+
+    fn children(data: &[u8], id: u32) -> Vec<&[u8]> {
+        elements(data)
+            .unwrap()
+            .into_iter()
+            .filter(|(i, _, _)| *i == id)
+            .map(|(_, payload, _)| payload)
+            .collect()
+    }
+
+    /// Id of the element at `position`
+    fn id_at(data: &[u8], position: u64) -> u32 {
+        vint(&data[position as usize..], true).unwrap().0 as u32
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            title: Some("Mock title".to_string()),
+            chapters: vec![
+                Chapter {
+                    start: 0,
+                    end: Some(4),
+                    title: "Intro".to_string(),
+                },
+                Chapter {
+                    start: 4,
+                    end: None,
+                    title: "Main".to_string(),
+                },
+            ],
+            cover: Some(Cover {
+                data: vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3],
+            }),
+            ..Metadata::default()
+        }
+    }
+
+    fn mux_fixtures(doc_type: DocType) -> Vec<u8> {
+        let subtitle = Subtitle {
+            language: "eng".to_string(),
+            name: Some("English".to_string()),
+            text: SUBTITLE.to_string(),
+        };
+        let mut output = vec![];
+        mux(
+            Cursor::new(VIDEO),
+            Cursor::new(AUDIO),
+            &mut output,
+            doc_type,
+            &metadata(),
+            &[subtitle],
+        )
+        .unwrap();
+        output
+    }
+
+    #[test]
+    fn reads_unknown_sizes_and_block_groups() {
+        let video = read(VIDEO, TRACK_TYPE_VIDEO);
+        let times: Vec<(i64, bool)> = video.blocks.iter().map(|b| (b.time, b.keyframe)).collect();
+        assert_eq!(
+            times,
+            (0..8)
+                .map(|i| (i * 1000, i == 0 || i == 6))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(video.duration, Some(8000.0));
+        for (i, (_, _, data, _)) in blocks(VIDEO, &video).into_iter().enumerate() {
+            assert_eq!(data, vec![0x10 + i as u8; 20 + i]);
+        }
+
+        let audio = read(AUDIO, TRACK_TYPE_AUDIO);
+        let times: Vec<i64> = audio.blocks.iter().map(|b| b.time).collect();
+        assert_eq!(times, (0..10).map(|i| i * 20).collect::<Vec<_>>());
+        assert_eq!(audio.duration, Some(200.0));
+
+        let mut frames = AudioFrames::read(Cursor::new(AUDIO)).unwrap();
+        assert_eq!(frames.codec_id, "A_OPUS");
+        assert_eq!(frames.len(), 10);
+        assert_eq!(frames.frame(0).unwrap(), (vec![0xa0; 10], 0));
+        assert_eq!(frames.frame(9).unwrap(), (vec![0xa9; 10], 5_000_000));
+    }
+
+    #[test]
+    fn round_trips_both_doc_types() {
+        for doc_type in [DocType::Webm, DocType::Matroska] {
+            let output = mux_fixtures(doc_type);
+            let header = child(&output, EBML).unwrap();
+            assert_eq!(
+                child(header, EBML_DOC_TYPE).unwrap(),
+                doc_type.name().as_bytes()
+            );
+
+            // Blocks keep their times, keyframes, data and block group children
+            let (video_in, audio_in) =
+                (read(VIDEO, TRACK_TYPE_VIDEO), read(AUDIO, TRACK_TYPE_AUDIO));
+            let (video, audio) = (
+                read(&output, TRACK_TYPE_VIDEO),
+                read(&output, TRACK_TYPE_AUDIO),
+            );
+            assert_eq!(blocks(&output, &video), blocks(VIDEO, &video_in));
+            assert_eq!(blocks(&output, &audio), blocks(AUDIO, &audio_in));
+            assert_eq!(video.entry, video_in.entry);
+            assert_eq!(audio.entry, audio_in.entry);
+
+            let subtitles = read(&output, TRACK_TYPE_SUBTITLE);
+            assert_eq!(
+                child(&subtitles.entry, CODEC_ID).unwrap(),
+                doc_type.subtitle_codec_id().as_bytes()
+            );
+            let cues: Vec<(i64, Vec<u8>, Option<Vec<u8>>)> = blocks(&output, &subtitles)
+                .into_iter()
+                .map(|(time, _, data, group)| (time, data, group))
+                .collect();
+            assert_eq!(
+                cues,
+                vec![
+                    (500, b"Hello".to_vec(), Some(uint(BLOCK_DURATION, 1000))),
+                    (2000, b"World".to_vec(), Some(uint(BLOCK_DURATION, 1250))),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn seek_head_and_cues_point_at_their_elements() {
+        for doc_type in [DocType::Webm, DocType::Matroska] {
+            let output = mux_fixtures(doc_type);
+            let segment = child(&output, SEGMENT).unwrap();
+
+            let mut seeks = vec![];
+            for seek in children(child(segment, SEEK_HEAD).unwrap(), SEEK) {
+                let id = read_uint(child(seek, SEEK_ID).unwrap()) as u32;
+                let position = read_uint(child(seek, SEEK_POSITION).unwrap());
+                assert_eq!(id_at(segment, position), id);
+                seeks.push(id);
+            }
+            let mut expected = vec![INFO, TRACKS, CHAPTERS, TAGS];
+            if doc_type == DocType::Matroska {
+                expected.push(ATTACHMENTS);
+            }
+            expected.push(CUES);
+            assert_eq!(seeks, expected);
+
+            // A cue for each video keyframe, at the cluster starting with it
+            let cue_points = children(child(segment, CUES).unwrap(), CUE_POINT);
+            let mut cues = vec![];
+            for cue_point in cue_points {
+                let time = read_uint(child(cue_point, CUE_TIME).unwrap());
+                let positions = child(cue_point, CUE_TRACK_POSITIONS).unwrap();
+                assert_eq!(read_uint(child(positions, CUE_TRACK).unwrap()), 1);
+                let position = read_uint(child(positions, CUE_CLUSTER_POSITION).unwrap());
+                assert_eq!(id_at(segment, position), CLUSTER);
+                let cluster = elements(&segment[position as usize..]).unwrap()[0].1;
+                assert_eq!(read_uint(child(cluster, TIMESTAMP).unwrap()), time);
+                cues.push(time);
+            }
+            assert_eq!(cues, vec![0, 6000]);
+        }
+    }
+
+    #[test]
+    fn writes_chapters_and_attachments() {
+        for doc_type in [DocType::Webm, DocType::Matroska] {
+            let output = mux_fixtures(doc_type);
+            let segment = child(&output, SEGMENT).unwrap();
+
+            let edition = child(child(segment, CHAPTERS).unwrap(), EDITION_ENTRY).unwrap();
+            let chapters: Vec<(u64, Option<u64>, &[u8])> = children(edition, CHAPTER_ATOM)
+                .into_iter()
+                .map(|atom| {
+                    let display = child(atom, CHAPTER_DISPLAY).unwrap();
+                    (
+                        read_uint(child(atom, CHAPTER_TIME_START).unwrap()),
+                        child(atom, CHAPTER_TIME_END).map(read_uint),
+                        child(display, CHAP_STRING).unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                chapters,
+                vec![
+                    (0, Some(4_000_000_000), &b"Intro"[..]),
+                    (4_000_000_000, None, &b"Main"[..]),
+                ]
+            );
+
+            let attachments = child(segment, ATTACHMENTS);
+            match doc_type {
+                DocType::Webm => assert!(attachments.is_none()),
+                DocType::Matroska => {
+                    let file = child(attachments.unwrap(), ATTACHED_FILE).unwrap();
+                    assert_eq!(child(file, FILE_NAME).unwrap(), b"cover.jpg");
+                    assert_eq!(child(file, FILE_MIME_TYPE).unwrap(), b"image/jpeg");
+                    assert_eq!(
+                        child(file, FILE_DATA).unwrap(),
+                        &metadata().cover.unwrap().data[..]
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+//! Merging of downloaded video-only and audio streams into one file, without ffmpeg
+
+pub mod matroska;
+pub mod mp4;
+pub mod tags;
+
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::stream_extractor::YTStreamExtractor;
+
+pub(crate) fn io_error(error: std::io::Error) -> ParsingError {
+    ParsingError::from(error.to_string())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Png => "image/png",
+            ImageFormat::Webp => "image/webp",
+        }
+    }
+}
+
+/// Image data of a thumbnail, e.g. from `DownloadManager::fetch`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cover {
+    pub data: Vec<u8>,
+}
+
+impl Cover {
+    /// Format by the file signature
+    pub fn format(&self) -> Option<ImageFormat> {
+        let data = &self.data;
+        if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else {
+            None
+        }
+    }
+}
+
+/// Text of a WebVTT or SRT subtitle file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Subtitle {
+    /// ISO 639-2 code, e.g. `eng`
+    pub language: String,
+    pub name: Option<String>,
+    pub text: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chapter {
+    /// Seconds
+    pub start: u64,
+    pub end: Option<u64>,
+    pub title: String,
+}
+
+/// Seconds of a `h:mm:ss` or `m:ss` timestamp
+fn timestamp_seconds(timestamp: &str) -> Option<u64> {
+    timestamp.split(':').try_fold(0, |seconds, part| {
+        Some(seconds * 60 + part.parse::<u64>().ok()?)
+    })
+}
+
+impl Chapter {
+    /// Chapters from the timestamps that start lines of the description, like yt-dlp does when
+    /// the video has no chapter markers. The last one ends at `duration`.
+    pub fn from_description(description: &str, duration: Option<u64>) -> Option<Vec<Chapter>> {
+        let pattern =
+            regex::Regex::new(r"^\s*[\[(]?((?:\d+:)?\d{1,2}:\d{2})[\])]?\s*[-–—:|]?\s*(.+?)\s*$")
+                .ok()?;
+        let mut starts: Vec<(u64, String)> = vec![];
+        for line in description.lines() {
+            if let Some(captures) = pattern.captures(line) {
+                let start = timestamp_seconds(&captures[1])?;
+                if starts.last().is_some_and(|(last, _)| *last >= start) {
+                    return None;
+                }
+                starts.push((start, captures[2].to_string()));
+            }
+        }
+        if starts.len() < 2 || starts[0].0 != 0 {
+            return None;
+        }
+        let chapters = starts
+            .iter()
+            .enumerate()
+            .map(|(i, (start, title))| Chapter {
+                start: *start,
+                end: starts.get(i + 1).map(|(start, _)| *start).or(duration),
+                title: title.clone(),
+            })
+            .collect();
+        Some(chapters)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub date: Option<chrono::NaiveDate>,
+    pub description: Option<String>,
+    pub cover: Option<Cover>,
+    pub chapters: Vec<Chapter>,
+}
+
+impl Metadata {
+    /// Everything but the cover, whose image has to be fetched from [`cover_url`]
+    pub fn from_extractor<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Self {
+        let description = extractor
+            .description(false)
+            .ok()
+            .map(|(description, _)| description)
+            .filter(|description| !description.is_empty());
+        let chapters = description
+            .as_deref()
+            .and_then(|d| Chapter::from_description(d, extractor.length().ok()))
+            .unwrap_or_default();
+        Metadata {
+            title: extractor.name().ok(),
+            artist: extractor.uploader_name().ok(),
+            date: extractor.upload_date().ok(),
+            description,
+            cover: None,
+            chapters,
+        }
+    }
+}
+
+/// The largest JPEG thumbnail, as MP4 covers can't be WebP, or the largest one if there is none
+pub fn cover_url<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Option<String> {
+    let thumbnails = extractor.video_thumbnails().ok()?;
+    let is_jpeg = |url: &str| url.split('?').next().unwrap_or_default().ends_with(".jpg");
+    thumbnails
+        .iter()
+        .filter(|t| is_jpeg(&t.url))
+        .max_by_key(|t| t.width * t.height)
+        .or_else(|| thumbnails.iter().max_by_key(|t| t.width * t.height))
+        .map(|t| t.url.clone())
+}
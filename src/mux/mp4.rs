@@ -0,0 +1,1322 @@
+//! MP4 muxing of a video and an audio track
+//!
+//! Inputs are the ISO BMFF files `video_only_stream()` and `audio_streams()` serve, fragmented
+//! DASH files as well as progressive ones. Sample entries (`avc1`, `av01`, `mp4a`, ...) are
+//! copied as they are, the samples are re-chunked and interleaved by decode time. Progressive
+//! output puts the sample tables in front of the data, so players can start right away.
+
+use super::{io_error, ImageFormat, Metadata};
+use crate::youtube_extractor::error::ParsingError;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// One `moov` with the sample tables of the whole file
+    Progressive,
+    /// `moof`/`mdat` pairs of about a second each
+    Fragmented,
+}
+
+const MOVIE_TIMESCALE: u32 = 1000;
+/// Seconds of samples per chunk or fragment, video chunks also start with a sync sample
+const CHUNK_SECONDS: f64 = 1.0;
+
+const TFHD_BASE_DATA_OFFSET: u32 = 0x01;
+const TFHD_SAMPLE_DESCRIPTION_INDEX: u32 = 0x02;
+const TFHD_DEFAULT_DURATION: u32 = 0x08;
+const TFHD_DEFAULT_SIZE: u32 = 0x10;
+const TFHD_DEFAULT_FLAGS: u32 = 0x20;
+const TFHD_DEFAULT_BASE_IS_MOOF: u32 = 0x020000;
+const TRUN_DATA_OFFSET: u32 = 0x01;
+const TRUN_FIRST_SAMPLE_FLAGS: u32 = 0x04;
+const TRUN_DURATION: u32 = 0x100;
+const TRUN_SIZE: u32 = 0x200;
+const TRUN_FLAGS: u32 = 0x400;
+const TRUN_COMPOSITION_OFFSET: u32 = 0x800;
+const SAMPLE_IS_NON_SYNC: u32 = 0x10000;
+/// `sample_depends_on` 2, the sample is a sync sample
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// `sample_depends_on` 1 and `sample_is_non_sync_sample`
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// Big endian reader over the payload of a box
+pub(crate) struct Bytes<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Bytes<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Bytes { data, position: 0 }
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Result<&'a [u8], ParsingError> {
+        let end = self
+            .position
+            .checked_add(n)
+            .filter(|end| *end <= self.data.len())
+            .ok_or("Box ends early")?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ParsingError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, ParsingError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, ParsingError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, ParsingError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    /// Version and flags of a full box
+    pub(crate) fn version(&mut self) -> Result<(u8, u32), ParsingError> {
+        let header = self.u32()?;
+        Ok(((header >> 24) as u8, header & 0xff_ffff))
+    }
+
+    /// `u32` for version 0 boxes, `u64` for version 1
+    pub(crate) fn sized(&mut self, version: u8) -> Result<u64, ParsingError> {
+        match version {
+            1 => self.u64(),
+            _ => Ok(self.u32()? as u64),
+        }
+    }
+}
+
+/// Type, payload and the complete box
+pub(crate) type Atom<'a> = ([u8; 4], &'a [u8], &'a [u8]);
+
+/// Each box in `data`
+pub(crate) fn boxes(data: &[u8]) -> Result<Vec<Atom<'_>>, ParsingError> {
+    let mut boxes = vec![];
+    let mut bytes = Bytes::new(data);
+    while bytes.position < data.len() {
+        let start = bytes.position;
+        let size = bytes.u32()? as u64;
+        let kind = bytes.array()?;
+        let size = match size {
+            0 => (data.len() - start) as u64,
+            1 => bytes.u64()?,
+            size => size,
+        };
+        let header = bytes.position - start;
+        let payload = bytes.take((size as usize).checked_sub(header).ok_or("Bad box size")?)?;
+        boxes.push((kind, payload, &data[start..bytes.position]));
+    }
+    Ok(boxes)
+}
+
+/// Payload of the first box of a type
+pub(crate) fn child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Result<Option<&'a [u8]>, ParsingError> {
+    Ok(boxes(data)?
+        .into_iter()
+        .find(|(k, _, _)| k == kind)
+        .map(|(_, payload, _)| payload))
+}
+
+/// Payload of the box at the end of a path of box types
+pub(crate) fn path<'a>(data: &'a [u8], kinds: &[&[u8; 4]]) -> Result<&'a [u8], ParsingError> {
+    let mut payload = data;
+    for kind in kinds {
+        payload = child(payload, kind)?.ok_or_else(|| {
+            ParsingError::from(format!("No {} box", String::from_utf8_lossy(*kind)))
+        })?;
+    }
+    Ok(payload)
+}
+
+pub(crate) fn atom(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut atom = Vec::with_capacity(payload.len() + 8);
+    atom.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    atom.extend_from_slice(kind);
+    atom.extend_from_slice(payload);
+    atom
+}
+
+pub(crate) fn full_atom(kind: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut content = ((version as u32) << 24 | flags).to_be_bytes().to_vec();
+    content.extend_from_slice(payload);
+    atom(kind, &content)
+}
+
+pub(crate) fn container(kind: &[u8; 4], children: &[Vec<u8>]) -> Vec<u8> {
+    atom(kind, &children.concat())
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    /// Position in the input file
+    offset: u64,
+    size: u32,
+    decode_time: u64,
+    duration: u32,
+    composition_offset: i32,
+    sync: bool,
+}
+
+struct Track {
+    handler: [u8; 4],
+    timescale: u32,
+    language: u16,
+    /// 16.16 fixed point, from `tkhd`
+    width: u32,
+    height: u32,
+    stsd: Vec<u8>,
+    /// Segment duration in movie timescale, media time and rate of each edit
+    edits: Vec<(u64, i64, u32)>,
+    samples: Vec<Sample>,
+}
+
+impl Track {
+    fn duration(&self) -> u64 {
+        self.samples.iter().map(|s| s.duration as u64).sum()
+    }
+
+    fn movie_duration(&self) -> u64 {
+        self.duration() * MOVIE_TIMESCALE as u64 / self.timescale.max(1) as u64
+    }
+
+    /// Samples split into chunks of about `CHUNK_SECONDS`
+    fn chunks(&self) -> Vec<&[Sample]> {
+        let limit = (CHUNK_SECONDS * self.timescale as f64) as u64;
+        let mut chunks = vec![];
+        let mut start = 0;
+        let mut duration = 0;
+        for (i, sample) in self.samples.iter().enumerate() {
+            let boundary = self.handler != *b"vide" || sample.sync;
+            if i > start && duration >= limit && boundary {
+                chunks.push(&self.samples[start..i]);
+                start = i;
+                duration = 0;
+            }
+            duration += sample.duration as u64;
+        }
+        if start < self.samples.len() {
+            chunks.push(&self.samples[start..]);
+        }
+        chunks
+    }
+}
+
+struct TrackDefaults {
+    duration: u32,
+    size: u32,
+    flags: u32,
+}
+
+/// Samples of the `stbl` of a progressive file of `file_size` bytes
+fn table_samples(stbl: &[u8], file_size: u64) -> Result<Vec<Sample>, ParsingError> {
+    let mut sizes = Bytes::new(path(stbl, &[b"stsz"])?);
+    sizes.version()?;
+    let uniform_size = sizes.u32()?;
+    let count = sizes.u32()? as usize;
+    let fitting = match uniform_size {
+        0 => sizes.remaining() / 4,
+        size => (file_size / size as u64) as usize,
+    };
+    if count > fitting {
+        return Err(ParsingError::from("More samples than the file holds"));
+    }
+    let mut samples = Vec::with_capacity(count);
+    for _ in 0..count {
+        let size = match uniform_size {
+            0 => sizes.u32()?,
+            size => size,
+        };
+        samples.push(Sample {
+            offset: 0,
+            size,
+            decode_time: 0,
+            duration: 0,
+            composition_offset: 0,
+            sync: true,
+        });
+    }
+
+    let mut stts = Bytes::new(path(stbl, &[b"stts"])?);
+    stts.version()?;
+    let mut index: usize = 0;
+    let mut decode_time = 0;
+    // Runs are cut off at the last sample, their lengths aren't trusted
+    for _ in 0..stts.u32()? {
+        let (run, delta) = (stts.u32()? as usize, stts.u32()?);
+        let end = index.saturating_add(run).min(samples.len());
+        for sample in &mut samples[index..end] {
+            sample.decode_time = decode_time;
+            sample.duration = delta;
+            decode_time += delta as u64;
+        }
+        index = end;
+    }
+
+    if let Some(ctts) = child(stbl, b"ctts")? {
+        let mut ctts = Bytes::new(ctts);
+        ctts.version()?;
+        let mut index: usize = 0;
+        for _ in 0..ctts.u32()? {
+            let (run, offset) = (ctts.u32()? as usize, ctts.u32()? as i32);
+            let end = index.saturating_add(run).min(samples.len());
+            for sample in &mut samples[index..end] {
+                sample.composition_offset = offset;
+            }
+            index = end;
+        }
+    }
+
+    if let Some(stss) = child(stbl, b"stss")? {
+        let mut stss = Bytes::new(stss);
+        stss.version()?;
+        samples.iter_mut().for_each(|sample| sample.sync = false);
+        for _ in 0..stss.u32()? {
+            if let Some(sample) = samples.get_mut((stss.u32()? as usize).wrapping_sub(1)) {
+                sample.sync = true;
+            }
+        }
+    }
+
+    let mut chunk_offsets = vec![];
+    if let Some(stco) = child(stbl, b"stco")? {
+        let mut stco = Bytes::new(stco);
+        stco.version()?;
+        for _ in 0..stco.u32()? {
+            chunk_offsets.push(stco.u32()? as u64);
+        }
+    } else {
+        let mut co64 = Bytes::new(path(stbl, &[b"co64"])?);
+        co64.version()?;
+        for _ in 0..co64.u32()? {
+            chunk_offsets.push(co64.u64()?);
+        }
+    }
+    let mut stsc = Bytes::new(path(stbl, &[b"stsc"])?);
+    stsc.version()?;
+    let mut runs = vec![];
+    for _ in 0..stsc.u32()? {
+        runs.push((stsc.u32()? as usize, stsc.u32()? as usize));
+        stsc.u32()?;
+    }
+    let mut index: usize = 0;
+    for (chunk, offset) in chunk_offsets.iter().enumerate() {
+        let per_chunk = runs
+            .iter()
+            .rev()
+            .find(|(first, _)| *first <= chunk + 1)
+            .map_or(0, |(_, count)| *count);
+        let mut offset = *offset;
+        let end = index.saturating_add(per_chunk).min(samples.len());
+        for sample in &mut samples[index..end] {
+            sample.offset = offset;
+            offset += sample.size as u64;
+        }
+        index = end;
+    }
+    Ok(samples)
+}
+
+/// Samples of a `moof` at `moof_offset` of the input
+fn fragment_samples(
+    moof: &[u8],
+    moof_offset: u64,
+    track_defaults: &TrackDefaults,
+    samples: &mut Vec<Sample>,
+) -> Result<(), ParsingError> {
+    for (kind, traf, _) in boxes(moof)? {
+        if kind != *b"traf" {
+            continue;
+        }
+        let mut tfhd = Bytes::new(path(traf, &[b"tfhd"])?);
+        let (_, flags) = tfhd.version()?;
+        tfhd.u32()?;
+        let mut base = moof_offset;
+        if flags & TFHD_BASE_DATA_OFFSET != 0 {
+            base = tfhd.u64()?;
+        }
+        if flags & TFHD_SAMPLE_DESCRIPTION_INDEX != 0 {
+            tfhd.u32()?;
+        }
+        let mut default_duration = track_defaults.duration;
+        let mut default_size = track_defaults.size;
+        let mut default_flags = track_defaults.flags;
+        if flags & TFHD_DEFAULT_DURATION != 0 {
+            default_duration = tfhd.u32()?;
+        }
+        if flags & TFHD_DEFAULT_SIZE != 0 {
+            default_size = tfhd.u32()?;
+        }
+        if flags & TFHD_DEFAULT_FLAGS != 0 {
+            default_flags = tfhd.u32()?;
+        }
+        let mut decode_time = match child(traf, b"tfdt")? {
+            Some(tfdt) => {
+                let mut tfdt = Bytes::new(tfdt);
+                let (version, _) = tfdt.version()?;
+                tfdt.sized(version)?
+            }
+            None => samples
+                .last()
+                .map_or(0, |s| s.decode_time + s.duration as u64),
+        };
+
+        let mut position = base;
+        for (kind, trun, _) in boxes(traf)? {
+            if kind != *b"trun" {
+                continue;
+            }
+            let mut trun = Bytes::new(trun);
+            let (_, flags) = trun.version()?;
+            let count = trun.u32()?;
+            if flags & TRUN_DATA_OFFSET != 0 {
+                position = (base as i64 + trun.u32()? as i32 as i64) as u64;
+            }
+            let first_flags = match flags & TRUN_FIRST_SAMPLE_FLAGS {
+                0 => None,
+                _ => Some(trun.u32()?),
+            };
+            for i in 0..count {
+                let duration = match flags & TRUN_DURATION {
+                    0 => default_duration,
+                    _ => trun.u32()?,
+                };
+                let size = match flags & TRUN_SIZE {
+                    0 => default_size,
+                    _ => trun.u32()?,
+                };
+                let mut sample_flags = match flags & TRUN_FLAGS {
+                    0 => default_flags,
+                    _ => trun.u32()?,
+                };
+                if i == 0 {
+                    sample_flags = first_flags.unwrap_or(sample_flags);
+                }
+                let composition_offset = match flags & TRUN_COMPOSITION_OFFSET {
+                    0 => 0,
+                    _ => trun.u32()? as i32,
+                };
+                samples.push(Sample {
+                    offset: position,
+                    size,
+                    decode_time,
+                    duration,
+                    composition_offset,
+                    sync: sample_flags & SAMPLE_IS_NON_SYNC == 0,
+                });
+                position += size as u64;
+                decode_time += duration as u64;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads the first track with the handler, `vide` or `soun`
+fn read_track<R: Read + Seek>(input: &mut R, handler: &[u8; 4]) -> Result<Track, ParsingError> {
+    let end = input.seek(SeekFrom::End(0)).map_err(io_error)?;
+    let mut position = 0;
+    let mut moov = None;
+    let mut moofs = vec![];
+    while position + 8 <= end {
+        input.seek(SeekFrom::Start(position)).map_err(io_error)?;
+        let mut header = [0; 16];
+        input.read_exact(&mut header[..8]).map_err(io_error)?;
+        let kind = [header[4], header[5], header[6], header[7]];
+        let (size, header_size) =
+            match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+                0 => (end - position, 8),
+                1 => {
+                    input.read_exact(&mut header[8..]).map_err(io_error)?;
+                    let mut large = [0; 8];
+                    large.copy_from_slice(&header[8..]);
+                    (u64::from_be_bytes(large), 16)
+                }
+                size => (size as u64, 8),
+            };
+        if size < header_size || position + size > end {
+            return Err(ParsingError::from("Box extends past the end of the file"));
+        }
+        if kind == *b"moov" || kind == *b"moof" {
+            let mut payload = vec![0; (size - header_size) as usize];
+            input.read_exact(&mut payload).map_err(io_error)?;
+            if kind == *b"moov" {
+                moov = Some(payload);
+            } else {
+                moofs.push((position, payload));
+            }
+        }
+        position += size;
+    }
+    let moov = moov.ok_or("No moov box")?;
+
+    let mut mvhd = Bytes::new(path(&moov, &[b"mvhd"])?);
+    let (version, _) = mvhd.version()?;
+    mvhd.sized(version)?;
+    mvhd.sized(version)?;
+    let movie_timescale = mvhd.u32()?.max(1);
+
+    for (kind, trak, _) in boxes(&moov)? {
+        if kind != *b"trak" {
+            continue;
+        }
+        let mdia = path(trak, &[b"mdia"])?;
+        let mut hdlr = Bytes::new(path(mdia, &[b"hdlr"])?);
+        hdlr.version()?;
+        hdlr.u32()?;
+        if hdlr.array::<4>()? != *handler {
+            continue;
+        }
+
+        let mut tkhd = Bytes::new(path(trak, &[b"tkhd"])?);
+        let (version, _) = tkhd.version()?;
+        tkhd.sized(version)?;
+        tkhd.sized(version)?;
+        let track_id = tkhd.u32()?;
+        let tkhd = path(trak, &[b"tkhd"])?;
+        let size = |offset: usize| {
+            tkhd.get(tkhd.len().saturating_sub(offset)..)
+                .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let (width, height) = (size(8), size(4));
+
+        let mut mdhd = Bytes::new(path(mdia, &[b"mdhd"])?);
+        let (version, _) = mdhd.version()?;
+        mdhd.sized(version)?;
+        mdhd.sized(version)?;
+        let timescale = mdhd.u32()?;
+        mdhd.sized(version)?;
+        let language = mdhd.u16()?;
+
+        let stbl = path(mdia, &[b"minf", b"stbl"])?;
+        let stsd = boxes(stbl)?
+            .into_iter()
+            .find(|(kind, _, _)| kind == b"stsd")
+            .map(|(_, _, stsd)| stsd.to_vec())
+            .ok_or("No stsd box")?;
+
+        let mut edits = vec![];
+        if let Some(elst) = child(trak, b"edts")?
+            .map(|edts| child(edts, b"elst"))
+            .transpose()?
+        {
+            let mut elst = elst.map(Bytes::new).ok_or("No elst box")?;
+            let (version, _) = elst.version()?;
+            for _ in 0..elst.u32()? {
+                let duration = elst.sized(version)?;
+                let media_time = match version {
+                    1 => elst.u64()? as i64,
+                    _ => elst.u32()? as i32 as i64,
+                };
+                let rate = elst.u32()?;
+                let duration = duration * MOVIE_TIMESCALE as u64 / movie_timescale as u64;
+                edits.push((duration, media_time, rate));
+            }
+        }
+
+        let mut samples = vec![];
+        if moofs.is_empty() {
+            samples = table_samples(stbl, end)?;
+        } else {
+            let mut defaults = TrackDefaults {
+                duration: 0,
+                size: 0,
+                flags: 0,
+            };
+            let trexs = child(&moov, b"mvex")?
+                .map(boxes)
+                .transpose()?
+                .unwrap_or_default();
+            for (kind, trex, _) in trexs {
+                let mut trex = Bytes::new(trex);
+                trex.version()?;
+                if kind == *b"trex" && trex.u32()? == track_id {
+                    trex.u32()?;
+                    defaults = TrackDefaults {
+                        duration: trex.u32()?,
+                        size: trex.u32()?,
+                        flags: trex.u32()?,
+                    };
+                }
+            }
+            for (offset, moof) in &moofs {
+                // Fragments of other tracks are skipped, ones mixing tracks aren't supported
+                let tracks: Vec<u32> = boxes(moof)?
+                    .into_iter()
+                    .filter(|(kind, _, _)| kind == b"traf")
+                    .filter_map(|(_, traf, _)| {
+                        let mut tfhd = Bytes::new(path(traf, &[b"tfhd"]).ok()?);
+                        tfhd.version().ok()?;
+                        tfhd.u32().ok()
+                    })
+                    .collect();
+                if !tracks.contains(&track_id) {
+                    continue;
+                }
+                if tracks.iter().any(|id| *id != track_id) {
+                    return Err(ParsingError::from("Fragments with several tracks"));
+                }
+                fragment_samples(moof, *offset, &defaults, &mut samples)?;
+            }
+        }
+        return Ok(Track {
+            handler: *handler,
+            timescale,
+            language,
+            width,
+            height,
+            stsd,
+            edits,
+            samples,
+        });
+    }
+    Err(ParsingError::from(format!(
+        "No {} track",
+        String::from_utf8_lossy(handler)
+    )))
+}
+
+fn data_atom(kind: &[u8; 4], data_type: u32, value: &[u8]) -> Vec<u8> {
+    let mut payload = data_type.to_be_bytes().to_vec();
+    payload.extend_from_slice(&0u32.to_be_bytes());
+    payload.extend_from_slice(value);
+    atom(kind, &atom(b"data", &payload))
+}
+
+/// iTunes style `ilst` with the metadata
+pub(crate) fn ilst(metadata: &Metadata) -> Vec<u8> {
+    const UTF8: u32 = 1;
+    let mut items = vec![];
+    let texts = [
+        (b"\xa9nam", metadata.title.clone()),
+        (b"\xa9ART", metadata.artist.clone()),
+        (
+            b"\xa9day",
+            metadata.date.map(|d| d.format("%Y-%m-%d").to_string()),
+        ),
+        (b"desc", metadata.description.clone()),
+        (b"ldes", metadata.description.clone()),
+    ];
+    for (kind, text) in texts.iter() {
+        if let Some(text) = text {
+            items.push(data_atom(kind, UTF8, text.as_bytes()));
+        }
+    }
+    if let Some(cover) = &metadata.cover {
+        match cover.format() {
+            Some(ImageFormat::Jpeg) => items.push(data_atom(b"covr", 13, &cover.data)),
+            Some(ImageFormat::Png) => items.push(data_atom(b"covr", 14, &cover.data)),
+            _ => tracing::warn!("skipping cover that is neither JPEG nor PNG"),
+        }
+    }
+    container(b"ilst", &items)
+}
+
+/// Nero style chapter list, which most players read
+fn chpl(metadata: &Metadata) -> Option<Vec<u8>> {
+    if metadata.chapters.is_empty() {
+        return None;
+    }
+    // Reserved
+    let mut payload = 0u32.to_be_bytes().to_vec();
+    payload.push(metadata.chapters.len().min(255) as u8);
+    for chapter in metadata.chapters.iter().take(255) {
+        // In 100 ns units
+        payload.extend_from_slice(&(chapter.start * 10_000_000).to_be_bytes());
+        let title = truncate(&chapter.title, 255);
+        payload.push(title.len() as u8);
+        payload.extend_from_slice(title.as_bytes());
+    }
+    Some(full_atom(b"chpl", 1, 0, &payload))
+}
+
+/// Longest prefix of at most `length` bytes that ends at a char boundary
+pub(crate) fn truncate(text: &str, length: usize) -> &str {
+    let end = (0..=length.min(text.len()))
+        .rev()
+        .find(|end| text.is_char_boundary(*end))
+        .unwrap_or(0);
+    &text[..end]
+}
+
+/// `udta` with the chapters and a `meta` box holding the `ilst`
+pub(crate) fn udta(metadata: &Metadata) -> Vec<u8> {
+    let mut hdlr = 0u32.to_be_bytes().to_vec();
+    hdlr.extend_from_slice(b"mdirappl");
+    hdlr.extend_from_slice(&[0; 9]);
+    let mut meta = 0u32.to_be_bytes().to_vec();
+    meta.extend(full_atom(b"hdlr", 0, 0, &hdlr));
+    meta.extend(ilst(metadata));
+    let mut children: Vec<Vec<u8>> = chpl(metadata).into_iter().collect();
+    children.push(atom(b"meta", &meta));
+    container(b"udta", &children)
+}
+
+fn mvhd(duration: u64, next_track_id: u32) -> Vec<u8> {
+    let mut payload = vec![];
+    payload.extend_from_slice(&[0; 16]);
+    payload.extend_from_slice(&MOVIE_TIMESCALE.to_be_bytes());
+    payload.extend_from_slice(&duration.to_be_bytes());
+    payload.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+    payload.extend_from_slice(&0x0100u16.to_be_bytes());
+    payload.extend_from_slice(&[0; 10]);
+    payload.extend_from_slice(&matrix());
+    payload.extend_from_slice(&[0; 24]);
+    payload.extend_from_slice(&next_track_id.to_be_bytes());
+    full_atom(b"mvhd", 1, 0, &payload)
+}
+
+fn matrix() -> Vec<u8> {
+    [0x0001_0000u32, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000]
+        .iter()
+        .flat_map(|v| v.to_be_bytes())
+        .collect()
+}
+
+fn tkhd(track: &Track, track_id: u32, duration: u64) -> Vec<u8> {
+    let audio = track.handler == *b"soun";
+    let mut payload = vec![];
+    payload.extend_from_slice(&[0; 16]);
+    payload.extend_from_slice(&track_id.to_be_bytes());
+    payload.extend_from_slice(&[0; 4]);
+    payload.extend_from_slice(&duration.to_be_bytes());
+    payload.extend_from_slice(&[0; 8]);
+    payload.extend_from_slice(&0u16.to_be_bytes());
+    // Alternate group
+    payload.extend_from_slice(&(audio as u16).to_be_bytes());
+    payload.extend_from_slice(&(if audio { 0x0100u16 } else { 0 }).to_be_bytes());
+    payload.extend_from_slice(&[0; 2]);
+    payload.extend_from_slice(&matrix());
+    payload.extend_from_slice(&track.width.to_be_bytes());
+    payload.extend_from_slice(&track.height.to_be_bytes());
+    // Enabled and in movie
+    full_atom(b"tkhd", 1, 0x3, &payload)
+}
+
+fn edts(track: &Track, layout: Layout) -> Option<Vec<u8>> {
+    let mut edits = track.edits.clone();
+    let start = track.samples.first().map_or(0, |s| s.decode_time);
+    if edits.is_empty() && start > 0 && layout == Layout::Progressive {
+        // Keep the track's delay, progressive sample tables always start at 0
+        let delay = start * MOVIE_TIMESCALE as u64 / track.timescale.max(1) as u64;
+        edits = vec![
+            (delay, -1, 0x0001_0000),
+            (track.movie_duration(), 0, 0x0001_0000),
+        ];
+    }
+    if edits.is_empty() {
+        return None;
+    }
+    let mut elst = (edits.len() as u32).to_be_bytes().to_vec();
+    for (duration, media_time, rate) in edits {
+        elst.extend_from_slice(&duration.to_be_bytes());
+        elst.extend_from_slice(&media_time.to_be_bytes());
+        elst.extend_from_slice(&rate.to_be_bytes());
+    }
+    Some(container(b"edts", &[full_atom(b"elst", 1, 0, &elst)]))
+}
+
+fn run_length<T: PartialEq + Copy>(values: impl Iterator<Item = T>) -> Vec<(u32, T)> {
+    let mut runs: Vec<(u32, T)> = vec![];
+    for value in values {
+        match runs.last_mut() {
+            Some((count, last)) if *last == value => *count += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    runs
+}
+
+/// Sample tables of a progressive track, `chunk_offsets` are the chunks' positions in the output
+fn stbl(track: &Track, chunks: &[&[Sample]], chunk_offsets: &[u64], co64: bool) -> Vec<u8> {
+    let samples = &track.samples;
+    let mut boxes = vec![track.stsd.clone()];
+
+    let durations = run_length(samples.iter().map(|s| s.duration));
+    let mut stts = (durations.len() as u32).to_be_bytes().to_vec();
+    for (count, duration) in durations {
+        stts.extend_from_slice(&count.to_be_bytes());
+        stts.extend_from_slice(&duration.to_be_bytes());
+    }
+    boxes.push(full_atom(b"stts", 0, 0, &stts));
+
+    if samples.iter().any(|s| s.composition_offset != 0) {
+        let offsets = run_length(samples.iter().map(|s| s.composition_offset));
+        let mut ctts = (offsets.len() as u32).to_be_bytes().to_vec();
+        for (count, offset) in offsets {
+            ctts.extend_from_slice(&count.to_be_bytes());
+            ctts.extend_from_slice(&offset.to_be_bytes());
+        }
+        let version = samples.iter().any(|s| s.composition_offset < 0) as u8;
+        boxes.push(full_atom(b"ctts", version, 0, &ctts));
+    }
+
+    if samples.iter().any(|s| !s.sync) {
+        let sync: Vec<u32> = (1..=samples.len() as u32)
+            .filter(|i| samples[*i as usize - 1].sync)
+            .collect();
+        let mut stss = (sync.len() as u32).to_be_bytes().to_vec();
+        sync.iter()
+            .for_each(|i| stss.extend_from_slice(&i.to_be_bytes()));
+        boxes.push(full_atom(b"stss", 0, 0, &stss));
+    }
+
+    let runs = run_length(chunks.iter().map(|chunk| chunk.len() as u32));
+    let mut stsc = (runs.len() as u32).to_be_bytes().to_vec();
+    let mut first_chunk = 1u32;
+    for (count, per_chunk) in runs {
+        stsc.extend_from_slice(&first_chunk.to_be_bytes());
+        stsc.extend_from_slice(&per_chunk.to_be_bytes());
+        stsc.extend_from_slice(&1u32.to_be_bytes());
+        first_chunk += count;
+    }
+    boxes.push(full_atom(b"stsc", 0, 0, &stsc));
+
+    let mut stsz = 0u32.to_be_bytes().to_vec();
+    stsz.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+    samples
+        .iter()
+        .for_each(|s| stsz.extend_from_slice(&s.size.to_be_bytes()));
+    boxes.push(full_atom(b"stsz", 0, 0, &stsz));
+
+    let mut offsets = (chunk_offsets.len() as u32).to_be_bytes().to_vec();
+    for offset in chunk_offsets {
+        match co64 {
+            true => offsets.extend_from_slice(&offset.to_be_bytes()),
+            false => offsets.extend_from_slice(&(*offset as u32).to_be_bytes()),
+        }
+    }
+    boxes.push(full_atom(
+        if co64 { b"co64" } else { b"stco" },
+        0,
+        0,
+        &offsets,
+    ));
+    container(b"stbl", &boxes)
+}
+
+/// Empty sample tables, the samples of fragmented files are described by the fragments
+fn empty_stbl(track: &Track) -> Vec<u8> {
+    let zero = 0u32.to_be_bytes();
+    container(
+        b"stbl",
+        &[
+            track.stsd.clone(),
+            full_atom(b"stts", 0, 0, &zero),
+            full_atom(b"stsc", 0, 0, &zero),
+            full_atom(b"stsz", 0, 0, &[0; 8]),
+            full_atom(b"stco", 0, 0, &zero),
+        ],
+    )
+}
+
+fn trak(track: &Track, track_id: u32, layout: Layout, stbl: Vec<u8>) -> Vec<u8> {
+    let video = track.handler == *b"vide";
+    let mut mdhd = vec![0; 16];
+    mdhd.extend_from_slice(&track.timescale.to_be_bytes());
+    mdhd.extend_from_slice(&track.duration().to_be_bytes());
+    mdhd.extend_from_slice(&track.language.to_be_bytes());
+    mdhd.extend_from_slice(&[0; 2]);
+
+    let mut hdlr = 0u32.to_be_bytes().to_vec();
+    hdlr.extend_from_slice(&track.handler);
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.extend_from_slice(if video {
+        b"VideoHandler\0"
+    } else {
+        b"SoundHandler\0"
+    });
+
+    let media_header = match video {
+        true => full_atom(b"vmhd", 0, 1, &[0; 8]),
+        false => full_atom(b"smhd", 0, 0, &[0; 4]),
+    };
+    let mut dref = 1u32.to_be_bytes().to_vec();
+    // Data in the same file
+    dref.extend(full_atom(b"url ", 0, 1, &[]));
+    let minf = container(
+        b"minf",
+        &[
+            media_header,
+            container(b"dinf", &[full_atom(b"dref", 0, 0, &dref)]),
+            stbl,
+        ],
+    );
+    let mdia = container(
+        b"mdia",
+        &[
+            full_atom(b"mdhd", 1, 0, &mdhd),
+            full_atom(b"hdlr", 0, 0, &hdlr),
+            minf,
+        ],
+    );
+    let mut children = vec![tkhd(track, track_id, track.movie_duration())];
+    children.extend(edts(track, layout));
+    children.push(mdia);
+    container(b"trak", &children)
+}
+
+fn ftyp(tracks: &[Track], layout: Layout) -> Vec<u8> {
+    let audio_only = tracks.iter().all(|track| track.handler == *b"soun");
+    let major_brand = if audio_only { *b"M4A " } else { *b"isom" };
+    let mut brands = vec![major_brand, *b"isom", *b"iso2", *b"mp41"];
+    if layout == Layout::Fragmented {
+        brands.push(*b"iso6");
+    }
+    for track in tracks {
+        let stsd = track.stsd.get(16..20).unwrap_or_default();
+        if stsd == b"avc1" || stsd == b"av01" {
+            brands.push([stsd[0], stsd[1], stsd[2], stsd[3]]);
+        }
+    }
+    brands.dedup();
+    let mut payload = major_brand.to_vec();
+    payload.extend_from_slice(&0x200u32.to_be_bytes());
+    brands
+        .iter()
+        .for_each(|brand| payload.extend_from_slice(brand));
+    atom(b"ftyp", &payload)
+}
+
+trait Input: Read + Seek {}
+
+impl<T: Read + Seek> Input for T {}
+
+/// Copies the samples from the input, reading runs of adjacent samples at once
+fn copy_samples<W: Write>(
+    input: &mut dyn Input,
+    samples: &[Sample],
+    output: &mut W,
+) -> Result<(), ParsingError> {
+    let mut i = 0;
+    while i < samples.len() {
+        let start = samples[i].offset;
+        let mut end = start + samples[i].size as u64;
+        i += 1;
+        while i < samples.len() && samples[i].offset == end {
+            end += samples[i].size as u64;
+            i += 1;
+        }
+        input.seek(SeekFrom::Start(start)).map_err(io_error)?;
+        let copied =
+            std::io::copy(&mut (&mut *input).take(end - start), output).map_err(io_error)?;
+        if copied != end - start {
+            return Err(ParsingError::from("Sample data past the end of the input"));
+        }
+    }
+    Ok(())
+}
+
+/// Chunks of all tracks, ordered by their start time
+fn interleave(tracks: &[Track]) -> Vec<(usize, &[Sample])> {
+    let mut chunks: Vec<(usize, &[Sample])> = tracks
+        .iter()
+        .enumerate()
+        .flat_map(|(i, track)| track.chunks().into_iter().map(move |chunk| (i, chunk)))
+        .collect();
+    chunks.sort_by(|(a, a_chunk), (b, b_chunk)| {
+        let start = |track: usize, chunk: &[Sample]| {
+            chunk[0].decode_time as f64 / tracks[track].timescale.max(1) as f64
+        };
+        start(*a, a_chunk)
+            .partial_cmp(&start(*b, b_chunk))
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    chunks
+}
+
+fn write_progressive<W: Write>(
+    inputs: &mut [&mut dyn Input],
+    tracks: &[Track],
+    metadata: &Metadata,
+    output: &mut W,
+) -> Result<(), ParsingError> {
+    let ftyp = ftyp(tracks, Layout::Progressive);
+    let chunks = interleave(tracks);
+    let data_size: u64 = chunks
+        .iter()
+        .flat_map(|(_, chunk)| chunk.iter())
+        .map(|s| s.size as u64)
+        .sum();
+    let mdat_header: u64 = if data_size + 8 > u32::MAX as u64 {
+        16
+    } else {
+        8
+    };
+
+    let moov = |data_start: u64, co64: bool| {
+        let mut offsets = vec![vec![]; tracks.len()];
+        let mut position = data_start;
+        for (track, chunk) in &chunks {
+            offsets[*track].push(position);
+            position += chunk.iter().map(|s| s.size as u64).sum::<u64>();
+        }
+        let duration = tracks.iter().map(Track::movie_duration).max().unwrap_or(0);
+        let mut children = vec![mvhd(duration, tracks.len() as u32 + 1)];
+        for (i, track) in tracks.iter().enumerate() {
+            let track_chunks: Vec<&[Sample]> = chunks
+                .iter()
+                .filter(|(t, _)| *t == i)
+                .map(|(_, chunk)| *chunk)
+                .collect();
+            let stbl = stbl(track, &track_chunks, &offsets[i], co64);
+            children.push(trak(track, i as u32 + 1, Layout::Progressive, stbl));
+        }
+        children.push(udta(metadata));
+        container(b"moov", &children)
+    };
+    // Chunk offset boxes have fixed sizes, so a first pass gives the size of the moov
+    let co64 = {
+        let moov_size = moov(0, true).len() as u64;
+        ftyp.len() as u64 + moov_size + mdat_header + data_size > u32::MAX as u64
+    };
+    let moov_size = moov(0, co64).len() as u64;
+    let moov = moov(ftyp.len() as u64 + moov_size + mdat_header, co64);
+
+    output.write_all(&ftyp).map_err(io_error)?;
+    output.write_all(&moov).map_err(io_error)?;
+    match mdat_header {
+        16 => {
+            output.write_all(&1u32.to_be_bytes()).map_err(io_error)?;
+            output.write_all(b"mdat").map_err(io_error)?;
+            output
+                .write_all(&(data_size + 16).to_be_bytes())
+                .map_err(io_error)?;
+        }
+        _ => {
+            output
+                .write_all(&(data_size as u32 + 8).to_be_bytes())
+                .map_err(io_error)?;
+            output.write_all(b"mdat").map_err(io_error)?;
+        }
+    }
+    for (track, chunk) in chunks {
+        copy_samples(inputs[track], chunk, output)?;
+    }
+    Ok(())
+}
+
+fn moof(sequence: u32, track_id: u32, samples: &[Sample]) -> Vec<u8> {
+    let signed = samples.iter().any(|s| s.composition_offset < 0);
+    let traf = |data_offset: u32| {
+        let tfhd = full_atom(
+            b"tfhd",
+            0,
+            TFHD_DEFAULT_BASE_IS_MOOF,
+            &track_id.to_be_bytes(),
+        );
+        let tfdt = full_atom(b"tfdt", 1, 0, &samples[0].decode_time.to_be_bytes());
+        let mut trun = (samples.len() as u32).to_be_bytes().to_vec();
+        trun.extend_from_slice(&data_offset.to_be_bytes());
+        for sample in samples {
+            let flags = match sample.sync {
+                true => SYNC_SAMPLE_FLAGS,
+                false => NON_SYNC_SAMPLE_FLAGS,
+            };
+            trun.extend_from_slice(&sample.duration.to_be_bytes());
+            trun.extend_from_slice(&sample.size.to_be_bytes());
+            trun.extend_from_slice(&flags.to_be_bytes());
+            trun.extend_from_slice(&sample.composition_offset.to_be_bytes());
+        }
+        let trun_flags =
+            TRUN_DATA_OFFSET | TRUN_DURATION | TRUN_SIZE | TRUN_FLAGS | TRUN_COMPOSITION_OFFSET;
+        let trun = full_atom(b"trun", signed as u8, trun_flags, &trun);
+        container(b"traf", &[tfhd, tfdt, trun])
+    };
+    let mfhd = full_atom(b"mfhd", 0, 0, &sequence.to_be_bytes());
+    // Data starts behind the moof and the mdat header, the moof's size doesn't depend on it
+    let size = container(b"moof", &[mfhd.clone(), traf(0)]).len() as u32;
+    container(b"moof", &[mfhd, traf(size + 8)])
+}
+
+fn write_fragmented<W: Write>(
+    inputs: &mut [&mut dyn Input],
+    tracks: &[Track],
+    metadata: &Metadata,
+    output: &mut W,
+) -> Result<(), ParsingError> {
+    let duration = tracks.iter().map(Track::movie_duration).max().unwrap_or(0);
+    let mut children = vec![mvhd(duration, tracks.len() as u32 + 1)];
+    let mut mvex = vec![full_atom(b"mehd", 1, 0, &duration.to_be_bytes())];
+    for (i, track) in tracks.iter().enumerate() {
+        let track_id = i as u32 + 1;
+        children.push(trak(track, track_id, Layout::Fragmented, empty_stbl(track)));
+        let mut trex = track_id.to_be_bytes().to_vec();
+        trex.extend_from_slice(&1u32.to_be_bytes());
+        trex.extend_from_slice(&[0; 12]);
+        mvex.push(full_atom(b"trex", 0, 0, &trex));
+    }
+    children.push(container(b"mvex", &mvex));
+    children.push(udta(metadata));
+    output
+        .write_all(&ftyp(tracks, Layout::Fragmented))
+        .map_err(io_error)?;
+    output
+        .write_all(&container(b"moov", &children))
+        .map_err(io_error)?;
+
+    for (sequence, (track, chunk)) in interleave(tracks).into_iter().enumerate() {
+        output
+            .write_all(&moof(sequence as u32 + 1, track as u32 + 1, chunk))
+            .map_err(io_error)?;
+        let size: u64 = chunk.iter().map(|s| s.size as u64).sum();
+        output
+            .write_all(&(size as u32 + 8).to_be_bytes())
+            .map_err(io_error)?;
+        output.write_all(b"mdat").map_err(io_error)?;
+        copy_samples(inputs[track], chunk, output)?;
+    }
+    Ok(())
+}
+
+/// Writes an MP4 file with the video track of `video` and the audio track of `audio`
+pub fn mux<V: Read + Seek, A: Read + Seek, W: Write>(
+    mut video: V,
+    mut audio: A,
+    output: W,
+    layout: Layout,
+    metadata: &Metadata,
+) -> Result<(), ParsingError> {
+    let tracks = [
+        read_track(&mut video, b"vide")?,
+        read_track(&mut audio, b"soun")?,
+    ];
+    write(
+        &mut [&mut video, &mut audio],
+        &tracks,
+        layout,
+        metadata,
+        output,
+    )
+}
+
+/// Writes the audio track of `input` as a progressive `.m4a` with the metadata
+pub(crate) fn remux_audio<R: Read + Seek, W: Write>(
+    mut input: R,
+    output: W,
+    metadata: &Metadata,
+) -> Result<(), ParsingError> {
+    let tracks = [read_track(&mut input, b"soun")?];
+    write(
+        &mut [&mut input],
+        &tracks,
+        Layout::Progressive,
+        metadata,
+        output,
+    )
+}
+
+fn write<W: Write>(
+    inputs: &mut [&mut dyn Input],
+    tracks: &[Track],
+    layout: Layout,
+    metadata: &Metadata,
+    output: W,
+) -> Result<(), ParsingError> {
+    let mut output = std::io::BufWriter::new(output);
+    match layout {
+        Layout::Progressive => write_progressive(inputs, tracks, metadata, &mut output)?,
+        Layout::Fragmented => write_fragmented(inputs, tracks, metadata, &mut output)?,
+    }
+    output.flush().map_err(io_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mux::Chapter;
+    use std::io::Cursor;
+
+    /// One avc1 track of 6 samples at 12800 Hz with B-frame offsets, syncs at 1 and 4 and an
+    /// edit list skipping 1024 units
+    const VIDEO_PROGRESSIVE: &[u8] = include_bytes!("fixtures/video_progressive.mp4");
+    /// The same samples in two fragments
+    const VIDEO_FRAGMENTED: &[u8] = include_bytes!("fixtures/video_fragmented.mp4");
+    /// One mp4a track of 8 samples of 10 bytes at 44100 Hz, with a uniform `stsz`
+    const AUDIO_PROGRESSIVE: &[u8] = include_bytes!("fixtures/audio_progressive.m4a");
+    /// The same samples in two fragments starting at 2048, sizes and durations from defaults
+    const AUDIO_FRAGMENTED: &[u8] = include_bytes!("fixtures/audio_fragmented.m4a");
+
+    /// Sample `i` of the fixtures is its size in bytes of `fill + i`
+    const VIDEO_FILL: u8 = 0x10;
+    const AUDIO_FILL: u8 = 0xa0;
+
+    fn track(file: &[u8], handler: &[u8; 4]) -> Track {
+        read_track(&mut Cursor::new(file), handler).unwrap()
+    }
+
+    /// Everything but the position and the start of the samples
+    fn table(track: &Track) -> Vec<(u32, u32, i32, bool)> {
+        track
+            .samples
+            .iter()
+            .map(|s| (s.size, s.duration, s.composition_offset, s.sync))
+            .collect()
+    }
+
+    fn check_data(file: &[u8], track: &Track, fill: u8) {
+        for (i, sample) in track.samples.iter().enumerate() {
+            let start = sample.offset as usize;
+            let data = &file[start..start + sample.size as usize];
+            assert!(data.iter().all(|b| *b == fill + i as u8), "sample {}", i);
+        }
+    }
+
+    fn metadata() -> Metadata {
+        Metadata {
+            title: Some("Mock title".to_string()),
+            chapters: vec![
+                Chapter {
+                    start: 0,
+                    end: Some(1),
+                    title: "Intro".to_string(),
+                },
+                Chapter {
+                    start: 1,
+                    end: None,
+                    title: "Main".to_string(),
+                },
+            ],
+            ..Metadata::default()
+        }
+    }
+
+    fn check_udta(file: &[u8]) {
+        let moov = path(file, &[b"moov"]).unwrap();
+        let mut chpl = Bytes::new(path(moov, &[b"udta", b"chpl"]).unwrap());
+        chpl.version().unwrap();
+        chpl.u32().unwrap();
+        let mut chapters = vec![];
+        for _ in 0..chpl.take(1).unwrap()[0] {
+            let start = chpl.u64().unwrap();
+            let length = chpl.take(1).unwrap()[0] as usize;
+            let title = String::from_utf8(chpl.take(length).unwrap().to_vec()).unwrap();
+            chapters.push((start, title));
+        }
+        assert_eq!(
+            chapters,
+            vec![(0, "Intro".to_string()), (10_000_000, "Main".to_string())]
+        );
+
+        let meta = path(moov, &[b"udta", b"meta"]).unwrap();
+        let name = path(&meta[4..], &[b"ilst", b"\xa9nam", b"data"]).unwrap();
+        assert_eq!(&name[8..], b"Mock title");
+    }
+
+    #[test]
+    fn fixtures_describe_the_same_samples() {
+        let video = track(VIDEO_PROGRESSIVE, b"vide");
+        assert_eq!(table(&video), table(&track(VIDEO_FRAGMENTED, b"vide")));
+        assert_eq!(
+            video.samples.iter().map(|s| s.sync).collect::<Vec<_>>(),
+            vec![true, false, false, true, false, false]
+        );
+        assert_eq!(video.edits, vec![(240, 1024, 0x0001_0000)]);
+        check_data(VIDEO_PROGRESSIVE, &video, VIDEO_FILL);
+        check_data(
+            VIDEO_FRAGMENTED,
+            &track(VIDEO_FRAGMENTED, b"vide"),
+            VIDEO_FILL,
+        );
+
+        let audio = track(AUDIO_FRAGMENTED, b"soun");
+        assert_eq!(table(&track(AUDIO_PROGRESSIVE, b"soun")), table(&audio));
+        assert_eq!(audio.samples[0].decode_time, 2048);
+        assert!(audio.edits.is_empty());
+        check_data(AUDIO_FRAGMENTED, &audio, AUDIO_FILL);
+    }
+
+    #[test]
+    fn round_trips_every_input_and_layout() {
+        let inputs = [
+            (VIDEO_PROGRESSIVE, AUDIO_PROGRESSIVE),
+            (VIDEO_FRAGMENTED, AUDIO_FRAGMENTED),
+        ];
+        for (video_file, audio_file) in inputs {
+            for layout in [Layout::Progressive, Layout::Fragmented] {
+                let mut output = vec![];
+                mux(
+                    Cursor::new(video_file),
+                    Cursor::new(audio_file),
+                    &mut output,
+                    layout,
+                    &metadata(),
+                )
+                .unwrap();
+
+                let (video_in, audio_in) = (track(video_file, b"vide"), track(audio_file, b"soun"));
+                let (video, audio) = (track(&output, b"vide"), track(&output, b"soun"));
+                assert_eq!(table(&video), table(&video_in), "{:?}", layout);
+                assert_eq!(table(&audio), table(&audio_in), "{:?}", layout);
+                assert_eq!(video.stsd, video_in.stsd);
+                assert_eq!(audio.stsd, audio_in.stsd);
+                check_data(&output, &video, VIDEO_FILL);
+                check_data(&output, &audio, AUDIO_FILL);
+                check_udta(&output);
+
+                // Edit lists are copied, a fragmented input's start becomes one in progressive
+                // files and stays the fragments' decode time otherwise
+                assert_eq!(video.edits, video_in.edits);
+                let audio_start = audio_in.samples[0].decode_time;
+                match (layout, audio_start) {
+                    (_, 0) => assert!(audio.edits.is_empty()),
+                    (Layout::Progressive, start) => {
+                        assert_eq!(audio.samples[0].decode_time, 0);
+                        assert_eq!(
+                            audio.edits,
+                            vec![
+                                (start * 1000 / 44100, -1, 0x0001_0000),
+                                (audio.movie_duration(), 0, 0x0001_0000)
+                            ]
+                        );
+                    }
+                    (Layout::Fragmented, start) => {
+                        assert!(audio.edits.is_empty());
+                        assert_eq!(audio.samples[0].decode_time, start);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_sample_counts_past_the_data() {
+        // A uniform size of 1 and 2^32 - 1 samples in a tiny file
+        let stsz = full_atom(b"stsz", 0, 0, &[0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff]);
+        assert!(table_samples(&stsz, 100).is_err());
+        // Per-sample sizes that the box doesn't hold
+        let stsz = full_atom(b"stsz", 0, 0, &[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]);
+        assert!(table_samples(&stsz, 100).is_err());
+
+        // Runs far longer than the samples
+        let huge_run = [0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1];
+        let stbl = [
+            full_atom(b"stsz", 0, 0, &[0, 0, 0, 1, 0, 0, 0, 2]),
+            full_atom(b"stts", 0, 0, &huge_run),
+            full_atom(b"ctts", 0, 0, &huge_run),
+            full_atom(
+                b"stsc",
+                0,
+                0,
+                &[0, 0, 0, 1, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1],
+            ),
+            full_atom(b"stco", 0, 0, &[0, 0, 0, 1, 0, 0, 0, 8]),
+        ]
+        .concat();
+        let samples = table_samples(&stbl, 100).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].decode_time, 1);
+        assert_eq!(samples[1].offset, 9);
+    }
+}
@@ -0,0 +1,970 @@
+//! Tagging of downloaded audio streams
+//!
+//! MP3 files get an ID3v2.4 tag, M4A files are rewritten as progressive MP4 with iTunes atoms and
+//! Opus gets Vorbis comments. WebM audio, as `audio_streams()` serves Opus, is moved into an Ogg
+//! container as WebM can't hold a cover; the result is a `.opus` file.
+
+use super::matroska::AudioFrames;
+use super::mp4;
+use super::{io_error, Cover, Metadata};
+use crate::youtube_extractor::error::ParsingError;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioFormat {
+    Mp3,
+    M4a,
+    Opus,
+}
+
+impl AudioFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::M4a => "m4a",
+            AudioFormat::Opus => "opus",
+        }
+    }
+}
+
+/// Copies `input` to `output` with the tags of the metadata, replacing earlier ones. Returns the
+/// format of the output, which differs from the input's for WebM.
+pub fn tag<R: Read + Seek, W: Write>(
+    mut input: R,
+    output: W,
+    metadata: &Metadata,
+) -> Result<AudioFormat, ParsingError> {
+    let mut header = [0; 8];
+    input.read_exact(&mut header).map_err(io_error)?;
+    input.seek(SeekFrom::Start(0)).map_err(io_error)?;
+    let mut output = std::io::BufWriter::new(output);
+    let format = if &header[4..8] == b"ftyp" {
+        mp4::remux_audio(input, &mut output, metadata)?;
+        AudioFormat::M4a
+    } else if &header[..4] == b"OggS" {
+        retag_ogg(input, &mut output, metadata)?;
+        AudioFormat::Opus
+    } else if header[..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+        webm_to_ogg(input, &mut output, metadata)?;
+        AudioFormat::Opus
+    } else if &header[..3] == b"ID3" || (header[0] == 0xff && header[1] & 0xe0 == 0xe0) {
+        retag_mp3(input, &mut output, metadata)?;
+        AudioFormat::Mp3
+    } else {
+        return Err(ParsingError::from("Unknown audio format"));
+    };
+    output.flush().map_err(io_error)?;
+    Ok(format)
+}
+
+fn date(metadata: &Metadata) -> Option<String> {
+    metadata.date.map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+fn syncsafe(value: u32) -> [u8; 4] {
+    [
+        (value >> 21) as u8 & 0x7f,
+        (value >> 14) as u8 & 0x7f,
+        (value >> 7) as u8 & 0x7f,
+        value as u8 & 0x7f,
+    ]
+}
+
+fn id3_frame(id: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut frame = id.to_vec();
+    frame.extend_from_slice(&syncsafe(payload.len() as u32));
+    frame.extend_from_slice(&[0, 0]);
+    frame.extend_from_slice(payload);
+    frame
+}
+
+const ID3_UTF8: u8 = 3;
+
+/// Chapter times are 32 bit milliseconds, later ones are clamped to the largest
+fn id3_milliseconds(seconds: u64) -> u32 {
+    seconds.saturating_mul(1000).min(u32::MAX as u64) as u32
+}
+
+fn id3_text(id: &[u8; 4], text: &str) -> Vec<u8> {
+    let mut payload = vec![ID3_UTF8];
+    payload.extend_from_slice(text.as_bytes());
+    id3_frame(id, &payload)
+}
+
+fn id3(metadata: &Metadata) -> Vec<u8> {
+    let mut frames = vec![];
+    let texts = [
+        (b"TIT2", metadata.title.clone()),
+        (b"TPE1", metadata.artist.clone()),
+        (b"TDRC", date(metadata)),
+    ];
+    for (id, text) in texts.iter() {
+        if let Some(text) = text {
+            frames.extend(id3_text(id, text));
+        }
+    }
+    if let Some(description) = &metadata.description {
+        // Language and an empty content descriptor
+        let mut comment = vec![ID3_UTF8];
+        comment.extend_from_slice(b"eng\0");
+        comment.extend_from_slice(description.as_bytes());
+        frames.extend(id3_frame(b"COMM", &comment));
+    }
+    if let Some(cover) = &metadata.cover {
+        if let Some(format) = cover.format() {
+            let mut picture = vec![ID3_UTF8];
+            picture.extend_from_slice(format.mime_type().as_bytes());
+            // Front cover with an empty description
+            picture.extend_from_slice(&[0, 3, 0]);
+            picture.extend_from_slice(&cover.data);
+            frames.extend(id3_frame(b"APIC", &picture));
+        }
+    }
+    let chapters: Vec<_> = metadata.chapters.iter().take(255).collect();
+    if !chapters.is_empty() {
+        let mut toc = b"toc\0".to_vec();
+        // Top level and ordered
+        toc.push(0x03);
+        toc.push(chapters.len() as u8);
+        for (i, chapter) in chapters.iter().enumerate() {
+            let element_id = format!("chp{}\0", i);
+            toc.extend_from_slice(element_id.as_bytes());
+            let mut chap = element_id.into_bytes();
+            let start = id3_milliseconds(chapter.start);
+            let end = chapter.end.map_or(start, id3_milliseconds);
+            chap.extend_from_slice(&start.to_be_bytes());
+            chap.extend_from_slice(&end.to_be_bytes());
+            // No byte offsets
+            chap.extend_from_slice(&[0xff; 8]);
+            chap.extend(id3_text(b"TIT2", &chapter.title));
+            frames.extend(id3_frame(b"CHAP", &chap));
+        }
+        frames.extend(id3_frame(b"CTOC", &toc));
+    }
+    let mut tag = b"ID3\x04\x00\x00".to_vec();
+    tag.extend_from_slice(&syncsafe(frames.len() as u32));
+    tag.extend(frames);
+    tag
+}
+
+/// Replaces the ID3v2 tag at the start of the file
+fn retag_mp3<R: Read + Seek, W: Write>(
+    mut input: R,
+    output: &mut W,
+    metadata: &Metadata,
+) -> Result<(), ParsingError> {
+    let mut header = [0; 10];
+    input.read_exact(&mut header).map_err(io_error)?;
+    let start = if &header[..3] == b"ID3" {
+        let size = header[6..10]
+            .iter()
+            .fold(0u64, |size, b| size << 7 | (*b & 0x7f) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        10 + size + footer
+    } else {
+        0
+    };
+    input.seek(SeekFrom::Start(start)).map_err(io_error)?;
+    output.write_all(&id3(metadata)).map_err(io_error)?;
+    std::io::copy(&mut input, output).map_err(io_error)?;
+    Ok(())
+}
+
+/// FLAC picture block, the Vorbis comment format for covers
+fn flac_picture(cover: &Cover) -> Option<Vec<u8>> {
+    let mime_type = cover.format()?.mime_type();
+    let mut picture = 3u32.to_be_bytes().to_vec();
+    picture.extend_from_slice(&(mime_type.len() as u32).to_be_bytes());
+    picture.extend_from_slice(mime_type.as_bytes());
+    // Empty description, unknown width, height, depth and palette size
+    picture.extend_from_slice(&[0; 20]);
+    picture.extend_from_slice(&(cover.data.len() as u32).to_be_bytes());
+    picture.extend_from_slice(&cover.data);
+    Some(picture)
+}
+
+fn chapter_time(seconds: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.000",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn vorbis_comments(metadata: &Metadata) -> Vec<String> {
+    use base64::Engine;
+    let mut comments = vec![];
+    let fields = [
+        ("TITLE", metadata.title.clone()),
+        ("ARTIST", metadata.artist.clone()),
+        ("DATE", date(metadata)),
+        ("DESCRIPTION", metadata.description.clone()),
+    ];
+    for (key, value) in fields.iter() {
+        if let Some(value) = value {
+            comments.push(format!("{}={}", key, value));
+        }
+    }
+    if let Some(picture) = metadata.cover.as_ref().and_then(flac_picture) {
+        let picture = base64::engine::general_purpose::STANDARD.encode(picture);
+        comments.push(format!("METADATA_BLOCK_PICTURE={}", picture));
+    }
+    for (i, chapter) in metadata.chapters.iter().enumerate() {
+        comments.push(format!(
+            "CHAPTER{:03}={}",
+            i + 1,
+            chapter_time(chapter.start)
+        ));
+        comments.push(format!("CHAPTER{:03}NAME={}", i + 1, chapter.title));
+    }
+    comments
+}
+
+fn le_u32(data: &[u8], position: &mut usize) -> Result<u32, ParsingError> {
+    let bytes = data
+        .get(*position..*position + 4)
+        .ok_or("OpusTags ends early")?;
+    *position += 4;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Length prefixed string of a Vorbis comment header
+fn le_field<'a>(data: &'a [u8], position: &mut usize) -> Result<&'a [u8], ParsingError> {
+    let length = le_u32(data, position)? as usize;
+    let field = data
+        .get(*position..*position + length)
+        .ok_or("OpusTags ends early")?;
+    *position += length;
+    Ok(field)
+}
+
+/// `OpusTags` packet with the metadata and the comments of `previous` it doesn't replace
+fn opus_tags(previous: Option<&[u8]>, metadata: &Metadata) -> Result<Vec<u8>, ParsingError> {
+    let mut vendor = concat!("rusty_pipe ", env!("CARGO_PKG_VERSION")).to_string();
+    let mut comments = vec![];
+    if let Some(previous) = previous {
+        let mut position = 8;
+        vendor = String::from_utf8_lossy(le_field(previous, &mut position)?).to_string();
+        let count = le_u32(previous, &mut position)?;
+        let replaced = [
+            "TITLE",
+            "ARTIST",
+            "DATE",
+            "DESCRIPTION",
+            "METADATA_BLOCK_PICTURE",
+        ];
+        for _ in 0..count {
+            let comment = String::from_utf8_lossy(le_field(previous, &mut position)?).to_string();
+            let key = comment.split('=').next().unwrap_or_default().to_uppercase();
+            if !replaced.contains(&key.as_str()) && !key.starts_with("CHAPTER") {
+                comments.push(comment);
+            }
+        }
+    }
+    comments.extend(vorbis_comments(metadata));
+
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor.as_bytes());
+    tags.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+    }
+    Ok(tags)
+}
+
+const OGG_CONTINUED: u8 = 0x01;
+const OGG_FIRST: u8 = 0x02;
+const OGG_LAST: u8 = 0x04;
+/// Granule position of pages on which no packet ends
+const NO_GRANULE: u64 = u64::MAX;
+/// Pages are closed at the first packet boundary after this many bytes
+const OGG_PAGE_SIZE: usize = 4096;
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut remainder = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            remainder = if remainder & 0x8000_0000 != 0 {
+                remainder << 1 ^ 0x04c1_1db7
+            } else {
+                remainder << 1
+            };
+            bit += 1;
+        }
+        table[i] = remainder;
+        i += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, b| {
+        crc << 8 ^ CRC_TABLE[((crc >> 24) as u8 ^ b) as usize]
+    })
+}
+
+struct Page {
+    flags: u8,
+    granule: u64,
+    serial: u32,
+    lacing: Vec<u8>,
+    data: Vec<u8>,
+}
+
+/// The next page, `None` at the end of the stream
+fn read_page<R: Read>(input: &mut R) -> Result<Option<Page>, ParsingError> {
+    let mut header = [0; 27];
+    if input.read(&mut header[..1]).map_err(io_error)? == 0 {
+        return Ok(None);
+    }
+    input.read_exact(&mut header[1..]).map_err(io_error)?;
+    if &header[..4] != b"OggS" {
+        return Err(ParsingError::from("Lost sync in the Ogg stream"));
+    }
+    let mut lacing = vec![0; header[26] as usize];
+    input.read_exact(&mut lacing).map_err(io_error)?;
+    let mut data = vec![0; lacing.iter().map(|l| *l as usize).sum()];
+    input.read_exact(&mut data).map_err(io_error)?;
+    let mut granule = [0; 8];
+    granule.copy_from_slice(&header[6..14]);
+    Ok(Some(Page {
+        flags: header[5],
+        granule: u64::from_le_bytes(granule),
+        serial: u32::from_le_bytes([header[14], header[15], header[16], header[17]]),
+        lacing,
+        data,
+    }))
+}
+
+struct OggWriter<W> {
+    output: W,
+    serial: u32,
+    sequence: u32,
+    lacing: Vec<u8>,
+    data: Vec<u8>,
+    granule: u64,
+    continued: bool,
+}
+
+impl<W: Write> OggWriter<W> {
+    fn new(output: W, serial: u32) -> Self {
+        OggWriter {
+            output,
+            serial,
+            sequence: 0,
+            lacing: vec![],
+            data: vec![],
+            granule: NO_GRANULE,
+            continued: false,
+        }
+    }
+
+    fn page(
+        &mut self,
+        flags: u8,
+        granule: u64,
+        lacing: &[u8],
+        data: &[u8],
+    ) -> Result<(), ParsingError> {
+        let mut page = b"OggS\0".to_vec();
+        page.push(flags);
+        page.extend_from_slice(&granule.to_le_bytes());
+        page.extend_from_slice(&self.serial.to_le_bytes());
+        page.extend_from_slice(&self.sequence.to_le_bytes());
+        page.extend_from_slice(&[0; 4]);
+        page.push(lacing.len() as u8);
+        page.extend_from_slice(lacing);
+        page.extend_from_slice(data);
+        let crc = ogg_crc(&page);
+        page[22..26].copy_from_slice(&crc.to_le_bytes());
+        self.output.write_all(&page).map_err(io_error)?;
+        self.sequence += 1;
+        Ok(())
+    }
+
+    /// Adds a packet to the open page, `granule` is the position at its end
+    fn packet(&mut self, packet: &[u8], granule: u64) -> Result<(), ParsingError> {
+        let mut rest = packet;
+        let mut first = true;
+        loop {
+            if self.lacing.len() == 255 {
+                self.flush(false)?;
+                self.continued = !first;
+            }
+            let length = rest.len().min(255);
+            self.lacing.push(length as u8);
+            self.data.extend_from_slice(&rest[..length]);
+            rest = &rest[length..];
+            first = false;
+            // A segment shorter than 255 bytes ends the packet
+            if length < 255 {
+                break;
+            }
+        }
+        self.granule = granule;
+        Ok(())
+    }
+
+    fn flush(&mut self, last: bool) -> Result<(), ParsingError> {
+        if self.lacing.is_empty() && !last {
+            return Ok(());
+        }
+        let mut flags = if last { OGG_LAST } else { 0 };
+        if self.continued {
+            flags |= OGG_CONTINUED;
+        }
+        if self.sequence == 0 {
+            flags |= OGG_FIRST;
+        }
+        let lacing = std::mem::take(&mut self.lacing);
+        let data = std::mem::take(&mut self.data);
+        self.page(flags, self.granule, &lacing, &data)?;
+        self.granule = NO_GRANULE;
+        self.continued = false;
+        Ok(())
+    }
+
+    /// Writes the `OpusHead` and `OpusTags` packets, each ending its page
+    fn headers(&mut self, head: &[u8], tags: &[u8]) -> Result<(), ParsingError> {
+        self.packet(head, 0)?;
+        self.flush(false)?;
+        self.packet(tags, 0)?;
+        self.flush(false)
+    }
+}
+
+/// Replaces the `OpusTags` of an Ogg Opus file, the audio pages are renumbered
+fn retag_ogg<R: Read, W: Write>(
+    input: R,
+    output: &mut W,
+    metadata: &Metadata,
+) -> Result<(), ParsingError> {
+    let mut input = std::io::BufReader::new(input);
+    let mut packets = vec![];
+    let mut packet = vec![];
+    let mut serial = None;
+    while packets.len() < 2 {
+        let page = read_page(&mut input)?.ok_or("Ogg stream ends in its headers")?;
+        serial = serial.or(Some(page.serial));
+        let mut position = 0;
+        for length in &page.lacing {
+            packet.extend_from_slice(&page.data[position..position + *length as usize]);
+            position += *length as usize;
+            if *length < 255 {
+                packets.push(std::mem::take(&mut packet));
+            }
+        }
+    }
+    if packets.len() != 2 || !packet.is_empty() {
+        return Err(ParsingError::from(
+            "Audio data on the pages of the Ogg headers",
+        ));
+    }
+    if !packets[0].starts_with(b"OpusHead") || !packets[1].starts_with(b"OpusTags") {
+        return Err(ParsingError::from("Not an Ogg Opus file"));
+    }
+    let serial = serial.unwrap_or_default();
+    let mut writer = OggWriter::new(output, serial);
+    writer.headers(&packets[0], &opus_tags(Some(&packets[1]), metadata)?)?;
+    while let Some(page) = read_page(&mut input)? {
+        if page.serial != serial {
+            return Err(ParsingError::from(
+                "Ogg files with several streams are not supported",
+            ));
+        }
+        writer.page(page.flags, page.granule, &page.lacing, &page.data)?;
+    }
+    Ok(())
+}
+
+/// Samples at 48 kHz of an Opus packet, from its TOC byte
+fn opus_samples(packet: &[u8]) -> Result<u64, ParsingError> {
+    let toc = *packet.first().ok_or("Empty Opus packet")?;
+    let config = (toc >> 3) as usize;
+    let frame = match config {
+        0..=11 => [480, 960, 1920, 2880][config % 4],
+        12..=15 => [480, 960][config % 2],
+        _ => [120, 240, 480, 960][config % 4],
+    };
+    let frames = match toc & 0x03 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1).ok_or("Opus packet ends early")? & 0x3f) as u64,
+    };
+    Ok(frame * frames)
+}
+
+/// Moves the Opus track of a WebM file into an Ogg container with Vorbis comments
+fn webm_to_ogg<R: Read + Seek, W: Write>(
+    input: R,
+    output: &mut W,
+    metadata: &Metadata,
+) -> Result<(), ParsingError> {
+    let mut frames = AudioFrames::read(input)?;
+    if frames.codec_id != "A_OPUS" || !frames.codec_private.starts_with(b"OpusHead") {
+        return Err(ParsingError::from(format!(
+            "Only Opus audio can be tagged, not {}",
+            frames.codec_id
+        )));
+    }
+    let mut writer = OggWriter::new(output, u32::from_le_bytes(*b"rust"));
+    writer.headers(&frames.codec_private, &opus_tags(None, metadata)?)?;
+    // Includes the pre-skip, which the decoder drops
+    let mut granule = 0u64;
+    for i in 0..frames.len() {
+        let (frame, discard_padding) = frames.frame(i)?;
+        granule += opus_samples(&frame)?;
+        if discard_padding > 0 {
+            granule = granule.saturating_sub(discard_padding as u64 * 48_000 / 1_000_000_000);
+        }
+        if writer.data.len() >= OGG_PAGE_SIZE {
+            writer.flush(false)?;
+        }
+        writer.packet(&frame, granule)?;
+    }
+    writer.flush(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mux::mp4::path;
+    use crate::mux::Chapter;
+    use std::io::Cursor;
+
+    /// Opus track, ten frames `0xa0 + i` every 20 ms, the last with 5 ms of discard padding
+    const AUDIO_WEBM: &[u8] = include_bytes!("fixtures/audio.webm");
+    /// One mp4a track of 8 samples of 10 bytes `0xa0 + i`
+    const AUDIO_M4A: &[u8] = include_bytes!("fixtures/audio_progressive.m4a");
+
+    fn metadata() -> Metadata {
+        Metadata {
+            title: Some("Mock title".to_string()),
+            artist: Some("Mock Channel".to_string()),
+            date: chrono::NaiveDate::from_ymd_opt(2024, 5, 17),
+            description: Some("First line\nSecond line".to_string()),
+            cover: Some(Cover {
+                data: vec![0xff, 0xd8, 0xff, 0xe0, 1, 2, 3],
+            }),
+            chapters: vec![
+                Chapter {
+                    start: 0,
+                    end: Some(75),
+                    title: "Intro".to_string(),
+                },
+                Chapter {
+                    start: 75,
+                    end: None,
+                    title: "Main".to_string(),
+                },
+            ],
+        }
+    }
+
+    fn tag_file(input: &[u8], metadata: &Metadata) -> (AudioFormat, Vec<u8>) {
+        let mut output = vec![];
+        let format = tag(Cursor::new(input), &mut output, metadata).unwrap();
+        (format, output)
+    }
+
+    /// Vendor and comments of an `OpusTags` packet
+    fn opus_comments(packet: &[u8]) -> (String, Vec<String>) {
+        assert!(packet.starts_with(b"OpusTags"));
+        let mut position = 8;
+        let vendor = String::from_utf8(le_field(packet, &mut position).unwrap().to_vec()).unwrap();
+        let count = le_u32(packet, &mut position).unwrap();
+        let comments = (0..count)
+            .map(|_| String::from_utf8(le_field(packet, &mut position).unwrap().to_vec()).unwrap())
+            .collect();
+        assert_eq!(position, packet.len());
+        (vendor, comments)
+    }
+
+    fn syncsafe_value(bytes: &[u8]) -> u32 {
+        bytes.iter().fold(0, |value, b| value << 7 | *b as u32)
+    }
+
+    /// Id and payload of each frame of an ID3v2.4 tag
+    fn id3_frames(tag: &[u8]) -> Vec<([u8; 4], &[u8])> {
+        assert_eq!(&tag[..6], b"ID3\x04\x00\x00");
+        assert_eq!(syncsafe_value(&tag[6..10]) as usize, tag.len() - 10);
+        let mut frames = vec![];
+        let mut rest = &tag[10..];
+        while !rest.is_empty() {
+            let id = [rest[0], rest[1], rest[2], rest[3]];
+            let size = syncsafe_value(&rest[4..8]) as usize;
+            frames.push((id, &rest[10..10 + size]));
+            rest = &rest[10 + size..];
+        }
+        frames
+    }
+
+    #[test]
+    fn syncsafe_sizes() {
+        assert_eq!(syncsafe(0), [0, 0, 0, 0]);
+        assert_eq!(syncsafe(127), [0, 0, 0, 0x7f]);
+        assert_eq!(syncsafe(128), [0, 0, 1, 0]);
+        assert_eq!(syncsafe(0x0fff_ffff), [0x7f; 4]);
+        assert_eq!(syncsafe_value(&syncsafe(1_234_567)), 1_234_567);
+
+        // Frames past 127 bytes, where plain and syncsafe sizes differ
+        let metadata = Metadata {
+            title: Some("t".repeat(300)),
+            cover: Some(Cover {
+                data: [&[0xff, 0xd8, 0xff][..], &[0; 1000]].concat(),
+            }),
+            ..Metadata::default()
+        };
+        let tag = id3(&metadata);
+        let frames = id3_frames(&tag);
+        assert_eq!(frames[0].0, *b"TIT2");
+        assert_eq!(frames[0].1.len(), 301);
+        assert_eq!(frames[1].0, *b"APIC");
+        assert!(frames[1].1.ends_with(&[0; 1000]));
+    }
+
+    #[test]
+    fn chapter_frames() {
+        let metadata = Metadata {
+            chapters: vec![
+                Chapter {
+                    start: 0,
+                    end: Some(90),
+                    title: "Intro".to_string(),
+                },
+                Chapter {
+                    start: 90,
+                    end: None,
+                    title: "Main".to_string(),
+                },
+                // Past the 49 days 32 bit milliseconds reach
+                Chapter {
+                    start: 5_000_000,
+                    end: Some(6_000_000),
+                    title: "Late".to_string(),
+                },
+            ],
+            ..Metadata::default()
+        };
+        let tag = id3(&metadata);
+        let frames = id3_frames(&tag);
+        let chaps: Vec<&[u8]> = frames
+            .iter()
+            .filter(|(id, _)| id == b"CHAP")
+            .map(|(_, payload)| *payload)
+            .collect();
+        let times = |chap: &[u8]| {
+            let start = u32::from_be_bytes([chap[5], chap[6], chap[7], chap[8]]);
+            let end = u32::from_be_bytes([chap[9], chap[10], chap[11], chap[12]]);
+            (start, end)
+        };
+        assert_eq!(chaps.len(), 3);
+        assert!(chaps[0].starts_with(b"chp0\0"));
+        assert_eq!(times(chaps[0]), (0, 90_000));
+        // Without an end the chapter is empty
+        assert_eq!(times(chaps[1]), (90_000, 90_000));
+        assert_eq!(times(chaps[2]), (u32::MAX, u32::MAX));
+        assert_eq!(&chaps[0][13..21], &[0xff; 8]);
+        assert_eq!(&chaps[0][21..], &id3_text(b"TIT2", "Intro")[..]);
+
+        let (id, toc) = frames.last().unwrap();
+        assert_eq!(id, b"CTOC");
+        assert_eq!(toc, b"toc\0\x03\x03chp0\0chp1\0chp2\0");
+    }
+
+    /// Packets of the pages of an Ogg stream
+    fn ogg_packets(mut stream: &[u8]) -> Vec<Vec<u8>> {
+        let mut packets = vec![];
+        let mut packet = vec![];
+        while let Some(page) = read_page(&mut stream).unwrap() {
+            let mut position = 0;
+            for length in &page.lacing {
+                packet.extend_from_slice(&page.data[position..position + *length as usize]);
+                position += *length as usize;
+                if *length < 255 {
+                    packets.push(std::mem::take(&mut packet));
+                }
+            }
+        }
+        assert!(packet.is_empty());
+        packets
+    }
+
+    #[test]
+    fn ogg_packets_across_pages() {
+        let packets = vec![
+            vec![1; 255],
+            vec![2; 10],
+            // 600 segments, filling the second page and ending on the third
+            vec![3; 600 * 255 - 1],
+            vec![4; 255 * 2],
+            vec![5; 1],
+        ];
+        let mut stream = vec![];
+        let mut writer = OggWriter::new(&mut stream, 7);
+        for (i, packet) in packets.iter().enumerate() {
+            writer.packet(packet, i as u64 + 1).unwrap();
+        }
+        writer.flush(true).unwrap();
+
+        let mut pages = vec![];
+        let mut rest = &stream[..];
+        while let Some(page) = read_page(&mut rest).unwrap() {
+            pages.push(page);
+        }
+        // The 255 byte packet ends with an empty segment
+        assert_eq!(&pages[0].lacing[..2], &[255, 0]);
+        assert_eq!(pages[0].lacing.len(), 255);
+        assert_eq!(pages[0].flags, OGG_FIRST);
+        // Only the first two packets end on the first page
+        assert_eq!(pages[0].granule, 2);
+        // No packet ends on the second page
+        assert_eq!(pages[1].flags, OGG_CONTINUED);
+        assert_eq!(pages[1].granule, NO_GRANULE);
+        assert_eq!(pages[1].lacing, vec![255; 255]);
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2].flags, OGG_CONTINUED | OGG_LAST);
+        assert_eq!(pages[2].granule, 5);
+        assert!(pages.iter().all(|page| page.serial == 7));
+
+        assert_eq!(ogg_packets(&stream), packets);
+    }
+
+    #[test]
+    fn ogg_crc_of_a_known_page() {
+        // Check value of CRC-32 with polynomial 0x04c11db7, no reflection, init and xor out 0
+        assert_eq!(ogg_crc(b"123456789"), 0x89a1_897f);
+
+        // First page of a stereo Opus stream with serial 0x1234
+        let page = [
+            0x4f, 0x67, 0x67, 0x53, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x34, 0x12, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x8e, 0x48, 0xf7, 0xf4, 0x01, 0x13,
+            0x4f, 0x70, 0x75, 0x73, 0x48, 0x65, 0x61, 0x64, 0x01, 0x02, 0x38, 0x01, 0x80, 0xbb,
+            0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let mut without_crc = page.to_vec();
+        without_crc[22..26].copy_from_slice(&[0; 4]);
+        assert_eq!(ogg_crc(&without_crc), 0xf4f7_488e);
+
+        let mut written = vec![];
+        let mut writer = OggWriter::new(&mut written, 0x1234);
+        writer.packet(&page[28..], 0).unwrap();
+        writer.flush(false).unwrap();
+        assert_eq!(written, page);
+    }
+
+    #[test]
+    fn opus_samples_of_every_config() {
+        // Frame durations of RFC 6716 table 2 in samples at 48 kHz: SILK, hybrid, CELT
+        let frame_sizes = [
+            480, 960, 1920, 2880, 480, 960, 1920, 2880, 480, 960, 1920, 2880, 480, 960, 480, 960,
+            120, 240, 480, 960, 120, 240, 480, 960, 120, 240, 480, 960, 120, 240, 480, 960,
+        ];
+        for (config, frame_size) in frame_sizes.iter().enumerate() {
+            let toc = (config as u8) << 3;
+            // Stereo flag doesn't matter
+            assert_eq!(opus_samples(&[toc | 0x04]).unwrap(), *frame_size);
+            assert_eq!(opus_samples(&[toc | 1]).unwrap(), frame_size * 2);
+            assert_eq!(opus_samples(&[toc | 2]).unwrap(), frame_size * 2);
+            // Code 3 counts its frames in the next byte, under the VBR and padding flags
+            assert_eq!(opus_samples(&[toc | 3, 0xc0 | 5]).unwrap(), frame_size * 5);
+        }
+        assert!(opus_samples(&[]).is_err());
+        assert!(opus_samples(&[3]).is_err());
+    }
+
+    #[test]
+    fn tags_webm_as_ogg_opus() {
+        let (format, output) = tag_file(AUDIO_WEBM, &metadata());
+        assert_eq!(format, AudioFormat::Opus);
+        assert_eq!(format.extension(), "opus");
+
+        let packets = ogg_packets(&output);
+        let frames = AudioFrames::read(Cursor::new(AUDIO_WEBM)).unwrap();
+        assert_eq!(packets[0], frames.codec_private);
+        assert!(packets[0].starts_with(b"OpusHead"));
+
+        let (vendor, comments) = opus_comments(&packets[1]);
+        assert!(vendor.starts_with("rusty_pipe "));
+        let picture = flac_picture(metadata().cover.as_ref().unwrap()).unwrap();
+        let picture = {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode(picture)
+        };
+        assert_eq!(
+            comments,
+            vec![
+                "TITLE=Mock title".to_string(),
+                "ARTIST=Mock Channel".to_string(),
+                "DATE=2024-05-17".to_string(),
+                "DESCRIPTION=First line\nSecond line".to_string(),
+                format!("METADATA_BLOCK_PICTURE={}", picture),
+                "CHAPTER001=00:00:00.000".to_string(),
+                "CHAPTER001NAME=Intro".to_string(),
+                "CHAPTER002=00:01:15.000".to_string(),
+                "CHAPTER002NAME=Main".to_string(),
+            ]
+        );
+
+        let audio: Vec<Vec<u8>> = (0..10u8)
+            .map(|i| {
+                let mut frames = AudioFrames::read(Cursor::new(AUDIO_WEBM)).unwrap();
+                frames.frame(i as usize).unwrap().0
+            })
+            .collect();
+        assert!(audio
+            .iter()
+            .enumerate()
+            .all(|(i, frame)| frame[0] == 0xa0 + i as u8));
+        assert_eq!(&packets[2..], &audio[..]);
+
+        // The last page ends the stream at the samples of the frames, less the discard padding
+        let mut rest = &output[..];
+        let mut last = None;
+        while let Some(page) = read_page(&mut rest).unwrap() {
+            last = Some(page);
+        }
+        let last = last.unwrap();
+        assert_eq!(last.flags & OGG_LAST, OGG_LAST);
+        let samples: u64 = audio.iter().map(|frame| opus_samples(frame).unwrap()).sum();
+        assert_eq!(last.granule, samples - 240);
+    }
+
+    #[test]
+    fn retags_ogg_opus() {
+        // An earlier tag with comments of other programs and a lowercase title
+        let mut previous = b"OpusTags".to_vec();
+        let comments = [
+            "title=Old title",
+            "ENCODER=other",
+            "chapter001=00:00:00.000",
+        ];
+        for field in ["other vendor"].iter().chain(comments.iter()) {
+            previous.extend_from_slice(&(field.len() as u32).to_le_bytes());
+            previous.extend_from_slice(field.as_bytes());
+            if *field == "other vendor" {
+                previous.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+            }
+        }
+        let (_, tagged) = tag_file(AUDIO_WEBM, &Metadata::default());
+        let packets = ogg_packets(&tagged);
+        let mut input = vec![];
+        let mut writer = OggWriter::new(&mut input, 42);
+        writer.headers(&packets[0], &previous).unwrap();
+        for (i, packet) in packets[2..].iter().enumerate() {
+            writer.packet(packet, i as u64 * 960).unwrap();
+        }
+        writer.flush(true).unwrap();
+
+        let metadata = Metadata {
+            title: Some("New title".to_string()),
+            ..Metadata::default()
+        };
+        let (format, output) = tag_file(&input, &metadata);
+        assert_eq!(format, AudioFormat::Opus);
+        let retagged = ogg_packets(&output);
+        assert_eq!(retagged[0], packets[0]);
+        assert_eq!(
+            opus_comments(&retagged[1]),
+            (
+                "other vendor".to_string(),
+                vec!["ENCODER=other".to_string(), "TITLE=New title".to_string()]
+            )
+        );
+        assert_eq!(&retagged[2..], &packets[2..]);
+        let mut rest = &output[..];
+        while let Some(page) = read_page(&mut rest).unwrap() {
+            assert_eq!(page.serial, 42);
+        }
+    }
+
+    #[test]
+    fn tags_m4a_with_itunes_atoms() {
+        let (format, output) = tag_file(AUDIO_M4A, &metadata());
+        assert_eq!(format, AudioFormat::M4a);
+
+        let meta = path(&output, &[b"moov", b"udta", b"meta"]).unwrap();
+        // `meta` is a full box
+        let ilst = path(&meta[4..], &[b"ilst"]).unwrap();
+        let text = |kind: &[u8; 4]| {
+            let data = path(ilst, &[kind, b"data"]).unwrap();
+            // UTF-8 type and locale
+            assert_eq!(&data[..8], &[0, 0, 0, 1, 0, 0, 0, 0]);
+            String::from_utf8(data[8..].to_vec()).unwrap()
+        };
+        assert_eq!(text(b"\xa9nam"), "Mock title");
+        assert_eq!(text(b"\xa9ART"), "Mock Channel");
+        assert_eq!(text(b"\xa9day"), "2024-05-17");
+        assert_eq!(text(b"desc"), "First line\nSecond line");
+        let cover = path(ilst, &[b"covr", b"data"]).unwrap();
+        assert_eq!(&cover[..4], &13u32.to_be_bytes());
+        assert_eq!(&cover[8..], &metadata().cover.unwrap().data[..]);
+
+        // The samples are copied
+        let mdat = path(&output, &[b"mdat"]).unwrap();
+        let samples: Vec<u8> = (0..8u8).flat_map(|i| vec![0xa0 + i; 10]).collect();
+        assert_eq!(mdat, &samples[..]);
+    }
+
+    #[test]
+    fn replaces_the_id3_tag_of_mp3() {
+        // Two MPEG-1 layer III frame headers standing in for the audio
+        let audio = [
+            &[0xff, 0xfb, 0x90, 0x64][..],
+            &[0x55; 413],
+            &[0xff, 0xfb, 0x90, 0x64],
+        ]
+        .concat();
+        let old = Metadata {
+            title: Some("Old title".to_string()),
+            artist: Some("Old artist".to_string()),
+            ..Metadata::default()
+        };
+        let with_tag = [id3(&old), audio.clone()].concat();
+        let new = metadata();
+
+        for input in [&with_tag, &audio].iter() {
+            let (format, output) = tag_file(input, &new);
+            assert_eq!(format, AudioFormat::Mp3);
+            let tag = id3(&new);
+            assert_eq!(&output[..tag.len()], &tag[..]);
+            assert_eq!(&output[tag.len()..], &audio[..]);
+
+            let frames = id3_frames(&tag);
+            let ids: Vec<&[u8; 4]> = frames.iter().map(|(id, _)| id).collect();
+            assert_eq!(
+                ids,
+                vec![b"TIT2", b"TPE1", b"TDRC", b"COMM", b"APIC", b"CHAP", b"CHAP", b"CTOC"]
+            );
+            assert_eq!(frames[0].1, b"\x03Mock title");
+            assert_eq!(frames[2].1, b"\x032024-05-17");
+        }
+
+        // A footer follows the frames when its flag is set
+        let mut footed = id3(&old);
+        footed[5] = 0x10;
+        let footer = [b"3DI".to_vec(), footed[3..10].to_vec()].concat();
+        let input = [footed.clone(), footer, audio.clone()].concat();
+        let (_, output) = tag_file(&input, &new);
+        assert_eq!(&output[id3(&new).len()..], &audio[..]);
+    }
+
+    #[test]
+    fn rejects_unknown_formats() {
+        let mut output = vec![];
+        assert!(tag(
+            Cursor::new(&b"RIFF\0\0\0\0WAVE"[..]),
+            &mut output,
+            &metadata()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,462 @@
+//! `newpipe.db`, read from any schema version since streams got upload dates (3) and written
+//! as version 9
+//!
+//! Bytes are opened by rusqlite only through a file, so the database goes through a directory
+//! that `tempfile` creates with a random name, readable by the user alone.
+
+use super::{
+    Channel, HistoryEntry, LocalPlaylist, NewPipeBackup, RemotePlaylist, SearchEntry, Stream,
+    StreamState,
+};
+use crate::subscriptions::Service;
+use crate::youtube_extractor::error::ParsingError;
+use rusqlite::{params, Connection, Row};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+const VERSION: i64 = 9;
+
+/// Tables and indices of Room's schema for version 9. Room checks them against its entities
+/// when NewPipe opens the imported database, as there is no `room_master_table`.
+const SCHEMA: &str = "
+CREATE TABLE subscriptions (uid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, service_id INTEGER NOT NULL, url TEXT, name TEXT, avatar_url TEXT, subscriber_count INTEGER, description TEXT, notification_mode INTEGER NOT NULL);
+CREATE UNIQUE INDEX index_subscriptions_service_id_url ON subscriptions (service_id, url);
+CREATE TABLE search_history (creation_date INTEGER, service_id INTEGER NOT NULL, search TEXT, id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL);
+CREATE INDEX index_search_history_search ON search_history (search);
+CREATE TABLE streams (uid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, service_id INTEGER NOT NULL, url TEXT NOT NULL, title TEXT NOT NULL, stream_type TEXT NOT NULL, duration INTEGER NOT NULL, uploader TEXT NOT NULL, uploader_url TEXT, thumbnail_url TEXT, view_count INTEGER, textual_upload_date TEXT, upload_date INTEGER, is_upload_date_approximation INTEGER);
+CREATE UNIQUE INDEX index_streams_service_id_url ON streams (service_id, url);
+CREATE TABLE stream_history (stream_id INTEGER NOT NULL, access_date INTEGER NOT NULL, repeat_count INTEGER NOT NULL, PRIMARY KEY(stream_id, access_date), FOREIGN KEY(stream_id) REFERENCES streams(uid) ON UPDATE CASCADE ON DELETE CASCADE);
+CREATE INDEX index_stream_history_stream_id ON stream_history (stream_id);
+CREATE TABLE stream_state (stream_id INTEGER NOT NULL, progress_time INTEGER NOT NULL, PRIMARY KEY(stream_id), FOREIGN KEY(stream_id) REFERENCES streams(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED);
+CREATE TABLE playlists (uid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, name TEXT, is_thumbnail_permanent INTEGER NOT NULL, thumbnail_stream_id INTEGER NOT NULL, display_index INTEGER NOT NULL);
+CREATE INDEX index_playlists_name ON playlists (name);
+CREATE TABLE playlist_stream_join (playlist_id INTEGER NOT NULL, stream_id INTEGER NOT NULL, join_index INTEGER NOT NULL, PRIMARY KEY(playlist_id, join_index), FOREIGN KEY(playlist_id) REFERENCES playlists(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED, FOREIGN KEY(stream_id) REFERENCES streams(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED);
+CREATE UNIQUE INDEX index_playlist_stream_join_playlist_id_join_index ON playlist_stream_join (playlist_id, join_index);
+CREATE INDEX index_playlist_stream_join_stream_id ON playlist_stream_join (stream_id);
+CREATE TABLE remote_playlists (uid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, service_id INTEGER NOT NULL, name TEXT, url TEXT, thumbnail_url TEXT, uploader TEXT, display_index INTEGER NOT NULL, stream_count INTEGER);
+CREATE INDEX index_remote_playlists_name ON remote_playlists (name);
+CREATE UNIQUE INDEX index_remote_playlists_service_id_url ON remote_playlists (service_id, url);
+CREATE TABLE feed (stream_id INTEGER NOT NULL, subscription_id INTEGER NOT NULL, PRIMARY KEY(stream_id, subscription_id), FOREIGN KEY(stream_id) REFERENCES streams(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED, FOREIGN KEY(subscription_id) REFERENCES subscriptions(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED);
+CREATE INDEX index_feed_subscription_id ON feed (subscription_id);
+CREATE TABLE feed_group (uid INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL, name TEXT NOT NULL, icon_id INTEGER NOT NULL, sort_order INTEGER NOT NULL);
+CREATE INDEX index_feed_group_sort_order ON feed_group (sort_order);
+CREATE TABLE feed_group_subscription_join (group_id INTEGER NOT NULL, subscription_id INTEGER NOT NULL, PRIMARY KEY(group_id, subscription_id), FOREIGN KEY(group_id) REFERENCES feed_group(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED, FOREIGN KEY(subscription_id) REFERENCES subscriptions(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED);
+CREATE INDEX index_feed_group_subscription_join_subscription_id ON feed_group_subscription_join (subscription_id);
+CREATE TABLE feed_last_updated (subscription_id INTEGER NOT NULL, last_updated INTEGER, PRIMARY KEY(subscription_id), FOREIGN KEY(subscription_id) REFERENCES subscriptions(uid) ON UPDATE CASCADE ON DELETE CASCADE DEFERRABLE INITIALLY DEFERRED);
+";
+
+/// Directory for the database file and its journal, removed with them on drop
+fn temp_dir() -> Result<tempfile::TempDir, ParsingError> {
+    let dir = tempfile::Builder::new()
+        .prefix("rusty_pipe-newpipe")
+        .tempdir()
+        .map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+fn date(millis: Option<i64>) -> Option<chrono::NaiveDateTime> {
+    chrono::DateTime::from_timestamp_millis(millis?).map(|date| date.naive_utc())
+}
+
+fn millis(date: Option<chrono::NaiveDateTime>) -> Option<i64> {
+    date.map(|date| date.and_utc().timestamp_millis())
+}
+
+/// `SELECT` of the columns, with `NULL` for the ones older schema versions lack. `None` if
+/// the table does not exist.
+fn select(
+    connection: &Connection,
+    table: &str,
+    columns: &[&str],
+) -> rusqlite::Result<Option<String>> {
+    let existing = connection
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<HashSet<String>>>()?;
+    if existing.is_empty() {
+        return Ok(None);
+    }
+    let columns: Vec<String> = columns
+        .iter()
+        .map(|column| match existing.contains(*column) {
+            true => column.to_string(),
+            false => format!("NULL AS {}", column),
+        })
+        .collect();
+    Ok(Some(format!(
+        "SELECT {} FROM {}",
+        columns.join(", "),
+        table
+    )))
+}
+
+fn query<T>(
+    connection: &Connection,
+    table: &str,
+    columns: &[&str],
+    order: &str,
+    map: impl FnMut(&Row) -> rusqlite::Result<T>,
+) -> rusqlite::Result<Vec<T>> {
+    let select = match select(connection, table, columns)? {
+        Some(select) => format!("{} ORDER BY {}", select, order),
+        None => return Ok(vec![]),
+    };
+    let mut statement = connection.prepare(&select)?;
+    let rows = statement.query_map([], map)?;
+    rows.collect()
+}
+
+fn service(row: &Row, index: usize) -> rusqlite::Result<Service> {
+    Ok(Service::from_id(row.get(index)?))
+}
+
+fn streams(connection: &Connection) -> rusqlite::Result<HashMap<i64, Stream>> {
+    let columns = [
+        "uid",
+        "service_id",
+        "url",
+        "title",
+        "stream_type",
+        "duration",
+        "uploader",
+        "uploader_url",
+        "thumbnail_url",
+        "view_count",
+        "textual_upload_date",
+        "upload_date",
+        "is_upload_date_approximation",
+    ];
+    let streams = query(connection, "streams", &columns, "uid", |row| {
+        let stream = Stream {
+            service: service(row, 1)?,
+            url: row.get(2)?,
+            title: row.get(3)?,
+            stream_type: row.get(4)?,
+            duration: row.get(5)?,
+            uploader: row.get(6)?,
+            uploader_url: row.get(7)?,
+            thumbnail_url: row.get(8)?,
+            view_count: row.get(9)?,
+            textual_upload_date: row.get(10)?,
+            upload_date: date(row.get(11)?),
+            upload_date_approximation: row.get(12)?,
+        };
+        Ok((row.get(0)?, stream))
+    })?;
+    Ok(streams.into_iter().collect())
+}
+
+fn stream(streams: &HashMap<i64, Stream>, uid: i64) -> rusqlite::Result<Stream> {
+    streams
+        .get(&uid)
+        .cloned()
+        .ok_or(rusqlite::Error::QueryReturnedNoRows)
+}
+
+fn playlists(
+    connection: &Connection,
+    streams: &HashMap<i64, Stream>,
+) -> rusqlite::Result<Vec<LocalPlaylist>> {
+    let mut entries: HashMap<i64, Vec<Stream>> = HashMap::new();
+    let joins = query(
+        connection,
+        "playlist_stream_join",
+        &["playlist_id", "stream_id"],
+        "playlist_id, join_index",
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+    )?;
+    for (playlist_id, stream_id) in joins {
+        entries
+            .entry(playlist_id)
+            .or_default()
+            .push(stream(streams, stream_id)?);
+    }
+    let columns = [
+        "uid",
+        "name",
+        "is_thumbnail_permanent",
+        "thumbnail_stream_id",
+        "thumbnail_url",
+        "display_index",
+    ];
+    query(
+        connection,
+        "playlists",
+        &columns,
+        "display_index, uid",
+        |row| {
+            // Versions before 7 kept the url of the thumbnail instead of its stream
+            let thumbnail_stream: Option<i64> = row.get(3)?;
+            let thumbnail_url = match thumbnail_stream {
+                Some(uid) => streams.get(&uid).and_then(|s| s.thumbnail_url.clone()),
+                None => row.get(4)?,
+            };
+            Ok(LocalPlaylist {
+                name: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                thumbnail_permanent: row.get::<_, Option<bool>>(2)?.unwrap_or(false),
+                thumbnail_url,
+                streams: entries.remove(&row.get(0)?).unwrap_or_default(),
+            })
+        },
+    )
+}
+
+fn read_connection(connection: &Connection) -> rusqlite::Result<NewPipeBackup> {
+    let streams = streams(connection)?;
+    let subscriptions = query(
+        connection,
+        "subscriptions",
+        &[
+            "service_id",
+            "url",
+            "name",
+            "avatar_url",
+            "subscriber_count",
+            "description",
+            "notification_mode",
+        ],
+        "uid",
+        |row| {
+            Ok(Channel {
+                service: service(row, 0)?,
+                url: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                name: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                avatar_url: row.get(3)?,
+                subscriber_count: row.get(4)?,
+                description: row.get(5)?,
+                notification_mode: row.get::<_, Option<i64>>(6)?.unwrap_or_default(),
+            })
+        },
+    )?;
+    let history = query(
+        connection,
+        "stream_history",
+        &["stream_id", "access_date", "repeat_count"],
+        "access_date DESC",
+        |row| {
+            Ok(HistoryEntry {
+                stream: stream(&streams, row.get(0)?)?,
+                access_date: date(row.get(1)?).unwrap_or_default(),
+                repeat_count: row.get(2)?,
+            })
+        },
+    )?;
+    let stream_states = query(
+        connection,
+        "stream_state",
+        &["stream_id", "progress_time"],
+        "stream_id",
+        |row| {
+            Ok(StreamState {
+                stream: stream(&streams, row.get(0)?)?,
+                progress: Duration::from_millis(row.get::<_, i64>(1)?.max(0) as u64),
+            })
+        },
+    )?;
+    let remote_playlists = query(
+        connection,
+        "remote_playlists",
+        &[
+            "service_id",
+            "url",
+            "name",
+            "thumbnail_url",
+            "uploader",
+            "stream_count",
+            "display_index",
+        ],
+        "display_index, uid",
+        |row| {
+            Ok(RemotePlaylist {
+                service: service(row, 0)?,
+                url: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                name: row.get(2)?,
+                thumbnail_url: row.get(3)?,
+                uploader: row.get(4)?,
+                stream_count: row.get(5)?,
+            })
+        },
+    )?;
+    let search_history = query(
+        connection,
+        "search_history",
+        &["service_id", "search", "creation_date"],
+        "creation_date DESC",
+        |row| {
+            Ok(SearchEntry {
+                service: service(row, 0)?,
+                search: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                creation_date: date(row.get(2)?),
+            })
+        },
+    )?;
+    Ok(NewPipeBackup {
+        subscriptions,
+        history,
+        stream_states,
+        playlists: playlists(connection, &streams)?,
+        remote_playlists,
+        search_history,
+        settings: Default::default(),
+    })
+}
+
+pub fn read(database: &[u8]) -> Result<NewPipeBackup, ParsingError> {
+    let dir = temp_dir()?;
+    let file = dir.path().join("newpipe.db");
+    std::fs::write(&file, database).map_err(|e| e.to_string())?;
+    let connection = Connection::open(&file).map_err(|e| e.to_string())?;
+    let backup =
+        read_connection(&connection).map_err(|e| format!("Cannot read newpipe.db: {}", e))?;
+    drop(connection);
+    Ok(backup)
+}
+
+/// Inserts each stream once, by service and url
+struct StreamTable<'a> {
+    connection: &'a Connection,
+    uids: HashMap<(i64, String), i64>,
+}
+
+impl<'a> StreamTable<'a> {
+    fn uid(&mut self, stream: &Stream) -> rusqlite::Result<i64> {
+        let key = (stream.service.id(), stream.url.clone());
+        if let Some(uid) = self.uids.get(&key) {
+            return Ok(*uid);
+        }
+        self.connection.execute(
+            "INSERT INTO streams (service_id, url, title, stream_type, duration, uploader, \
+             uploader_url, thumbnail_url, view_count, textual_upload_date, upload_date, \
+             is_upload_date_approximation) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                key.0,
+                stream.url,
+                stream.title,
+                stream.stream_type,
+                stream.duration,
+                stream.uploader,
+                stream.uploader_url,
+                stream.thumbnail_url,
+                stream.view_count,
+                stream.textual_upload_date,
+                millis(stream.upload_date),
+                stream.upload_date_approximation,
+            ],
+        )?;
+        let uid = self.connection.last_insert_rowid();
+        self.uids.insert(key, uid);
+        Ok(uid)
+    }
+}
+
+fn write_connection(connection: &Connection, backup: &NewPipeBackup) -> rusqlite::Result<()> {
+    connection.execute_batch(SCHEMA)?;
+    connection.pragma_update(None, "user_version", VERSION)?;
+    let mut streams = StreamTable {
+        connection,
+        uids: HashMap::new(),
+    };
+    for channel in &backup.subscriptions {
+        connection.execute(
+            "INSERT OR IGNORE INTO subscriptions (service_id, url, name, avatar_url, \
+             subscriber_count, description, notification_mode) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                channel.service.id(),
+                channel.url,
+                channel.name,
+                channel.avatar_url,
+                channel.subscriber_count,
+                channel.description,
+                channel.notification_mode,
+            ],
+        )?;
+    }
+    for entry in &backup.history {
+        connection.execute(
+            "INSERT OR REPLACE INTO stream_history (stream_id, access_date, repeat_count) \
+             VALUES (?1, ?2, ?3)",
+            params![
+                streams.uid(&entry.stream)?,
+                millis(Some(entry.access_date)),
+                entry.repeat_count,
+            ],
+        )?;
+    }
+    for state in &backup.stream_states {
+        connection.execute(
+            "INSERT OR REPLACE INTO stream_state (stream_id, progress_time) VALUES (?1, ?2)",
+            params![
+                streams.uid(&state.stream)?,
+                state.progress.as_millis() as i64
+            ],
+        )?;
+    }
+    for (index, playlist) in backup.playlists.iter().enumerate() {
+        let mut uids = vec![];
+        for stream in &playlist.streams {
+            uids.push(streams.uid(stream)?);
+        }
+        let thumbnail = playlist
+            .streams
+            .iter()
+            .position(|s| s.thumbnail_url.is_some() && s.thumbnail_url == playlist.thumbnail_url)
+            .or(if uids.is_empty() { None } else { Some(0) })
+            .map_or(-1, |i| uids[i]);
+        connection.execute(
+            "INSERT INTO playlists (name, is_thumbnail_permanent, thumbnail_stream_id, \
+             display_index) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                playlist.name,
+                playlist.thumbnail_permanent,
+                thumbnail,
+                index as i64
+            ],
+        )?;
+        let playlist_id = connection.last_insert_rowid();
+        for (join_index, uid) in uids.into_iter().enumerate() {
+            connection.execute(
+                "INSERT INTO playlist_stream_join (playlist_id, stream_id, join_index) \
+                 VALUES (?1, ?2, ?3)",
+                params![playlist_id, uid, join_index as i64],
+            )?;
+        }
+    }
+    for (index, playlist) in backup.remote_playlists.iter().enumerate() {
+        connection.execute(
+            "INSERT OR IGNORE INTO remote_playlists (service_id, name, url, thumbnail_url, \
+             uploader, display_index, stream_count) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                playlist.service.id(),
+                playlist.name,
+                playlist.url,
+                playlist.thumbnail_url,
+                playlist.uploader,
+                index as i64,
+                playlist.stream_count,
+            ],
+        )?;
+    }
+    for entry in &backup.search_history {
+        connection.execute(
+            "INSERT INTO search_history (creation_date, service_id, search) VALUES (?1, ?2, ?3)",
+            params![
+                millis(entry.creation_date),
+                entry.service.id(),
+                entry.search
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write(backup: &NewPipeBackup) -> Result<Vec<u8>, ParsingError> {
+    let dir = temp_dir()?;
+    let file = dir.path().join("newpipe.db");
+    let mut connection = Connection::open(&file).map_err(|e| e.to_string())?;
+    let transaction = connection.transaction().map_err(|e| e.to_string())?;
+    write_connection(&transaction, backup)
+        .map_err(|e| format!("Cannot write newpipe.db: {}", e))?;
+    transaction.commit().map_err(|e| e.to_string())?;
+    drop(connection);
+    Ok(std::fs::read(&file).map_err(|e| e.to_string())?)
+}
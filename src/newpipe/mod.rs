@@ -0,0 +1,386 @@
+//! NewPipe backups, the zip NewPipe's "Export database" writes
+//!
+//! The zip holds the app's Room database `newpipe.db` and its settings, as `preferences.json`
+//! or, before NewPipe 0.27, as `newpipe.settings`. History, playlists and stream states
+//! reference [`Stream`]s, which are deduplicated again when a backup is written.
+
+mod database;
+pub mod settings;
+
+pub use settings::{SettingValue, Settings};
+
+use crate::subscriptions::{Service, Subscription};
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::link_handler::YTLink;
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use std::io::{Read, Seek, Write};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub service: Service,
+    pub url: String,
+    pub name: String,
+    pub avatar_url: Option<String>,
+    pub subscriber_count: Option<i64>,
+    pub description: Option<String>,
+    /// 0 when new streams are not notified about
+    pub notification_mode: i64,
+}
+
+impl Channel {
+    pub fn subscription(&self) -> Option<Subscription> {
+        Subscription::new(self.service, &self.url, &self.name)
+    }
+}
+
+impl From<&Subscription> for Channel {
+    fn from(subscription: &Subscription) -> Self {
+        Channel {
+            service: subscription.service,
+            url: subscription.url.clone(),
+            name: subscription.name.clone(),
+            avatar_url: None,
+            subscriber_count: None,
+            description: None,
+            notification_mode: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub service: Service,
+    pub url: String,
+    pub title: String,
+    /// `VIDEO_STREAM`, `AUDIO_STREAM`, `LIVE_STREAM`, ...
+    pub stream_type: String,
+    /// Seconds, 0 for live streams
+    pub duration: i64,
+    pub uploader: String,
+    pub uploader_url: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub view_count: Option<i64>,
+    pub textual_upload_date: Option<String>,
+    pub upload_date: Option<chrono::NaiveDateTime>,
+    pub upload_date_approximation: Option<bool>,
+}
+
+impl Stream {
+    /// Id to pass to `YTStreamExtractor::new`, for YouTube streams
+    pub fn video_id(&self) -> Option<String> {
+        match self.service {
+            Service::YouTube => YTLink::stream_id(&self.url),
+            _ => None,
+        }
+    }
+
+    pub fn from_video(video: &YTStreamInfoItemExtractor) -> Option<Self> {
+        let live = video.is_live().unwrap_or(false);
+        Some(Stream {
+            service: Service::YouTube,
+            url: YTLink::Stream(video.video_id().ok()?).url(),
+            title: video.name().ok()?,
+            stream_type: if live { "LIVE_STREAM" } else { "VIDEO_STREAM" }.to_string(),
+            duration: if live {
+                0
+            } else {
+                video.duration().unwrap_or_default() as i64
+            },
+            uploader: video.uploader_name().unwrap_or_default(),
+            uploader_url: video.uploader_url().ok(),
+            thumbnail_url: video
+                .thumbnails()
+                .ok()
+                .and_then(|thumbnails| thumbnails.into_iter().max_by_key(|t| t.width))
+                .map(|t| t.url),
+            view_count: video.view_count().ok().map(|c| c as i64),
+            textual_upload_date: video.textual_upload_date().ok(),
+            upload_date: video.upload_date().ok(),
+            upload_date_approximation: Some(true),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub stream: Stream,
+    pub access_date: chrono::NaiveDateTime,
+    pub repeat_count: i64,
+}
+
+/// Where playback of a stream was left off
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamState {
+    pub stream: Stream,
+    pub progress: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalPlaylist {
+    pub name: String,
+    /// Thumbnail of one of the streams, if the user picked it instead of the first stream's
+    pub thumbnail_permanent: bool,
+    pub thumbnail_url: Option<String>,
+    pub streams: Vec<Stream>,
+}
+
+/// Bookmarked playlist of a service
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemotePlaylist {
+    pub service: Service,
+    pub url: String,
+    pub name: Option<String>,
+    pub thumbnail_url: Option<String>,
+    pub uploader: Option<String>,
+    pub stream_count: Option<i64>,
+}
+
+impl RemotePlaylist {
+    /// Id to pass to `YTPlaylistExtractor::new`, for YouTube playlists
+    pub fn playlist_id(&self) -> Option<String> {
+        match self.service {
+            Service::YouTube => YTLink::playlist_id(&self.url),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchEntry {
+    pub service: Service,
+    pub search: String,
+    pub creation_date: Option<chrono::NaiveDateTime>,
+}
+
+/// Playlists and history keep the order NewPipe shows them in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewPipeBackup {
+    pub subscriptions: Vec<Channel>,
+    pub history: Vec<HistoryEntry>,
+    pub stream_states: Vec<StreamState>,
+    pub playlists: Vec<LocalPlaylist>,
+    pub remote_playlists: Vec<RemotePlaylist>,
+    pub search_history: Vec<SearchEntry>,
+    pub settings: Settings,
+}
+
+const DATABASE: &str = "newpipe.db";
+const PREFERENCES: &str = "preferences.json";
+const LEGACY_SETTINGS: &str = "newpipe.settings";
+
+fn zip_error(error: zip::result::ZipError) -> ParsingError {
+    ParsingError::from(error.to_string())
+}
+
+fn read_file<R: Read + Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Option<Vec<u8>>, ParsingError> {
+    let mut file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(zip_error(e)),
+    };
+    let mut content = vec![];
+    file.read_to_end(&mut content)
+        .map_err(|e| ParsingError::from(e.to_string()))?;
+    Ok(Some(content))
+}
+
+impl NewPipeBackup {
+    pub fn read<R: Read + Seek>(reader: R) -> Result<Self, ParsingError> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+        let database = read_file(&mut archive, DATABASE)?.ok_or("Backup has no newpipe.db")?;
+        let mut backup = database::read(&database)?;
+        if let Some(preferences) = read_file(&mut archive, PREFERENCES)? {
+            backup.settings = settings::from_json(&preferences)?;
+        } else if let Some(legacy) = read_file(&mut archive, LEGACY_SETTINGS)? {
+            backup.settings = settings::from_java_serialized(&legacy)?;
+        }
+        Ok(backup)
+    }
+
+    /// Settings are written as `preferences.json` only, which NewPipe reads since 0.27
+    pub fn write<W: Write + Seek>(&self, writer: W) -> Result<(), ParsingError> {
+        let database = database::write(self)?;
+        let mut zip = zip::ZipWriter::new(writer);
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        for (name, content) in [
+            (DATABASE, database),
+            (PREFERENCES, settings::to_json(&self.settings)),
+        ]
+        .iter()
+        {
+            zip.start_file(*name, options).map_err(zip_error)?;
+            zip.write_all(content)
+                .map_err(|e| ParsingError::from(e.to_string()))?;
+        }
+        zip.finish().map_err(zip_error)?;
+        Ok(())
+    }
+
+    /// The subscriptions with a recognizable channel
+    pub fn subscriptions(&self) -> Vec<Subscription> {
+        self.subscriptions
+            .iter()
+            .filter_map(Channel::subscription)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn date(day: u32, hour: u32) -> chrono::NaiveDateTime {
+        chrono::NaiveDate::from_ymd_opt(2024, 5, day)
+            .unwrap()
+            .and_hms_milli_opt(hour, 30, 15, 250)
+            .unwrap()
+    }
+
+    fn stream(id: &str, service: Service) -> Stream {
+        Stream {
+            service,
+            url: format!("https://www.youtube.com/watch?v={}", id),
+            title: format!("Title of {}", id),
+            stream_type: "VIDEO_STREAM".to_string(),
+            duration: 212,
+            uploader: "Uploader".to_string(),
+            uploader_url: Some("https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw".into()),
+            thumbnail_url: None,
+            view_count: Some(1_500_000_000),
+            textual_upload_date: Some("15 years ago".to_string()),
+            upload_date: Some(date(1, 0)),
+            upload_date_approximation: Some(true),
+        }
+    }
+
+    fn backup() -> NewPipeBackup {
+        let first = stream("dQw4w9WgXcQ", Service::YouTube);
+        let second = stream("jNQXAC9IVRw", Service::YouTube);
+        let other = Stream {
+            url: "https://soundcloud.com/artist/track".to_string(),
+            thumbnail_url: Some("https://i1.sndcdn.com/artworks.jpg".to_string()),
+            view_count: None,
+            upload_date: None,
+            upload_date_approximation: None,
+            ..stream("track", Service::SoundCloud)
+        };
+        NewPipeBackup {
+            subscriptions: vec![Channel {
+                service: Service::YouTube,
+                url: "https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw".to_string(),
+                name: "Rick Astley".to_string(),
+                avatar_url: Some("https://yt3.ggpht.com/avatar.jpg".to_string()),
+                subscriber_count: Some(4_000_000),
+                description: Some("Never gonna\ngive you up".to_string()),
+                notification_mode: 1,
+            }],
+            // Newest first, as read back
+            history: vec![
+                HistoryEntry {
+                    stream: second.clone(),
+                    access_date: date(3, 12),
+                    repeat_count: 1,
+                },
+                HistoryEntry {
+                    stream: first.clone(),
+                    access_date: date(2, 8),
+                    repeat_count: 4,
+                },
+            ],
+            stream_states: vec![StreamState {
+                stream: first.clone(),
+                progress: Duration::from_millis(61_500),
+            }],
+            playlists: vec![
+                LocalPlaylist {
+                    name: "Favourites".to_string(),
+                    thumbnail_permanent: false,
+                    thumbnail_url: None,
+                    // The same stream twice, stored once
+                    streams: vec![first.clone(), other, first],
+                },
+                LocalPlaylist {
+                    name: "Empty".to_string(),
+                    thumbnail_permanent: true,
+                    // The thumbnail is stored as one of the streams, an empty playlist has none
+                    thumbnail_url: None,
+                    streams: vec![],
+                },
+            ],
+            remote_playlists: vec![RemotePlaylist {
+                service: Service::YouTube,
+                url: "https://www.youtube.com/playlist?list=PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI"
+                    .to_string(),
+                name: Some("Playlist".to_string()),
+                thumbnail_url: None,
+                uploader: Some("Uploader".to_string()),
+                stream_count: Some(20),
+            }],
+            search_history: vec![
+                SearchEntry {
+                    service: Service::YouTube,
+                    search: "rust".to_string(),
+                    creation_date: Some(date(4, 9)),
+                },
+                SearchEntry {
+                    service: Service::PeerTube,
+                    search: "peertube".to_string(),
+                    creation_date: Some(date(4, 1)),
+                },
+            ],
+            settings: settings::tests::fixture_settings(),
+        }
+    }
+
+    fn zip(files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+        for (name, content) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn round_trips_a_backup() {
+        let backup = backup();
+        let mut written = Cursor::new(vec![]);
+        backup.write(&mut written).unwrap();
+        written.set_position(0);
+        assert_eq!(NewPipeBackup::read(written).unwrap(), backup);
+    }
+
+    #[test]
+    fn reads_legacy_settings() {
+        let backup = backup();
+        let database = database::write(&backup).unwrap();
+        let legacy = zip(&[
+            (DATABASE, database.clone()),
+            (LEGACY_SETTINGS, settings::tests::NEWPIPE_SETTINGS.to_vec()),
+        ]);
+        assert_eq!(NewPipeBackup::read(Cursor::new(legacy)).unwrap(), backup);
+
+        // preferences.json wins over newpipe.settings
+        let both = zip(&[
+            (DATABASE, database),
+            (PREFERENCES, b"{\"content_country\":\"FR\"}".to_vec()),
+            (LEGACY_SETTINGS, settings::tests::NEWPIPE_SETTINGS.to_vec()),
+        ]);
+        let read = NewPipeBackup::read(Cursor::new(both)).unwrap();
+        assert_eq!(read.settings.len(), 1);
+        assert_eq!(
+            read.settings.get("content_country"),
+            Some(&SettingValue::String("FR".to_string()))
+        );
+
+        let no_database = zip(&[(PREFERENCES, b"{}".to_vec())]);
+        assert!(NewPipeBackup::read(Cursor::new(no_database)).is_err());
+    }
+}
@@ -0,0 +1,444 @@
+//! NewPipe's shared preferences, as `preferences.json` (NewPipe 0.27 and later) or as the
+//! Java serialized `HashMap` in `newpipe.settings` older versions write
+
+use crate::youtube_extractor::error::ParsingError;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryInto;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    StringSet(Vec<String>),
+}
+
+pub type Settings = BTreeMap<String, SettingValue>;
+
+pub fn from_json(input: &[u8]) -> Result<Settings, ParsingError> {
+    serde_json::from_slice(input).map_err(|e| ParsingError::from(e.to_string()))
+}
+
+pub fn to_json(settings: &Settings) -> Vec<u8> {
+    serde_json::to_vec_pretty(settings).unwrap_or_default()
+}
+
+const TC_NULL: u8 = 0x70;
+const TC_REFERENCE: u8 = 0x71;
+const TC_CLASSDESC: u8 = 0x72;
+const TC_OBJECT: u8 = 0x73;
+const TC_STRING: u8 = 0x74;
+const TC_BLOCKDATA: u8 = 0x77;
+const TC_ENDBLOCKDATA: u8 = 0x78;
+const TC_BLOCKDATALONG: u8 = 0x7a;
+const TC_LONGSTRING: u8 = 0x7c;
+const BASE_HANDLE: u32 = 0x7e0000;
+const SC_WRITE_METHOD: u8 = 0x01;
+/// Nesting of objects and class descriptors, settings go a few levels deep at most
+const MAX_DEPTH: usize = 64;
+
+struct ClassDesc {
+    name: String,
+    flags: u8,
+    /// Type code and name
+    fields: Vec<(u8, String)>,
+    super_class: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+enum JavaValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Object {
+        class: String,
+        fields: HashMap<String, JavaValue>,
+        /// Objects written by `writeObject` after the fields, block data is left out
+        annotations: Vec<JavaValue>,
+    },
+}
+
+enum Handle {
+    Class(usize),
+    Value(JavaValue),
+}
+
+/// Reader for the subset of the serialization stream protocol `HashMap`s of boxed primitives,
+/// strings and `HashSet`s use
+struct Deserializer<'a> {
+    input: &'a [u8],
+    classes: Vec<ClassDesc>,
+    handles: Vec<Handle>,
+    depth: usize,
+}
+
+impl<'a> Deserializer<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Deserializer {
+            input,
+            classes: vec![],
+            handles: vec![],
+            depth: 0,
+        }
+    }
+
+    /// Runs `read` one level deeper, failing instead of overflowing the stack on hostile input
+    fn nested<T>(
+        &mut self,
+        read: impl FnOnce(&mut Self) -> Result<T, ParsingError>,
+    ) -> Result<T, ParsingError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParsingError::from("newpipe.settings nests too deep"));
+        }
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ParsingError> {
+        if self.input.len() < n {
+            return Err(ParsingError::from("newpipe.settings ends early"));
+        }
+        let (bytes, rest) = self.input.split_at(n);
+        self.input = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ParsingError> {
+        Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    fn u8(&mut self) -> Result<u8, ParsingError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ParsingError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, ParsingError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn utf(&mut self, long: bool) -> Result<String, ParsingError> {
+        let length = if long {
+            u64::from_be_bytes(self.array()?) as usize
+        } else {
+            self.u16()? as usize
+        };
+        Ok(modified_utf8(self.take(length)?))
+    }
+
+    fn primitive(&mut self, type_code: u8) -> Result<JavaValue, ParsingError> {
+        Ok(match type_code {
+            b'B' => JavaValue::Int(self.u8()? as i8 as i64),
+            b'C' | b'S' => JavaValue::Int(self.u16()? as i16 as i64),
+            b'I' => JavaValue::Int(self.u32()? as i32 as i64),
+            b'J' => JavaValue::Int(i64::from_be_bytes(self.array()?)),
+            b'F' => JavaValue::Float(f32::from_be_bytes(self.array()?) as f64),
+            b'D' => JavaValue::Float(f64::from_be_bytes(self.array()?)),
+            b'Z' => JavaValue::Bool(self.u8()? != 0),
+            _ => self.content()?.unwrap_or(JavaValue::Null),
+        })
+    }
+
+    fn reference(&mut self) -> Result<&Handle, ParsingError> {
+        let handle = self.u32()?;
+        let handles = &self.handles;
+        handle
+            .checked_sub(BASE_HANDLE)
+            .and_then(|i| handles.get(i as usize))
+            .ok_or_else(|| ParsingError::from(format!("Unknown handle {:x}", handle)))
+    }
+
+    /// Index into `classes`, `None` for a null class descriptor
+    fn class_desc(&mut self) -> Result<Option<usize>, ParsingError> {
+        self.nested(Self::read_class_desc)
+    }
+
+    fn read_class_desc(&mut self) -> Result<Option<usize>, ParsingError> {
+        match self.u8()? {
+            TC_NULL => Ok(None),
+            TC_REFERENCE => match self.reference()? {
+                Handle::Class(class) => Ok(Some(*class)),
+                Handle::Value(_) => Err(ParsingError::from("Handle is no class descriptor")),
+            },
+            TC_CLASSDESC => {
+                let name = self.utf(false)?;
+                let _serial_version_uid = self.take(8)?;
+                let index = self.classes.len();
+                self.handles.push(Handle::Class(index));
+                self.classes.push(ClassDesc {
+                    name,
+                    flags: 0,
+                    fields: vec![],
+                    super_class: None,
+                });
+                let flags = self.u8()?;
+                let mut fields = vec![];
+                for _ in 0..self.u16()? {
+                    let type_code = self.u8()?;
+                    let name = self.utf(false)?;
+                    if type_code == b'L' || type_code == b'[' {
+                        // Class name of the field's type
+                        self.content()?;
+                    }
+                    fields.push((type_code, name));
+                }
+                self.annotations()?;
+                let super_class = self.class_desc()?;
+                let class = &mut self.classes[index];
+                class.flags = flags;
+                class.fields = fields;
+                class.super_class = super_class;
+                Ok(Some(index))
+            }
+            tc => Err(ParsingError::from(format!(
+                "Unsupported class descriptor {:x}",
+                tc
+            ))),
+        }
+    }
+
+    /// Contents up to the end of a block, without the block data
+    fn annotations(&mut self) -> Result<Vec<JavaValue>, ParsingError> {
+        let mut values = vec![];
+        loop {
+            match self.input.first() {
+                Some(&TC_ENDBLOCKDATA) => {
+                    self.take(1)?;
+                    return Ok(values);
+                }
+                _ => values.extend(self.content()?),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<JavaValue, ParsingError> {
+        let class = self
+            .class_desc()?
+            .ok_or("Object without class descriptor")?;
+        let handle = self.handles.len();
+        self.handles.push(Handle::Value(JavaValue::Null));
+        let mut hierarchy = vec![class];
+        while let Some(super_class) = self.classes[*hierarchy.last().unwrap_or(&class)].super_class
+        {
+            // A reference back to a class of the hierarchy
+            if hierarchy.contains(&super_class) {
+                return Err(ParsingError::from("Class is its own superclass"));
+            }
+            hierarchy.push(super_class);
+        }
+        let mut fields = HashMap::new();
+        let mut annotations = vec![];
+        for class in hierarchy.into_iter().rev() {
+            let (flags, class_fields) = {
+                let class = &self.classes[class];
+                (class.flags, class.fields.clone())
+            };
+            for (type_code, name) in class_fields {
+                let value = self.primitive(type_code)?;
+                fields.insert(name, value);
+            }
+            if flags & SC_WRITE_METHOD != 0 {
+                annotations.extend(self.annotations()?);
+            }
+        }
+        let object = JavaValue::Object {
+            class: self.classes[class].name.clone(),
+            fields,
+            annotations,
+        };
+        self.handles[handle] = Handle::Value(object.clone());
+        Ok(object)
+    }
+
+    /// The next value, `None` for block data
+    fn content(&mut self) -> Result<Option<JavaValue>, ParsingError> {
+        self.nested(Self::read_content)
+    }
+
+    fn read_content(&mut self) -> Result<Option<JavaValue>, ParsingError> {
+        let value = match self.u8()? {
+            TC_NULL => JavaValue::Null,
+            TC_OBJECT => self.object()?,
+            tc @ TC_STRING | tc @ TC_LONGSTRING => {
+                let string = JavaValue::String(self.utf(tc == TC_LONGSTRING)?);
+                self.handles.push(Handle::Value(string.clone()));
+                string
+            }
+            TC_REFERENCE => match self.reference()? {
+                Handle::Value(value) => value.clone(),
+                Handle::Class(_) => return Err(ParsingError::from("Handle is no value")),
+            },
+            TC_BLOCKDATA => {
+                let length = self.u8()? as usize;
+                self.take(length)?;
+                return Ok(None);
+            }
+            TC_BLOCKDATALONG => {
+                let length = self.u32()? as usize;
+                self.take(length)?;
+                return Ok(None);
+            }
+            tc => return Err(ParsingError::from(format!("Unsupported content {:x}", tc))),
+        };
+        Ok(Some(value))
+    }
+}
+
+/// Strings are UTF-16 code units in UTF-8 with a two byte encoding of NUL
+fn modified_utf8(bytes: &[u8]) -> String {
+    let mut units = vec![];
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = |n: usize| bytes.get(i + n).map_or(0, |b| (b & 0x3f) as u16);
+        let b = bytes[i] as u16;
+        if b < 0x80 {
+            units.push(b);
+            i += 1;
+        } else if b & 0xe0 == 0xc0 {
+            units.push((b & 0x1f) << 6 | byte(1));
+            i += 2;
+        } else {
+            units.push((b & 0x0f) << 12 | byte(1) << 6 | byte(2));
+            i += 3;
+        }
+    }
+    String::from_utf16_lossy(&units)
+}
+
+fn setting(value: JavaValue) -> Option<SettingValue> {
+    match value {
+        JavaValue::String(string) => Some(SettingValue::String(string)),
+        JavaValue::Object {
+            class,
+            mut fields,
+            annotations,
+        } => match class.as_str() {
+            "java.util.HashSet" | "java.util.LinkedHashSet" => Some(SettingValue::StringSet(
+                annotations
+                    .into_iter()
+                    .filter_map(|value| match value {
+                        JavaValue::String(string) => Some(string),
+                        _ => None,
+                    })
+                    .collect(),
+            )),
+            _ => match fields.remove("value")? {
+                JavaValue::Bool(value) => Some(SettingValue::Bool(value)),
+                JavaValue::Int(value) => Some(SettingValue::Int(value)),
+                JavaValue::Float(value) => Some(SettingValue::Float(value)),
+                _ => None,
+            },
+        },
+        _ => None,
+    }
+}
+
+/// Reads the `HashMap<String, ?>` of `newpipe.settings`
+pub fn from_java_serialized(input: &[u8]) -> Result<Settings, ParsingError> {
+    if !input.starts_with(&[0xac, 0xed, 0x00, 0x05]) {
+        return Err(ParsingError::from(
+            "newpipe.settings is no serialized object",
+        ));
+    }
+    let mut deserializer = Deserializer::new(&input[4..]);
+    let annotations = match deserializer.content()? {
+        Some(JavaValue::Object { annotations, .. }) => annotations,
+        _ => return Err(ParsingError::from("newpipe.settings holds no map")),
+    };
+    let mut settings = Settings::new();
+    let mut entries = annotations.into_iter();
+    while let (Some(key), Some(value)) = (entries.next(), entries.next()) {
+        if let JavaValue::String(key) = key {
+            match setting(value) {
+                Some(value) => {
+                    settings.insert(key, value);
+                }
+                None => tracing::debug!(key = %key, "skipping setting of unsupported type"),
+            }
+        }
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// `SharedPreferences.getAll()` of NewPipe written by `ObjectOutputStream`
+    pub(crate) const NEWPIPE_SETTINGS: &[u8] = include_bytes!("fixtures/newpipe.settings");
+
+    pub(crate) fn fixture_settings() -> Settings {
+        let string = |s: &str| SettingValue::String(s.to_string());
+        let set = |items: &[&str]| {
+            SettingValue::StringSet(items.iter().map(|item| item.to_string()).collect())
+        };
+        vec![
+            ("show_search_suggestions", SettingValue::Bool(true)),
+            ("last_resize_mode", SettingValue::Int(3)),
+            ("last_orientation_landscape", SettingValue::Bool(false)),
+            ("playback_speed", SettingValue::Float(1.25)),
+            ("watch_history_size", SettingValue::Int(9_000_000_000)),
+            ("default_resolution", string("720p")),
+            ("content_country", string("DE")),
+            // Surrogate pairs and NUL have their own encodings in modified UTF-8
+            ("app_language_key", string("été 🎵 \0")),
+            ("peertube_instance_list", string("[]")),
+            ("main_page_content", set(&["trending", "subscriptions"])),
+            ("enabled_notification_channels", set(&[])),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
+    }
+
+    #[test]
+    fn reads_newpipe_settings() {
+        let settings = from_java_serialized(NEWPIPE_SETTINGS).unwrap();
+        assert_eq!(settings, fixture_settings());
+        assert_eq!(from_json(&to_json(&settings)).unwrap(), settings);
+    }
+
+    /// Stream header and a class descriptor of `name` without fields
+    fn class(name: &str, flags: u8) -> Vec<u8> {
+        let mut class = vec![TC_CLASSDESC];
+        class.extend_from_slice(&(name.len() as u16).to_be_bytes());
+        class.extend_from_slice(name.as_bytes());
+        class.extend_from_slice(&[0; 8]);
+        class.push(flags);
+        class.extend_from_slice(&[0, 0, TC_ENDBLOCKDATA]);
+        class
+    }
+
+    #[test]
+    fn rejects_superclass_loops() {
+        // The superclass is a reference to the class itself
+        let mut input = vec![0xac, 0xed, 0x00, 0x05, TC_OBJECT];
+        input.extend(class("Loop", 0));
+        input.push(TC_REFERENCE);
+        input.extend_from_slice(&BASE_HANDLE.to_be_bytes());
+        let error = from_java_serialized(&input).unwrap_err();
+        assert!(error.to_string().contains("own superclass"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        // Objects whose annotations hold the next object, far deeper than settings go
+        let mut input = vec![0xac, 0xed, 0x00, 0x05];
+        for _ in 0..10_000 {
+            input.push(TC_OBJECT);
+            input.extend(class("Nested", SC_WRITE_METHOD));
+            input.push(TC_NULL);
+        }
+        let error = from_java_serialized(&input).unwrap_err();
+        assert!(error.to_string().contains("too deep"), "{}", error);
+    }
+}
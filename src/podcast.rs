@@ -0,0 +1,429 @@
+//! RSS 2.0 podcast feeds with iTunes tags for channels and playlists
+//!
+//! Enclosures point at a url template instead of the extracted stream urls, which expire
+//! after a few hours. The stream proxy of rusty-pipe-server serves `/{video_id}/{itag}`.
+
+use crate::downloader_trait::Downloader;
+use crate::utils::utils::escape_xml;
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::itag_item::Itag;
+use crate::youtube_extractor::link_handler::YTLink;
+use crate::youtube_extractor::playlist_extractor::YTPlaylistExtractor;
+use crate::youtube_extractor::stream_extractor::{Thumbnail, YTStreamExtractor};
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use futures::stream::{self, StreamExt};
+
+#[derive(Clone, Debug)]
+pub struct PodcastConfig {
+    /// Enclosure url, `{video_id}` and `{itag}` are replaced by the id of the episode's video
+    /// and `enclosure_itag`
+    pub enclosure_url: String,
+    /// Mime type of what `enclosure_url` serves
+    pub enclosure_type: String,
+    /// Stream served at `enclosure_url`, its size is the enclosure's length
+    pub enclosure_itag: u32,
+    /// How many pages of videos become episodes
+    pub pages: usize,
+    /// Load the page of every video for its full description, exact upload date and stream
+    /// size, one more request per episode. Without it sizes are estimated from the duration.
+    pub video_details: bool,
+    /// Video pages loaded at the same time
+    pub concurrency: usize,
+}
+
+impl Default for PodcastConfig {
+    fn default() -> Self {
+        PodcastConfig {
+            enclosure_url: "http://127.0.0.1:3000/{video_id}/{itag}".to_string(),
+            enclosure_type: "audio/mp4".to_string(),
+            enclosure_itag: 140,
+            pages: 1,
+            video_details: false,
+            concurrency: 4,
+        }
+    }
+}
+
+struct Show {
+    title: String,
+    link: String,
+    author: String,
+    description: String,
+    artwork: Option<String>,
+}
+
+struct Episode {
+    id: String,
+    title: String,
+    author: Option<String>,
+    description: Option<String>,
+    duration: Option<u64>,
+    pub_date: Option<String>,
+    image: Option<String>,
+    /// Bytes of the enclosure's stream
+    size: Option<u64>,
+}
+
+fn largest(thumbnails: Vec<Thumbnail>) -> Option<String> {
+    thumbnails
+        .into_iter()
+        .max_by_key(|t| t.width * t.height)
+        .map(|t| t.url)
+}
+
+/// `Fri, 01 Jan 2021 00:00:00 +0000`, with the two digit day of the RSS examples that chrono's
+/// `to_rfc2822` doesn't pad
+fn rfc2822(date: chrono::NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S +0000").to_string()
+}
+
+fn episode(video: &YTStreamInfoItemExtractor) -> Option<Episode> {
+    let duration = match video.is_live() {
+        Ok(false) => video.duration().ok().map(|d| d as u64),
+        _ => None,
+    };
+    Some(Episode {
+        id: video.video_id().ok()?,
+        title: video.name().ok()?,
+        author: video.uploader_name().ok(),
+        description: None,
+        duration,
+        pub_date: video.upload_date().ok().map(rfc2822),
+        image: largest(video.thumbnails().unwrap_or_default()),
+        size: None,
+    })
+}
+
+/// Fills in the description, upload date and stream size from the video's page
+async fn add_details<D: Downloader>(downloader: D, mut episode: Episode, itag: u32) -> Episode {
+    match YTStreamExtractor::new(downloader, &episode.id).await {
+        Ok(extractor) => {
+            episode.description = extractor.description(false).ok().map(|(d, _)| d);
+            if let Ok(date) = extractor.upload_date() {
+                episode.pub_date = date.and_hms_opt(0, 0, 0).map(rfc2822);
+            }
+            if let Ok(length) = extractor.length() {
+                episode.duration = Some(length);
+            }
+            episode.size = [
+                extractor.audio_streams(),
+                extractor.video_stream(),
+                extractor.video_only_stream(),
+            ]
+            .iter()
+            .flatten()
+            .flatten()
+            .find(|stream| stream.itag == itag)
+            .and_then(|stream| stream.content_length.as_ref()?.parse().ok());
+        }
+        Err(e) => tracing::warn!(video_id = %episode.id, error = %e, "cannot load episode details"),
+    }
+    episode
+}
+
+async fn episodes<D: Downloader + Clone>(
+    downloader: &D,
+    videos: &[YTStreamInfoItemExtractor],
+    config: &PodcastConfig,
+) -> Vec<Episode> {
+    let episodes = videos.iter().filter_map(episode);
+    if !config.video_details {
+        return episodes.collect();
+    }
+    stream::iter(episodes)
+        .map(|episode| add_details(downloader.clone(), episode, config.enclosure_itag))
+        .buffered(config.concurrency.max(1))
+        .collect()
+        .await
+}
+
+/// `h:mm:ss`
+fn itunes_duration(seconds: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Written when the size is unknown, podcatchers reject a length of 0
+const UNKNOWN_LENGTH: u64 = 1;
+
+/// Size of the enclosure, estimated from the duration and the itag's average bitrate when the
+/// video page wasn't loaded. Live videos and itags without an average bitrate, which are the
+/// video ones, get [`UNKNOWN_LENGTH`].
+fn enclosure_length(episode: &Episode, itag: u32) -> u64 {
+    episode
+        .size
+        .or_else(|| {
+            let kbps = Itag::itag(itag as i64).ok()?.avg_bitrate as u64;
+            Some(episode.duration? * kbps * 1000 / 8)
+        })
+        .filter(|length| *length > 0)
+        .unwrap_or(UNKNOWN_LENGTH)
+}
+
+fn render(show: &Show, episodes: &[Episode], config: &PodcastConfig) -> String {
+    let mut feed = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+         <channel>\n",
+    );
+    feed.push_str(&format!("<title>{}</title>\n", escape_xml(&show.title)));
+    feed.push_str(&format!("<link>{}</link>\n", escape_xml(&show.link)));
+    feed.push_str(&format!(
+        "<description>{}</description>\n",
+        escape_xml(&show.description)
+    ));
+    feed.push_str("<generator>rusty_pipe</generator>\n");
+    feed.push_str(&format!(
+        "<itunes:author>{}</itunes:author>\n",
+        escape_xml(&show.author)
+    ));
+    feed.push_str(&format!(
+        "<itunes:summary>{}</itunes:summary>\n",
+        escape_xml(&show.description)
+    ));
+    if let Some(artwork) = &show.artwork {
+        feed.push_str(&format!(
+            "<itunes:image href=\"{}\"/>\n",
+            escape_xml(artwork)
+        ));
+        feed.push_str(&format!(
+            "<image><url>{}</url><title>{}</title><link>{}</link></image>\n",
+            escape_xml(artwork),
+            escape_xml(&show.title),
+            escape_xml(&show.link)
+        ));
+    }
+    for episode in episodes {
+        let link = YTLink::Stream(episode.id.clone()).url();
+        let enclosure = config
+            .enclosure_url
+            .replace("{video_id}", &episode.id)
+            .replace("{itag}", &config.enclosure_itag.to_string());
+        feed.push_str("<item>\n");
+        feed.push_str(&format!("<title>{}</title>\n", escape_xml(&episode.title)));
+        feed.push_str(&format!("<link>{}</link>\n", escape_xml(&link)));
+        feed.push_str(&format!(
+            "<guid isPermaLink=\"false\">{}</guid>\n",
+            escape_xml(&episode.id)
+        ));
+        feed.push_str(&format!(
+            "<enclosure url=\"{}\" type=\"{}\" length=\"{}\"/>\n",
+            escape_xml(&enclosure),
+            escape_xml(&config.enclosure_type),
+            enclosure_length(episode, config.enclosure_itag)
+        ));
+        if let Some(pub_date) = &episode.pub_date {
+            feed.push_str(&format!("<pubDate>{}</pubDate>\n", pub_date));
+        }
+        let description = episode.description.as_deref().unwrap_or(&episode.title);
+        feed.push_str(&format!(
+            "<description>{}</description>\n",
+            escape_xml(description)
+        ));
+        feed.push_str(&format!(
+            "<itunes:summary>{}</itunes:summary>\n",
+            escape_xml(description)
+        ));
+        if let Some(author) = &episode.author {
+            feed.push_str(&format!(
+                "<itunes:author>{}</itunes:author>\n",
+                escape_xml(author)
+            ));
+        }
+        if let Some(duration) = episode.duration {
+            feed.push_str(&format!(
+                "<itunes:duration>{}</itunes:duration>\n",
+                itunes_duration(duration)
+            ));
+        }
+        if let Some(image) = &episode.image {
+            feed.push_str(&format!("<itunes:image href=\"{}\"/>\n", escape_xml(image)));
+        }
+        feed.push_str("</item>\n");
+    }
+    feed.push_str("</channel>\n</rss>\n");
+    feed
+}
+
+/// Feed of the newest videos of a channel, `channel_id` takes the same forms as
+/// `YTChannelExtractor::new`
+pub async fn channel_feed<D: Downloader + Clone>(
+    downloader: D,
+    channel_id: &str,
+    config: &PodcastConfig,
+) -> Result<String, ParsingError> {
+    let extractor = YTChannelExtractor::new(downloader.clone(), channel_id, None).await?;
+    let mut videos = extractor.videos()?;
+    let mut next_page = extractor.next_page_url()?;
+    for _ in 1..config.pages {
+        let page_url = match next_page {
+            Some(page_url) => page_url,
+            None => break,
+        };
+        let page = YTChannelExtractor::new(downloader.clone(), channel_id, Some(page_url)).await?;
+        videos.extend(page.videos()?);
+        next_page = page.next_page_url()?;
+    }
+    let name = extractor.name()?;
+    let show = Show {
+        title: name.clone(),
+        link: YTLink::Channel(channel_id.to_string()).url(),
+        description: format!("Videos of {} on YouTube", name),
+        author: name,
+        artwork: largest(extractor.avatars().unwrap_or_default()),
+    };
+    let episodes = episodes(&downloader, &videos, config).await;
+    Ok(render(&show, &episodes, config))
+}
+
+/// Feed of a playlist, episodes keep the playlist's order
+pub async fn playlist_feed<D: Downloader + Clone>(
+    downloader: D,
+    playlist_id: &str,
+    config: &PodcastConfig,
+) -> Result<String, ParsingError> {
+    let extractor = YTPlaylistExtractor::new(downloader.clone(), playlist_id, None).await?;
+    let mut videos = extractor.videos()?;
+    let mut next_page = extractor.next_page_url()?;
+    for _ in 1..config.pages {
+        let page_url = match next_page {
+            Some(page_url) => page_url,
+            None => break,
+        };
+        let page =
+            YTPlaylistExtractor::new(downloader.clone(), playlist_id, Some(page_url)).await?;
+        videos.extend(page.videos()?);
+        next_page = page.next_page_url()?;
+    }
+    let title = extractor.name()?;
+    let author = extractor.uploader_name().unwrap_or_default();
+    let show = Show {
+        description: format!("{} by {} on YouTube", title, author),
+        title,
+        link: YTLink::Playlist(playlist_id.to_string()).url(),
+        author,
+        artwork: largest(extractor.uploader_avatars().unwrap_or_default())
+            .or_else(|| largest(extractor.thumbnails().unwrap_or_default())),
+    };
+    let episodes = episodes(&downloader, &videos, config).await;
+    Ok(render(&show, &episodes, config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn episode(id: &str) -> Episode {
+        Episode {
+            id: id.to_string(),
+            title: format!("Episode {}", id),
+            author: None,
+            description: None,
+            duration: None,
+            pub_date: None,
+            image: None,
+            size: None,
+        }
+    }
+
+    #[test]
+    fn enclosure_lengths() {
+        let sized = Episode {
+            size: Some(1234),
+            duration: Some(60),
+            ..episode("a")
+        };
+        assert_eq!(enclosure_length(&sized, 140), 1234);
+        // 128 kbps for a minute
+        let timed = Episode {
+            duration: Some(60),
+            ..episode("b")
+        };
+        assert_eq!(enclosure_length(&timed, 140), 960_000);
+        // Video itags have no average bitrate, unknown itags none at all
+        assert_eq!(enclosure_length(&timed, 137), UNKNOWN_LENGTH);
+        assert_eq!(enclosure_length(&timed, 1), UNKNOWN_LENGTH);
+        assert_eq!(enclosure_length(&episode("c"), 140), UNKNOWN_LENGTH);
+        let empty = Episode {
+            size: Some(0),
+            ..episode("d")
+        };
+        assert_eq!(enclosure_length(&empty, 140), UNKNOWN_LENGTH);
+    }
+
+    #[test]
+    fn renders_rss_with_itunes_tags() {
+        let show = Show {
+            title: "Fish & Chips".to_string(),
+            link: "https://www.youtube.com/channel/UCfish".to_string(),
+            author: "Cook <Co>".to_string(),
+            description: "Videos of Fish & Chips on YouTube".to_string(),
+            artwork: Some("https://yt3.ggpht.com/avatar?s=900&c=1".to_string()),
+        };
+        let date = chrono::NaiveDate::from_ymd_opt(2021, 1, 1)
+            .and_then(|d| d.and_hms_opt(9, 5, 0))
+            .unwrap();
+        let episodes = vec![
+            Episode {
+                author: Some("Cook <Co>".to_string()),
+                description: Some("Batter \"up\" & fry".to_string()),
+                duration: Some(3723),
+                pub_date: Some(rfc2822(date)),
+                image: Some("https://i.ytimg.com/vi/aaaaaaaaaaa/hq.jpg".to_string()),
+                size: Some(59_568_000),
+                ..episode("aaaaaaaaaaa")
+            },
+            episode("bbbbbbbbbbb"),
+        ];
+        let config = PodcastConfig {
+            enclosure_url: "https://proxy.example/{video_id}.m4a?itag={itag}&v={video_id}"
+                .to_string(),
+            ..PodcastConfig::default()
+        };
+        assert_eq!(
+            render(&show, &episodes, &config),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\">\n\
+             <channel>\n\
+             <title>Fish &amp; Chips</title>\n\
+             <link>https://www.youtube.com/channel/UCfish</link>\n\
+             <description>Videos of Fish &amp; Chips on YouTube</description>\n\
+             <generator>rusty_pipe</generator>\n\
+             <itunes:author>Cook &lt;Co&gt;</itunes:author>\n\
+             <itunes:summary>Videos of Fish &amp; Chips on YouTube</itunes:summary>\n\
+             <itunes:image href=\"https://yt3.ggpht.com/avatar?s=900&amp;c=1\"/>\n\
+             <image><url>https://yt3.ggpht.com/avatar?s=900&amp;c=1</url>\
+             <title>Fish &amp; Chips</title>\
+             <link>https://www.youtube.com/channel/UCfish</link></image>\n\
+             <item>\n\
+             <title>Episode aaaaaaaaaaa</title>\n\
+             <link>https://www.youtube.com/watch?v=aaaaaaaaaaa</link>\n\
+             <guid isPermaLink=\"false\">aaaaaaaaaaa</guid>\n\
+             <enclosure url=\"https://proxy.example/aaaaaaaaaaa.m4a?itag=140&amp;v=aaaaaaaaaaa\" \
+             type=\"audio/mp4\" length=\"59568000\"/>\n\
+             <pubDate>Fri, 01 Jan 2021 09:05:00 +0000</pubDate>\n\
+             <description>Batter &quot;up&quot; &amp; fry</description>\n\
+             <itunes:summary>Batter &quot;up&quot; &amp; fry</itunes:summary>\n\
+             <itunes:author>Cook &lt;Co&gt;</itunes:author>\n\
+             <itunes:duration>1:02:03</itunes:duration>\n\
+             <itunes:image href=\"https://i.ytimg.com/vi/aaaaaaaaaaa/hq.jpg\"/>\n\
+             </item>\n\
+             <item>\n\
+             <title>Episode bbbbbbbbbbb</title>\n\
+             <link>https://www.youtube.com/watch?v=bbbbbbbbbbb</link>\n\
+             <guid isPermaLink=\"false\">bbbbbbbbbbb</guid>\n\
+             <enclosure url=\"https://proxy.example/bbbbbbbbbbb.m4a?itag=140&amp;v=bbbbbbbbbbb\" \
+             type=\"audio/mp4\" length=\"1\"/>\n\
+             <description>Episode bbbbbbbbbbb</description>\n\
+             <itunes:summary>Episode bbbbbbbbbbb</itunes:summary>\n\
+             </item>\n\
+             </channel>\n\
+             </rss>\n"
+        );
+    }
+}
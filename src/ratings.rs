@@ -0,0 +1,149 @@
+//! Like and dislike counts from outside of YouTube, which stopped showing dislikes
+
+use crate::downloader_trait::Downloader;
+use crate::youtube_extractor::error::ParsingError;
+use async_trait::async_trait;
+use serde_json::Value;
+
+pub const RETURN_YOUTUBE_DISLIKE_API_URL: &str = "https://returnyoutubedislikeapi.com";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RatingSource {
+    /// The watch page
+    YouTube,
+    ReturnYouTubeDislike,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RatingCount {
+    pub count: i128,
+    pub source: RatingSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ratings {
+    pub likes: Option<i128>,
+    pub dislikes: Option<i128>,
+    pub source: RatingSource,
+}
+
+#[cfg(target_arch = "wasm32")]
+#[async_trait(?Send)]
+pub trait RatingsProvider {
+    async fn ratings(&self, video_id: &str) -> Result<Ratings, ParsingError>;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait()]
+pub trait RatingsProvider {
+    async fn ratings(&self, video_id: &str) -> Result<Ratings, ParsingError>;
+}
+
+/// Counts from [Return YouTube Dislike](https://returnyoutubedislike.com), with dislikes
+/// extrapolated from its users' votes
+pub struct ReturnYouTubeDislike<D> {
+    downloader: D,
+    api_url: String,
+}
+
+impl<D: Downloader> ReturnYouTubeDislike<D> {
+    pub fn new(downloader: D) -> Self {
+        ReturnYouTubeDislike {
+            downloader,
+            api_url: RETURN_YOUTUBE_DISLIKE_API_URL.to_string(),
+        }
+    }
+
+    /// Uses another instance or a mock server
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn url(&self, video_id: &str) -> String {
+        format!("{}/votes?videoId={}", self.api_url, video_id)
+    }
+}
+
+/// Counts of a `votes` response
+pub fn parse_votes(response: &str) -> Result<Ratings, ParsingError> {
+    let votes: Value = serde_json::from_str(response).map_err(|e| e.to_string())?;
+    let count = |key: &str| votes.get(key).and_then(|c| c.as_i64()).map(i128::from);
+    if votes.get("deleted").and_then(|d| d.as_bool()) == Some(true) {
+        return Err(ParsingError::from("video was deleted"));
+    }
+    Ok(Ratings {
+        likes: count("likes"),
+        dislikes: Some(count("dislikes").ok_or("votes response has no dislikes")?),
+        source: RatingSource::ReturnYouTubeDislike,
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+#[async_trait(?Send)]
+impl<D: Downloader> RatingsProvider for ReturnYouTubeDislike<D> {
+    async fn ratings(&self, video_id: &str) -> Result<Ratings, ParsingError> {
+        let response = self.downloader.download(&self.url(video_id)).await?;
+        parse_votes(&response)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait()]
+impl<D: Downloader + Send + Sync> RatingsProvider for ReturnYouTubeDislike<D> {
+    async fn ratings(&self, video_id: &str) -> Result<Ratings, ParsingError> {
+        let response = self.downloader.download(&self.url(video_id)).await?;
+        parse_votes(&response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_votes() {
+        let ratings = parse_votes(
+            r#"{"id":"dQw4w9WgXcQ","likes":16843531,"dislikes":481862,"rating":4.89,
+                "viewCount":1438307512,"deleted":false}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ratings,
+            Ratings {
+                likes: Some(16843531),
+                dislikes: Some(481862),
+                source: RatingSource::ReturnYouTubeDislike,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_deleted_videos() {
+        let error = parse_votes(r#"{"likes":0,"dislikes":0,"deleted":true}"#).unwrap_err();
+        assert!(error.to_string().contains("deleted"), "{}", error);
+    }
+
+    #[test]
+    fn missing_and_non_numeric_counts() {
+        // Likes are optional, YouTube usually has them
+        let ratings = parse_votes(r#"{"dislikes":12}"#).unwrap();
+        assert_eq!((ratings.likes, ratings.dislikes), (None, Some(12)));
+        let ratings = parse_votes(r#"{"likes":"many","dislikes":12}"#).unwrap();
+        assert_eq!((ratings.likes, ratings.dislikes), (None, Some(12)));
+
+        for response in [
+            r#"{"likes":10}"#,
+            r#"{"likes":10,"dislikes":"12"}"#,
+            r#"{"likes":10,"dislikes":1.5}"#,
+            r#"{"likes":10,"dislikes":null}"#,
+            "{}",
+            "[]",
+            "Not Found",
+        ]
+        .iter()
+        {
+            assert!(parse_votes(response).is_err(), "{}", response);
+        }
+    }
+}
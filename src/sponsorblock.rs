@@ -0,0 +1,331 @@
+//! Crowdsourced segments of videos from [SponsorBlock](https://sponsor.ajay.app)
+
+use crate::downloader_trait::Downloader;
+use crate::utils::sha256::sha256_hex;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::stream_extractor::YTStreamExtractor;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde_json::Value;
+
+pub const DEFAULT_API_URL: &str = "https://sponsor.ajay.app";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Category {
+    Sponsor,
+    SelfPromo,
+    Intro,
+    Outro,
+    MusicOfftopic,
+    PoiHighlight,
+}
+
+impl Category {
+    pub const ALL: [Category; 6] = [
+        Category::Sponsor,
+        Category::SelfPromo,
+        Category::Intro,
+        Category::Outro,
+        Category::MusicOfftopic,
+        Category::PoiHighlight,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Category::Sponsor => "sponsor",
+            Category::SelfPromo => "selfpromo",
+            Category::Intro => "intro",
+            Category::Outro => "outro",
+            Category::MusicOfftopic => "music_offtopic",
+            Category::PoiHighlight => "poi_highlight",
+        }
+    }
+
+    pub fn from_name(category: &str) -> Option<Category> {
+        Category::ALL
+            .iter()
+            .copied()
+            .find(|c| c.as_str() == category)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Skip,
+    Mute,
+    /// The whole video is of the category, the segment spans nothing
+    Full,
+    /// A point of interest at `start`
+    Poi,
+    Chapter,
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [
+        Action::Skip,
+        Action::Mute,
+        Action::Full,
+        Action::Poi,
+        Action::Chapter,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Skip => "skip",
+            Action::Mute => "mute",
+            Action::Full => "full",
+            Action::Poi => "poi",
+            Action::Chapter => "chapter",
+        }
+    }
+
+    pub fn from_name(action: &str) -> Option<Action> {
+        Action::ALL.iter().copied().find(|a| a.as_str() == action)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Segment {
+    /// Seconds
+    pub start: f64,
+    pub end: f64,
+    pub category: Category,
+    pub action: Action,
+}
+
+/// Hex SHA-256 of a video id, whose prefix identifies the video in privacy preserving lookups
+pub fn video_hash(video_id: &str) -> String {
+    sha256_hex(video_id.as_bytes())
+}
+
+fn json_list(values: &[&str]) -> String {
+    let list = serde_json::to_string(values).unwrap_or_default();
+    utf8_percent_encode(&list, NON_ALPHANUMERIC).to_string()
+}
+
+fn parse_segment(segment: &Value) -> Option<Segment> {
+    let times = segment.get("segment")?.as_array()?;
+    Some(Segment {
+        start: times.first()?.as_f64()?,
+        end: times.get(1)?.as_f64()?,
+        category: Category::from_name(segment.get("category")?.as_str()?)?,
+        action: Action::from_name(segment.get("actionType")?.as_str()?)?,
+    })
+}
+
+/// Segments of the video with id `video_id` in a `skipSegments` response of a hash prefix
+pub fn parse_segments(response: &str, video_id: &str) -> Result<Vec<Segment>, ParsingError> {
+    let videos: Value = serde_json::from_str(response).map_err(|e| e.to_string())?;
+    let videos = videos
+        .as_array()
+        .ok_or("skipSegments response is not an array")?;
+    let mut segments: Vec<Segment> = videos
+        .iter()
+        .filter(|video| video.get("videoID").and_then(|id| id.as_str()) == Some(video_id))
+        .filter_map(|video| video.get("segments").and_then(|s| s.as_array()))
+        .flatten()
+        .filter_map(parse_segment)
+        .collect();
+    segments.sort_by(|a, b| a.start.total_cmp(&b.start));
+    Ok(segments)
+}
+
+pub struct SponsorBlock<D> {
+    downloader: D,
+    api_url: String,
+    categories: Vec<Category>,
+    /// Hex digits of the video id's SHA-256 sent to the server, 4 to 32
+    hash_prefix_length: usize,
+}
+
+impl<D: Downloader> SponsorBlock<D> {
+    pub fn new(downloader: D) -> Self {
+        SponsorBlock {
+            downloader,
+            api_url: DEFAULT_API_URL.to_string(),
+            categories: Category::ALL.to_vec(),
+            hash_prefix_length: 4,
+        }
+    }
+
+    /// Uses another instance or a mock server
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn with_categories(mut self, categories: Vec<Category>) -> Self {
+        self.categories = categories;
+        self
+    }
+
+    pub fn with_hash_prefix_length(mut self, length: usize) -> Self {
+        self.hash_prefix_length = length.clamp(4, 32);
+        self
+    }
+
+    /// Only the prefix of the id's hash is sent, the server answers with the segments of every
+    /// video sharing it and the ones of `video_id` are picked out here
+    pub fn url(&self, video_id: &str) -> String {
+        let hash = video_hash(video_id);
+        let categories: Vec<&str> = self.categories.iter().map(|c| c.as_str()).collect();
+        let actions: Vec<&str> = Action::ALL.iter().map(|a| a.as_str()).collect();
+        format!(
+            "{}/api/skipSegments/{}?categories={}&actionTypes={}",
+            self.api_url,
+            &hash[..self.hash_prefix_length],
+            json_list(&categories),
+            json_list(&actions)
+        )
+    }
+
+    /// Segments sorted by start, empty if the video has none
+    pub async fn segments(&self, video_id: &str) -> Result<Vec<Segment>, ParsingError> {
+        if self.categories.is_empty() {
+            return Ok(vec![]);
+        }
+        match self.downloader.download(&self.url(video_id)).await {
+            Ok(response) => parse_segments(&response, video_id),
+            Err(error) if error.http_status() == Some(404) => Ok(vec![]),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Segments of the extracted video, with ends past its length cut off
+    pub async fn stream_segments<E: Downloader>(
+        &self,
+        extractor: &YTStreamExtractor<E>,
+    ) -> Result<Vec<Segment>, ParsingError> {
+        let mut segments = self.segments(&extractor.video_id()).await?;
+        if let Ok(length) = extractor.length() {
+            let length = length as f64;
+            segments.retain(|s| s.start <= length);
+            for segment in &mut segments {
+                segment.end = segment.end.min(length);
+            }
+        }
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use futures::executor::block_on;
+    use std::collections::HashMap;
+
+    const RESPONSE: &str = r#"[
+        {"videoID": "otherVideo1", "hash": "5f6b…", "segments": [
+            {"segment": [1.0, 2.0], "category": "sponsor", "actionType": "skip"}
+        ]},
+        {"videoID": "dQw4w9WgXcQ", "hash": "5f6b…", "segments": [
+            {"segment": [90.5, 100.0], "category": "outro", "actionType": "skip"},
+            {"segment": [12.0, 20.25], "category": "music_offtopic", "actionType": "mute"},
+            {"segment": [0.0, 0.0], "category": "sponsor", "actionType": "full"},
+            {"segment": [43.0, 43.0], "category": "poi_highlight", "actionType": "poi"},
+            {"segment": [60.0, 75.0], "category": "chapter", "actionType": "chapter"},
+            {"segment": [5.0, 8.0], "category": "filler", "actionType": "skip"},
+            {"segment": [6.0, 9.0], "category": "intro", "actionType": "unknown"},
+            {"segment": [7.0], "category": "intro", "actionType": "skip"}
+        ]}
+    ]"#;
+
+    /// Answers every request with `status`, and `RESPONSE` for a 200
+    struct Api {
+        status: u16,
+    }
+
+    #[async_trait]
+    impl Downloader for Api {
+        async fn download(&self, url: &str) -> Result<String, ParsingError> {
+            match self.status {
+                200 => Ok(RESPONSE.to_string()),
+                status => Err(ParsingError::HttpError {
+                    cause: format!("{} returned {}", url, status),
+                    status,
+                }),
+            }
+        }
+
+        async fn download_with_header(
+            &self,
+            url: &str,
+            _header: HashMap<String, String>,
+        ) -> Result<String, ParsingError> {
+            self.download(url).await
+        }
+
+        fn eval_js(&self, _script: &str) -> Result<String, String> {
+            unimplemented!()
+        }
+    }
+
+    fn segment(start: f64, end: f64, category: Category, action: Action) -> Segment {
+        Segment {
+            start,
+            end,
+            category,
+            action,
+        }
+    }
+
+    #[test]
+    fn video_hash_of_a_known_video() {
+        assert_eq!(
+            video_hash("dQw4w9WgXcQ"),
+            "5f6b0b4e201f2a7e66927abb5cadeec81624dcc8efe6644b78aa182213f653a2"
+        );
+        let url = SponsorBlock::new(Api { status: 200 })
+            .with_hash_prefix_length(6)
+            .url("dQw4w9WgXcQ");
+        assert!(
+            url.starts_with("https://sponsor.ajay.app/api/skipSegments/5f6b0b?"),
+            "{}",
+            url
+        );
+    }
+
+    #[test]
+    fn parses_segments() {
+        // Sorted, without the other video's and the ones of unknown categories or actions
+        assert_eq!(
+            parse_segments(RESPONSE, "dQw4w9WgXcQ").unwrap(),
+            vec![
+                segment(0.0, 0.0, Category::Sponsor, Action::Full),
+                segment(12.0, 20.25, Category::MusicOfftopic, Action::Mute),
+                segment(43.0, 43.0, Category::PoiHighlight, Action::Poi),
+                segment(90.5, 100.0, Category::Outro, Action::Skip),
+            ]
+        );
+        assert_eq!(parse_segments(RESPONSE, "notInThere1").unwrap(), vec![]);
+        assert!(parse_segments("{}", "dQw4w9WgXcQ").is_err());
+    }
+
+    #[test]
+    fn requests_categories() {
+        let url = SponsorBlock::new(Api { status: 200 })
+            .with_categories(vec![Category::Sponsor, Category::MusicOfftopic])
+            .url("dQw4w9WgXcQ");
+        let query = percent_encoding::percent_decode_str(url.split('?').nth(1).unwrap())
+            .decode_utf8()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            query,
+            "categories=[\"sponsor\",\"music_offtopic\"]\
+             &actionTypes=[\"skip\",\"mute\",\"full\",\"poi\",\"chapter\"]"
+        );
+
+        // Nothing to ask for, the downloader would fail
+        let none = SponsorBlock::new(Api { status: 500 }).with_categories(vec![]);
+        assert_eq!(block_on(none.segments("dQw4w9WgXcQ")).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn not_found_means_no_segments() {
+        let segments = |status| block_on(SponsorBlock::new(Api { status }).segments("dQw4w9WgXcQ"));
+        assert_eq!(segments(404).unwrap(), vec![]);
+        assert_eq!(segments(500).unwrap_err().http_status(), Some(500));
+        assert_eq!(segments(200).unwrap().len(), 4);
+    }
+}
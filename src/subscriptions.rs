@@ -0,0 +1,548 @@
+//! Channel subscriptions exported by YouTube (Google Takeout), NewPipe and RSS readers (OPML)
+
+use crate::downloader_trait::Downloader;
+use crate::utils::utils::escape_xml;
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::error::ParsingError;
+use crate::youtube_extractor::link_handler::YTLink;
+use quick_xml::events::Event;
+use serde_json::{json, Value};
+
+/// Streaming service of a subscription, by NewPipe's service ids
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    YouTube,
+    SoundCloud,
+    MediaCCC,
+    PeerTube,
+    Bandcamp,
+    Other(i64),
+}
+
+impl Service {
+    pub fn from_id(id: i64) -> Self {
+        match id {
+            0 => Service::YouTube,
+            1 => Service::SoundCloud,
+            2 => Service::MediaCCC,
+            3 => Service::PeerTube,
+            4 => Service::Bandcamp,
+            id => Service::Other(id),
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        match self {
+            Service::YouTube => 0,
+            Service::SoundCloud => 1,
+            Service::MediaCCC => 2,
+            Service::PeerTube => 3,
+            Service::Bandcamp => 4,
+            Service::Other(id) => *id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subscription {
+    pub service: Service,
+    /// `UC…` id, or a handle, `user/name` or `c/name` path until
+    /// [`resolve_channel_ids`] ran. The url for services other than YouTube.
+    pub channel_id: String,
+    pub name: String,
+    pub url: String,
+}
+
+impl Subscription {
+    /// Subscription to a YouTube channel, `channel` is a channel url, id or handle
+    pub fn youtube(channel: &str, name: &str) -> Option<Self> {
+        let channel_id = match YTLink::parse_or_id(channel)? {
+            YTLink::Channel(id) => id,
+            _ => return None,
+        };
+        Some(Subscription {
+            service: Service::YouTube,
+            url: YTLink::Channel(channel_id.clone()).url(),
+            channel_id,
+            name: name.to_string(),
+        })
+    }
+
+    /// Subscription to a channel url of any service, as NewPipe stores them
+    pub fn new(service: Service, url: &str, name: &str) -> Option<Self> {
+        if service == Service::YouTube {
+            return Self::youtube(url, name);
+        }
+        Some(Subscription {
+            service,
+            channel_id: url.to_string(),
+            name: name.to_string(),
+            url: url.to_string(),
+        })
+    }
+
+    /// Whether `channel_id` is a `UC…` id, always true for other services
+    pub fn is_resolved(&self) -> bool {
+        self.service != Service::YouTube || self.channel_id.starts_with("UC")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubscriptionFormat {
+    /// `subscriptions.csv` of Google Takeout
+    TakeoutCsv,
+    /// `subscriptions.json` of older Google Takeout exports
+    TakeoutJson,
+    /// `subscriptions.json` exported by NewPipe
+    NewPipe,
+    Opml,
+}
+
+impl SubscriptionFormat {
+    /// Guesses the format from the start of an export
+    pub fn detect(input: &str) -> Self {
+        let input = input.trim_start_matches('\u{feff}').trim_start();
+        if input.starts_with('<') {
+            SubscriptionFormat::Opml
+        } else if input.starts_with('{') {
+            SubscriptionFormat::NewPipe
+        } else if input.starts_with('[') {
+            SubscriptionFormat::TakeoutJson
+        } else {
+            SubscriptionFormat::TakeoutCsv
+        }
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Vec<Subscription>, ParsingError> {
+        let input = input.trim_start_matches('\u{feff}');
+        match self {
+            SubscriptionFormat::TakeoutCsv => Ok(parse_takeout_csv(input)),
+            SubscriptionFormat::TakeoutJson => parse_takeout_json(input),
+            SubscriptionFormat::NewPipe => parse_newpipe(input),
+            SubscriptionFormat::Opml => parse_opml(input),
+        }
+    }
+
+    /// Subscriptions to other services than YouTube are only kept by NewPipe. Takeout only
+    /// holds `UC…` ids, so it fails for subscriptions [`resolve_channel_ids`] did not resolve.
+    /// OPML links their channel page without a feed.
+    pub fn write(&self, subscriptions: &[Subscription]) -> Result<String, ParsingError> {
+        let youtube = subscriptions
+            .iter()
+            .filter(|s| s.service == Service::YouTube);
+        if let SubscriptionFormat::TakeoutCsv | SubscriptionFormat::TakeoutJson = self {
+            if let Some(unresolved) = youtube.clone().find(|s| !s.is_resolved()) {
+                return Err(ParsingError::from(format!(
+                    "Channel id of {} is unresolved: {}",
+                    unresolved.name, unresolved.channel_id
+                )));
+            }
+        }
+        Ok(match self {
+            SubscriptionFormat::TakeoutCsv => write_takeout_csv(youtube),
+            SubscriptionFormat::TakeoutJson => write_takeout_json(youtube),
+            SubscriptionFormat::NewPipe => write_newpipe(subscriptions),
+            SubscriptionFormat::Opml => write_opml(youtube),
+        })
+    }
+}
+
+fn skipped(entry: &str) {
+    tracing::warn!(entry, "skipping subscription without a youtube channel");
+}
+
+/// Rows of a CSV file, quoted fields may contain commas, quotes and newlines
+fn csv_rows(input: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Rows of `Channel Id,Channel Url,Channel Title`
+fn parse_takeout_csv(input: &str) -> Vec<Subscription> {
+    let mut subscriptions = vec![];
+    for row in csv_rows(input) {
+        let id = row.first().map(|id| id.trim()).unwrap_or_default();
+        let url = row.get(1).map(|url| url.trim()).unwrap_or_default();
+        if (id.is_empty() && url.is_empty()) || id.eq_ignore_ascii_case("Channel Id") {
+            continue;
+        }
+        let name = row.get(2).map(|name| name.trim()).unwrap_or_default();
+        match Subscription::youtube(id, name).or_else(|| Subscription::youtube(url, name)) {
+            Some(subscription) => subscriptions.push(subscription),
+            None => skipped(id),
+        }
+    }
+    subscriptions
+}
+
+fn write_takeout_csv<'a>(subscriptions: impl Iterator<Item = &'a Subscription>) -> String {
+    let mut csv = String::from("Channel Id,Channel Url,Channel Title\n");
+    for subscription in subscriptions {
+        csv.push_str(&format!(
+            "{},{},{}\n",
+            csv_field(&subscription.channel_id),
+            csv_field(&format!(
+                "http://www.youtube.com/channel/{}",
+                subscription.channel_id
+            )),
+            csv_field(&subscription.name)
+        ));
+    }
+    csv
+}
+
+/// Array of `youtube#subscription` resources
+fn parse_takeout_json(input: &str) -> Result<Vec<Subscription>, ParsingError> {
+    let json: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let mut subscriptions = vec![];
+    for item in json
+        .as_array()
+        .ok_or("Takeout subscriptions are not an array")?
+    {
+        let snippet = &item["snippet"];
+        let id = snippet["resourceId"]["channelId"]
+            .as_str()
+            .unwrap_or_default();
+        let name = snippet["title"].as_str().unwrap_or_default();
+        match Subscription::youtube(id, name) {
+            Some(subscription) => subscriptions.push(subscription),
+            None => skipped(id),
+        }
+    }
+    Ok(subscriptions)
+}
+
+fn write_takeout_json<'a>(subscriptions: impl Iterator<Item = &'a Subscription>) -> String {
+    let items: Vec<Value> = subscriptions
+        .map(|subscription| {
+            json!({
+                "kind": "youtube#subscription",
+                "snippet": {
+                    "title": subscription.name,
+                    "resourceId": {
+                        "kind": "youtube#channel",
+                        "channelId": subscription.channel_id,
+                    },
+                },
+            })
+        })
+        .collect();
+    serde_json::to_string_pretty(&items).unwrap_or_default()
+}
+
+fn parse_newpipe(input: &str) -> Result<Vec<Subscription>, ParsingError> {
+    let json: Value = serde_json::from_str(input).map_err(|e| e.to_string())?;
+    let mut subscriptions = vec![];
+    for item in json["subscriptions"]
+        .as_array()
+        .ok_or("No subscriptions in NewPipe export")?
+    {
+        let service = Service::from_id(item["service_id"].as_i64().unwrap_or_default());
+        let url = item["url"].as_str().unwrap_or_default();
+        let name = item["name"].as_str().unwrap_or_default();
+        match Subscription::new(service, url, name) {
+            Some(subscription) => subscriptions.push(subscription),
+            None => skipped(url),
+        }
+    }
+    Ok(subscriptions)
+}
+
+fn write_newpipe(subscriptions: &[Subscription]) -> String {
+    let items: Vec<Value> = subscriptions
+        .iter()
+        .map(|subscription| {
+            json!({
+                "service_id": subscription.service.id(),
+                "url": subscription.url,
+                "name": subscription.name,
+            })
+        })
+        .collect();
+    let export = json!({
+        "app_version": "0.24.1",
+        "app_version_int": 996,
+        "subscriptions": items,
+    });
+    serde_json::to_string_pretty(&export).unwrap_or_default()
+}
+
+/// Outlines with an `xmlUrl` of a channel's feed or an `htmlUrl` of a channel page, other
+/// feeds are skipped
+fn parse_opml(input: &str) -> Result<Vec<Subscription>, ParsingError> {
+    let mut reader = quick_xml::Reader::from_str(input);
+    let mut subscriptions = vec![];
+    loop {
+        let event = reader.read_event().map_err(|e| e.to_string())?;
+        let outline = match &event {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"outline" => e,
+            Event::Eof => break,
+            _ => continue,
+        };
+        let mut attributes = std::collections::HashMap::new();
+        for attribute in outline.attributes().flatten() {
+            if let Ok(value) = attribute.unescape_value() {
+                let key = String::from_utf8_lossy(attribute.key.as_ref()).to_string();
+                attributes.insert(key, value.to_string());
+            }
+        }
+        let xml_url = attributes.get("xmlUrl");
+        let html_url = attributes.get("htmlUrl");
+        // Folders have neither
+        let url = match xml_url.or(html_url) {
+            Some(url) => url,
+            None => continue,
+        };
+        let name = attributes
+            .get("title")
+            .or_else(|| attributes.get("text"))
+            .cloned()
+            .unwrap_or_default();
+        let feed_channel = xml_url
+            .and_then(|url| url.split_once("channel_id="))
+            .map(|(_, id)| id.split('&').next().unwrap_or_default());
+        let subscription = feed_channel
+            .and_then(|id| Subscription::youtube(id, &name))
+            .or_else(|| Subscription::youtube(html_url?, &name));
+        match subscription {
+            Some(subscription) => subscriptions.push(subscription),
+            None => tracing::debug!(url = %url, "skipping feed that is no youtube channel"),
+        }
+    }
+    Ok(subscriptions)
+}
+
+fn write_opml<'a>(subscriptions: impl Iterator<Item = &'a Subscription>) -> String {
+    let mut opml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <opml version=\"1.1\">\n\
+         <head><title>YouTube subscriptions</title></head>\n\
+         <body>\n\
+         <outline text=\"YouTube Subscriptions\" title=\"YouTube Subscriptions\">\n",
+    );
+    for subscription in subscriptions {
+        let name = escape_xml(&subscription.name);
+        let url = escape_xml(&subscription.url);
+        if subscription.is_resolved() {
+            opml.push_str(&format!(
+                "<outline text=\"{name}\" title=\"{name}\" type=\"rss\" \
+                 xmlUrl=\"https://www.youtube.com/feeds/videos.xml?channel_id={id}\" \
+                 htmlUrl=\"{url}\"/>\n",
+                name = name,
+                id = escape_xml(&subscription.channel_id),
+                url = url,
+            ));
+        } else {
+            // Feeds only take `UC…` ids
+            opml.push_str(&format!(
+                "<outline text=\"{name}\" title=\"{name}\" htmlUrl=\"{url}\"/>\n",
+                name = name,
+                url = url,
+            ));
+        }
+    }
+    opml.push_str("</outline>\n</body>\n</opml>\n");
+    opml
+}
+
+/// Replaces handles and user or custom url paths by the `UC…` id they redirect to. Returns
+/// the subscriptions that could not be resolved, which are left as they were.
+pub async fn resolve_channel_ids<D: Downloader>(
+    downloader: &D,
+    subscriptions: &mut [Subscription],
+) -> Vec<(Subscription, ParsingError)> {
+    let mut failures = vec![];
+    for subscription in subscriptions.iter_mut().filter(|s| !s.is_resolved()) {
+        match YTChannelExtractor::<D>::resolve_channel_id(downloader, &subscription.channel_id)
+            .await
+        {
+            Ok(channel_id) => {
+                subscription.url = YTLink::Channel(channel_id.clone()).url();
+                subscription.channel_id = channel_id;
+            }
+            Err(e) => failures.push((subscription.clone(), e)),
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMATS: [SubscriptionFormat; 4] = [
+        SubscriptionFormat::TakeoutCsv,
+        SubscriptionFormat::TakeoutJson,
+        SubscriptionFormat::NewPipe,
+        SubscriptionFormat::Opml,
+    ];
+
+    fn youtube(id: &str, name: &str) -> Subscription {
+        Subscription::youtube(id, name).unwrap()
+    }
+
+    fn subscriptions() -> Vec<Subscription> {
+        vec![
+            youtube("UCuAXFkgsw1L7xaCfnd5JJOw", "Rick Astley"),
+            youtube(
+                "UC_x5XG1OV2P6uZZ5FSM9Ttw",
+                "Quotes \"and\", commas & <tags>",
+            ),
+            youtube("UCBR8-60-B28hp2BmDPdntcQ", "Ünïcödé 🎵"),
+        ]
+    }
+
+    #[test]
+    fn csv_quoting() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+
+        let rows = csv_rows("a,\"b,c\",\"d \"\"e\"\"\"\r\n\"multi\nline\",,\nlast");
+        assert_eq!(
+            rows,
+            vec![
+                vec!["a", "b,c", "d \"e\""],
+                vec!["multi\nline", "", ""],
+                vec!["last"],
+            ]
+        );
+
+        let name = "Line\nbreak, \"quoted\"";
+        let csv = SubscriptionFormat::TakeoutCsv
+            .write(&[youtube("UCuAXFkgsw1L7xaCfnd5JJOw", name)])
+            .unwrap();
+        let parsed = SubscriptionFormat::TakeoutCsv.parse(&csv).unwrap();
+        assert_eq!(parsed, vec![youtube("UCuAXFkgsw1L7xaCfnd5JJOw", name)]);
+    }
+
+    #[test]
+    fn detects_formats() {
+        for format in FORMATS.iter() {
+            let written = format.write(&subscriptions()).unwrap();
+            assert_eq!(SubscriptionFormat::detect(&written), *format);
+            let with_bom = format!("\u{feff}\n  {}", written);
+            assert_eq!(SubscriptionFormat::detect(&with_bom), *format);
+        }
+        assert_eq!(
+            SubscriptionFormat::detect(""),
+            SubscriptionFormat::TakeoutCsv
+        );
+    }
+
+    #[test]
+    fn skips_byte_order_marks() {
+        let csv = "\u{feff}Channel Id,Channel Url,Channel Title\n\
+                   UCuAXFkgsw1L7xaCfnd5JJOw,http://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw,Rick Astley\n";
+        assert_eq!(
+            SubscriptionFormat::TakeoutCsv.parse(csv).unwrap(),
+            vec![youtube("UCuAXFkgsw1L7xaCfnd5JJOw", "Rick Astley")]
+        );
+        for format in FORMATS.iter() {
+            let written = format!("\u{feff}{}", format.write(&subscriptions()).unwrap());
+            assert_eq!(format.parse(&written).unwrap(), subscriptions());
+        }
+    }
+
+    #[test]
+    fn round_trips_every_format() {
+        let soundcloud = Subscription::new(
+            Service::SoundCloud,
+            "https://soundcloud.com/artist",
+            "Artist",
+        )
+        .unwrap();
+        let mut all = subscriptions();
+        all.push(soundcloud);
+        for format in FORMATS.iter() {
+            let parsed = format.parse(&format.write(&all).unwrap()).unwrap();
+            if *format == SubscriptionFormat::NewPipe {
+                assert_eq!(parsed, all);
+            } else {
+                assert_eq!(parsed, subscriptions(), "{:?}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn opml_falls_back_to_html_urls() {
+        let opml = r#"<?xml version="1.0"?>
+            <opml version="1.0"><body>
+            <outline text="Folder">
+                <outline text="Page only" htmlUrl="https://www.youtube.com/channel/UCuAXFkgsw1L7xaCfnd5JJOw"/>
+                <outline title="Handle" text="ignored" htmlUrl="https://www.youtube.com/@handle"/>
+                <outline text="Other feed" xmlUrl="https://example.com/feed.xml"/>
+                <outline text="Other page" htmlUrl="https://example.com/"/>
+                <outline text="Feed" type="rss"
+                    xmlUrl="https://www.youtube.com/feeds/videos.xml?channel_id=UCBR8-60-B28hp2BmDPdntcQ"/>
+            </outline>
+            </body></opml>"#;
+        assert_eq!(
+            SubscriptionFormat::Opml.parse(opml).unwrap(),
+            vec![
+                youtube("UCuAXFkgsw1L7xaCfnd5JJOw", "Page only"),
+                youtube("@handle", "Handle"),
+                youtube("UCBR8-60-B28hp2BmDPdntcQ", "Feed"),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_unresolved_handles() {
+        let mut all = subscriptions();
+        all.push(youtube("@handle", "Handle"));
+        for format in [
+            SubscriptionFormat::TakeoutCsv,
+            SubscriptionFormat::TakeoutJson,
+        ]
+        .iter()
+        {
+            let error = format.write(&all).unwrap_err();
+            assert!(error.to_string().contains("@handle"), "{}", error);
+        }
+
+        let opml = SubscriptionFormat::Opml.write(&all).unwrap();
+        assert!(!opml.contains("/channel/@handle"));
+        assert!(!opml.contains("channel_id=@handle"));
+        assert!(opml.contains(
+            "<outline text=\"Handle\" title=\"Handle\" htmlUrl=\"https://www.youtube.com/@handle\"/>"
+        ));
+        assert_eq!(SubscriptionFormat::Opml.parse(&opml).unwrap(), all);
+
+        let newpipe = SubscriptionFormat::NewPipe.write(&all).unwrap();
+        assert_eq!(SubscriptionFormat::NewPipe.parse(&newpipe).unwrap(), all);
+    }
+}
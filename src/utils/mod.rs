@@ -1 +1,2 @@
+pub mod sha256;
 pub mod utils;
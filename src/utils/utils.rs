@@ -1,6 +1,9 @@
+use crate::downloader_trait::Downloader;
 use crate::youtube_extractor::error::ParsingError;
+use lazy_static::lazy_static;
 use serde_json::Value;
 use std::collections::hash_map::HashMap;
+use tracing::Instrument;
 
 pub fn remove_non_digit_chars<T: std::str::FromStr>(input: &str) -> Result<T, T::Err> {
     let re = regex::Regex::new("\\D+").unwrap();
@@ -182,3 +185,80 @@ pub fn text_from_object(text_object: &Value, html: bool) -> Result<Option<String
         return Ok(None);
     }
 }
+
+lazy_static! {
+    /// The form of the interstitial, or a meta refresh or script sending the browser to it.
+    /// Links to consent.youtube.com elsewhere in a page, like in descriptions, don't match.
+    static ref CONSENT_PAGE: regex::Regex = regex::Regex::new(
+        r#"(?i)<form[^>]*\saction="https://consent\.youtube\.com/|http-equiv="refresh"[^>]*url='?https://consent\.youtube\.com/|location(?:\.href)?\s*=\s*["']https://consent\.youtube\.com/"#
+    )
+    .unwrap();
+}
+
+/// Whether a response is the consent.youtube.com interstitial instead of the requested page
+pub fn is_consent_interstitial(body: &str) -> bool {
+    let body = body.trim_start();
+    if body.starts_with('{') || body.starts_with('[') {
+        return false;
+    }
+    CONSENT_PAGE.is_match(body)
+}
+
+/// Downloads a youtube.com url with the downloader's cookies and the consent cookies set
+pub async fn youtube_download<D: Downloader>(
+    downloader: &D,
+    url: &str,
+    mut headers: HashMap<String, String>,
+) -> Result<String, ParsingError> {
+    let mut jar = downloader.cookie_jar();
+    jar.accept_consent();
+    headers.insert("Cookie".to_string(), jar.cookie_header());
+    let span = tracing::debug_span!("download", url, bytes = tracing::field::Empty);
+    let response = downloader
+        .download_with_header(url, headers)
+        .instrument(span.clone())
+        .await?;
+    span.record("bytes", response.len() as u64);
+    if is_consent_interstitial(&response) {
+        return Err(ParsingError::ConsentRequired);
+    }
+    Ok(response)
+}
+
+/// Escapes text for XML element content and attribute values
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consent_interstitial() {
+        let form = r#"<html><body><form action="https://consent.youtube.com/save" method="POST"><input type="hidden" name="continue" value="https://www.youtube.com/watch?v=abc"></form></body></html>"#;
+        assert!(is_consent_interstitial(form));
+        let refresh = r#"<html><head><meta http-equiv="refresh" content="0; url='https://consent.youtube.com/m?continue=x'"></head></html>"#;
+        assert!(is_consent_interstitial(refresh));
+        let script = r#"<script>window.location.href = "https://consent.youtube.com/m?continue=x";</script>"#;
+        assert!(is_consent_interstitial(script));
+
+        // Pages that merely mention the host
+        let watch = r#"<html><body><a href="https://consent.youtube.com/">Privacy</a><script>var ytInitialData = {};</script></body></html>"#;
+        assert!(!is_consent_interstitial(watch));
+        assert!(!is_consent_interstitial(
+            r#"{"action": "https://consent.youtube.com/save"}"#
+        ));
+    }
+}
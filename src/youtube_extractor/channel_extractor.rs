@@ -1,11 +1,14 @@
 use crate::downloader_trait::Downloader;
-use crate::utils::utils::text_from_object;
+use crate::metrics::{parse_failure, Stopwatch, CHANNEL};
+use crate::utils::utils::{text_from_object, youtube_download};
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::stream_extractor::{Thumbnail, HARDCODED_CLIENT_VERSION};
 use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
 use futures::try_join;
 use serde_json::Value;
 use std::collections::HashMap;
+use tracing::field::Empty;
+use tracing::Instrument;
 
 pub static CHANNEL_URL_BASE: &str = "https://www.youtube.com/channel/";
 
@@ -19,10 +22,45 @@ pub struct YTChannelExtractor<D> {
 
 impl<D: Downloader> YTChannelExtractor<D> {
     async fn initial_data(downloader: &D, id: &str) -> Result<Value, ParsingError> {
-        let mut url = format!("{}{}/videos?pbj=1&view=0&flow=grid", CHANNEL_URL_BASE, id);
+        Ok(Self::follow_redirects(downloader, id).await?.0)
+    }
+
+    /// Channel id (`UC…`) of a handle, `user/name` or `c/name` path
+    pub async fn resolve_channel_id(downloader: &D, id: &str) -> Result<String, ParsingError> {
+        if id.starts_with("UC") && !id.contains('/') {
+            return Ok(id.to_string());
+        }
+        let (initial_data, redirected_id) = Self::follow_redirects(downloader, id).await?;
+        redirected_id
+            .or_else(|| {
+                (|| {
+                    initial_data
+                        .get("header")?
+                        .get("c4TabbedHeaderRenderer")?
+                        .get("channelId")?
+                        .as_str()
+                })()
+                .map(|id| id.to_string())
+            })
+            .ok_or_else(|| parse_failure(CHANNEL, "channel_id", "Cant resolve channel id"))
+    }
+
+    /// Response of the videos tab, and the channel id redirects led to if there were any
+    async fn follow_redirects(
+        downloader: &D,
+        id: &str,
+    ) -> Result<(Value, Option<String>), ParsingError> {
+        // Handles and legacy user or custom urls redirect to the channel id below
+        let base = if id.starts_with('@') || id.contains('/') {
+            "https://www.youtube.com/"
+        } else {
+            CHANNEL_URL_BASE
+        };
+        let mut url = format!("{}{}/videos?pbj=1&view=0&flow=grid", base, id);
 
         let mut level = 0;
         let mut ajax_json = Value::Null;
+        let mut redirected_id = None;
         while level < 3 {
             let mut headers = HashMap::new();
             headers.insert("X-YouTube-Client-Name".to_string(), "1".to_string());
@@ -31,7 +69,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
                 HARDCODED_CLIENT_VERSION.to_string(),
             );
             headers.insert("Accept-Language".to_string(), "en".to_string());
-            let response = downloader.download_with_header(&url, headers).await?;
+            let response = youtube_download(downloader, &url, headers).await?;
             let json_response = serde_json::from_str::<Value>(&response)
                 .map_err(|e| ParsingError::from(e.to_string()))?;
             let endpoint = (|| {
@@ -61,10 +99,12 @@ impl<D: Downloader> YTChannelExtractor<D> {
                         "Redirected id is not pointing to a channel",
                     ));
                 }
+                tracing::Span::current().record("fallback", "redirect");
                 url = format!(
                     "https://www.youtube.com/channel/{}/videos?pbj=1&view=0&flow=grid",
                     browse_id
                 );
+                redirected_id = Some(browse_id.to_string());
                 level += 1;
             } else {
                 ajax_json = json_response;
@@ -77,7 +117,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
         } else {
             let init_data =
                 (|| ajax_json.get(1)?.get("response"))().ok_or("reponse null in ajax json")?;
-            Ok(init_data.clone())
+            Ok((init_data.clone(), redirected_id))
         }
     }
 
@@ -89,7 +129,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
                 .get("tabs")?
                 .as_array()
         })()
-        .ok_or("Tabs not found")?;
+        .ok_or_else(|| parse_failure(CHANNEL, "tabs", "Tabs not found"))?;
         let mut video_tab = &Value::Null;
 
         for tab in tabs {
@@ -140,12 +180,36 @@ impl<D: Downloader> YTChannelExtractor<D> {
         downloader: D,
         channel_id: &str,
         page_url: Option<String>,
+    ) -> Result<Self, ParsingError> {
+        let endpoint = if page_url.is_some() {
+            "/browse_ajax"
+        } else {
+            "/channel/videos"
+        };
+        let span = tracing::info_span!(
+            "channel_extractor",
+            channel_id,
+            endpoint,
+            parse_ms = Empty,
+            fallback = Empty
+        );
+        Self::load(downloader, channel_id, page_url)
+            .instrument(span)
+            .await
+    }
+
+    async fn load(
+        downloader: D,
+        channel_id: &str,
+        page_url: Option<String>,
     ) -> Result<Self, ParsingError> {
         if let Some(page_url) = page_url {
             let initial_data = YTChannelExtractor::initial_data(&downloader, channel_id);
             let page = YTChannelExtractor::page(&downloader, &page_url);
             let (initial_data, page) = try_join!(initial_data, page)?;
+            let stopwatch = Stopwatch::start();
             let video_tab = YTChannelExtractor::<D>::video_tab(&initial_data)?;
+            stopwatch.record();
 
             Ok(YTChannelExtractor {
                 downloader,
@@ -155,7 +219,9 @@ impl<D: Downloader> YTChannelExtractor<D> {
             })
         } else {
             let initial_data = YTChannelExtractor::initial_data(&downloader, channel_id).await?;
+            let stopwatch = Stopwatch::start();
             let video_tab = YTChannelExtractor::<D>::video_tab(&initial_data)?;
+            stopwatch.record();
             Ok(YTChannelExtractor {
                 downloader,
                 initial_data,
@@ -204,7 +270,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
             "X-YouTube-Client-Version".to_string(),
             HARDCODED_CLIENT_VERSION.to_string(),
         );
-        let response = downloader.download_with_header(&page_url, headers).await?;
+        let response = youtube_download(downloader, page_url, headers).await?;
         let json_response = serde_json::from_str::<Value>(&response)
             .map_err(|e| ParsingError::from(e.to_string()))?;
 
@@ -233,6 +299,19 @@ impl<D: Downloader> YTChannelExtractor<D> {
 }
 
 impl<D: Downloader> YTChannelExtractor<D> {
+    /// `UC…` id of the channel, also when it was loaded by a handle or a legacy url
+    pub fn channel_id(&self) -> Result<String, ParsingError> {
+        (|| {
+            self.initial_data
+                .get("header")?
+                .get("c4TabbedHeaderRenderer")?
+                .get("channelId")?
+                .as_str()
+        })()
+        .map(|id| id.to_string())
+        .ok_or_else(|| parse_failure(CHANNEL, "channel_id", "Cant get channel id"))
+    }
+
     pub fn name(&self) -> Result<String, ParsingError> {
         Ok((|| {
             self.initial_data
@@ -241,7 +320,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
                 .get("title")?
                 .as_str()
         })()
-        .ok_or("Cant get title")?
+        .ok_or_else(|| parse_failure(CHANNEL, "name", "Cant get title"))?
         .to_string())
     }
 
@@ -304,7 +383,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
                 .get("gridRenderer")?
                 .get("items")
         })()
-        .ok_or("Cant get videos")?;
+        .ok_or_else(|| parse_failure(CHANNEL, "videos", "Cant get videos"))?;
         YTChannelExtractor::<D>::collect_streams_from(videos)
     }
 
@@ -327,7 +406,7 @@ impl<D: Downloader> YTChannelExtractor<D> {
             if let Some(conti) = conti {
                 Ok(YTChannelExtractor::<D>::next_page_url_from(conti))
             } else {
-                println!("Continuation is None");
+                tracing::debug!("Continuation is None");
                 Ok(None)
             }
         }
@@ -1,3 +1,4 @@
+use crate::metrics::{parse_failure, CHANNEL_INFO_ITEM};
 use crate::utils::utils::{mixed_number_word_parse, remove_non_digit_chars, text_from_object};
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::stream_extractor::Thumbnail;
@@ -35,7 +36,7 @@ impl YTChannelInfoItemExtractor {
                 }
             }
         }
-        Err(ParsingError::from("Cannot get name"))
+        Err(parse_failure(CHANNEL_INFO_ITEM, "name", "Cannot get name"))
     }
 
     pub fn channel_id(&self) -> Result<String, ParsingError> {
@@ -11,6 +11,13 @@ pub enum ParsingError {
 
     #[fail(display = "Download Error : {}", cause)]
     DownloadError { cause: String },
+
+    /// The server answered with an error status
+    #[fail(display = "Download Error : {}", cause)]
+    HttpError { cause: String, status: u16 },
+
+    #[fail(display = "Redirected to the consent page even though consent cookies were sent")]
+    ConsentRequired,
 }
 
 impl ParsingError {
@@ -21,6 +28,16 @@ impl ParsingError {
     }
 }
 
+impl ParsingError {
+    /// Status of the HTTP response that failed the download, if it got that far
+    pub fn http_status(&self) -> Option<u16> {
+        match self {
+            ParsingError::HttpError { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
 impl From<&str> for ParsingError {
     fn from(cause: &str) -> Self {
         ParsingError::parsing_error_from_str(cause)
@@ -0,0 +1,330 @@
+use percent_encoding::percent_decode_str;
+
+/// What a YouTube URL or bare id points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum YTLink {
+    Stream(String),
+    Playlist(String),
+    /// Channel id, or a path like `@handle`, `user/name` or `c/name` which
+    /// `YTChannelExtractor::new` resolves to the channel
+    Channel(String),
+    Search(String),
+}
+
+struct Url<'a> {
+    host: String,
+    path: Vec<&'a str>,
+    query: &'a str,
+}
+
+impl<'a> Url<'a> {
+    fn parse(input: &'a str) -> Option<Url<'a>> {
+        let input = input.trim();
+        let rest = input.splitn(2, "://").last().unwrap_or(input);
+        let rest = rest.split('#').next().unwrap_or_default();
+        let mut split = rest.splitn(2, '?');
+        let authority_path = split.next().unwrap_or_default();
+        let query = split.next().unwrap_or_default();
+        let mut split = authority_path.splitn(2, '/');
+        let host = split
+            .next()?
+            .split(':')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let is_youtube = ["youtube.com", "youtu.be", "youtube-nocookie.com"]
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        if !is_youtube {
+            return None;
+        }
+        let path = split
+            .next()
+            .unwrap_or_default()
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        Some(Url { host, path, query })
+    }
+
+    fn list(&self) -> Option<String> {
+        self.param("list").filter(|list| is_list_param(list))
+    }
+
+    fn param(&self, name: &str) -> Option<String> {
+        self.query.split('&').find_map(|pair| {
+            let mut split = pair.splitn(2, '=');
+            if split.next()? != name {
+                return None;
+            }
+            let value = split.next()?.replace('+', " ");
+            let value = percent_decode_str(&value).decode_utf8_lossy().to_string();
+            if value.is_empty() {
+                None
+            } else {
+                Some(value)
+            }
+        })
+    }
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+/// `[A-Za-z0-9_-]{11}`
+fn is_video_id(id: &str) -> bool {
+    id.len() == 11 && id.chars().all(is_id_char)
+}
+
+fn is_channel_id(id: &str) -> bool {
+    id.len() == 24 && id.starts_with("UC") && id.chars().all(is_id_char)
+}
+
+fn is_playlist_id(id: &str) -> bool {
+    id.len() > 12
+        && ["PL", "UU", "LL", "FL", "OL", "RD"]
+            .iter()
+            .any(|prefix| id.starts_with(prefix))
+        && id.chars().all(is_id_char)
+}
+
+/// `list` parameters, which also hold short ids like `WL` or `LL`
+fn is_list_param(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(is_id_char)
+}
+
+fn is_handle(handle: &str) -> bool {
+    handle.starts_with('@') && handle.len() > 1
+}
+
+impl YTLink {
+    /// Classifies a URL of any youtube.com, youtu.be or youtube-nocookie.com form
+    pub fn parse(input: &str) -> Option<YTLink> {
+        let url = Url::parse(input)?;
+        if url.host == "youtu.be" {
+            return url
+                .path
+                .first()
+                .filter(|id| is_video_id(id))
+                .map(|id| YTLink::Stream(id.to_string()));
+        }
+        let first = *url.path.first().unwrap_or(&"");
+        let second = url.path.get(1).copied().unwrap_or_default();
+        match first {
+            "watch" => url
+                .param("v")
+                .filter(|id| is_video_id(id))
+                .map(YTLink::Stream)
+                .or_else(|| url.list().map(YTLink::Playlist)),
+            "shorts" | "embed" | "v" | "e" | "live" if is_video_id(second) => {
+                Some(YTLink::Stream(second.to_string()))
+            }
+            "playlist" => url.list().map(YTLink::Playlist),
+            "channel" if is_channel_id(second) => Some(YTLink::Channel(second.to_string())),
+            "user" | "c" if !second.is_empty() => {
+                Some(YTLink::Channel(format!("{}/{}", first, second)))
+            }
+            "results" => url
+                .param("search_query")
+                .or_else(|| url.param("q"))
+                .map(YTLink::Search),
+            handle if is_handle(handle) => Some(YTLink::Channel(handle.to_string())),
+            _ => None,
+        }
+    }
+
+    /// Like [`YTLink::parse`], but also takes a bare video, playlist or channel id or
+    /// `@handle`, for input that is known to be about YouTube
+    pub fn parse_or_id(input: &str) -> Option<YTLink> {
+        let input = input.trim();
+        if is_channel_id(input) || is_handle(input) {
+            Some(YTLink::Channel(input.to_string()))
+        } else if is_video_id(input) {
+            Some(YTLink::Stream(input.to_string()))
+        } else if is_playlist_id(input) {
+            Some(YTLink::Playlist(input.to_string()))
+        } else {
+            YTLink::parse(input)
+        }
+    }
+
+    /// Id of a video URL, bare ids are left to [`YTLink::parse_or_id`]
+    pub fn stream_id(input: &str) -> Option<String> {
+        match YTLink::parse(input)? {
+            YTLink::Stream(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Also accepts watch URLs that carry a `list` parameter
+    pub fn playlist_id(input: &str) -> Option<String> {
+        Url::parse(input)?.list()
+    }
+
+    pub fn channel_id(input: &str) -> Option<String> {
+        match YTLink::parse(input)? {
+            YTLink::Channel(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match self {
+            YTLink::Stream(id) => format!("https://www.youtube.com/watch?v={}", id),
+            YTLink::Playlist(id) => format!("https://www.youtube.com/playlist?list={}", id),
+            YTLink::Channel(id) if is_channel_id(id) => {
+                format!("https://www.youtube.com/channel/{}", id)
+            }
+            YTLink::Channel(path) => format!("https://www.youtube.com/{}", path),
+            YTLink::Search(query) => format!(
+                "https://www.youtube.com/results?search_query={}",
+                percent_encoding::utf8_percent_encode(query, percent_encoding::NON_ALPHANUMERIC)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIDEO: &str = "dQw4w9WgXcQ";
+    const CHANNEL: &str = "UCuAXFkgsw1L7xaCfnd5JJOw";
+    const PLAYLIST: &str = "PLFgquLnL59alCl_2TQvOiD5Vgm1hCaGSI";
+
+    fn stream() -> Option<YTLink> {
+        Some(YTLink::Stream(VIDEO.to_string()))
+    }
+
+    #[test]
+    fn video_urls() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+            "http://youtube.com/watch?feature=share&v=dQw4w9WgXcQ&t=42",
+            "www.youtube.com/watch?v=dQw4w9WgXcQ#t=1m",
+            "https://m.youtube.com/watch?v=dQw4w9WgXcQ",
+            "https://music.youtube.com/watch?v=dQw4w9WgXcQ&list=RDAMVM",
+            "https://WWW.YOUTUBE.COM:443/watch?v=dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ",
+            "https://youtu.be/dQw4w9WgXcQ?si=abc&t=10",
+            "https://www.youtube.com/shorts/dQw4w9WgXcQ",
+            "https://www.youtube.com/embed/dQw4w9WgXcQ?start=3",
+            "https://www.youtube-nocookie.com/embed/dQw4w9WgXcQ",
+            "https://www.youtube.com/v/dQw4w9WgXcQ",
+            "https://www.youtube.com/e/dQw4w9WgXcQ",
+            "https://www.youtube.com/live/dQw4w9WgXcQ?feature=share",
+            "  https://youtu.be/dQw4w9WgXcQ  ",
+        ]
+        .iter()
+        {
+            assert_eq!(YTLink::parse(url), stream(), "{}", url);
+            assert_eq!(YTLink::stream_id(url), Some(VIDEO.to_string()), "{}", url);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_video_ids() {
+        for url in [
+            "https://www.youtube.com/watch?v=dQw4w9WgXc",
+            "https://www.youtube.com/watch?v=dQw4w9WgXcQQ",
+            "https://www.youtube.com/watch?v=dQw4w9WgX%21Q",
+            "https://youtu.be/dQw4w9WgX.Q",
+            "https://www.youtube.com/shorts/",
+            "https://www.youtube.com/watch",
+            "https://example.com/watch?v=dQw4w9WgXcQ",
+            "https://notyoutube.com/watch?v=dQw4w9WgXcQ",
+        ]
+        .iter()
+        {
+            assert_eq!(YTLink::stream_id(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn playlist_urls() {
+        let playlist = Some(YTLink::Playlist(PLAYLIST.to_string()));
+        let url = format!("https://www.youtube.com/playlist?list={}", PLAYLIST);
+        assert_eq!(YTLink::parse(&url), playlist);
+        let watch = format!("https://www.youtube.com/watch?list={}&index=2", PLAYLIST);
+        assert_eq!(YTLink::parse(&watch), playlist);
+        // The video wins, the playlist is still there for playlist_id
+        let both = format!("https://youtube.com/watch?v={}&list={}", VIDEO, PLAYLIST);
+        assert_eq!(YTLink::parse(&both), stream());
+        assert_eq!(YTLink::playlist_id(&both), Some(PLAYLIST.to_string()));
+        assert_eq!(
+            YTLink::playlist_id("https://www.youtube.com/playlist?list=WL"),
+            Some("WL".to_string())
+        );
+        assert_eq!(
+            YTLink::playlist_id("https://www.youtube.com/playlist?list=PL%3Cscript"),
+            None
+        );
+        assert_eq!(
+            YTLink::playlist_id("https://www.youtube.com/playlist"),
+            None
+        );
+    }
+
+    #[test]
+    fn channel_urls() {
+        let channel = |id: &str| Some(YTLink::Channel(id.to_string()));
+        let url = format!("https://www.youtube.com/channel/{}/videos", CHANNEL);
+        assert_eq!(YTLink::parse(&url), channel(CHANNEL));
+        assert_eq!(
+            YTLink::parse("https://www.youtube.com/user/RickAstleyVEVO"),
+            channel("user/RickAstleyVEVO")
+        );
+        assert_eq!(
+            YTLink::parse("https://www.youtube.com/c/RickAstley/featured"),
+            channel("c/RickAstley")
+        );
+        assert_eq!(
+            YTLink::parse("https://www.youtube.com/@RickAstleyYT"),
+            channel("@RickAstleyYT")
+        );
+        assert_eq!(YTLink::parse("https://www.youtube.com/channel/nope"), None);
+        assert_eq!(YTLink::parse("https://www.youtube.com/@"), None);
+        assert_eq!(
+            YTLink::channel_id(&YTLink::Channel(CHANNEL.to_string()).url()),
+            Some(CHANNEL.to_string())
+        );
+    }
+
+    #[test]
+    fn search_urls() {
+        assert_eq!(
+            YTLink::parse("https://www.youtube.com/results?search_query=never+gonna%21"),
+            Some(YTLink::Search("never gonna!".to_string()))
+        );
+        let search = YTLink::Search("a & b".to_string());
+        assert_eq!(YTLink::parse(&search.url()), Some(search));
+    }
+
+    #[test]
+    fn bare_ids_only_when_asked() {
+        for id in [VIDEO, CHANNEL, PLAYLIST, "@RickAstleyYT"].iter() {
+            assert_eq!(YTLink::parse(id), None, "{}", id);
+        }
+        assert_eq!(YTLink::parse_or_id(VIDEO), stream());
+        assert_eq!(
+            YTLink::parse_or_id(CHANNEL),
+            Some(YTLink::Channel(CHANNEL.to_string()))
+        );
+        assert_eq!(
+            YTLink::parse_or_id(PLAYLIST),
+            Some(YTLink::Playlist(PLAYLIST.to_string()))
+        );
+        assert_eq!(
+            YTLink::parse_or_id(" @RickAstleyYT "),
+            Some(YTLink::Channel("@RickAstleyYT".to_string()))
+        );
+        assert_eq!(
+            YTLink::parse_or_id("https://youtu.be/dQw4w9WgXcQ"),
+            stream()
+        );
+        for not_an_id in ["dQw4w9WgX!Q", "dQw4w9WgXc", "hello world", ""].iter() {
+            assert_eq!(YTLink::parse_or_id(not_an_id), None, "{}", not_an_id);
+        }
+    }
+}
@@ -2,6 +2,7 @@ pub mod channel_extractor;
 pub mod channel_info_item_extractor;
 pub mod error;
 pub mod itag_item;
+pub mod link_handler;
 pub mod playlist_extractor;
 pub mod playlist_info_item_extractor;
 pub mod search_extractor;
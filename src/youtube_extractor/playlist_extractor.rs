@@ -1,10 +1,15 @@
 use crate::downloader_trait::Downloader;
-use crate::utils::utils::{remove_non_digit_chars, text_from_object, url_from_navigation_endpoint};
+use crate::metrics::{parse_failure, record_fallback, Stopwatch, PLAYLIST};
+use crate::utils::utils::{
+    remove_non_digit_chars, text_from_object, url_from_navigation_endpoint, youtube_download,
+};
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::stream_extractor::{Thumbnail, HARDCODED_CLIENT_VERSION};
 use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
 use serde_json::Value;
 use std::collections::HashMap;
+use tracing::field::Empty;
+use tracing::Instrument;
 
 #[derive(Clone, PartialEq)]
 pub struct YTPlaylistExtractor<D> {
@@ -19,13 +24,36 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
         downloader: D,
         playlist_id: &str,
         page_url: Option<String>,
+    ) -> Result<Self, ParsingError> {
+        let endpoint = if page_url.is_some() {
+            "/browse_ajax"
+        } else {
+            "/playlist"
+        };
+        let span = tracing::info_span!(
+            "playlist_extractor",
+            playlist_id,
+            endpoint,
+            parse_ms = Empty
+        );
+        Self::load(downloader, playlist_id, page_url)
+            .instrument(span)
+            .await
+    }
+
+    async fn load(
+        downloader: D,
+        playlist_id: &str,
+        page_url: Option<String>,
     ) -> Result<Self, ParsingError> {
         if let Some(page_url) = page_url {
             let initial_data = YTPlaylistExtractor::initial_data(&downloader, playlist_id);
             let page = YTPlaylistExtractor::page(&downloader, &page_url);
             use futures::try_join;
             let (initial_data, page) = try_join!(initial_data, page)?;
+            let stopwatch = Stopwatch::start();
             let playlist_info = YTPlaylistExtractor::<D>::playlist_info(&initial_data)?;
+            stopwatch.record();
 
             Ok(Self {
                 downloader,
@@ -35,7 +63,9 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
             })
         } else {
             let initial_data = YTPlaylistExtractor::initial_data(&downloader, playlist_id).await?;
+            let stopwatch = Stopwatch::start();
             let playlist_info = YTPlaylistExtractor::<D>::playlist_info(&initial_data)?;
+            stopwatch.record();
             Ok(Self {
                 downloader,
                 init_data: initial_data,
@@ -53,7 +83,7 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
             "X-YouTube-Client-Version".to_string(),
             HARDCODED_CLIENT_VERSION.to_string(),
         );
-        let response = downloader.download_with_header(&url, headers).await?;
+        let response = youtube_download(downloader, &url, headers).await?;
         let json_response = serde_json::from_str::<Value>(&response)
             .map_err(|e| ParsingError::from(e.to_string()))?;
         let json_response = json_response
@@ -76,7 +106,11 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
         if let Some(pinfo) = pinfo {
             Ok(pinfo.clone())
         } else {
-            Err(ParsingError::from("Cant get playlist info"))
+            Err(parse_failure(
+                PLAYLIST,
+                "playlist_info",
+                "Cant get playlist info",
+            ))
         }
     }
 
@@ -119,7 +153,7 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
             "X-YouTube-Client-Version".to_string(),
             HARDCODED_CLIENT_VERSION.to_string(),
         );
-        let response = downloader.download_with_header(&page_url, headers).await?;
+        let response = youtube_download(downloader, page_url, headers).await?;
         let json_response = serde_json::from_str::<Value>(&response)
             .map_err(|e| ParsingError::from(e.to_string()))?;
 
@@ -165,9 +199,10 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
                 .as_str()
         })();
         if let Some(title) = title {
+            record_fallback(PLAYLIST, "name", "microformat");
             return Ok(title.to_string());
         }
-        Err(ParsingError::from("Cant get name"))
+        Err(parse_failure(PLAYLIST, "name", "Cant get name"))
     }
 
     pub fn thumbnails(&self) -> Result<Vec<Thumbnail>, ParsingError> {
@@ -188,7 +223,7 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
                 .get("thumbnails")?
                 .as_array()
         })())
-        .ok_or("Cant get thumbnails")?
+        .ok_or_else(|| parse_failure(PLAYLIST, "thumbnails", "Cant get thumbnails"))?
         {
             if let Ok(thumb) = serde_json::from_value(thumb.to_owned()) {
                 thumbnails.push(thumb)
@@ -216,7 +251,11 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
                 }
             }
         }
-        Err(ParsingError::from("Cant get uploader info"))
+        Err(parse_failure(
+            PLAYLIST,
+            "uploader_info",
+            "Cant get uploader info",
+        ))
     }
 
     pub fn uploader_url(&self) -> Result<String, ParsingError> {
@@ -283,7 +322,7 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
                 .get("playlistVideoListRenderer")?
                 .get("contents")
         })()
-        .ok_or("Cant get videos")?;
+        .ok_or_else(|| parse_failure(PLAYLIST, "videos", "Cant get videos"))?;
         YTPlaylistExtractor::<D>::collect_streams_from(videos)
     }
 
@@ -311,7 +350,7 @@ impl<D: Downloader> YTPlaylistExtractor<D> {
             if let Some(conti) = conti {
                 Ok(YTPlaylistExtractor::<D>::next_page_url_from(conti))
             } else {
-                println!("Continuation is None");
+                tracing::debug!("Continuation is None");
                 Ok(None)
             }
         }
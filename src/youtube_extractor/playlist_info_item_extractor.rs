@@ -1,3 +1,4 @@
+use crate::metrics::{parse_failure, PLAYLIST_INFO_ITEM};
 use crate::utils::utils::remove_non_digit_chars;
 use crate::utils::utils::*;
 use crate::youtube_extractor::error::ParsingError;
@@ -42,7 +43,7 @@ impl YTPlaylistInfoItemExtractor {
                 }
             }
         }
-        Err(ParsingError::from("Cannot get name"))
+        Err(parse_failure(PLAYLIST_INFO_ITEM, "name", "Cannot get name"))
     }
 
     pub fn playlist_id(&self) -> Result<String, ParsingError> {
@@ -1,4 +1,6 @@
 use super::super::downloader_trait::Downloader;
+use crate::metrics::{parse_failure, Stopwatch, SEARCH};
+use crate::utils::utils::youtube_download;
 use crate::youtube_extractor::channel_info_item_extractor::YTChannelInfoItemExtractor;
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::playlist_info_item_extractor::YTPlaylistInfoItemExtractor;
@@ -7,6 +9,8 @@ use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtrac
 use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
 use serde_json::{Map, Value};
 use std::collections::HashMap;
+use tracing::field::Empty;
+use tracing::Instrument;
 
 /// https://url.spec.whatwg.org/#fragment-percent-encode-set
 const FRAGMENT: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b'<').add(b'>').add(b'`');
@@ -40,7 +44,8 @@ impl<D: Downloader> YTSearchExtractor<D> {
             "X-YouTube-Client-Version".to_string(),
             HARDCODED_CLIENT_VERSION.to_string(),
         );
-        let resp = downloader.download_with_header(&url, headers).await?;
+        let resp = youtube_download(downloader, &url, headers).await?;
+        let stopwatch = Stopwatch::start();
         let resp_json = serde_json::from_str::<Value>(&resp)
             .map_err(|er| ParsingError::parsing_error_from_str(&er.to_string()))?;
         let resp_json = resp_json
@@ -51,6 +56,7 @@ impl<D: Downloader> YTSearchExtractor<D> {
             .as_object()
             .ok_or(format!("initial data not json object "))?
             .to_owned();
+        stopwatch.record();
         Ok(resp_json)
     }
 
@@ -96,6 +102,23 @@ impl<D: Downloader> YTSearchExtractor<D> {
         downloader: D,
         query: &str,
         page_url: Option<String>,
+    ) -> Result<YTSearchExtractor<D>, ParsingError> {
+        let span = tracing::info_span!(
+            "search_extractor",
+            query,
+            page = page_url.as_deref().unwrap_or("1"),
+            endpoint = "/results",
+            parse_ms = Empty
+        );
+        Self::load(downloader, query, page_url)
+            .instrument(span)
+            .await
+    }
+
+    async fn load(
+        downloader: D,
+        query: &str,
+        page_url: Option<String>,
     ) -> Result<YTSearchExtractor<D>, ParsingError> {
         let url = format!(
             "https://www.youtube.com/results?disable_polymer=1&search_query={}",
@@ -168,7 +191,7 @@ impl<D: Downloader> YTSearchExtractor<D> {
                 .as_array()?;
             Some(data)
         })()
-        .ok_or("cant get sections ")?;
+        .ok_or_else(|| parse_failure(SEARCH, "search_results", "cant get sections "))?;
 
         let mut search_items: Vec<YTSearchItem> = vec![];
 
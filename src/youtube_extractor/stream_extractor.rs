@@ -5,9 +5,13 @@ use std::collections::HashMap;
 
 use super::super::utils::utils::*;
 use super::itag_item::ItagType;
+use crate::metrics::{parse_failure, record_fallback, record_parse_failure, Stopwatch, STREAM};
+use crate::ratings::{RatingCount, RatingSource, Ratings, RatingsProvider};
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::search_extractor::YTSearchItem;
 use lazy_static::lazy_static;
+use tracing::field::Empty;
+use tracing::Instrument;
 
 const FORMATS: &str = "formats";
 const ADAPTIVE_FORMATS: &str = "adaptiveFormats";
@@ -37,6 +41,10 @@ pub struct YTStreamExtractor<D: Downloader> {
     primary_info_renderer: Value,
     secondary_info_renderer: Value,
     downloader: D,
+    /// Counts of a ratings provider, for `like_count` and `dislike_count` to fall back to
+    ratings: Option<Ratings>,
+    /// Secondary sources `new` had to read, for the diagnostics report
+    load_fallbacks: Vec<&'static str>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -59,6 +67,16 @@ pub struct StreamItem {
     pub mime_type: String,
 }
 
+/// A caption track of the player, its url serves the format named in an added `fmt` parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptionTrack {
+    pub url: String,
+    pub language_code: String,
+    pub name: String,
+    /// Generated by speech recognition instead of uploaded
+    pub auto_generated: bool,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Thumbnail {
     pub url: String,
@@ -68,16 +86,28 @@ pub struct Thumbnail {
 
 impl<D: Downloader> YTStreamExtractor<D> {
     pub async fn new(downloader: D, video_id: &str) -> Result<Self, ParsingError> {
+        let span = tracing::info_span!(
+            "stream_extractor",
+            video_id,
+            endpoint = "/watch",
+            parse_ms = Empty,
+            fallback = Empty
+        );
+        Self::load(downloader, video_id).instrument(span).await
+    }
+
+    async fn load(downloader: D, video_id: &str) -> Result<Self, ParsingError> {
         use futures::try_join;
         let url = format!(
             "https://www.youtube.com/watch?v={}&disable_polymer=1",
             video_id
         );
 
-        let doc = downloader.download(&url);
+        let doc = youtube_download(&downloader, &url, HashMap::new());
         let inital_ajax_json = Self::initial_ajax_json(&downloader, &url).await?;
         let initial_data = YTStreamExtractor::<D>::initial_data(&inital_ajax_json);
         let (doc, initial_data) = try_join!(doc, initial_data)?;
+        let stopwatch = Stopwatch::start();
 
         let initial_response = Self::player_response_from_initial_ajax(&inital_ajax_json);
 
@@ -90,6 +120,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
         let secondary_info_renderer =
             YTStreamExtractor::<D>::secondary_info_renderer(&initial_data)?;
         if let Some(response) = initial_response {
+            stopwatch.record();
             Ok(YTStreamExtractor {
                 player_response: response,
                 downloader,
@@ -99,9 +130,13 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 secondary_info_renderer,
                 doc: String::from(doc),
                 video_id: String::from(video_id),
+                ratings: None,
+                load_fallbacks: vec![],
             })
         } else {
             // OLD METHOD
+            tracing::Span::current().record("fallback", "player_config");
+            record_fallback(STREAM, "player_response", "player_config");
             let player_config = YTStreamExtractor::<D>::player_config(&doc)
                 .ok_or("cannot get player_config".to_string())?;
 
@@ -113,6 +148,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
             let player_url = YTStreamExtractor::<D>::player_url(&player_config)
                 .ok_or("Cant get player url".to_owned())?;
             let player_code = YTStreamExtractor::<D>::player_code(&downloader, &player_url).await?;
+            stopwatch.record();
             Ok(YTStreamExtractor {
                 player_response,
                 downloader,
@@ -122,6 +158,8 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 secondary_info_renderer,
                 doc: String::from(doc),
                 video_id: String::from(video_id),
+                ratings: None,
+                load_fallbacks: vec!["player_config"],
             })
         }
     }
@@ -185,7 +223,11 @@ impl<D: Downloader> YTStreamExtractor<D> {
                                     url_and_itags.insert(stream_url, stream_item);
                                 }
                             },
-                            Err(_) => {}
+                            Err(e) => record_parse_failure(
+                                STREAM,
+                                "stream_item",
+                                &ParsingError::from(e.to_string()),
+                            ),
                         }
                     }
                 }
@@ -209,14 +251,13 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 format!("https://youtube.com{}", player_url)
             }
         };
-        let player_code = downloader.download(&player_url).await?;
+        let player_code = youtube_download(downloader, &player_url, HashMap::new()).await?;
         let player_code = YTStreamExtractor::<D>::load_decryption_code(&player_code)?;
         Ok(player_code)
     }
 
     fn decrypt_signature(downloader: &D, encrypted_sig: &str, decryption_code: &str) -> String {
-        println!("encrypted_sig: {:#?}", encrypted_sig);
-        println!("decryption_code {:#?}", decryption_code);
+        tracing::trace!(encrypted_sig, decryption_code, "decrypting signature");
 
         let script = format!("{};decrypt(\"{}\")", decryption_code, encrypted_sig);
         let res = downloader.eval_js(&script);
@@ -285,7 +326,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
             HARDCODED_CLIENT_VERSION.to_string(),
         );
         let url = format!("{}&pbj=1", url);
-        let data = downloader.download_with_header(&url, headers).await?;
+        let data = youtube_download(downloader, &url, headers).await?;
         let initial_ajax_json: Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
         Ok(initial_ajax_json)
     }
@@ -450,11 +491,12 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .and_then(|t| t.get("title"))
                 .and_then(|t| t.as_str())
             {
+                record_fallback(STREAM, "name", "videoDetails");
                 title = t.to_string();
             }
         }
         if title.is_empty() {
-            Err(ParsingError::parsing_error_from_str("Cant get title"))
+            Err(parse_failure(STREAM, "name", "Cant get title"))
         } else {
             Ok(title)
         }
@@ -486,6 +528,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 ))
                 .map(|s| s.to_owned());
         } else if let Some(date) = micro.get("publishDate") {
+            record_fallback(STREAM, "upload_date", "publishDate");
             return date
                 .as_str()
                 .ok_or(ParsingError::parsing_error_from_str(
@@ -494,6 +537,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .map(|s| s.to_owned());
         } else {
             // Stream is a lifestream
+            record_fallback(STREAM, "upload_date", "liveBroadcastDetails");
             let life_details =
                 micro
                     .get("liveBroadcastDetails")
@@ -515,7 +559,9 @@ impl<D: Downloader> YTStreamExtractor<D> {
                     ))
                     .map(|s| s.to_owned());
             } else {
-                return Err(ParsingError::parsing_error_from_str(
+                return Err(parse_failure(
+                    STREAM,
+                    "upload_date",
                     "Cannot get upload date (everyhting)",
                 ));
             }
@@ -536,9 +582,14 @@ impl<D: Downloader> YTStreamExtractor<D> {
             .get("videoDetails")
             .and_then(|f| f.get("shortDescription").and_then(|f| f.as_str()))
         {
+            record_fallback(STREAM, "description", "shortDescription");
             return Ok((desc.to_string(), false));
         }
-        Err(ParsingError::parsing_error_from_str("Cant get description"))
+        Err(parse_failure(STREAM, "description", "Cant get description"))
+    }
+
+    pub(crate) fn load_fallbacks(&self) -> &[&'static str] {
+        &self.load_fallbacks
     }
 
     pub fn video_id(&self) -> String {
@@ -569,7 +620,9 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 }
             }
         }
-        Err(ParsingError::parsing_error_from_str(
+        Err(parse_failure(
+            STREAM,
+            "video_thumbnails",
             "Cant get video thumbnails",
         ))
     }
@@ -595,11 +648,12 @@ impl<D: Downloader> YTStreamExtractor<D> {
             .and_then(|f| f.as_str())
         {
             if let Ok(duration) = duration_ms.parse::<u64>() {
+                record_fallback(STREAM, "length", "approxDurationMs");
                 return Ok(duration / 1000);
             }
         }
 
-        Err(ParsingError::parsing_error_from_str("Cant get length"))
+        Err(parse_failure(STREAM, "length", "Cant get length"))
     }
 
     pub fn view_count(&self) -> Result<u128, ParsingError> {
@@ -619,6 +673,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .and_then(|f| f.get("viewCount"))
                 .and_then(|f| f.as_str())
             {
+                record_fallback(STREAM, "view_count", "videoDetails");
                 views = vc.to_string();
             }
         }
@@ -631,10 +686,65 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 }
             }
         }
-        Err(ParsingError::parsing_error_from_str("Cant get view count"))
+        Err(parse_failure(STREAM, "view_count", "Cant get view count"))
+    }
+
+    /// Fetches the counts `like_count` and `dislike_count` fall back to when the page has none
+    pub async fn load_ratings<P: RatingsProvider + ?Sized>(
+        &mut self,
+        provider: &P,
+    ) -> Result<(), ParsingError> {
+        self.ratings = Some(provider.ratings(&self.video_id).await?);
+        Ok(())
+    }
+
+    /// The page's count, or the loaded ratings' one if the page has none. Stays -1 when the
+    /// uploader disabled ratings. The page's error is only counted as a parse failure when
+    /// there is nothing to fall back to.
+    fn rating_count(
+        &self,
+        field: &'static str,
+        youtube: Result<i128, ParsingError>,
+        fallback: Option<i128>,
+    ) -> Result<RatingCount, ParsingError> {
+        match (youtube, fallback, &self.ratings) {
+            (Ok(count), _, _) => Ok(RatingCount {
+                count,
+                source: RatingSource::YouTube,
+            }),
+            (Err(_), Some(count), Some(ratings)) => {
+                record_fallback(STREAM, field, "ratings_provider");
+                Ok(RatingCount {
+                    count,
+                    source: ratings.source,
+                })
+            }
+            (Err(error), _, _) => {
+                record_parse_failure(STREAM, field, &error);
+                Err(error)
+            }
+        }
+    }
+
+    pub fn like_count_with_source(&self) -> Result<RatingCount, ParsingError> {
+        let fallback = self.ratings.as_ref().and_then(|r| r.likes);
+        self.rating_count("like_count", self.youtube_like_count(), fallback)
+    }
+
+    pub fn dislike_count_with_source(&self) -> Result<RatingCount, ParsingError> {
+        let fallback = self.ratings.as_ref().and_then(|r| r.dislikes);
+        self.rating_count("dislike_count", self.youtube_dislike_count(), fallback)
     }
 
     pub fn like_count(&self) -> Result<i128, ParsingError> {
+        self.like_count_with_source().map(|c| c.count)
+    }
+
+    pub fn dislike_count(&self) -> Result<i128, ParsingError> {
+        self.dislike_count_with_source().map(|c| c.count)
+    }
+
+    fn youtube_like_count(&self) -> Result<i128, ParsingError> {
         let mut like_string = String::new();
         if let Some(likes) = self
             .primary_info_renderer
@@ -655,7 +765,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .and_then(|f| f.as_bool())
             {
                 if allow_ratings {
-                    return Err(ParsingError::parsing_error_from_str(
+                    return Err(ParsingError::from(
                         "Ratings are enabled even though the like button is missing",
                     ));
                 } else {
@@ -667,12 +777,10 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 return Ok(likes);
             }
         }
-        Err(ParsingError::parsing_error_from_str(
-            "could not get like count",
-        ))
+        Err(ParsingError::from("could not get like count"))
     }
 
-    pub fn dislike_count(&self) -> Result<i128, ParsingError> {
+    fn youtube_dislike_count(&self) -> Result<i128, ParsingError> {
         let mut like_string = String::new();
         if let Some(likes) = self
             .primary_info_renderer
@@ -693,7 +801,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .and_then(|f| f.as_bool())
             {
                 if allow_ratings {
-                    return Err(ParsingError::parsing_error_from_str(
+                    return Err(ParsingError::from(
                         "Ratings are enabled even though the dislike button is missing",
                     ));
                 } else {
@@ -705,9 +813,7 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 return Ok(likes);
             }
         }
-        Err(ParsingError::parsing_error_from_str(
-            "could not get dislike count",
-        ))
+        Err(ParsingError::from("could not get dislike count"))
     }
 
     pub fn uploader_url(&self) -> Result<String, ParsingError> {
@@ -728,13 +834,27 @@ impl<D: Downloader> YTStreamExtractor<D> {
             .and_then(|f| f.get("channelId"))
             .and_then(|f| f.as_str())
         {
+            record_fallback(STREAM, "uploader_url", "videoDetails");
             return Ok(format!("https://www.youtube.com/channel/{}", uploader_id));
         }
-        Err(ParsingError::parsing_error_from_str(
+        Err(parse_failure(
+            STREAM,
+            "uploader_url",
             "Cant get uploader url",
         ))
     }
 
+    /// `UC…` id of the uploader, which [`uploader_url`](Self::uploader_url) may leave out for
+    /// a handle
+    pub fn uploader_id(&self) -> Result<String, ParsingError> {
+        self.player_response
+            .get("videoDetails")
+            .and_then(|f| f.get("channelId"))
+            .and_then(|f| f.as_str())
+            .map(|id| id.to_string())
+            .ok_or_else(|| parse_failure(STREAM, "uploader_id", "Cant get uploader id"))
+    }
+
     pub fn uploader_name(&self) -> Result<String, ParsingError> {
         let mut uploader_name = String::new();
         if let Some(uploader) = self
@@ -754,12 +874,15 @@ impl<D: Downloader> YTStreamExtractor<D> {
                 .and_then(|f| f.get("author"))
                 .and_then(|f| f.as_str())
             {
+                record_fallback(STREAM, "uploader_name", "videoDetails");
                 uploader_name = author.to_owned();
             }
         }
 
         if uploader_name.is_empty() {
-            Err(ParsingError::parsing_error_from_str(
+            Err(parse_failure(
+                STREAM,
+                "uploader_name",
                 "Cant get uploader name",
             ))
         } else {
@@ -844,6 +967,35 @@ impl<D: Downloader> YTStreamExtractor<D> {
         Ok(audio_streams)
     }
 
+    /// Empty for videos without captions
+    pub fn captions(&self) -> Result<Vec<CaptionTrack>, ParsingError> {
+        let tracks = match self
+            .player_response
+            .get("captions")
+            .and_then(|f| f.get("playerCaptionsTracklistRenderer"))
+            .and_then(|f| f.get("captionTracks"))
+        {
+            Some(tracks) => tracks.as_array().ok_or("captionTracks is not an array")?,
+            None => return Ok(vec![]),
+        };
+        let mut captions = vec![];
+        for track in tracks {
+            let url = track.get("baseUrl").and_then(|f| f.as_str());
+            let language_code = track.get("languageCode").and_then(|f| f.as_str());
+            if let (Some(url), Some(language_code)) = (url, language_code) {
+                let name = text_from_object(track.get("name").unwrap_or(&Value::Null), false)?
+                    .filter(|name| !name.is_empty());
+                captions.push(CaptionTrack {
+                    url: url.to_string(),
+                    language_code: language_code.to_string(),
+                    name: name.unwrap_or_else(|| language_code.to_string()),
+                    auto_generated: track.get("kind").and_then(|f| f.as_str()) == Some("asr"),
+                });
+            }
+        }
+        Ok(captions)
+    }
+
     pub fn related(&self) -> Result<Vec<YTSearchItem>, ParsingError> {
         let results = (|| {
             self.initial_data
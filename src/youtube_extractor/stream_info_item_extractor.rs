@@ -1,3 +1,4 @@
+use crate::metrics::{parse_failure, STREAM_INFO_ITEM};
 use crate::utils::utils::*;
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::stream_extractor::Thumbnail;
@@ -17,7 +18,7 @@ impl YTStreamInfoItemExtractor {
                 }
             }
         }
-        Err(ParsingError::from("Cannot get name"))
+        Err(parse_failure(STREAM_INFO_ITEM, "name", "Cannot get name"))
     }
 
     pub fn is_ad(&self) -> Result<bool, ParsingError> {
@@ -30,7 +31,7 @@ impl YTStreamInfoItemExtractor {
         Ok(self
             .video_info
             .get("videoId")
-            .ok_or("video id not found")?
+            .ok_or_else(|| parse_failure(STREAM_INFO_ITEM, "video_id", "video id not found"))?
             .as_str()
             .ok_or("videoid not string")?
             .to_string())
@@ -134,7 +135,11 @@ impl YTStreamInfoItemExtractor {
             }
         }
         if duration.is_none() || duration.clone().unwrap_or_default().is_empty() {
-            Err(ParsingError::from("Cant get duration"))
+            Err(parse_failure(
+                STREAM_INFO_ITEM,
+                "textual_duration",
+                "Cant get duration",
+            ))
         } else {
             Ok(duration.unwrap_or_default())
         }
@@ -167,10 +172,23 @@ impl YTStreamInfoItemExtractor {
             }
         }
         if duration.is_none() || duration.clone().unwrap_or_default().is_empty() {
-            Err(ParsingError::from("Cant get duration"))
+            Err(parse_failure(
+                STREAM_INFO_ITEM,
+                "duration",
+                "Cant get duration",
+            ))
         } else {
-            Ok(remove_non_digit_chars::<i32>(&duration.unwrap_or_default())
-                .map_err(|f| ParsingError::from(f.to_string()))?)
+            // h:mm:ss
+            let mut seconds: i32 = 0;
+            for part in duration.unwrap_or_default().split(':') {
+                let part = remove_non_digit_chars::<i32>(part)
+                    .map_err(|f| ParsingError::from(f.to_string()))?;
+                seconds = seconds
+                    .checked_mul(60)
+                    .and_then(|seconds| seconds.checked_add(part))
+                    .ok_or("Duration out of range")?;
+            }
+            Ok(seconds)
         }
     }
 
@@ -199,7 +217,11 @@ impl YTStreamInfoItemExtractor {
                 .unwrap_or_default();
 
                 if name.is_empty() {
-                    return Err(ParsingError::from("Cant get uploader name"));
+                    return Err(parse_failure(
+                        STREAM_INFO_ITEM,
+                        "uploader_name",
+                        "Cant get uploader name",
+                    ));
                 }
             }
         }
@@ -245,7 +267,11 @@ impl YTStreamInfoItemExtractor {
                 );
 
                 if url.is_err() || url.clone().unwrap_or_default().is_empty() {
-                    return Err(ParsingError::from("Cant get uploader url"));
+                    return Err(parse_failure(
+                        STREAM_INFO_ITEM,
+                        "uploader_url",
+                        "Cant get uploader url",
+                    ));
                 }
             }
         }
@@ -262,7 +288,7 @@ impl YTStreamInfoItemExtractor {
                 .unwrap_or(&Value::Null),
             false,
         )?;
-        Ok(pt.ok_or("Cant get upload date")?)
+        pt.ok_or_else(|| parse_failure(STREAM_INFO_ITEM, "upload_date", "Cant get upload date"))
     }
 
     pub fn upload_date(&self) -> Result<chrono::NaiveDateTime, ParsingError> {
@@ -289,7 +315,11 @@ impl YTStreamInfoItemExtractor {
             }
         }
 
-        Err(ParsingError::from("Cant get view count"))
+        Err(parse_failure(
+            STREAM_INFO_ITEM,
+            "textual_view_count",
+            "Cant get view count",
+        ))
     }
 
     pub fn view_count(&self) -> Result<i32, ParsingError> {
@@ -308,7 +338,11 @@ impl YTStreamInfoItemExtractor {
             }
         }
 
-        Err(ParsingError::from("Cant get view count"))
+        Err(parse_failure(
+            STREAM_INFO_ITEM,
+            "view_count",
+            "Cant get view count",
+        ))
     }
 
     pub fn thumbnails(&self) -> Result<Vec<Thumbnail>, ParsingError> {
@@ -351,3 +385,62 @@ impl YTStreamInfoItemExtractor {
         Ok(thumbnails)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn item(video_info: Value) -> YTStreamInfoItemExtractor {
+        YTStreamInfoItemExtractor {
+            video_info: video_info.as_object().cloned().unwrap(),
+        }
+    }
+
+    fn with_length(length: &str) -> YTStreamInfoItemExtractor {
+        item(json!({ "lengthText": { "simpleText": length } }))
+    }
+
+    #[test]
+    fn duration() {
+        assert_eq!(with_length("0:07").duration().unwrap(), 7);
+        assert_eq!(with_length("3:32").duration().unwrap(), 212);
+        assert_eq!(with_length("59:59").duration().unwrap(), 3599);
+        assert_eq!(with_length("1:02:03").duration().unwrap(), 3723);
+        assert_eq!(with_length("12:00:00").duration().unwrap(), 43200);
+
+        // Empty lengthText falls back to the time overlay of the thumbnail
+        let overlay = item(json!({
+            "lengthText": { "runs": [] },
+            "thumbnailOverlays": [{
+                "thumbnailOverlayTimeStatusRenderer": {
+                    "text": { "runs": [{ "text": "4:05" }] },
+                    "style": "DEFAULT",
+                },
+            }],
+        }));
+        assert_eq!(overlay.duration().unwrap(), 245);
+
+        let live = item(json!({
+            "badges": [{ "metadataBadgeRenderer": { "label": "LIVE NOW" } }],
+        }));
+        assert_eq!(live.duration().unwrap(), -1);
+    }
+
+    #[test]
+    fn unparseable_duration() {
+        for length in [
+            "",
+            "SHORTS",
+            "1::30",
+            "3:",
+            "99999999999",
+            "999999999:00:00",
+        ]
+        .iter()
+        {
+            assert!(with_length(length).duration().is_err(), "{}", length);
+        }
+        assert!(item(json!({})).duration().is_err());
+    }
+}
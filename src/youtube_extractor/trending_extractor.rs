@@ -1,9 +1,13 @@
 use crate::downloader_trait::Downloader;
+use crate::metrics::{parse_failure, Stopwatch, TRENDING};
+use crate::utils::utils::youtube_download;
 use crate::youtube_extractor::error::ParsingError;
 use crate::youtube_extractor::stream_extractor::HARDCODED_CLIENT_VERSION;
 use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
 use serde_json::Value;
 use std::collections::HashMap;
+use tracing::field::Empty;
+use tracing::Instrument;
 
 #[derive(Clone, PartialEq)]
 pub struct YTTrendingExtractor<D> {
@@ -21,19 +25,29 @@ impl<D: Downloader> YTTrendingExtractor<D> {
             HARDCODED_CLIENT_VERSION.to_string(),
         );
         let url = format!("{}&pbj=1", url);
-        let data = downloader.download_with_header(&url, headers).await?;
+        let data = youtube_download(downloader, &url, headers).await?;
+        let stopwatch = Stopwatch::start();
         let mut json =
             serde_json::from_str::<Value>(&data).map_err(|e| ParsingError::from(e.to_string()))?;
-        Ok(json
+        let initial_data = json
             .get_mut(1)
             .ok_or("No index 1")?
             .get_mut("response")
             .ok_or("No response")?
-            .take())
+            .take();
+        stopwatch.record();
+        Ok(initial_data)
     }
 
     pub async fn new(downloader: D) -> Result<Self, ParsingError> {
-        let initial_data = YTTrendingExtractor::initial_data(&downloader).await?;
+        let span = tracing::info_span!(
+            "trending_extractor",
+            endpoint = "/feed/trending",
+            parse_ms = Empty
+        );
+        let initial_data = YTTrendingExtractor::initial_data(&downloader)
+            .instrument(span)
+            .await?;
         Ok(Self {
             downloader,
             initial_data,
@@ -55,7 +69,7 @@ impl<D: Downloader> YTTrendingExtractor<D> {
                 .get("contents")?
                 .as_array()
         })()
-        .ok_or("No item sections")?;
+        .ok_or_else(|| parse_failure(TRENDING, "videos", "No item sections"))?;
         let mut videos = vec![];
         for item_section in item_section_renderers {
             let shelf_content = (|| {
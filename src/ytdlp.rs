@@ -0,0 +1,330 @@
+//! Documents in the schema of yt-dlp's `--write-info-json`
+//!
+//! Playlists and channels become `_type: playlist` documents whose entries are `url` stubs,
+//! as yt-dlp writes them with `--flat-playlist`. `entries` are passed in separately so the
+//! videos of several pages can be combined.
+//!
+//! Caption tracks are listed under `subtitles` and, for speech recognition, under
+//! `automatic_captions`, in the formats of YouTube's `timedtext` endpoint that yt-dlp offers.
+//! Translations of the tracks aren't.
+
+use crate::downloader_trait::Downloader;
+use crate::mux::Chapter;
+use crate::youtube_extractor::channel_extractor::YTChannelExtractor;
+use crate::youtube_extractor::link_handler::YTLink;
+use crate::youtube_extractor::playlist_extractor::YTPlaylistExtractor;
+use crate::youtube_extractor::stream_extractor::{
+    CaptionTrack, StreamItem, Thumbnail, YTStreamExtractor,
+};
+use crate::youtube_extractor::stream_info_item_extractor::YTStreamInfoItemExtractor;
+use serde_json::{json, Map, Value};
+
+/// File extension yt-dlp uses for a mime type, audio in mp4 is `m4a`
+fn ext(mime_type: &str) -> &'static str {
+    let essence = mime_type.split(';').next().unwrap_or_default().trim();
+    match essence {
+        "audio/mp4" => "m4a",
+        "video/mp4" => "mp4",
+        "audio/webm" | "video/webm" => "webm",
+        "video/3gpp" => "3gp",
+        _ => "unknown_video",
+    }
+}
+
+/// Codecs listed in the mime type, e.g. `avc1.42001E, mp4a.40.2`
+fn codecs(mime_type: &str) -> Vec<String> {
+    mime_type
+        .split("codecs=\"")
+        .nth(1)
+        .and_then(|codecs| codecs.split('"').next())
+        .map(|codecs| codecs.split(',').map(|c| c.trim().to_string()).collect())
+        .unwrap_or_default()
+}
+
+fn format(stream: &StreamItem, adaptive: bool) -> Value {
+    let is_audio = stream.mime_type.starts_with("audio/");
+    let codecs = codecs(&stream.mime_type);
+    let (vcodec, acodec) = match (is_audio, adaptive) {
+        (true, _) => (None, codecs.first().cloned()),
+        (false, true) => (codecs.first().cloned(), None),
+        (false, false) => (codecs.first().cloned(), codecs.get(1).cloned()),
+    };
+    let ext = ext(&stream.mime_type);
+    let resolution = match (stream.width, stream.height) {
+        (Some(width), Some(height)) => format!("{}x{}", width, height),
+        _ => "audio only".to_string(),
+    };
+    let note = stream
+        .quality_label
+        .clone()
+        .unwrap_or_else(|| stream.quality.clone());
+    let tbr = stream.average_bitrate.unwrap_or(stream.bitrate) as f64 / 1000.0;
+    let mut format = json!({
+        "format_id": stream.itag.to_string(),
+        "format_note": note,
+        "format": format!("{} - {} ({})", stream.itag, resolution, note),
+        "url": stream.url,
+        "ext": ext,
+        "protocol": "https",
+        "vcodec": vcodec.unwrap_or_else(|| "none".to_string()),
+        "acodec": acodec.unwrap_or_else(|| "none".to_string()),
+        "tbr": tbr,
+        "width": stream.width,
+        "height": stream.height,
+        "resolution": resolution,
+        "filesize": stream.content_length.as_ref().and_then(|l| l.parse::<u64>().ok()),
+        "asr": stream.audio_sample_rate.as_ref().and_then(|r| r.parse::<u64>().ok()),
+        "audio_channels": stream.audio_channels,
+    });
+    if adaptive {
+        format["container"] = Value::from(format!("{}_dash", ext));
+    }
+    if is_audio {
+        format["abr"] = Value::from(tbr);
+    } else if adaptive {
+        format["vbr"] = Value::from(tbr);
+    }
+    format
+}
+
+/// Worst to best, the order yt-dlp lists formats in
+fn formats<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Vec<Value> {
+    let mut formats = vec![];
+    for (streams, adaptive) in [
+        (extractor.audio_streams(), true),
+        (extractor.video_stream(), false),
+        (extractor.video_only_stream(), true),
+    ]
+    .iter()
+    {
+        let mut streams: Vec<&StreamItem> = streams.iter().flatten().collect();
+        streams.sort_by_key(|s| (s.height, s.average_bitrate.unwrap_or(s.bitrate)));
+        formats.extend(streams.into_iter().map(|s| format(s, *adaptive)));
+    }
+    formats
+}
+
+fn thumbnails(thumbnails: &[Thumbnail]) -> Vec<Value> {
+    let mut thumbnails: Vec<&Thumbnail> = thumbnails.iter().collect();
+    thumbnails.sort_by_key(|t| t.width * t.height);
+    thumbnails
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            json!({
+                "id": i.to_string(),
+                "url": t.url,
+                "width": t.width,
+                "height": t.height,
+                "resolution": format!("{}x{}", t.width, t.height),
+            })
+        })
+        .collect()
+}
+
+fn chapters(description: &str, duration: Option<u64>) -> Option<Vec<Value>> {
+    let chapters = Chapter::from_description(description, duration)?;
+    let chapters = chapters
+        .iter()
+        .map(|c| json!({ "start_time": c.start, "end_time": c.end, "title": c.title }))
+        .collect();
+    Some(chapters)
+}
+
+/// Formats yt-dlp requests from the `timedtext` endpoint with `fmt`
+const SUBTITLE_FORMATS: [&str; 6] = ["json3", "srv1", "srv2", "srv3", "ttml", "vtt"];
+
+/// `subtitles` and `automatic_captions`, each `{language: [{ext, url, name}]}`
+fn subtitles(tracks: &[CaptionTrack]) -> (Map<String, Value>, Map<String, Value>) {
+    let (mut subtitles, mut automatic_captions) = (Map::new(), Map::new());
+    for track in tracks {
+        let separator = if track.url.contains('?') { '&' } else { '?' };
+        let formats = SUBTITLE_FORMATS.iter().map(|ext| {
+            json!({
+                "ext": ext,
+                "url": format!("{}{}fmt={}", track.url, separator, ext),
+                "name": track.name,
+            })
+        });
+        let languages = if track.auto_generated {
+            &mut automatic_captions
+        } else {
+            &mut subtitles
+        };
+        let language = languages
+            .entry(track.language_code.clone())
+            .or_insert_with(|| Value::Array(vec![]));
+        if let Value::Array(language) = language {
+            language.extend(formats);
+        }
+    }
+    (subtitles, automatic_captions)
+}
+
+pub fn stream_info<D: Downloader>(extractor: &YTStreamExtractor<D>) -> Value {
+    let id = extractor.video_id();
+    let webpage_url = YTLink::Stream(id.clone()).url();
+    let description = extractor.description(false).ok().map(|(d, _)| d);
+    let duration = extractor.length().ok();
+    let channel_url = extractor.uploader_url().ok();
+    let channel_id = channel_url.as_deref().and_then(YTLink::channel_id);
+    let thumbnails = thumbnails(&extractor.video_thumbnails().unwrap_or_default());
+    let (subtitles, automatic_captions) = subtitles(&extractor.captions().unwrap_or_default());
+    json!({
+        "_type": "video",
+        "id": id,
+        "title": extractor.name().ok(),
+        "fulltitle": extractor.name().ok(),
+        "formats": formats(extractor),
+        "thumbnails": thumbnails,
+        "thumbnail": thumbnails.last().map(|t| t["url"].clone()),
+        "description": description,
+        "duration": duration,
+        "upload_date": extractor.upload_date().ok().map(|d| d.format("%Y%m%d").to_string()),
+        "uploader": extractor.uploader_name().ok(),
+        "uploader_id": channel_id,
+        "uploader_url": channel_url,
+        "channel": extractor.uploader_name().ok(),
+        "channel_id": channel_id,
+        "channel_url": channel_url,
+        "view_count": extractor.view_count().ok().map(|c| c as u64),
+        "like_count": extractor.like_count().ok().filter(|c| *c >= 0).map(|c| c as u64),
+        "chapters": description.as_deref().and_then(|d| chapters(d, duration)),
+        "subtitles": subtitles,
+        "automatic_captions": automatic_captions,
+        "webpage_url": webpage_url,
+        "original_url": webpage_url,
+        "webpage_url_basename": "watch",
+        "webpage_url_domain": "youtube.com",
+        "extractor": "youtube",
+        "extractor_key": "Youtube",
+    })
+}
+
+/// `url` entry of a flat playlist
+fn entry(video: &YTStreamInfoItemExtractor) -> Value {
+    let channel_url = video.uploader_url().ok();
+    json!({
+        "_type": "url",
+        "ie_key": "Youtube",
+        "id": video.video_id().ok(),
+        "url": video.url().ok(),
+        "title": video.name().ok(),
+        "duration": video.duration().ok().filter(|d| *d >= 0),
+        "view_count": video.view_count().ok(),
+        "uploader": video.uploader_name().ok(),
+        "channel_id": channel_url.as_deref().and_then(YTLink::channel_id),
+        "channel_url": channel_url,
+        "thumbnails": thumbnails(&video.thumbnails().unwrap_or_default()),
+    })
+}
+
+fn playlist_document(fields: Value, entries: &[YTStreamInfoItemExtractor]) -> Value {
+    let mut document = Map::new();
+    document.insert("_type".to_string(), Value::from("playlist"));
+    if let Value::Object(fields) = fields {
+        document.extend(fields);
+    }
+    document.insert(
+        "entries".to_string(),
+        entries.iter().map(entry).collect::<Vec<Value>>().into(),
+    );
+    document.insert("playlist_count".to_string(), entries.len().into());
+    document.insert("extractor".to_string(), Value::from("youtube:tab"));
+    document.insert("extractor_key".to_string(), Value::from("YoutubeTab"));
+    Value::Object(document)
+}
+
+pub fn playlist_info<D: Downloader>(
+    playlist_id: &str,
+    extractor: &YTPlaylistExtractor<D>,
+    entries: &[YTStreamInfoItemExtractor],
+) -> Value {
+    let channel_url = extractor.uploader_url().ok();
+    let url = YTLink::Playlist(playlist_id.to_string()).url();
+    playlist_document(
+        json!({
+            "id": playlist_id,
+            "title": extractor.name().ok(),
+            "thumbnails": thumbnails(&extractor.thumbnails().unwrap_or_default()),
+            "uploader": extractor.uploader_name().ok(),
+            "uploader_url": channel_url,
+            "channel": extractor.uploader_name().ok(),
+            "channel_id": channel_url.as_deref().and_then(YTLink::channel_id),
+            "channel_url": channel_url,
+            "webpage_url": url,
+            "original_url": url,
+        }),
+        entries,
+    )
+}
+
+pub fn channel_info<D: Downloader>(
+    channel_id: &str,
+    extractor: &YTChannelExtractor<D>,
+    entries: &[YTStreamInfoItemExtractor],
+) -> Value {
+    let url = YTLink::Channel(channel_id.to_string()).url();
+    let mut images = extractor.avatars().unwrap_or_default();
+    images.extend(extractor.banners().unwrap_or_default());
+    playlist_document(
+        json!({
+            "id": channel_id,
+            "title": extractor.name().ok().map(|name| format!("{} - Videos", name)),
+            "thumbnails": thumbnails(&images),
+            "uploader": extractor.name().ok(),
+            "uploader_id": channel_id,
+            "uploader_url": url,
+            "channel": extractor.name().ok(),
+            "channel_id": channel_id,
+            "channel_url": url,
+            "webpage_url": format!("{}/videos", url),
+            "original_url": url,
+        }),
+        entries,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(language_code: &str, name: &str, auto_generated: bool) -> CaptionTrack {
+        CaptionTrack {
+            url: format!(
+                "https://www.youtube.com/api/timedtext?v=dQw4w9WgXcQ&lang={}",
+                language_code
+            ),
+            language_code: language_code.to_string(),
+            name: name.to_string(),
+            auto_generated,
+        }
+    }
+
+    #[test]
+    fn subtitles_by_language() {
+        let tracks = [
+            track("en", "English", false),
+            track("de", "German", false),
+            track("en", "English (auto-generated)", true),
+        ];
+        let (manual, automatic) = subtitles(&tracks);
+        assert_eq!(manual.keys().collect::<Vec<_>>(), vec!["de", "en"]);
+        let english = manual["en"].as_array().unwrap();
+        let exts: Vec<&str> = english.iter().map(|f| f["ext"].as_str().unwrap()).collect();
+        assert_eq!(exts, SUBTITLE_FORMATS);
+        assert_eq!(
+            english[5],
+            json!({
+                "ext": "vtt",
+                "url": "https://www.youtube.com/api/timedtext?v=dQw4w9WgXcQ&lang=en&fmt=vtt",
+                "name": "English",
+            })
+        );
+        assert_eq!(automatic.keys().collect::<Vec<_>>(), vec!["en"]);
+        assert_eq!(automatic["en"][0]["name"], "English (auto-generated)");
+
+        let (manual, automatic) = subtitles(&[]);
+        assert!(manual.is_empty() && automatic.is_empty());
+    }
+}